@@ -0,0 +1,137 @@
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use ipnetwork::{Ipv4Network, Ipv6Network};
+use nftblockd::utils::iptrie::deduplicate;
+use nftblockd::utils::subnet::{get_nft_expressions, split_entries, validate_subnets};
+use std::hint::black_box;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const SIZES: [usize; 2] = [100_000, 1_000_000];
+
+/// Deterministic xorshift generator, so every run benchmarks the same feed.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Generates a synthetic IPv4 feed of aligned networks with prefixes between /8 and /32.
+fn ipv4_feed(size: usize) -> Vec<String> {
+    let mut rng = XorShift(0x5eed_1234_abcd_ef01);
+    (0..size)
+        .map(|_| {
+            let value = rng.next_u64();
+            let prefix = 8 + (value % 25) as u8;
+            let addr = Ipv4Addr::from((value >> 32) as u32);
+            let network = Ipv4Network::new(addr, prefix).unwrap();
+            format!("{}/{}", network.network(), prefix)
+        })
+        .collect()
+}
+
+/// Generates a synthetic IPv6 feed of aligned networks with prefixes between /16 and /128.
+fn ipv6_feed(size: usize) -> Vec<String> {
+    let mut rng = XorShift(0x0ddb_a115_f00d_cafe);
+    (0..size)
+        .map(|_| {
+            let value = (u128::from(rng.next_u64()) << 64) | u128::from(rng.next_u64());
+            let prefix = 16 + (value % 113) as u8;
+            let network = Ipv6Network::new(Ipv6Addr::from(value), prefix).unwrap();
+            format!("{}/{}", network.network(), prefix)
+        })
+        .collect()
+}
+
+fn bench_validate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("validate_subnets");
+    group.sample_size(10);
+    for size in SIZES {
+        let ipv4 = ipv4_feed(size);
+        let ipv6 = ipv6_feed(size);
+        group.bench_with_input(BenchmarkId::new("ipv4", size), &ipv4, |b, feed| {
+            b.iter(|| validate_subnets::<Ipv4Network>(black_box(feed), false));
+        });
+        group.bench_with_input(BenchmarkId::new("ipv6", size), &ipv6, |b, feed| {
+            b.iter(|| validate_subnets::<Ipv6Network>(black_box(feed), false));
+        });
+    }
+    group.finish();
+}
+
+/// Validates the entries borrowed from the fetched body, without collecting them into strings first.
+fn bench_validate_in_place(c: &mut Criterion) {
+    let mut group = c.benchmark_group("validate_in_place");
+    group.sample_size(10);
+    for size in SIZES {
+        let ipv4 = ipv4_feed(size).join("\n");
+        let ipv6 = ipv6_feed(size).join("\n");
+        group.bench_with_input(BenchmarkId::new("ipv4", size), &ipv4, |b, body| {
+            b.iter(|| validate_subnets::<Ipv4Network>(split_entries(black_box(body), None), false));
+        });
+        group.bench_with_input(BenchmarkId::new("ipv6", size), &ipv6, |b, body| {
+            b.iter(|| validate_subnets::<Ipv6Network>(split_entries(black_box(body), None), false));
+        });
+    }
+    group.finish();
+}
+
+fn bench_deduplicate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deduplicate");
+    group.sample_size(10);
+    for size in SIZES {
+        let ipv4 = validate_subnets::<Ipv4Network>(&ipv4_feed(size), false).unwrap();
+        let ipv6 = validate_subnets::<Ipv6Network>(&ipv6_feed(size), false).unwrap();
+        group.bench_with_input(BenchmarkId::new("ipv4", size), &ipv4, |b, ips| {
+            b.iter_batched(
+                || ips.clone(),
+                |ips| deduplicate(black_box(ips)),
+                BatchSize::LargeInput,
+            );
+        });
+        group.bench_with_input(BenchmarkId::new("ipv6", size), &ipv6, |b, ips| {
+            b.iter_batched(
+                || ips.clone(),
+                |ips| deduplicate(black_box(ips)),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn bench_expressions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_nft_expressions");
+    group.sample_size(10);
+    for size in SIZES {
+        let ipv4 = deduplicate(validate_subnets::<Ipv4Network>(&ipv4_feed(size), false).unwrap());
+        let ipv6 = deduplicate(validate_subnets::<Ipv6Network>(&ipv6_feed(size), false).unwrap());
+        group.bench_with_input(BenchmarkId::new("ipv4", size), &ipv4, |b, ips| {
+            b.iter_batched(
+                || ips.clone(),
+                |ips| get_nft_expressions(black_box(ips)),
+                BatchSize::LargeInput,
+            );
+        });
+        group.bench_with_input(BenchmarkId::new("ipv6", size), &ipv6, |b, ips| {
+            b.iter_batched(
+                || ips.clone(),
+                |ips| get_nft_expressions(black_box(ips)),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_validate,
+    bench_validate_in_place,
+    bench_deduplicate,
+    bench_expressions
+);
+criterion_main!(benches);
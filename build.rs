@@ -1,5 +1,10 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The service code needs `tonic`, which is only linked with the control socket;
+    // the messages are always generated, since the status is built from them.
+    let control_socket = std::env::var_os("CARGO_FEATURE_CONTROL_SOCKET").is_some();
     tonic_prost_build::configure()
+        .build_client(control_socket)
+        .build_server(control_socket)
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .compile_protos(&["proto/nftblockd.proto"], &["proto"])?;
 
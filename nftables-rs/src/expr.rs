@@ -396,6 +396,14 @@ pub enum MetaKey {
     ///
     /// [nftract debugging]: <https://wiki.nftables.org/wiki-nftables/index.php/Ruleset_debug/tracing>
     Nftrace,
+
+    // matching by time:
+    /// Time of the packet's arrival.
+    Time,
+    /// Day of the week, e.g., `"Monday"`.
+    Day,
+    /// Hour of the day, e.g., `"17:00"`.
+    Hour,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
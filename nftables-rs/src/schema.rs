@@ -1,5 +1,8 @@
 use schemars::JsonSchema;
-use std::{borrow::Cow, collections::HashSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+};
 
 use crate::visitor::deserialize_optional_flags;
 use crate::{
@@ -322,7 +325,7 @@ pub struct Set<'a> {
         default
     )]
     /// The set’s flags.
-    pub flags: Option<HashSet<SetFlag>>,
+    pub flags: Option<BTreeSet<SetFlag>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Initial set element(s).
     ///
@@ -396,7 +399,7 @@ pub struct Map<'a> {
         default
     )]
     /// The map’s flags.
-    pub flags: Option<HashSet<SetFlag>>,
+    pub flags: Option<BTreeSet<SetFlag>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Initial map set element(s).
     ///
@@ -499,7 +502,18 @@ pub enum SetPolicy {
 }
 
 #[derive(
-    Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, EnumString, Hash, JsonSchema,
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Serialize,
+    Deserialize,
+    EnumString,
+    Hash,
+    JsonSchema,
 )]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
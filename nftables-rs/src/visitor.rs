@@ -57,22 +57,23 @@ where
     deserializer.deserialize_any(StringOrVec(PhantomData))
 }
 
-/// Deserialize null, a string or string sequence into an `Option<HashSet<T>>`.
-pub fn deserialize_optional_flags<'de, D, T>(
-    deserializer: D,
-) -> Result<Option<HashSet<T>>, D::Error>
+/// Deserialize null, a string or string sequence into an `Option` of a set of flags,
+/// such as `Option<HashSet<T>>` or `Option<BTreeSet<T>>`.
+pub fn deserialize_optional_flags<'de, D, T, C>(deserializer: D) -> Result<Option<C>, D::Error>
 where
-    T: FromStr + Eq + core::hash::Hash + Deserialize<'de>,
+    T: FromStr,
     <T as FromStr>::Err: std::fmt::Display,
+    C: FromIterator<T> + Deserialize<'de>,
     D: de::Deserializer<'de>,
 {
-    struct FlagSet<T>(PhantomData<T>);
-    impl<'de, T> de::Visitor<'de> for FlagSet<T>
+    struct FlagSet<T, C>(PhantomData<(T, C)>);
+    impl<'de, T, C> de::Visitor<'de> for FlagSet<T, C>
     where
-        T: FromStr + Eq + core::hash::Hash + Deserialize<'de>,
+        T: FromStr,
         <T as FromStr>::Err: std::fmt::Display,
+        C: FromIterator<T> + Deserialize<'de>,
     {
-        type Value = Option<HashSet<T>>;
+        type Value = Option<C>;
 
         fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
             formatter.write_str("single string or list of strings")
@@ -89,17 +90,15 @@ where
         where
             E: de::Error,
         {
-            let mut h: HashSet<T> = HashSet::new();
-            h.insert(T::from_str(value).map_err(<E>::custom)?);
-            Ok(Some(h))
+            let flag = T::from_str(value).map_err(<E>::custom)?;
+            Ok(Some(std::iter::once(flag).collect()))
         }
 
         fn visit_seq<S>(self, visitor: S) -> Result<Self::Value, S::Error>
         where
             S: de::SeqAccess<'de>,
         {
-            let h: HashSet<T> =
-                Deserialize::deserialize(de::value::SeqAccessDeserializer::new(visitor))?;
+            let h: C = Deserialize::deserialize(de::value::SeqAccessDeserializer::new(visitor))?;
             Ok(Some(h))
         }
     }
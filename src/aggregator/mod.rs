@@ -0,0 +1,275 @@
+use crate::error::AppError;
+use crate::grpc::ctl::nftblockd::ReplicaStatus;
+use crate::nftables::builder::{RuleProto, SetElements};
+use crate::set::blocklist::{APPLIED_HEADER, REPLICA_HEADER};
+use crate::set::export::export_plain;
+use crate::set::observer::UpdateObserver;
+use crate::settings::Environment;
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use log::info;
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+/// Default number of replicas tracked; the least recently seen one is forgotten when another one reports.
+const MAX_REPLICAS: usize = 1024;
+
+/// Replicas not seen for this long are forgotten, e.g., decommissioned hosts.
+const REPLICA_TTL: i64 = 7 * 24 * 3600;
+
+/// A served blocklist and its `ETag`.
+#[derive(Debug, Clone)]
+struct ServedList {
+    body: String,
+    etag: String,
+}
+
+/// The `ETag`s a replica reported as applied.
+#[derive(Debug, Clone, Default)]
+struct ReplicaState {
+    ipv4: Option<String>,
+    ipv6: Option<String>,
+    last_seen: i64,
+}
+
+/// Serves the merged blocklist of this instance to other `nftblockd` instances.
+///
+/// Registered as an `UpdateObserver`, it keeps the last applied IPv4 and IPv6 elements
+/// as plain CIDR lists and serves them on `/ipv4` and `/ipv6`. Every request must carry
+/// `Authorization: Bearer <token>`; replicas send it through `NFTBLOCKD_REQUEST_HEADERS`.
+/// Lists are served with an `ETag`, and replicas report the `ETag` they applied,
+/// which is used to show the fleet convergence.
+#[derive(Clone)]
+pub struct Aggregator {
+    token: Arc<str>,
+    ipv4: Arc<RwLock<Option<ServedList>>>,
+    ipv6: Arc<RwLock<Option<ServedList>>>,
+    replicas: Arc<RwLock<BTreeMap<String, ReplicaState>>>,
+    max_replicas: usize,
+}
+
+impl Aggregator {
+    /// Creates an `Aggregator` that accepts requests carrying `token`.
+    #[must_use]
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: Arc::from(token),
+            ipv4: Arc::new(RwLock::new(None)),
+            ipv6: Arc::new(RwLock::new(None)),
+            replicas: Arc::new(RwLock::new(BTreeMap::new())),
+            max_replicas: MAX_REPLICAS,
+        }
+    }
+
+    /// Sets how many replicas are tracked at most, since any client knowing the token can report under a new name.
+    #[must_use]
+    pub fn with_max_replicas(mut self, max_replicas: usize) -> Self {
+        self.max_replicas = max_replicas;
+        self
+    }
+
+    /// Creates an `Aggregator` from `NFTBLOCKD_SERVE_ADDR` and `NFTBLOCKD_SERVE_TOKEN`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when `NFTBLOCKD_SERVE_ADDR` is not set, i.e., the aggregator mode is disabled.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the address is invalid or the token is missing.
+    pub fn from_env(environment: &Environment) -> Result<Option<(SocketAddr, Self)>, AppError> {
+        let Some(addr) = environment
+            .var("NFTBLOCKD_SERVE_ADDR")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        let addr = addr
+            .parse::<SocketAddr>()
+            .map_err(|e| AppError::ParseError(format!("invalid serve address: {addr}: {e}")))?;
+        let token = environment
+            .var("NFTBLOCKD_SERVE_TOKEN")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .ok_or(AppError::ParseError(
+                "NFTBLOCKD_SERVE_TOKEN must be set to serve the blocklist".to_string(),
+            ))?;
+        Ok(Some((addr, Self::new(&token))))
+    }
+
+    /// Builds the HTTP routes serving the blocklists.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/ipv4", get(serve_ipv4))
+            .route("/ipv6", get(serve_ipv6))
+            .with_state(self.clone())
+    }
+
+    /// Serves the blocklists on `addr` until `cancellation_token` is cancelled.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the address cannot be bound or the server fails.
+    pub async fn serve(
+        self,
+        addr: SocketAddr,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), AppError> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("serving the merged blocklist on http://{addr}");
+        axum::serve(listener, self.router())
+            .with_graceful_shutdown(async move { cancellation_token.cancelled().await })
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the convergence of every replica that polled this instance, ordered by name.
+    ///
+    /// A replica has converged when the lists it applied are the lists currently served.
+    #[must_use]
+    pub fn replicas(&self) -> Vec<ReplicaStatus> {
+        let current = |list: &RwLock<Option<ServedList>>| {
+            list.read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_ref()
+                .map(|l| l.etag.clone())
+        };
+        let (ipv4, ipv6) = (current(&self.ipv4), current(&self.ipv6));
+        self.replicas
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(name, state)| ReplicaStatus {
+                name: name.clone(),
+                ipv4_digest: state.ipv4.clone().unwrap_or_default(),
+                ipv6_digest: state.ipv6.clone().unwrap_or_default(),
+                // A family that is not served cannot diverge.
+                converged: (ipv4.is_none() || state.ipv4 == ipv4)
+                    && (ipv6.is_none() || state.ipv6 == ipv6),
+                last_seen: state.last_seen,
+            })
+            .collect()
+    }
+
+    fn respond(
+        &self,
+        headers: &HeaderMap,
+        proto: &RuleProto,
+        list: &RwLock<Option<ServedList>>,
+    ) -> Response {
+        if !self.is_authorized(headers) {
+            return (StatusCode::UNAUTHORIZED, "unauthorized\n").into_response();
+        }
+        self.record_replica(headers, proto);
+        // Serving an empty list before the first update would make replicas flush their sets.
+        let Some(list) = list.read().unwrap_or_else(PoisonError::into_inner).clone() else {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "no blocklist applied yet\n",
+            )
+                .into_response();
+        };
+        if headers
+            .get(header::IF_NONE_MATCH)
+            .is_some_and(|v| v.as_bytes() == list.etag.as_bytes())
+        {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, list.etag)]).into_response();
+        }
+        (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    "text/plain; charset=utf-8".to_string(),
+                ),
+                (header::ETAG, list.etag),
+            ],
+            list.body,
+        )
+            .into_response()
+    }
+
+    fn record_replica(&self, headers: &HeaderMap, proto: &RuleProto) {
+        let Some(name) = headers.get(REPLICA_HEADER).and_then(|v| v.to_str().ok()) else {
+            return;
+        };
+        let applied = headers
+            .get(APPLIED_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(ToString::to_string);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        let mut replicas = self
+            .replicas
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !replicas.contains_key(name) {
+            replicas.retain(|_, state| now.saturating_sub(state.last_seen) < REPLICA_TTL);
+            while replicas.len() >= self.max_replicas.max(1) {
+                let Some(oldest) = replicas
+                    .iter()
+                    .min_by_key(|(_, state)| state.last_seen)
+                    .map(|(name, _)| name.clone())
+                else {
+                    break;
+                };
+                replicas.remove(&oldest);
+            }
+        }
+        let state = replicas.entry(name.to_string()).or_default();
+        match proto {
+            RuleProto::Ip => state.ipv4 = applied,
+            RuleProto::Ip6 => state.ipv6 = applied,
+            RuleProto::Other => {}
+        }
+        state.last_seen = now;
+    }
+
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        // Compare in constant time so the token cannot be guessed byte by byte.
+        token.len() == self.token.len()
+            && token
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+impl UpdateObserver for Aggregator {
+    fn on_blocked(&self, _source: &str, proto: &RuleProto, elements: &SetElements<'_>) {
+        let list = match proto {
+            RuleProto::Ip => &self.ipv4,
+            RuleProto::Ip6 => &self.ipv6,
+            RuleProto::Other => return,
+        };
+        let body = export_plain(elements);
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let etag = format!("\"{:016x}\"", hasher.finish());
+        *list.write().unwrap_or_else(PoisonError::into_inner) = Some(ServedList { body, etag });
+    }
+}
+
+async fn serve_ipv4(State(aggregator): State<Aggregator>, headers: HeaderMap) -> Response {
+    aggregator.respond(&headers, &RuleProto::Ip, &aggregator.ipv4)
+}
+
+async fn serve_ipv6(State(aggregator): State<Aggregator>, headers: HeaderMap) -> Response {
+    aggregator.respond(&headers, &RuleProto::Ip6, &aggregator.ipv6)
+}
@@ -0,0 +1,228 @@
+use crate::error::AppError;
+use crate::set::change_rate::ChangeAnomaly;
+use crate::set::observer::{UpdateObserver, UpdateReport};
+use crate::settings::Environment;
+use crate::utils::duration::env_duration;
+use crate::utils::hostname;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{error, info};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How the connection to the SMTP relay is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Implicit TLS, usually on port 465.
+    Tls,
+    /// Plain connection upgraded with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// Unencrypted connection; only meant for a local relay.
+    None,
+}
+
+impl SmtpSecurity {
+    /// Parses the value of `NFTBLOCKD_SMTP_SECURITY`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the value is not one of `tls`, `starttls`, or `none`.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "tls" => Ok(SmtpSecurity::Tls),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "none" => Ok(SmtpSecurity::None),
+            other => Err(AppError::ParseError(format!(
+                "invalid SMTP security mode: {other}; expected `tls`, `starttls`, or `none`"
+            ))),
+        }
+    }
+}
+
+/// Tracks the last successful update so that a stale feed is reported only once.
+#[derive(Debug)]
+struct Freshness {
+    last_success: Instant,
+    stale_reported: bool,
+}
+
+/// Emails a configured address when the retry budget is exhausted or the blocklist goes stale.
+///
+/// Meant for appliances without a metrics stack. Mails are sent in the background,
+/// so the update loop is never blocked by a slow relay.
+pub struct SmtpAlerter {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+    hostname: String,
+    stale_after: Duration,
+    freshness: Mutex<Freshness>,
+}
+
+impl SmtpAlerter {
+    /// Creates an `SmtpAlerter` from the `NFTBLOCKD_SMTP_*` environment variables.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when `NFTBLOCKD_SMTP_HOST` is not set, i.e., alerting is disabled.
+    ///
+    /// # Errors
+    /// Will return `AppError` when an address, the port, the security mode, or the stale threshold is invalid.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let Some(host) = environment
+            .var("NFTBLOCKD_SMTP_HOST")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        let security = SmtpSecurity::parse(
+            &environment
+                .var("NFTBLOCKD_SMTP_SECURITY")
+                .unwrap_or("starttls".to_string()),
+        )?;
+        let default_port = match security {
+            SmtpSecurity::Tls => "465",
+            SmtpSecurity::StartTls => "587",
+            SmtpSecurity::None => "25",
+        };
+        let port = environment
+            .var("NFTBLOCKD_SMTP_PORT")
+            .unwrap_or(default_port.to_string())
+            .parse::<u16>()?;
+        let stale_after = env_duration(environment, "NFTBLOCKD_ALERT_STALE_AFTER", "1h")?;
+        let from = parse_mailbox(
+            &environment
+                .var("NFTBLOCKD_SMTP_FROM")
+                .unwrap_or(format!("nftblockd@{}", hostname())),
+        )?;
+        let to = parse_mailbox(&environment.var("NFTBLOCKD_SMTP_TO").map_err(|_| {
+            AppError::ParseError("NFTBLOCKD_SMTP_TO must be set to enable SMTP alerts".to_string())
+        })?)?;
+
+        let builder = match security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &host,
+            )),
+        }
+        .map_err(|e| AppError::ParseError(format!("invalid SMTP relay: {host}: {e}")))?;
+        let mut builder = builder.port(port);
+        if let Ok(username) = environment.var("NFTBLOCKD_SMTP_USERNAME") {
+            let password = environment
+                .var("NFTBLOCKD_SMTP_PASSWORD")
+                .unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        info!("SMTP alerts enabled; sending to {to} via {host}:{port}");
+        Ok(Some(Self {
+            transport: builder.build(),
+            from,
+            to,
+            hostname: hostname(),
+            stale_after,
+            freshness: Mutex::new(Freshness {
+                last_success: Instant::now(),
+                stale_reported: false,
+            }),
+        }))
+    }
+
+    /// Sends an alert in the background; failures are only logged.
+    fn send(&self, subject: &str, body: String) {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(format!("[nftblockd] {}: {subject}", self.hostname))
+            .body(body);
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                error!("failed to build an alert email: {e}");
+                return;
+            }
+        };
+        let transport = self.transport.clone();
+        tokio::spawn(async move {
+            if let Err(e) = transport.send(message).await {
+                error!("failed to send an alert email: {e}");
+            }
+        });
+    }
+}
+
+impl UpdateObserver for SmtpAlerter {
+    fn on_applied(&self, _report: &UpdateReport) {
+        let mut freshness = self
+            .freshness
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        freshness.last_success = Instant::now();
+        freshness.stale_reported = false;
+    }
+
+    fn on_error(&self, error: &AppError) {
+        let stale_for = {
+            let mut freshness = self
+                .freshness
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let stale_for = freshness.last_success.elapsed();
+            if freshness.stale_reported || stale_for < self.stale_after {
+                return;
+            }
+            freshness.stale_reported = true;
+            stale_for
+        };
+        self.send(
+            "blocklist is stale",
+            format!(
+                "The blocklist has not been updated for {} seconds.\n\nLast error: {error}\n",
+                stale_for.as_secs()
+            ),
+        );
+    }
+
+    fn on_stale(&self, source: &str, age: Duration) {
+        self.send(
+            "source is stale",
+            format!(
+                "The data of {source} has not been refreshed for {} seconds.\n",
+                age.as_secs()
+            ),
+        );
+    }
+
+    fn on_retries_exhausted(&self, error: &AppError) {
+        self.send(
+            "retries exhausted",
+            format!("The retry budget has been exhausted and the table was flushed.\n\n{error}\n"),
+        );
+    }
+
+    fn on_updates_stopped(&self, error: &AppError) {
+        self.send(
+            "updates stopped",
+            format!(
+                "Updates were paused after an error that retrying cannot fix; the last applied ruleset is kept.\n\n{error}\n"
+            ),
+        );
+    }
+
+    fn on_change_anomaly(&self, anomaly: &ChangeAnomaly) {
+        self.send(
+            "anomalous change rate",
+            format!(
+                "{anomaly}.\n\nThis usually means a corrupted feed or an incident at its provider.\n"
+            ),
+        );
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, AppError> {
+    address
+        .parse()
+        .map_err(|e| AppError::ParseError(format!("invalid email address: {address}: {e}")))
+}
@@ -0,0 +1,116 @@
+use crate::error::AppError;
+use crate::set::change_rate::ChangeAnomaly;
+use crate::set::fetch_policy::Proxies;
+use crate::set::observer::UpdateObserver;
+use crate::settings::Environment;
+use crate::utils::hostname;
+use log::{error, info};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Body of an alert posted to the webhook.
+#[derive(Debug, Clone, Serialize)]
+struct Alert<'a> {
+    host: &'a str,
+    /// Short kind of the alert, e.g., `change_anomaly`.
+    alert: &'a str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+}
+
+/// Posts alerts as JSON to a webhook, e.g., of a chat or an incident management tool.
+pub struct WebhookAlerter {
+    client: reqwest::Client,
+    url: String,
+    hostname: String,
+}
+
+impl WebhookAlerter {
+    /// Creates a `WebhookAlerter` posting to `NFTBLOCKD_ALERT_WEBHOOK`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when `NFTBLOCKD_ALERT_WEBHOOK` is not set, i.e., webhook alerts are disabled.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the URL is invalid or the HTTP client cannot be built.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let Some(url) = environment
+            .var("NFTBLOCKD_ALERT_WEBHOOK")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        reqwest::Url::parse(&url)
+            .map_err(|e| AppError::ParseError(format!("invalid NFTBLOCKD_ALERT_WEBHOOK: {e}")))?;
+        let client = Proxies::from_env(environment)?
+            .apply(reqwest::Client::builder())
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        info!("webhook alerts enabled");
+        Ok(Some(Self {
+            client,
+            url,
+            hostname: hostname(),
+        }))
+    }
+
+    /// Posts an alert in the background; failures are only logged.
+    fn send(&self, alert: &str, message: String, source: Option<&str>) {
+        let request = self.client.post(&self.url).json(&Alert {
+            host: &self.hostname,
+            alert,
+            message,
+            source,
+        });
+        tokio::spawn(async move {
+            // The URL may contain a token, so it is left out of the error.
+            if let Err(e) = request
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+            {
+                error!("failed to post a webhook alert: {}", e.without_url());
+            }
+        });
+    }
+}
+
+impl UpdateObserver for WebhookAlerter {
+    fn on_stale(&self, source: &str, age: Duration) {
+        self.send(
+            "stale",
+            format!(
+                "The data of {source} has not been refreshed for {} seconds.",
+                age.as_secs()
+            ),
+            Some(source),
+        );
+    }
+
+    fn on_retries_exhausted(&self, error: &AppError) {
+        self.send(
+            "retries_exhausted",
+            format!("The retry budget has been exhausted and the table was flushed: {error}"),
+            None,
+        );
+    }
+
+    fn on_updates_stopped(&self, error: &AppError) {
+        self.send(
+            "updates_stopped",
+            format!("Updates were paused and the last applied ruleset is kept: {error}"),
+            None,
+        );
+    }
+
+    fn on_change_anomaly(&self, anomaly: &ChangeAnomaly) {
+        self.send(
+            "change_anomaly",
+            format!("{anomaly}; the feed may be corrupted."),
+            Some(&anomaly.source),
+        );
+    }
+}
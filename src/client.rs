@@ -1,8 +1,17 @@
 use std::fmt;
+use std::time::Duration;
 
+#[cfg(feature = "history")]
+use nftblockd::utils::instance::state_dir;
 use nftblockd::{
-    error::AppError, grpc::ctl::nftblockd::status_service_client::StatusServiceClient,
+    error::AppError,
+    grpc::ctl::nftblockd::{
+        PauseRequest, TopOffendersRequest, TraceRequest, status_service_client::StatusServiceClient,
+    },
+    utils::{duration::parse_duration, instance::socket_path},
 };
+#[cfg(feature = "history")]
+use std::net::IpAddr;
 
 use clap::{Parser, Subcommand};
 use tonic::Response;
@@ -14,6 +23,10 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Name of the `nftblockd` instance to talk to.
+    #[arg(long, global = true, value_name = "NAME", env = "NFTBLOCKD_INSTANCE")]
+    pub instance: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -24,24 +37,99 @@ pub enum Commands {
         #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
         json: bool,
     },
+    /// Deletes the table, so that no traffic is dropped.
     Flush {
+        /// Only empties the blocklist sets and pauses updates until `resume`,
+        /// keeping the chains, counters, and anti-lockout sets.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        keep_table: bool,
+        #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Stops updating the blocklist, e.g., during maintenance, until `resume`.
+    Pause {
+        /// Also removes the table, so that no traffic is dropped while paused.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        disable_rules: bool,
+        #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Resumes updating the blocklist after `pause`, re-applying the table right away.
+    Resume {
+        #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Confirms the ruleset applied provisionally when `NFTBLOCKD_CONFIRM_TIMEOUT` is set.
+    Confirm {
+        #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Marks the packets from and to an address with `meta nftrace`, so that `nft monitor trace` shows
+    /// how they traverse the chains; the trace rules are removed after the timeout.
+    Trace {
+        /// Address or network whose packets are traced.
+        #[arg(required_unless_present = "stop")]
+        network: Option<String>,
+        /// Time after which the trace rules are removed, e.g., `30s` or `10m`.
+        #[arg(long, default_value = "10m", value_parser = parse_duration)]
+        timeout: Duration,
+        /// Removes the trace rules right away.
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "network")]
+        stop: bool,
         #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
         json: bool,
     },
     Status {
         #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
         json: bool,
+        /// Shows the N sources with the most dropped packets seen over nflog instead.
+        #[arg(long, value_name = "N")]
+        top: Option<u32>,
     },
     Stats {
         #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
         json: bool,
     },
+    /// Shows when the given IP address was blocked and by which source.
+    #[cfg(feature = "history")]
+    History {
+        ip: IpAddr,
+        /// Path to the history database written by `nftblockd`;
+        /// defaults to `history.db` in the state directory of the instance.
+        #[arg(long, env = "NFTBLOCKD_HISTORY_DB")]
+        db: Option<String>,
+        #[arg(short = 'j', long = "json", action = clap::ArgAction::SetTrue)]
+        json: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
     let cli = Cli::parse();
-    let mut client = StatusServiceClient::connect("unix:///run/nftblockd.sock").await?;
+    // The history is read directly from the database, so it works even when the daemon is down.
+    #[cfg(feature = "history")]
+    if let Commands::History { ip, db, json } = &cli.command {
+        let db = db.clone().unwrap_or_else(|| {
+            state_dir(cli.instance.as_deref())
+                .join("history.db")
+                .to_string_lossy()
+                .into_owned()
+        });
+        let entries = nftblockd::history::History::open_read_only(db)?.lookup(*ip)?;
+        if *json {
+            println!("{}", serde_json::to_string(&entries)?);
+        } else if entries.is_empty() {
+            println!("{ip} has never been blocked");
+        } else {
+            for entry in entries {
+                println!("{entry}");
+            }
+        }
+        return Ok(());
+    }
+    let mut client =
+        StatusServiceClient::connect(format!("unix://{}", socket_path(cli.instance.as_deref())))
+            .await?;
 
     match cli.command {
         Commands::Reload { json } => {
@@ -49,12 +137,56 @@ async fn main() -> Result<(), AppError> {
             let response = client.reload_table(request).await?;
             print_response(response, json)?;
         }
-        Commands::Flush { json } => {
+        Commands::Flush { keep_table, json } => {
+            let request = tonic::Request::new(());
+            let response = if keep_table {
+                client.flush_sets(request).await?
+            } else {
+                client.flush_table(request).await?
+            };
+            print_response(response, json)?;
+        }
+        Commands::Pause {
+            disable_rules,
+            json,
+        } => {
+            let request = tonic::Request::new(PauseRequest { disable_rules });
+            let response = client.pause_updates(request).await?;
+            print_response(response, json)?;
+        }
+        Commands::Resume { json } => {
             let request = tonic::Request::new(());
-            let response = client.flush_table(request).await?;
+            let response = client.resume_updates(request).await?;
+            print_response(response, json)?;
+        }
+        Commands::Confirm { json } => {
+            let request = tonic::Request::new(());
+            let response = client.confirm_apply(request).await?;
+            print_response(response, json)?;
+        }
+        Commands::Trace {
+            network,
+            timeout,
+            stop,
+            json,
+        } => {
+            let request = tonic::Request::new(TraceRequest {
+                network: network.unwrap_or_default(),
+                timeout_secs: timeout.as_secs(),
+                stop,
+            });
+            let response = client.start_trace(request).await?;
+            print_response(response, json)?;
+        }
+        Commands::Status {
+            json,
+            top: Some(limit),
+        } => {
+            let request = tonic::Request::new(TopOffendersRequest { limit });
+            let response = client.get_top_offenders(request).await?;
             print_response(response, json)?;
         }
-        Commands::Status { json } => {
+        Commands::Status { json, top: None } => {
             let request = tonic::Request::new(());
             let response = client.get_status(request).await?;
             print_response(response, json)?;
@@ -64,6 +196,8 @@ async fn main() -> Result<(), AppError> {
             let response = client.get_drop_stats(request).await?;
             print_response(response, json)?;
         }
+        #[cfg(feature = "history")]
+        Commands::History { .. } => {}
     }
 
     Ok(())
@@ -1,19 +1,82 @@
-use std::fmt::Debug;
+use serde::Serialize;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+/// Describes whether an error is worth retrying.
+///
+/// `Retryable` errors are caused by transient conditions (network, `nft` invocation, IO),
+/// while `Fatal` errors indicate a configuration or input problem that will not resolve itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Retryable,
+    Fatal,
+}
+
+/// A cloneable handle to the underlying error that caused an `AppError`.
+///
+/// `AppError` must stay `Clone` because it is stored in the service status,
+/// so the original error is kept behind an `Arc`.
+#[derive(Clone)]
+pub struct ErrorSource(Arc<dyn StdError + Send + Sync>);
+
+impl ErrorSource {
+    pub fn new<E>(error: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self(Arc::new(error))
+    }
+}
+
+impl Debug for ErrorSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+impl Display for ErrorSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl StdError for ErrorSource {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Sources are compared by their message only.
+impl PartialEq for ErrorSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for ErrorSource {}
+
 /// Represents the different types of errors that can occur in the application.
 ///
 /// Each variant of `AppError` corresponds to a specific error category
-/// and provides an appropriate error message.
+/// and provides an appropriate error message. Variants wrapping an external
+/// error keep it as their `source()`.
 #[allow(clippy::enum_variant_names)]
 #[derive(Error, Clone, PartialEq, Eq)]
 pub enum AppError {
     #[error("request error: {0}")]
-    RequestError(String),
+    RequestError(String, #[source] Option<ErrorSource>),
     #[error("file error: {0}")]
-    FileError(String),
-    #[error("nftables failed: {0}")]
-    NftablesError(String),
+    FileError(String, #[source] Option<ErrorSource>),
+    /// An `nft` invocation failed; the `NftablesFailure` tells why and how to remedy it.
+    #[error("nftables failed: {} ({})", .1, .0.hint())]
+    NftablesError(NftablesFailure, String, #[source] Option<ErrorSource>),
+    /// The update exceeded `NFTBLOCKD_MEMORY_LIMIT` and was aborted.
+    #[error("memory limit exceeded: {0}")]
+    MemoryError(String),
     #[error("could not parse IP address: {0}")]
     ParseError(String),
     #[error("could not parse json: {0}")]
@@ -25,34 +88,273 @@ pub enum AppError {
     #[error("grpc error: {0}")]
     GrpcError(String),
     #[error("io error: {0}")]
-    IoError(String),
+    IoError(String, #[source] Option<ErrorSource>),
+    #[error("database error: {0}")]
+    DatabaseError(String, #[source] Option<ErrorSource>),
+    #[error("privilege error: {0}")]
+    PrivilegeError(String),
+    /// Every problem found in the configuration, so that all of them can be fixed at once.
+    #[error("invalid configuration: {}", .0.join("; "))]
+    ConfigError(Vec<String>),
     #[error("nftblockd error: {0}")]
     NftblockdError(String),
 }
 
+impl AppError {
+    /// Classifies the error as `Retryable` or `Fatal`.
+    ///
+    /// # Returns
+    /// `ErrorClass::Retryable` for network, `nft`, IO and gRPC failures; `ErrorClass::Fatal` otherwise.
+    #[must_use]
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            AppError::RequestError(..)
+            | AppError::NftablesError(
+                NftablesFailure::ApplyFailed | NftablesFailure::Timeout,
+                ..,
+            )
+            | AppError::MemoryError(_)
+            | AppError::IoError(..)
+            | AppError::DatabaseError(..)
+            | AppError::GrpcError(_) => ErrorClass::Retryable,
+            AppError::NftablesError(
+                NftablesFailure::NftNotFound
+                | NftablesFailure::PermissionDenied
+                | NftablesFailure::KernelFeatureMissing,
+                ..,
+            )
+            | AppError::FileError(..)
+            | AppError::ParseError(_)
+            | AppError::DeserializeError(_)
+            | AppError::TableNotFound(_)
+            | AppError::ChainNotFound(_)
+            | AppError::PrivilegeError(_)
+            | AppError::ConfigError(_)
+            | AppError::NftblockdError(_) => ErrorClass::Fatal,
+        }
+    }
+
+    /// Tells which part of `nftblockd` failed, which also determines its exit code.
+    #[must_use]
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            AppError::FileError(..) | AppError::ParseError(_) | AppError::ConfigError(_) => {
+                FailureKind::Config
+            }
+            AppError::RequestError(..) | AppError::DeserializeError(_) => FailureKind::Fetch,
+            AppError::NftablesError(NftablesFailure::PermissionDenied, ..)
+            | AppError::PrivilegeError(_) => FailureKind::Privilege,
+            AppError::NftablesError(..)
+            | AppError::TableNotFound(_)
+            | AppError::ChainNotFound(_) => FailureKind::Apply,
+            AppError::IoError(..)
+            | AppError::DatabaseError(..)
+            | AppError::GrpcError(_)
+            | AppError::MemoryError(_)
+            | AppError::NftblockdError(_) => FailureKind::Other,
+        }
+    }
+
+    /// Tells why `nft` failed, or `None` for errors not raised by `nft`.
+    #[must_use]
+    pub fn nftables_failure(&self) -> Option<NftablesFailure> {
+        match self {
+            AppError::NftablesError(failure, ..) => Some(*failure),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        self.class() == ErrorClass::Retryable
+    }
+
+    /// Tells whether the error is caused by the host or the configuration rather than by fetched data.
+    ///
+    /// # Returns
+    /// `true` for a missing `nft`, missing privileges or kernel features, a missing table or chain,
+    /// and configuration errors; `false` for errors such as a malformed feed or a failed policy check.
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self,
+            AppError::NftablesError(
+                NftablesFailure::NftNotFound
+                    | NftablesFailure::PermissionDenied
+                    | NftablesFailure::KernelFeatureMissing,
+                ..,
+            ) | AppError::FileError(..)
+                | AppError::TableNotFound(_)
+                | AppError::ChainNotFound(_)
+                | AppError::PrivilegeError(_)
+                | AppError::ConfigError(_)
+        )
+    }
+}
+
+/// Why an `nft` invocation failed, so that automation can branch on the cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NftablesFailure {
+    /// `nft` rejected the ruleset, e.g., a syntax error or an object conflicting with another table.
+    ApplyFailed,
+    /// The `nft` executable is not installed or not in `PATH`.
+    NftNotFound,
+    /// The process may not change the ruleset, i.e., it lacks `CAP_NET_ADMIN`.
+    PermissionDenied,
+    /// The kernel lacks a feature of the ruleset, e.g., `nf_tables` or a set type built as a missing module.
+    KernelFeatureMissing,
+    /// `nft` did not finish within `NFTBLOCKD_APPLY_TIMEOUT` and was killed.
+    Timeout,
+}
+
+impl NftablesFailure {
+    /// Classifies a failed invocation by how `nft` could not be run or by the error it printed.
+    #[must_use]
+    pub fn classify(error: &nftables::helper::NftablesError) -> Self {
+        use nftables::helper::NftablesError;
+        match error {
+            NftablesError::NftExecution { inner, .. } => match inner.kind() {
+                std::io::ErrorKind::NotFound => Self::NftNotFound,
+                std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+                _ => Self::ApplyFailed,
+            },
+            NftablesError::NftFailed { stderr, .. } => Self::classify_message(stderr),
+            _ => Self::ApplyFailed,
+        }
+    }
+
+    /// Classifies the error output of `nft`, e.g., `Error: Could not process rule: Operation not permitted`.
+    #[must_use]
+    pub fn classify_message(message: &str) -> Self {
+        let message = message.to_ascii_lowercase();
+        if message.contains("operation not permitted") || message.contains("permission denied") {
+            Self::PermissionDenied
+        } else if message.contains("not supported") {
+            Self::KernelFeatureMissing
+        } else {
+            Self::ApplyFailed
+        }
+    }
+
+    /// How to remedy the failure.
+    #[must_use]
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::ApplyFailed => {
+                "nft rejected the ruleset; check the extra rules and the set names against the other tables"
+            }
+            Self::NftNotFound => "install nftables or add the directory of `nft` to PATH",
+            Self::PermissionDenied => {
+                "the process lacks CAP_NET_ADMIN; run `nftblockd --check-privileges`"
+            }
+            Self::KernelFeatureMissing => {
+                "the kernel lacks a feature of the ruleset; load the nf_tables modules or check the kernel configuration"
+            }
+            Self::Timeout => {
+                "nft was killed; raise NFTBLOCKD_APPLY_TIMEOUT or lower NFTBLOCKD_MAX_SET_SIZE"
+            }
+        }
+    }
+}
+
+/// What made `nftblockd` exit, documented by its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    /// Any other failure, e.g., of the control socket or the history database; exit code 1.
+    Other,
+    /// Invalid configuration, i.e., variables, arguments, or local files; exit code 2.
+    Config,
+    /// A blocklist could not be downloaded or decoded; exit code 3.
+    Fetch,
+    /// `nftables` rejected the ruleset; exit code 4.
+    Apply,
+    /// Missing privileges or a failure to drop them; exit code 5.
+    Privilege,
+}
+
+impl FailureKind {
+    #[must_use]
+    pub fn exit_code(self) -> u8 {
+        match self {
+            FailureKind::Other => 1,
+            FailureKind::Config => 2,
+            FailureKind::Fetch => 3,
+            FailureKind::Apply => 4,
+            FailureKind::Privilege => 5,
+        }
+    }
+}
+
+/// Machine-readable description of a fatal exit, written for orchestration systems.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailureReport {
+    pub kind: FailureKind,
+    pub exit_code: u8,
+    /// Whether retrying, e.g., restarting the service, may succeed without changing anything.
+    pub retryable: bool,
+    pub error: String,
+    /// Why `nft` failed, when it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nftables_failure: Option<NftablesFailure>,
+    /// Unix timestamp of the exit.
+    pub time: u64,
+}
+
+impl FailureReport {
+    #[must_use]
+    pub fn new(error: &AppError, time: SystemTime) -> Self {
+        let kind = error.kind();
+        Self {
+            kind,
+            exit_code: kind.exit_code(),
+            retryable: error.is_retryable(),
+            error: error.to_string(),
+            nftables_failure: error.nftables_failure(),
+            time: time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    /// Writes the report as JSON to `path`, replacing it atomically.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be written.
+    pub fn write(&self, path: &Path) -> Result<(), AppError> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
 impl Debug for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self)
     }
 }
 
-impl From<ureq::Error> for AppError {
-    /// Converts a `ureq::Error` into an `AppError`.
+impl From<std::io::Error> for AppError {
+    /// Converts a `std::io::Error` into an `AppError`.
     ///
     /// # Returns
-    /// A new `AppError` with the `RequestError`  and the corresponding error message.
-    fn from(error: ureq::Error) -> Self {
-        AppError::RequestError(error.to_string())
+    /// A new `AppError` with the `IoError`, the corresponding error message and the original error as its source.
+    fn from(value: std::io::Error) -> Self {
+        AppError::IoError(value.to_string(), Some(ErrorSource::new(value)))
     }
 }
 
-impl From<std::io::Error> for AppError {
-    /// Converts a `std::io::Error` into an `AppError`.
+#[cfg(feature = "history")]
+impl From<rusqlite::Error> for AppError {
+    /// Converts a `rusqlite::Error` into an `AppError`.
     ///
     /// # Returns
-    /// A new `AppError` with the `FileError`  and the corresponding error message.
-    fn from(value: std::io::Error) -> Self {
-        AppError::IoError(value.to_string())
+    /// A new `AppError` with the `DatabaseError`, the corresponding error message and the original error as its source.
+    fn from(value: rusqlite::Error) -> Self {
+        AppError::DatabaseError(value.to_string(), Some(ErrorSource::new(value)))
     }
 }
 
@@ -60,9 +362,13 @@ impl From<nftables::helper::NftablesError> for AppError {
     /// Converts an `nftables::helper::NftablesError` into an `AppError`.
     ///
     /// # Returns
-    /// A new `AppError` with the `NftablesError`  and the corresponding error message.
+    /// A new `AppError` with the `NftablesError`, the corresponding error message and the original error as its source.
     fn from(value: nftables::helper::NftablesError) -> Self {
-        AppError::NftablesError(value.to_string())
+        AppError::NftablesError(
+            NftablesFailure::classify(&value),
+            value.to_string(),
+            Some(ErrorSource::new(value)),
+        )
     }
 }
 
@@ -108,12 +414,14 @@ impl From<std::ffi::NulError> for AppError {
     }
 }
 
+#[cfg(feature = "control-socket")]
 impl From<tonic::transport::Error> for AppError {
     fn from(value: tonic::transport::Error) -> Self {
         AppError::GrpcError(value.to_string())
     }
 }
 
+#[cfg(feature = "control-socket")]
 impl From<tonic::Status> for AppError {
     fn from(value: tonic::Status) -> Self {
         AppError::GrpcError(value.to_string())
@@ -128,6 +436,6 @@ impl From<std::num::ParseIntError> for AppError {
 
 impl From<reqwest::Error> for AppError {
     fn from(value: reqwest::Error) -> Self {
-        AppError::RequestError(value.to_string())
+        AppError::RequestError(value.to_string(), Some(ErrorSource::new(value)))
     }
 }
@@ -0,0 +1,253 @@
+use crate::error::{AppError, FailureKind, NftablesFailure};
+use crate::set::change_rate::ChangeAnomaly;
+use crate::set::observer::{UpdateObserver, UpdateReport};
+use crate::settings::Environment;
+use log::debug;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An event of the update lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// An update cycle started.
+    CycleStart,
+    /// A blocklist was fetched from `source`.
+    Fetched {
+        source: String,
+        /// `false` when the source reported the blocklist as not modified.
+        modified: bool,
+        /// Number of entries of a modified blocklist.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        entries: Option<usize>,
+    },
+    /// The ruleset was applied.
+    Applied {
+        table: String,
+        ipv4_elements: usize,
+        ipv6_elements: usize,
+        invalid_entries: usize,
+        duration_ms: u128,
+        /// Peak resident memory of the update in bytes.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        peak_memory: Option<u64>,
+        /// Commits the git sources were applied at, by source.
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        revisions: BTreeMap<String, String>,
+    },
+    /// An update attempt failed.
+    Error {
+        kind: FailureKind,
+        retryable: bool,
+        error: String,
+        /// Why `nft` failed, when it did.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nftables_failure: Option<NftablesFailure>,
+    },
+    /// Fetching or parsing the blocklist of `source` failed.
+    SourceFailed {
+        source: String,
+        kind: FailureKind,
+        error: String,
+    },
+    /// A fresh fetch of `source` changed far more entries than its rolling average.
+    ChangeAnomaly {
+        source: String,
+        added: usize,
+        removed: usize,
+        /// Rolling average of the changed entries, rounded.
+        average: usize,
+    },
+}
+
+/// A line of the event stream: the event with its time and the instance that emitted it.
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Unix timestamp of the event.
+    time: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Where the events are written to.
+enum Sink {
+    Socket(UnixStream),
+    Fifo(File),
+}
+
+impl Sink {
+    /// Connects to the Unix socket or opens the FIFO at `path` without blocking.
+    fn open(path: &Path) -> io::Result<Self> {
+        if std::fs::metadata(path)?.file_type().is_fifo() {
+            // Fails with `ENXIO` while nobody reads the FIFO.
+            let fifo = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)?;
+            return Ok(Sink::Fifo(fifo));
+        }
+        let socket = UnixStream::connect(path)?;
+        socket.set_nonblocking(true)?;
+        Ok(Sink::Socket(socket))
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        match self {
+            Sink::Socket(socket) => socket.write_all(line),
+            Sink::Fifo(fifo) => fifo.write_all(line),
+        }
+    }
+}
+
+/// Emits the update lifecycle as JSON lines to a Unix stream socket or a FIFO,
+/// so that external automations can react without parsing the logs.
+///
+/// Every line is a JSON object with the `event` (e.g., `cycle_start`, `fetched`, `applied`, or `error`),
+/// its `time`, and the `instance` and `profile`, if any, e.g.:
+///
+/// ```text
+/// {"time":1760000000,"event":"applied","table":"blocklist","ipv4_elements":1024,"ipv6_elements":64,"invalid_entries":0,"duration_ms":812}
+/// ```
+///
+/// Delivery is best effort: events are dropped while nobody listens, and a reader too slow to keep up
+/// is disconnected, so that the update loop never waits for it.
+pub struct EventStream {
+    path: PathBuf,
+    instance: Option<String>,
+    profile: Option<String>,
+    sink: Mutex<Option<Sink>>,
+}
+
+impl EventStream {
+    /// Creates an `EventStream` writing to the Unix socket or FIFO at `path`; it is opened on the first event.
+    #[must_use]
+    pub fn new(path: PathBuf, instance: Option<String>) -> Self {
+        Self {
+            path,
+            instance,
+            profile: None,
+            sink: Mutex::new(None),
+        }
+    }
+
+    /// Creates an `EventStream` from `NFTBLOCKD_EVENTS_PATH`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when the variable is not set, i.e., the event stream is disabled.
+    #[must_use]
+    pub fn from_env(environment: &Environment, instance: Option<&str>) -> Option<Self> {
+        environment
+            .var("NFTBLOCKD_EVENTS_PATH")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|path| Self::new(PathBuf::from(path), instance.map(ToString::to_string)))
+    }
+
+    /// Labels every line with the name of the `profile`, if any.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<&str>) -> Self {
+        self.profile = profile.map(ToString::to_string);
+        self
+    }
+
+    /// Writes `event` as a single line, reconnecting when the previous reader went away.
+    pub fn emit(&self, event: &Event) {
+        let line = Line {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            instance: self.instance.as_deref(),
+            profile: self.profile.as_deref(),
+            event,
+        };
+        let Ok(mut line) = serde_json::to_vec(&line) else {
+            return;
+        };
+        line.push(b'\n');
+        let mut sink = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+        if sink.is_none() {
+            match Sink::open(&self.path) {
+                Ok(opened) => *sink = Some(opened),
+                Err(e) => {
+                    debug!("no reader of the event stream {}: {e}", self.path.display());
+                    return;
+                }
+            }
+        }
+        if let Some(opened) = sink.as_mut()
+            && let Err(e) = opened.write_line(&line)
+        {
+            debug!(
+                "dropped the reader of the event stream {}: {e}",
+                self.path.display()
+            );
+            *sink = None;
+        }
+    }
+}
+
+impl UpdateObserver for EventStream {
+    fn on_cycle_start(&self) {
+        self.emit(&Event::CycleStart);
+    }
+
+    fn on_fetched(&self, endpoint: &str, entries: Option<usize>) {
+        self.emit(&Event::Fetched {
+            source: endpoint.to_string(),
+            modified: entries.is_some(),
+            entries,
+        });
+    }
+
+    fn on_applied(&self, report: &UpdateReport) {
+        self.emit(&Event::Applied {
+            table: report.table_name.clone(),
+            ipv4_elements: report.ipv4_elements,
+            ipv6_elements: report.ipv6_elements,
+            invalid_entries: report.ipv4_invalid_entries + report.ipv6_invalid_entries,
+            duration_ms: report.duration.as_millis(),
+            peak_memory: report.peak_memory,
+            revisions: report.revisions.clone(),
+        });
+    }
+
+    fn on_error(&self, error: &AppError) {
+        self.emit(&Event::Error {
+            kind: error.kind(),
+            retryable: error.is_retryable(),
+            error: error.to_string(),
+            nftables_failure: error.nftables_failure(),
+        });
+    }
+
+    fn on_source_failed(&self, source: &str, error: &AppError) {
+        self.emit(&Event::SourceFailed {
+            source: source.to_string(),
+            kind: error.kind(),
+            error: error.to_string(),
+        });
+    }
+
+    fn on_change_anomaly(&self, anomaly: &ChangeAnomaly) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        self.emit(&Event::ChangeAnomaly {
+            source: anomaly.source.clone(),
+            added: anomaly.added,
+            removed: anomaly.removed,
+            average: anomaly.average.round() as usize,
+        });
+    }
+}
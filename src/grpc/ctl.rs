@@ -1,11 +1,12 @@
 use std::fmt::Display;
 
 use crate::grpc::ctl::nftblockd::{
-    ChainDropStats, DropStats, IpFamilyDropStats, Stats, StatusSummary,
+    ChainDropStats, DeferredSource, DropStats, IpFamilyDropStats, Offender, ReplicaStatus, Stats,
+    StatusSummary, TopOffenders,
 };
 
 pub mod nftblockd {
-    tonic::include_proto!("nftblockd");
+    include!(concat!(env!("OUT_DIR"), "/nftblockd.rs"));
 }
 
 impl Display for StatusSummary {
@@ -14,10 +15,77 @@ impl Display for StatusSummary {
             f,
             "status_code={} status={} message={}",
             self.status_code, self.status, self.message
+        )?;
+        if let Some(stats) = &self.stats {
+            let main = stats.main_blocklist_drop_stats.unwrap_or_default();
+            let custom = stats.custom_blocklist_drop_stats.unwrap_or_default();
+            write!(
+                f,
+                "\nblocklist_dropped: {}\ncustom_blocklist_dropped: {}",
+                main.combined.unwrap_or_default(),
+                custom.combined.unwrap_or_default()
+            )?;
+        }
+        if self.confirm_deadline > 0 {
+            write!(f, "\nconfirm_deadline={}", self.confirm_deadline)?;
+        }
+        for note in &self.notes {
+            write!(f, "\nnote={note}")?;
+        }
+        for deferred in &self.deferred {
+            write!(f, "\n{deferred}")?;
+        }
+        for replica in &self.replicas {
+            write!(f, "\n{replica}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DeferredSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deferred={} until={}", self.source, self.until)
+    }
+}
+
+impl Display for ReplicaStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "replica={} converged={} ipv4_digest={} ipv6_digest={} last_seen={}",
+            self.name, self.converged, self.ipv4_digest, self.ipv6_digest, self.last_seen
         )
     }
 }
 
+impl Display for Offender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "source={} packets={} set={} last_destination={}",
+            self.source, self.packets, self.set_name, self.last_destination
+        )?;
+        if self.last_port != 0 {
+            write!(f, " last_port={}", self.last_port)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TopOffenders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.offenders.is_empty() {
+            return write!(f, "no dropped packets seen over nflog");
+        }
+        let lines = self
+            .offenders
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
 impl Display for Stats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
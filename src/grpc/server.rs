@@ -1,43 +1,219 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
+#[cfg(feature = "control-socket")]
+use std::sync::PoisonError;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "control-socket")]
+use std::time::UNIX_EPOCH;
+use std::time::{Duration, SystemTime};
 
-use crate::grpc::ctl::nftblockd::StatusSummary;
-use crate::utils::status::NftblockdStatus;
-use crate::{
-    grpc::ctl::nftblockd::{Stats, status_service_server::StatusService},
-    utils::stats::Stats as StatsInfo,
+#[cfg(feature = "control-socket")]
+use crate::grpc::ctl::nftblockd::{
+    DeferredSource, PauseRequest, Stats, StatusSummary, TopOffenders, TopOffendersRequest,
+    TraceRequest, status_service_server::StatusService,
 };
+use crate::utils::stats::{SourceStats, Stats as StatsInfo};
+use crate::utils::status::NftblockdStatus;
 
+#[cfg(feature = "aggregator")]
+use crate::aggregator::Aggregator;
 use crate::error::AppError;
-use tokio::sync::RwLock;
+use crate::nflog::OffenderStats;
+use crate::nftables::confirm::Confirmation;
+use crate::set::quarantine::Quarantine;
+use ipnetwork::IpNetwork;
+use tokio::sync::{Notify, RwLock};
+#[cfg(feature = "control-socket")]
 use tonic::{Request, Response, Status};
 
 pub enum Command {
     Flush {
         respond_to: tokio::sync::oneshot::Sender<Result<(), AppError>>,
     },
+    /// Stops updating until `Resume` and empties the blocklist sets, keeping the table.
+    FlushSets {
+        respond_to: tokio::sync::oneshot::Sender<Result<(), AppError>>,
+    },
     Reload {
         respond_to: tokio::sync::oneshot::Sender<Result<(), AppError>>,
     },
+    /// Stops updating until `Resume`; `disable_rules` also removes the table.
+    Pause {
+        disable_rules: bool,
+        respond_to: tokio::sync::oneshot::Sender<Result<(), AppError>>,
+    },
+    Resume {
+        respond_to: tokio::sync::oneshot::Sender<Result<(), AppError>>,
+    },
+    /// Traces the packets of `network` until `timeout` passes; `None` removes the trace rules.
+    Trace {
+        network: Option<(IpNetwork, Duration)>,
+        respond_to: tokio::sync::oneshot::Sender<Result<(), AppError>>,
+    },
 }
 
 pub struct ServiceStatusStruct {
     pub status: Arc<RwLock<NftblockdStatus>>,
+    /// Drop counters accumulated from the rulesets replaced so far.
     pub stats: Arc<RwLock<StatsInfo>>,
+    /// Drop counters of the live ruleset, refreshed periodically.
+    pub live_stats: Arc<RwLock<StatsInfo>>,
+    /// Time of the last successfully applied ruleset.
+    pub last_applied: Arc<RwLock<Option<SystemTime>>>,
+    /// Sources deferred by a `Retry-After`, with the time they may be fetched again.
+    pub deferred: Arc<RwLock<BTreeMap<String, SystemTime>>>,
+    /// Time every source was last fetched successfully.
+    pub refreshed: Arc<RwLock<BTreeMap<String, SystemTime>>>,
+    /// Elements and failed fetches of every source.
+    pub sources: Arc<RwLock<BTreeMap<String, SourceStats>>>,
+    /// Rulesets applied provisionally and the last confirmed one to roll back to.
+    pub confirmation: Arc<RwLock<Confirmation>>,
+    /// Wakes the blocklist loop waiting for a confirmation.
+    pub confirmed: Arc<Notify>,
     pub command_channel: tokio::sync::mpsc::Sender<Command>,
+    /// Set when serving the blocklist to replicas; their convergence is included in the status.
+    #[cfg(feature = "aggregator")]
+    pub aggregator: Option<Aggregator>,
+    /// Dropped packets received over nflog, per matching set and per source.
+    pub offenders: Arc<Mutex<OffenderStats>>,
+    /// Name of the instance when several run on one host.
+    pub instance: Option<String>,
+    /// Name of the profile whose update loop this status belongs to; `None` for the main configuration.
+    pub profile: Option<String>,
+    /// Status of every profile run next to the main configuration, whose metrics are exported with it.
+    pub profiles: Arc<Mutex<Vec<Arc<ServiceStatusStruct>>>>,
+    /// Monitored addresses promoted into the quarantine set; disabled when `None`.
+    pub quarantine: Option<Arc<Mutex<Quarantine>>>,
+    /// Features the daemon runs without, included in the status.
+    pub notes: Vec<String>,
+}
+
+impl ServiceStatusStruct {
+    /// Creates the shared service state with the default status and empty stats.
+    #[must_use]
+    pub fn new(command_channel: tokio::sync::mpsc::Sender<Command>) -> Self {
+        Self {
+            status: Arc::new(RwLock::new(NftblockdStatus::default())),
+            stats: Arc::new(RwLock::new(StatsInfo::default())),
+            live_stats: Arc::new(RwLock::new(StatsInfo::default())),
+            last_applied: Arc::new(RwLock::new(None)),
+            deferred: Arc::new(RwLock::new(BTreeMap::new())),
+            refreshed: Arc::new(RwLock::new(BTreeMap::new())),
+            sources: Arc::new(RwLock::new(BTreeMap::new())),
+            confirmation: Arc::new(RwLock::new(Confirmation::default())),
+            confirmed: Arc::new(Notify::new()),
+            command_channel,
+            #[cfg(feature = "aggregator")]
+            aggregator: None,
+            offenders: Arc::new(Mutex::new(OffenderStats::default())),
+            instance: None,
+            profile: None,
+            profiles: Arc::new(Mutex::new(Vec::new())),
+            quarantine: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Returns the total drop counters: the accumulated ones plus those of the live ruleset.
+    pub async fn total_stats(&self) -> StatsInfo {
+        let mut stats = self.stats.read().await.clone();
+        stats += self.live_stats.read().await.clone();
+        stats
+    }
+
+    /// Labels the exported metrics with the name of the `instance`.
+    #[must_use]
+    pub fn with_instance(mut self, instance: Option<String>) -> Self {
+        self.instance = instance;
+        self
+    }
+
+    /// Labels the exported metrics, events, and logs with the name of the `profile`.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Notes the features the daemon runs without in the status, e.g., IPv6 on a kernel without it.
+    #[must_use]
+    pub fn with_notes(mut self, notes: Vec<String>) -> Self {
+        self.notes = notes;
+        self
+    }
+
+    /// Enables promoting monitored addresses into the quarantine set.
+    #[must_use]
+    pub fn with_quarantine(mut self, quarantine: Option<Quarantine>) -> Self {
+        self.quarantine = quarantine.map(|q| Arc::new(Mutex::new(q)));
+        self
+    }
+
+    /// Includes the convergence of the replicas of `aggregator` in the status.
+    #[cfg(feature = "aggregator")]
+    #[must_use]
+    pub fn with_aggregator(mut self, aggregator: Option<Aggregator>) -> Self {
+        self.aggregator = aggregator;
+        self
+    }
 }
 
+#[cfg(feature = "control-socket")]
 #[tonic::async_trait]
 impl StatusService for ServiceStatusStruct {
     async fn get_status(&self, _request: Request<()>) -> Result<Response<StatusSummary>, Status> {
-        let status = StatusSummary::from(self.status.read().await.clone());
+        let mut status = StatusSummary::from(self.status.read().await.clone());
+        status.stats = Some(self.total_stats().await.into());
+        status.last_applied = self
+            .last_applied
+            .read()
+            .await
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        status.deferred = self
+            .deferred
+            .read()
+            .await
+            .iter()
+            .map(|(source, until)| DeferredSource {
+                source: source.clone(),
+                until: until
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX)),
+            })
+            .collect();
+        status.notes.clone_from(&self.notes);
+        status.confirm_deadline = self
+            .confirmation
+            .read()
+            .await
+            .deadline()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        #[cfg(feature = "aggregator")]
+        if let Some(aggregator) = &self.aggregator {
+            status.replicas = aggregator.replicas();
+        }
         Ok(Response::new(status))
     }
 
     async fn get_drop_stats(&self, _request: Request<()>) -> Result<Response<Stats>, Status> {
-        let reply = Stats::from(self.stats.read().await.clone());
+        let reply = Stats::from(self.total_stats().await);
         Ok(Response::new(reply))
     }
 
+    async fn get_top_offenders(
+        &self,
+        request: Request<TopOffendersRequest>,
+    ) -> Result<Response<TopOffenders>, Status> {
+        let limit = usize::try_from(request.into_inner().limit).unwrap_or(usize::MAX);
+        let offenders = self
+            .offenders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .top(limit);
+        Ok(Response::new(TopOffenders { offenders }))
+    }
+
     async fn reload_table(&self, _request: Request<()>) -> Result<Response<StatusSummary>, Status> {
         let chan = tokio::sync::oneshot::channel();
         self.command_channel
@@ -72,4 +248,131 @@ impl StatusService for ServiceStatusStruct {
             ))),
         }
     }
+
+    async fn flush_sets(&self, _request: Request<()>) -> Result<Response<StatusSummary>, Status> {
+        let chan = tokio::sync::oneshot::channel();
+        self.command_channel
+            .send(Command::FlushSets { respond_to: chan.0 })
+            .await
+            .ok();
+
+        match chan.1.await {
+            Ok(Ok(())) => Ok(Response::new(StatusSummary::new_ok(
+                "sets flushed; updates paused until resumed",
+            ))),
+            Ok(Err(e)) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+            Err(e) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+        }
+    }
+
+    async fn pause_updates(
+        &self,
+        request: Request<PauseRequest>,
+    ) -> Result<Response<StatusSummary>, Status> {
+        let chan = tokio::sync::oneshot::channel();
+        self.command_channel
+            .send(Command::Pause {
+                disable_rules: request.into_inner().disable_rules,
+                respond_to: chan.0,
+            })
+            .await
+            .ok();
+
+        match chan.1.await {
+            Ok(Ok(())) => Ok(Response::new(StatusSummary::new_ok("updates paused"))),
+            Ok(Err(e)) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+            Err(e) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+        }
+    }
+
+    async fn resume_updates(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<StatusSummary>, Status> {
+        let chan = tokio::sync::oneshot::channel();
+        self.command_channel
+            .send(Command::Resume { respond_to: chan.0 })
+            .await
+            .ok();
+
+        match chan.1.await {
+            Ok(Ok(())) => Ok(Response::new(StatusSummary::new_ok("updates resumed"))),
+            Ok(Err(e)) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+            Err(e) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+        }
+    }
+
+    async fn confirm_apply(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<StatusSummary>, Status> {
+        match self.confirmation.write().await.confirm() {
+            Ok(()) => {
+                self.confirmed.notify_one();
+                Ok(Response::new(StatusSummary::new_ok("ruleset confirmed")))
+            }
+            Err(e) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+        }
+    }
+
+    async fn start_trace(
+        &self,
+        request: Request<TraceRequest>,
+    ) -> Result<Response<StatusSummary>, Status> {
+        let request = request.into_inner();
+        let network = if request.stop {
+            None
+        } else {
+            let Ok(network) = request.network.trim().parse::<IpNetwork>() else {
+                return Ok(Response::new(StatusSummary::new_failed(
+                    format!("invalid address or network: {}", request.network).as_str(),
+                )));
+            };
+            if request.timeout_secs == 0 {
+                return Ok(Response::new(StatusSummary::new_failed(
+                    "the trace timeout must be positive",
+                )));
+            }
+            Some((network, Duration::from_secs(request.timeout_secs)))
+        };
+        let message = match &network {
+            Some((network, timeout)) => format!(
+                "tracing {network} for {} s; run `nft monitor trace` to follow its packets",
+                timeout.as_secs()
+            ),
+            None => "trace stopped".to_string(),
+        };
+        let chan = tokio::sync::oneshot::channel();
+        self.command_channel
+            .send(Command::Trace {
+                network,
+                respond_to: chan.0,
+            })
+            .await
+            .ok();
+
+        match chan.1.await {
+            Ok(Ok(())) => Ok(Response::new(StatusSummary::new_ok(message.as_str()))),
+            Ok(Err(e)) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+            Err(e) => Ok(Response::new(StatusSummary::new_failed(
+                e.to_string().as_str(),
+            ))),
+        }
+    }
 }
@@ -0,0 +1,355 @@
+use crate::error::AppError;
+use crate::nftables::builder::{RuleProto, SetElements};
+use crate::nftables::element_label;
+use crate::set::manual::ManualSet;
+use crate::set::observer::UpdateObserver;
+use ipnetwork::IpNetwork;
+use log::{debug, error, info};
+use nftables::expr::Expression;
+use rusqlite::{Connection, OpenFlags, params};
+use serde::Serialize;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS history (
+    prefix     TEXT    NOT NULL,
+    source     TEXT    NOT NULL,
+    family     INTEGER NOT NULL,
+    start      BLOB    NOT NULL,
+    end        BLOB    NOT NULL,
+    first_seen INTEGER NOT NULL,
+    last_seen  INTEGER NOT NULL,
+    appearances INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (prefix, source)
+);
+CREATE INDEX IF NOT EXISTS history_range ON history (family, start, end);
+CREATE TABLE IF NOT EXISTS escalations (
+    prefix    TEXT    NOT NULL PRIMARY KEY,
+    escalated INTEGER NOT NULL
+);
+";
+
+/// Adds the `appearances` column to databases created before it existed.
+const MIGRATION: &str = "ALTER TABLE history ADD COLUMN appearances INTEGER NOT NULL DEFAULT 1";
+
+/// A single blocked prefix as recorded in the history database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryEntry {
+    /// The blocked element, e.g., `192.0.2.0/24`, `192.0.2.1-192.0.2.9` or `192.0.2.1`.
+    pub prefix: String,
+    /// The endpoint the element was fetched from.
+    pub source: String,
+    /// Unix timestamp of the first update that contained the element.
+    pub first_seen: i64,
+    /// Unix timestamp of the last update that contained the element.
+    pub last_seen: i64,
+}
+
+impl Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "prefix={} source={} first_seen={} last_seen={}",
+            self.prefix,
+            self.source,
+            format_timestamp(self.first_seen),
+            format_timestamp(self.last_seen)
+        )
+    }
+}
+
+/// A blocked element prepared for storage.
+///
+/// Bounds are stored as big-endian octets, so `BLOB` comparison in `SQLite` orders them numerically
+/// within a family.
+struct HistoryRow {
+    prefix: String,
+    family: u8,
+    start: Vec<u8>,
+    end: Vec<u8>,
+}
+
+/// Embedded `SQLite` database recording when each prefix was blocked and by which source.
+///
+/// Registered as an `UpdateObserver`, it upserts every applied element with its first-seen
+/// and last-seen timestamps, and counts how often it reappeared in a source after being left out.
+/// Writes run on the blocking thread pool.
+#[derive(Clone)]
+pub struct History {
+    connection: Arc<Mutex<Connection>>,
+    escalation: Option<Escalation>,
+}
+
+/// Repeat offenders that are escalated to permanent entries of the manual set.
+#[derive(Debug, Clone)]
+struct Escalation {
+    /// Appearances, summed over all sources, after which a prefix is escalated.
+    after: u64,
+    /// File of the manual set.
+    manual_path: PathBuf,
+}
+
+impl History {
+    /// Opens (or creates) the history database at `path`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the database cannot be opened or its schema cannot be created.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, AppError> {
+        if let Some(parent) = path.as_ref().parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        let migrated = connection
+            .prepare("SELECT 1 FROM pragma_table_info('history') WHERE name = 'appearances'")?
+            .exists([])?;
+        if !migrated {
+            connection.execute_batch(MIGRATION)?;
+        }
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            escalation: None,
+        })
+    }
+
+    /// Opens an existing history database read-only, e.g., for `nftblockdctl history`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the database does not exist or cannot be opened.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self, AppError> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            escalation: None,
+        })
+    }
+
+    /// Escalates every prefix that appeared `after` times, summed over all sources, to a permanent entry
+    /// of the manual set stored at `manual_path`, so that it stays blocked once the feeds drop it.
+    ///
+    /// A prefix appears when a source first lists it and again whenever the source lists it after
+    /// leaving it out of its previous update. Escalation is off when `after` is `None`.
+    #[must_use]
+    pub fn with_escalation(mut self, after: Option<u64>, manual_path: PathBuf) -> Self {
+        self.escalation = after.map(|after| Escalation { after, manual_path });
+        self
+    }
+
+    /// Records all `elements` fetched from `source` as seen at `timestamp`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the transaction fails.
+    pub fn record(
+        &self,
+        source: &str,
+        elements: &SetElements<'_>,
+        timestamp: i64,
+    ) -> Result<(), AppError> {
+        let rows = elements.iter().filter_map(history_row).collect::<Vec<_>>();
+        self.record_rows(source, &rows, timestamp)
+    }
+
+    fn record_rows(
+        &self,
+        source: &str,
+        rows: &[HistoryRow],
+        timestamp: i64,
+    ) -> Result<(), AppError> {
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let transaction = connection.transaction()?;
+        {
+            // Prefixes not seen in the previous update of the source reappear.
+            let previous: Option<i64> = transaction.query_row(
+                "SELECT MAX(last_seen) FROM history WHERE source = ?1",
+                params![source],
+                |row| row.get(0),
+            )?;
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO history (prefix, source, family, start, end, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+                 ON CONFLICT (prefix, source) DO UPDATE SET
+                     appearances = appearances + (last_seen < ?7),
+                     last_seen = excluded.last_seen",
+            )?;
+            for row in rows {
+                statement.execute(params![
+                    row.prefix,
+                    source,
+                    row.family,
+                    row.start,
+                    row.end,
+                    timestamp,
+                    previous.unwrap_or(timestamp)
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        debug!("recorded {} history entries from {source}", rows.len());
+        Ok(())
+    }
+
+    /// Adds the prefixes that reached the escalation threshold to the manual set, skipping those already in it.
+    /// Every prefix is escalated only once, so that removing its entry from the manual set sticks.
+    ///
+    /// # Returns
+    /// The escalated prefixes.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the query fails or the manual set cannot be read or written.
+    pub fn escalate(&self) -> Result<Vec<String>, AppError> {
+        let Some(escalation) = &self.escalation else {
+            return Ok(Vec::new());
+        };
+        let repeated = {
+            let connection = self
+                .connection
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let mut statement = connection.prepare_cached(
+                "SELECT prefix, SUM(appearances) FROM history
+                 WHERE prefix NOT IN (SELECT prefix FROM escalations)
+                 GROUP BY prefix HAVING SUM(appearances) >= ?1
+                 ORDER BY prefix",
+            )?;
+            statement
+                .query_map(params![escalation.after], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let mut manual = ManualSet::load(&escalation.manual_path)?;
+        let now = SystemTime::now();
+        let mut escalated = Vec::new();
+        for (prefix, appearances) in repeated {
+            // Ranges cannot be stored in the manual set.
+            if prefix.contains('-') || manual.contains(&prefix) {
+                continue;
+            }
+            manual.add(
+                &prefix,
+                None,
+                Some(format!("escalated after {appearances} appearances")),
+                now,
+            )?;
+            info!("escalated {prefix} to the manual set after {appearances} appearances");
+            escalated.push(prefix);
+        }
+        if escalated.is_empty() {
+            return Ok(escalated);
+        }
+        manual.save()?;
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let transaction = connection.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT OR IGNORE INTO escalations (prefix, escalated) VALUES (?1, ?2)",
+            )?;
+            for prefix in &escalated {
+                statement.execute(params![prefix, unix_timestamp()])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(escalated)
+    }
+
+    /// Looks up every recorded element that contains `ip`, oldest first.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the query fails.
+    pub fn lookup(&self, ip: IpAddr) -> Result<Vec<HistoryEntry>, AppError> {
+        let (family, octets) = family_and_octets(ip);
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut statement = connection.prepare(
+            "SELECT prefix, source, first_seen, last_seen FROM history
+             WHERE family = ?1 AND start <= ?2 AND end >= ?2
+             ORDER BY first_seen, prefix, source",
+        )?;
+        let entries = statement
+            .query_map(params![family, octets], |row| {
+                Ok(HistoryEntry {
+                    prefix: row.get(0)?,
+                    source: row.get(1)?,
+                    first_seen: row.get(2)?,
+                    last_seen: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+}
+
+impl UpdateObserver for History {
+    fn on_blocked(&self, source: &str, _proto: &RuleProto, elements: &SetElements<'_>) {
+        let rows = elements.iter().filter_map(history_row).collect::<Vec<_>>();
+        let history = self.clone();
+        let source = source.to_string();
+        let timestamp = unix_timestamp();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = history.record_rows(&source, &rows, timestamp) {
+                error!("failed to record blocklist history: {e}");
+                return;
+            }
+            if let Err(e) = history.escalate() {
+                error!("failed to escalate repeat offenders: {e}");
+            }
+        });
+    }
+}
+
+/// Converts a set element into its label and address bounds.
+fn history_row(element: &Expression<'_>) -> Option<HistoryRow> {
+    let prefix = element_label(element)?;
+    let (start, end) = if let Some((start, end)) = prefix.split_once('-') {
+        (IpAddr::from_str(start).ok()?, IpAddr::from_str(end).ok()?)
+    } else {
+        let network = IpNetwork::from_str(&prefix).ok()?;
+        (network.network(), network.broadcast())
+    };
+    let (family, start) = family_and_octets(start);
+    let (_, end) = family_and_octets(end);
+    Some(HistoryRow {
+        prefix,
+        family,
+        start,
+        end,
+    })
+}
+
+fn family_and_octets(ip: IpAddr) -> (u8, Vec<u8>) {
+    match ip {
+        IpAddr::V4(ip) => (4, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (6, ip.octets().to_vec()),
+    }
+}
+
+fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    time::OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|t| {
+            t.format(&time::format_description::well_known::Rfc3339)
+                .ok()
+        })
+        .unwrap_or(timestamp.to_string())
+}
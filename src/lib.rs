@@ -1,5 +1,15 @@
+#[cfg(feature = "aggregator")]
+pub mod aggregator;
+#[cfg(feature = "alerts")]
+pub mod alert;
 pub mod error;
+pub mod events;
 pub mod grpc;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod metrics;
+pub mod nflog;
 pub mod nftables;
 pub mod set;
+pub mod settings;
 pub mod utils;
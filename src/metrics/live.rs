@@ -0,0 +1,28 @@
+use crate::grpc::server::ServiceStatusStruct;
+use crate::nftables::config::NftConfig;
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Periodically reads the drop counters of the live ruleset into `status.live_stats`.
+pub async fn stats_loop(
+    status: Arc<ServiceStatusStruct>,
+    config: NftConfig<'_>,
+    interval: Duration,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        match config.scrape_stats() {
+            Ok(stats) => *status.live_stats.write().await = stats,
+            Err(e) => warn!("failed to read the drop counters: {e}"),
+        }
+        tokio::select! {
+            () = tokio::time::sleep(interval.max(Duration::from_secs(1))) => {}
+            () = cancellation_token.cancelled() => {
+                info!("stopping stats loop");
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+#[cfg(feature = "metrics")]
+pub mod statsd;
+#[cfg(feature = "metrics")]
+pub mod textfile;
+
+#[cfg(any(feature = "metrics", feature = "control-socket"))]
+mod live;
+#[cfg(any(feature = "metrics", feature = "control-socket"))]
+pub use live::stats_loop;
+
+#[cfg(feature = "metrics")]
+use crate::error::AppError;
+#[cfg(feature = "metrics")]
+use crate::grpc::server::ServiceStatusStruct;
+use crate::nflog::OffenderStats;
+use crate::utils::stats::{ChainDropStats, DropStats, SourceStats, Stats};
+use crate::utils::status::NftblockdStatus;
+#[cfg(feature = "metrics")]
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+#[cfg(feature = "metrics")]
+use log::info;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+#[cfg(feature = "metrics")]
+use std::net::SocketAddr;
+#[cfg(feature = "metrics")]
+use std::sync::{Arc, PoisonError};
+use std::time::SystemTime;
+#[cfg(feature = "metrics")]
+use tokio::net::TcpListener;
+#[cfg(feature = "metrics")]
+use tokio_util::sync::CancellationToken;
+
+/// Renders the status and the drop counters in the Prometheus text exposition format.
+///
+/// Counters are labeled by `set` (`blocklist` or `custom_blocklist`), `chain` and `family`.
+#[must_use]
+pub fn render_metrics(status: &NftblockdStatus, stats: &Stats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_status Status of the daemon: 0 ok, 1 pending, 2 pre-fail, 3 failed, 4 paused.\n\
+         # TYPE nftblockd_status gauge\n\
+         nftblockd_status {}",
+        status.get_status_code()
+    );
+    let sets = [
+        ("blocklist", &stats.main_blocklist_drop_stats),
+        ("custom_blocklist", &stats.custom_blocklist_drop_stats),
+    ];
+    for (metric, help, value) in [
+        (
+            "nftblockd_dropped_packets_total",
+            "Packets dropped by the nftblockd rules.",
+            (|s: &DropStats| s.packets) as fn(&DropStats) -> u64,
+        ),
+        (
+            "nftblockd_dropped_bytes_total",
+            "Bytes dropped by the nftblockd rules.",
+            |s: &DropStats| s.bytes,
+        ),
+    ] {
+        let _ = writeln!(out, "# HELP {metric} {help}\n# TYPE {metric} counter");
+        for (set, chain_stats) in sets {
+            for (chain, family, drop_stats) in per_direction(chain_stats) {
+                let _ = writeln!(
+                    out,
+                    "{metric}{{set=\"{set}\",chain=\"{chain}\",family=\"{family}\"}} {}",
+                    value(drop_stats)
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Adds an `nftblockd_instance` label to every sample of `metrics` when running as a named instance.
+///
+/// The label is not called `instance`, because Prometheus uses that one for the scrape target.
+#[must_use]
+pub fn with_instance_label(metrics: &str, instance: Option<&str>) -> String {
+    with_labels(metrics, instance, None)
+}
+
+/// Adds the `nftblockd_instance` and `nftblockd_profile` labels to every sample of `metrics`,
+/// leaving out those that are not set.
+#[must_use]
+pub fn with_labels(metrics: &str, instance: Option<&str>, profile: Option<&str>) -> String {
+    let labels = [
+        ("nftblockd_instance", instance),
+        ("nftblockd_profile", profile),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| value.map(|value| format!("{label}=\"{value}\"")))
+    .collect::<Vec<_>>()
+    .join(",");
+    if labels.is_empty() {
+        return metrics.to_string();
+    }
+    let mut out = String::with_capacity(metrics.len());
+    for line in metrics.lines() {
+        if line.starts_with('#') {
+            out.push_str(line);
+        } else if let Some((name, rest)) = line.split_once('{') {
+            let _ = write!(out, "{name}{{{labels},{rest}");
+        } else if let Some((name, value)) = line.split_once(' ') {
+            let _ = write!(out, "{name}{{{labels}}} {value}");
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Number of top offenders exposed as metrics; more would blow up the label cardinality.
+const TOP_OFFENDERS: usize = 10;
+
+/// Renders the packets received over nflog per set and for the top offenders.
+#[must_use]
+pub fn render_offender_metrics(offenders: &OffenderStats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_nflog_packets_total Dropped packets received over nflog.\n\
+         # TYPE nftblockd_nflog_packets_total counter"
+    );
+    for (set, packets) in &offenders.per_set {
+        let _ = writeln!(
+            out,
+            "nftblockd_nflog_packets_total{{set=\"{set}\"}} {packets}"
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_top_offender_packets Dropped packets of the most active sources.\n\
+         # TYPE nftblockd_top_offender_packets gauge"
+    );
+    for offender in offenders.top(TOP_OFFENDERS) {
+        let _ = writeln!(
+            out,
+            "nftblockd_top_offender_packets{{source=\"{}\",set=\"{}\"}} {}",
+            offender.source, offender.set_name, offender.packets
+        );
+    }
+    out
+}
+
+/// Renders the age of the data of every source in the Prometheus text format.
+#[must_use]
+pub fn render_source_metrics(refreshed: &BTreeMap<String, SystemTime>, now: SystemTime) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_source_data_age_seconds Time since the source was last fetched successfully.\n\
+         # TYPE nftblockd_source_data_age_seconds gauge"
+    );
+    for (source, fetched) in refreshed {
+        let age = now.duration_since(*fetched).unwrap_or_default().as_secs();
+        let _ = writeln!(
+            out,
+            "nftblockd_source_data_age_seconds{{source=\"{source}\"}} {age}"
+        );
+    }
+    out
+}
+
+/// Renders the elements every source contributed to the last applied blocklists, its failed fetches,
+/// and its unrouted entries.
+#[must_use]
+pub fn render_source_stats(sources: &BTreeMap<String, SourceStats>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_source_elements Elements the source contributed to the last applied blocklist.\n\
+         # TYPE nftblockd_source_elements gauge"
+    );
+    for (source, stats) in sources {
+        for (family, elements) in [("ipv4", stats.ipv4_elements), ("ipv6", stats.ipv6_elements)] {
+            let _ = writeln!(
+                out,
+                "nftblockd_source_elements{{source=\"{source}\",family=\"{family}\"}} {elements}"
+            );
+        }
+    }
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_source_failures_total Failed fetches of the source.\n\
+         # TYPE nftblockd_source_failures_total counter"
+    );
+    for (source, stats) in sources {
+        let _ = writeln!(
+            out,
+            "nftblockd_source_failures_total{{source=\"{source}\"}} {}",
+            stats.failures
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP nftblockd_source_unrouted Entries of the last fetch of the source no announced route covers.\n\
+         # TYPE nftblockd_source_unrouted gauge"
+    );
+    for (source, stats) in sources {
+        let _ = writeln!(
+            out,
+            "nftblockd_source_unrouted{{source=\"{source}\"}} {}",
+            stats.unrouted
+        );
+    }
+    out
+}
+
+/// Flattens the counters of a set into `(chain, family, counters)` triples.
+fn per_direction(stats: &ChainDropStats) -> [(&'static str, &'static str, &DropStats); 4] {
+    [
+        ("prerouting", "ipv4", &stats.prerouting.ipv4),
+        ("prerouting", "ipv6", &stats.prerouting.ipv6),
+        ("postrouting", "ipv4", &stats.postrouting.ipv4),
+        ("postrouting", "ipv6", &stats.postrouting.ipv6),
+    ]
+}
+
+/// Renders the status, the drop counters, and the metrics of the sources of one update loop.
+#[cfg(feature = "metrics")]
+async fn render_status_metrics(status: &ServiceStatusStruct) -> String {
+    let mut body = render_metrics(&*status.status.read().await, &status.total_stats().await);
+    body.push_str(&render_source_metrics(
+        &*status.refreshed.read().await,
+        SystemTime::now(),
+    ));
+    body.push_str(&render_source_stats(&*status.sources.read().await));
+    body
+}
+
+/// Serves `/metrics` on `addr` until `cancellation_token` is cancelled.
+///
+/// # Errors
+/// Will return `AppError` when the address cannot be bound or the server fails.
+#[cfg(feature = "metrics")]
+pub async fn serve_metrics(
+    addr: SocketAddr,
+    status: Arc<ServiceStatusStruct>,
+    cancellation_token: CancellationToken,
+) -> Result<(), AppError> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("serving metrics on http://{addr}/metrics");
+    let router = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(status);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move { cancellation_token.cancelled().await })
+        .await?;
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+async fn metrics(State(status): State<Arc<ServiceStatusStruct>>) -> impl IntoResponse {
+    let mut body = render_status_metrics(&status).await;
+    body.push_str(&render_offender_metrics(
+        &status
+            .offenders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    ));
+    let mut body = with_labels(&body, status.instance.as_deref(), status.profile.as_deref());
+    let profiles = status
+        .profiles
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    // The samples of the profiles follow those of the main configuration, described only once.
+    for profile in profiles {
+        let metrics = with_labels(
+            &render_status_metrics(&profile).await,
+            profile.instance.as_deref(),
+            profile.profile.as_deref(),
+        );
+        for line in metrics.lines().filter(|line| !line.starts_with('#')) {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+}
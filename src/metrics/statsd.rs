@@ -0,0 +1,207 @@
+use crate::error::AppError;
+use crate::nftables::builder::{RuleProto, SetElements};
+use crate::set::change_rate::ChangeAnomaly;
+use crate::set::observer::{UpdateObserver, UpdateReport};
+use crate::settings::Environment;
+use log::debug;
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Emits the timings and element counts of every update to a statsd or DogStatsD agent over UDP.
+///
+/// Metrics are sent as one datagram per update; delivery is best effort.
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    /// DogStatsD tags (e.g., `env:prod,role:edge`) appended to every metric; plain statsd does not support them.
+    tags: Option<String>,
+}
+
+impl StatsdSink {
+    /// Creates a `StatsdSink` sending to `addr`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the socket cannot be created or `addr` cannot be resolved.
+    pub fn new(addr: &str, prefix: &str, tags: Option<String>) -> Result<Self, AppError> {
+        let target = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            AppError::ParseError(format!("statsd address does not resolve: {addr}"))
+        })?;
+        let local: SocketAddr = if target.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            prefix: prefix.to_string(),
+            tags,
+        })
+    }
+
+    /// Creates a `StatsdSink` from the `NFTBLOCKD_STATSD_*` environment variables.
+    /// The default prefix includes the name of the `instance`, if any.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when `NFTBLOCKD_STATSD_ADDR` is not set, i.e., the sink is disabled.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the socket cannot be created or the address cannot be resolved.
+    pub fn from_env(
+        environment: &Environment,
+        instance: Option<&str>,
+    ) -> Result<Option<Self>, AppError> {
+        let Some(addr) = environment
+            .var("NFTBLOCKD_STATSD_ADDR")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        let prefix = environment
+            .var("NFTBLOCKD_STATSD_PREFIX")
+            .unwrap_or(match instance {
+                Some(instance) => format!("nftblockd.{instance}"),
+                None => "nftblockd".to_string(),
+            });
+        let tags = environment
+            .var("NFTBLOCKD_STATSD_TAGS")
+            .ok()
+            .filter(|s| !s.is_empty());
+        Self::new(&addr, &prefix, tags).map(Some)
+    }
+
+    /// Appends the name of the `profile`, if any, to the prefix, e.g., `nftblockd.tenant-a`.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<&str>) -> Self {
+        if let Some(profile) = profile {
+            self.prefix = format!("{}.{profile}", self.prefix);
+        }
+        self
+    }
+
+    /// Formats a single metric line, e.g., `nftblockd.apply_time:12|ms|#env:prod`.
+    fn line(&self, out: &mut String, name: &str, value: impl std::fmt::Display, kind: &str) {
+        self.tagged_line(out, name, value, kind, &[]);
+    }
+
+    /// Formats a metric line with DogStatsD `tags` (e.g., `source:https://...`) added to the configured ones;
+    /// plain statsd does not support tags, so it cannot break these metrics down.
+    fn tagged_line(
+        &self,
+        out: &mut String,
+        name: &str,
+        value: impl std::fmt::Display,
+        kind: &str,
+        tags: &[(&str, &str)],
+    ) {
+        let _ = write!(out, "{}.{name}:{value}|{kind}", self.prefix);
+        let tags = self
+            .tags
+            .iter()
+            .cloned()
+            .chain(tags.iter().map(|(key, value)| format!("{key}:{value}")))
+            .collect::<Vec<_>>();
+        if !tags.is_empty() {
+            let _ = write!(out, "|#{}", tags.join(","));
+        }
+        out.push('\n');
+    }
+
+    fn timing(&self, out: &mut String, name: &str, duration: Duration) {
+        self.line(out, name, duration.as_millis(), "ms");
+    }
+
+    fn send(&self, payload: &str) {
+        // A missing agent must never slow down or fail an update.
+        if let Err(e) = self.socket.send(payload.trim_end().as_bytes()) {
+            debug!("failed to send statsd metrics: {e}");
+        }
+    }
+}
+
+impl UpdateObserver for StatsdSink {
+    fn on_applied(&self, report: &UpdateReport) {
+        let mut out = String::new();
+        self.timing(&mut out, "fetch_time", report.fetch_duration);
+        self.timing(&mut out, "parse_time", report.parse_duration);
+        self.timing(&mut out, "apply_time", report.apply_duration);
+        self.timing(&mut out, "update_time", report.duration);
+        self.line(&mut out, "elements.ipv4", report.ipv4_elements, "g");
+        self.line(&mut out, "elements.ipv6", report.ipv6_elements, "g");
+        self.line(
+            &mut out,
+            "invalid_entries.ipv4",
+            report.ipv4_invalid_entries,
+            "g",
+        );
+        self.line(
+            &mut out,
+            "invalid_entries.ipv6",
+            report.ipv6_invalid_entries,
+            "g",
+        );
+        if let Some(peak) = report.peak_memory {
+            self.line(&mut out, "memory.peak", peak, "g");
+        }
+        self.line(&mut out, "updates", 1, "c");
+        self.send(&out);
+    }
+
+    fn on_blocked(&self, source: &str, proto: &RuleProto, elements: &SetElements<'_>) {
+        let family = match proto {
+            RuleProto::Ip6 => "ipv6",
+            _ => "ipv4",
+        };
+        let mut out = String::new();
+        self.tagged_line(
+            &mut out,
+            "source_elements",
+            elements.len(),
+            "g",
+            &[("source", source), ("family", family)],
+        );
+        self.send(&out);
+    }
+
+    fn on_error(&self, _error: &AppError) {
+        let mut out = String::new();
+        self.line(&mut out, "update_failures", 1, "c");
+        self.send(&out);
+    }
+
+    fn on_source_failed(&self, source: &str, _error: &AppError) {
+        let mut out = String::new();
+        self.tagged_line(&mut out, "source_failures", 1, "c", &[("source", source)]);
+        self.send(&out);
+    }
+
+    fn on_retries_exhausted(&self, _error: &AppError) {
+        let mut out = String::new();
+        self.line(&mut out, "retries_exhausted", 1, "c");
+        self.send(&out);
+    }
+
+    fn on_updates_stopped(&self, _error: &AppError) {
+        let mut out = String::new();
+        self.line(&mut out, "updates_stopped", 1, "c");
+        self.send(&out);
+    }
+
+    fn on_stale(&self, _source: &str, _age: Duration) {
+        let mut out = String::new();
+        self.line(&mut out, "stale_sources", 1, "c");
+        self.send(&out);
+    }
+
+    fn on_change_anomaly(&self, _anomaly: &ChangeAnomaly) {
+        let mut out = String::new();
+        self.line(&mut out, "change_anomalies", 1, "c");
+        self.send(&out);
+    }
+}
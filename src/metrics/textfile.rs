@@ -0,0 +1,218 @@
+use crate::error::AppError;
+use crate::metrics::{render_source_stats, with_labels};
+use crate::nftables::builder::{RuleProto, SetElements};
+use crate::set::observer::{UpdateObserver, UpdateReport};
+use crate::settings::Environment;
+use crate::utils::stats::SourceStats;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Values written to the textfile, updated after every cycle.
+#[derive(Debug, Default)]
+struct TextfileState {
+    last_success: Option<u64>,
+    ipv4_elements: usize,
+    ipv6_elements: usize,
+    ipv4_invalid_entries: usize,
+    ipv6_invalid_entries: usize,
+    last_duration: f64,
+    failures: u64,
+    retries_exhausted: u64,
+    updates_stopped: u64,
+    sources: BTreeMap<String, SourceStats>,
+}
+
+/// Writes the outcome of every update cycle to a file read by the node_exporter textfile collector.
+///
+/// Meant for hosts that do not expose a metrics port. The file is replaced atomically,
+/// so the collector never reads a partially written file.
+#[derive(Debug)]
+pub struct TextfileExporter {
+    path: PathBuf,
+    instance: Option<String>,
+    profile: Option<String>,
+    state: Mutex<TextfileState>,
+}
+
+impl TextfileExporter {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            instance: None,
+            profile: None,
+            state: Mutex::new(TextfileState::default()),
+        }
+    }
+
+    /// Creates a `TextfileExporter` writing to `NFTBLOCKD_TEXTFILE_PATH`, or `None` when it is not set.
+    /// The metrics are labeled with the name of the `instance`, if any.
+    #[must_use]
+    pub fn from_env(environment: &Environment, instance: Option<&str>) -> Option<Self> {
+        environment
+            .var("NFTBLOCKD_TEXTFILE_PATH")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|path| Self {
+                instance: instance.map(ToString::to_string),
+                ..Self::new(path)
+            })
+    }
+
+    /// Labels the metrics with the name of the `profile`, if any, and writes them to a file of its own,
+    /// e.g., `nftblockd-tenant-a.prom` next to `nftblockd.prom`, so that the profiles do not overwrite each other.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<&str>) -> Self {
+        if let Some(profile) = profile {
+            let stem = self
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.path.set_file_name(format!("{stem}-{profile}.prom"));
+            self.profile = Some(profile.to_string());
+        }
+        self
+    }
+
+    /// Renders the current values in the Prometheus text exposition format.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut out = String::new();
+        if let Some(last_success) = state.last_success {
+            let _ = writeln!(
+                out,
+                "# HELP nftblockd_last_success_timestamp_seconds Time of the last successful update.\n\
+                 # TYPE nftblockd_last_success_timestamp_seconds gauge\n\
+                 nftblockd_last_success_timestamp_seconds {last_success}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP nftblockd_elements Elements loaded into the blocklist set by the last successful update.\n\
+             # TYPE nftblockd_elements gauge\n\
+             nftblockd_elements{{family=\"ipv4\"}} {}\n\
+             nftblockd_elements{{family=\"ipv6\"}} {}",
+            state.ipv4_elements, state.ipv6_elements
+        );
+        let _ = writeln!(
+            out,
+            "# HELP nftblockd_invalid_entries Invalid entries skipped in the blocklists of the last successful update.\n\
+             # TYPE nftblockd_invalid_entries gauge\n\
+             nftblockd_invalid_entries{{family=\"ipv4\"}} {}\n\
+             nftblockd_invalid_entries{{family=\"ipv6\"}} {}",
+            state.ipv4_invalid_entries, state.ipv6_invalid_entries
+        );
+        let _ = writeln!(
+            out,
+            "# HELP nftblockd_update_duration_seconds Duration of the last successful update.\n\
+             # TYPE nftblockd_update_duration_seconds gauge\n\
+             nftblockd_update_duration_seconds {}",
+            state.last_duration
+        );
+        let _ = writeln!(
+            out,
+            "# HELP nftblockd_update_failures_total Failed update attempts.\n\
+             # TYPE nftblockd_update_failures_total counter\n\
+             nftblockd_update_failures_total {}",
+            state.failures
+        );
+        let _ = writeln!(
+            out,
+            "# HELP nftblockd_retries_exhausted_total Times the retry budget was exhausted and the table flushed.\n\
+             # TYPE nftblockd_retries_exhausted_total counter\n\
+             nftblockd_retries_exhausted_total {}",
+            state.retries_exhausted
+        );
+        let _ = writeln!(
+            out,
+            "# HELP nftblockd_updates_stopped_total Times updates were paused after an error that retrying cannot fix.\n\
+             # TYPE nftblockd_updates_stopped_total counter\n\
+             nftblockd_updates_stopped_total {}",
+            state.updates_stopped
+        );
+        out.push_str(&render_source_stats(&state.sources));
+        out
+    }
+
+    /// Writes the textfile next to its final path and renames it into place.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be written or renamed.
+    pub fn write(&self) -> Result<(), AppError> {
+        // node_exporter only reads files ending with `.prom`, so the temporary file is ignored.
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(
+            &temporary,
+            with_labels(
+                &self.render(),
+                self.instance.as_deref(),
+                self.profile.as_deref(),
+            ),
+        )?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    fn update(&self, update: impl FnOnce(&mut TextfileState)) {
+        update(&mut self.state.lock().unwrap_or_else(PoisonError::into_inner));
+        if let Err(e) = self.write() {
+            warn!("failed to write {}: {e}", self.path.display());
+        }
+    }
+}
+
+impl UpdateObserver for TextfileExporter {
+    fn on_applied(&self, report: &UpdateReport) {
+        self.update(|state| {
+            state.last_success = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs());
+            state.ipv4_elements = report.ipv4_elements;
+            state.ipv6_elements = report.ipv6_elements;
+            state.ipv4_invalid_entries = report.ipv4_invalid_entries;
+            state.ipv6_invalid_entries = report.ipv6_invalid_entries;
+            state.last_duration = report.duration.as_secs_f64();
+        });
+    }
+
+    fn on_blocked(&self, source: &str, proto: &RuleProto, elements: &SetElements<'_>) {
+        self.update(|state| {
+            let stats = state.sources.entry(source.to_string()).or_default();
+            match proto {
+                RuleProto::Ip6 => stats.ipv6_elements = elements.len(),
+                _ => stats.ipv4_elements = elements.len(),
+            }
+        });
+    }
+
+    fn on_error(&self, _error: &AppError) {
+        self.update(|state| state.failures += 1);
+    }
+
+    fn on_source_failed(&self, source: &str, _error: &AppError) {
+        self.update(|state| {
+            state
+                .sources
+                .entry(source.to_string())
+                .or_default()
+                .failures += 1;
+        });
+    }
+
+    fn on_retries_exhausted(&self, _error: &AppError) {
+        self.update(|state| state.retries_exhausted += 1);
+    }
+
+    fn on_updates_stopped(&self, _error: &AppError) {
+        self.update(|state| state.updates_stopped += 1);
+    }
+}
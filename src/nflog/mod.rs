@@ -0,0 +1,162 @@
+use crate::grpc::ctl::nftblockd::Offender;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[cfg(feature = "nflog")]
+mod socket;
+#[cfg(feature = "nflog")]
+pub use socket::nflog_reader;
+
+const NFULA_PAYLOAD: u16 = 9;
+const NFULA_PREFIX: u16 = 10;
+const NLA_TYPE_MASK: u16 = 0x3fff;
+/// Upper bound of tracked sources, so a scan from a large network cannot exhaust memory.
+const MAX_TRACKED_SOURCES: usize = 100_000;
+
+/// Metadata of a dropped packet received over nflog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NflogPacket {
+    /// The chain of the rule that logged the packet, taken from its log prefix.
+    pub chain: String,
+    /// The set that matched the packet, taken from the log prefix of the rule.
+    pub set_name: String,
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    /// Destination port for TCP and UDP packets.
+    pub dst_port: Option<u16>,
+}
+
+/// Per-source statistics of the packets received over nflog.
+#[derive(Debug, Clone)]
+struct SourceStats {
+    packets: u64,
+    last_dst: IpAddr,
+    last_port: Option<u16>,
+    set_name: String,
+}
+
+/// Counts the dropped packets per matching set and per source address.
+#[derive(Debug, Clone, Default)]
+pub struct OffenderStats {
+    sources: HashMap<IpAddr, SourceStats>,
+    /// Packets per matching set.
+    pub per_set: BTreeMap<String, u64>,
+}
+
+impl OffenderStats {
+    /// Records a dropped packet.
+    pub fn record(&mut self, packet: NflogPacket) {
+        *self.per_set.entry(packet.set_name.clone()).or_default() += 1;
+        if self.sources.len() >= MAX_TRACKED_SOURCES && !self.sources.contains_key(&packet.src) {
+            // Forget the one-off sources first; they cannot be among the top offenders.
+            self.sources.retain(|_, s| s.packets > 1);
+            if self.sources.len() >= MAX_TRACKED_SOURCES {
+                self.sources.clear();
+            }
+        }
+        let source = self.sources.entry(packet.src).or_insert(SourceStats {
+            packets: 0,
+            last_dst: packet.dst,
+            last_port: packet.dst_port,
+            set_name: String::new(),
+        });
+        source.packets += 1;
+        source.last_dst = packet.dst;
+        source.last_port = packet.dst_port;
+        source.set_name = packet.set_name;
+    }
+
+    /// Returns the `limit` sources with the most dropped packets, most active first.
+    #[must_use]
+    pub fn top(&self, limit: usize) -> Vec<Offender> {
+        let mut sources = self.sources.iter().collect::<Vec<_>>();
+        sources.sort_by(|(a_ip, a), (b_ip, b)| b.packets.cmp(&a.packets).then(a_ip.cmp(b_ip)));
+        sources
+            .into_iter()
+            .take(limit)
+            .map(|(ip, s)| Offender {
+                source: ip.to_string(),
+                packets: s.packets,
+                last_destination: s.last_dst.to_string(),
+                last_port: s.last_port.map_or(0, u32::from),
+                set_name: s.set_name.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Parses the body of an `NFULNL_MSG_PACKET` message, i.e., the attributes following the `nfgenmsg` header.
+///
+/// # Returns
+/// `None` if the message does not carry an nftblockd log prefix or an IP payload.
+#[must_use]
+pub fn parse_packet_attributes(mut attributes: &[u8]) -> Option<NflogPacket> {
+    let mut prefix = None;
+    let mut payload = None;
+    while attributes.len() >= 4 {
+        let len = usize::from(u16::from_ne_bytes([attributes[0], attributes[1]]));
+        let kind = u16::from_ne_bytes([attributes[2], attributes[3]]) & NLA_TYPE_MASK;
+        if len < 4 || len > attributes.len() {
+            break;
+        }
+        let data = &attributes[4..len];
+        match kind {
+            NFULA_PREFIX => prefix = Some(data),
+            NFULA_PAYLOAD => payload = Some(data),
+            _ => {}
+        }
+        attributes = &attributes[align(len).min(attributes.len())..];
+    }
+    // The prefix is NUL-terminated and has the form `table;chain;set;dropped: `.
+    let prefix = String::from_utf8_lossy(prefix?);
+    let mut fields = prefix.trim_end_matches('\0').split(';').skip(1);
+    let chain = fields.next()?.to_string();
+    let set_name = fields.next()?.to_string();
+    let (src, dst, dst_port) = parse_ip_payload(payload?)?;
+    Some(NflogPacket {
+        chain,
+        set_name,
+        src,
+        dst,
+        dst_port,
+    })
+}
+
+/// Reads the addresses and the destination port from the network header of a packet.
+#[must_use]
+pub fn parse_ip_payload(payload: &[u8]) -> Option<(IpAddr, IpAddr, Option<u16>)> {
+    let (src, dst, protocol, transport) = match payload.first()? >> 4 {
+        4 if payload.len() >= 20 => {
+            let header_len = usize::from(payload[0] & 0x0f) * 4;
+            let src: [u8; 4] = payload[12..16].try_into().ok()?;
+            let dst: [u8; 4] = payload[16..20].try_into().ok()?;
+            (
+                IpAddr::V4(Ipv4Addr::from(src)),
+                IpAddr::V4(Ipv4Addr::from(dst)),
+                payload[9],
+                payload.get(header_len..),
+            )
+        }
+        6 if payload.len() >= 40 => {
+            let src: [u8; 16] = payload[8..24].try_into().ok()?;
+            let dst: [u8; 16] = payload[24..40].try_into().ok()?;
+            (
+                IpAddr::V6(Ipv6Addr::from(src)),
+                IpAddr::V6(Ipv6Addr::from(dst)),
+                payload[6],
+                payload.get(40..),
+            )
+        }
+        _ => return None,
+    };
+    // Only TCP and UDP have ports; extension headers are not followed.
+    let dst_port = match (protocol, transport) {
+        (6 | 17, Some(t)) if t.len() >= 4 => Some(u16::from_be_bytes([t[2], t[3]])),
+        _ => None,
+    };
+    Some((src, dst, dst_port))
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
@@ -0,0 +1,168 @@
+use crate::error::{AppError, ErrorSource};
+use crate::nflog::{NflogPacket, OffenderStats, align, parse_packet_attributes};
+use log::{info, warn};
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+
+const NETLINK_NETFILTER: libc::c_int = 12;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_HDRLEN: usize = 16;
+const NFGENMSG_LEN: usize = 4;
+const NFNL_SUBSYS_ULOG: u16 = 4;
+const NFULNL_MSG_PACKET: u16 = NFNL_SUBSYS_ULOG << 8;
+const NFULNL_MSG_CONFIG: u16 = (NFNL_SUBSYS_ULOG << 8) | 1;
+const NFULA_CFG_CMD: u16 = 1;
+const NFULA_CFG_MODE: u16 = 2;
+const NFULNL_CFG_CMD_BIND: u8 = 1;
+const NFULNL_COPY_PACKET: u8 = 2;
+/// Enough of the packet to read the IPv6 header and the ports of the transport header.
+const COPY_RANGE: u32 = 64;
+
+/// Subscribes to the nflog `group`, records every received packet into `offenders`,
+/// and passes it on to `on_packet`.
+///
+/// Blocks the calling thread; run it with `tokio::task::spawn_blocking`.
+///
+/// # Errors
+/// Will return `AppError` when the netlink socket cannot be created or bound to the group.
+pub fn nflog_reader<F>(
+    group: u16,
+    offenders: Arc<Mutex<OffenderStats>>,
+    mut on_packet: F,
+) -> Result<(), AppError>
+where
+    F: FnMut(&NflogPacket),
+{
+    let socket = NflogSocket::bind(group)?;
+    info!("reading dropped packets from nflog group {group}");
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let len = match socket.recv(&mut buffer) {
+            Ok(len) => len,
+            // The kernel drops messages when we fall behind; the statistics are best effort.
+            Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                warn!("nflog receive buffer overrun; some packets were not counted");
+                continue;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut messages = &buffer[..len];
+        while messages.len() >= NLMSG_HDRLEN {
+            let msg_len = u32::from_ne_bytes(messages[0..4].try_into().unwrap_or_default());
+            let msg_len = usize::try_from(msg_len).unwrap_or(usize::MAX);
+            let msg_type = u16::from_ne_bytes([messages[4], messages[5]]);
+            if msg_len < NLMSG_HDRLEN || msg_len > messages.len() {
+                break;
+            }
+            if msg_type == NFULNL_MSG_PACKET
+                && let Some(packet) =
+                    parse_packet_attributes(&messages[NLMSG_HDRLEN + NFGENMSG_LEN..msg_len])
+            {
+                on_packet(&packet);
+                offenders
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .record(packet);
+            }
+            messages = &messages[align(msg_len).min(messages.len())..];
+        }
+    }
+}
+
+/// A netlink socket subscribed to an nflog group.
+struct NflogSocket {
+    fd: libc::c_int,
+}
+
+impl NflogSocket {
+    fn bind(group: u16) -> Result<Self, AppError> {
+        // SAFETY: plain socket creation; the descriptor is owned by `NflogSocket` and closed on drop.
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW, NETLINK_NETFILTER) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let socket = Self { fd };
+        // SAFETY: `sockaddr_nl` is a plain C struct, for which all zeroes is a valid value.
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::sa_family_t::try_from(libc::AF_NETLINK).unwrap_or_default();
+        // SAFETY: `addr` is a valid `sockaddr_nl` and its size is passed along.
+        let bound = unsafe {
+            libc::bind(
+                fd,
+                (&raw const addr).cast::<libc::sockaddr>(),
+                u32::try_from(size_of::<libc::sockaddr_nl>()).unwrap_or_default(),
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        socket.configure(group, NFULA_CFG_CMD, &[NFULNL_CFG_CMD_BIND])?;
+        let mut mode = COPY_RANGE.to_be_bytes().to_vec();
+        mode.extend([NFULNL_COPY_PACKET, 0]);
+        socket.configure(group, NFULA_CFG_MODE, &mode)?;
+        Ok(socket)
+    }
+
+    /// Sends a single-attribute configuration message for `group` and waits for its acknowledgement.
+    fn configure(&self, group: u16, attribute: u16, data: &[u8]) -> Result<(), AppError> {
+        let attribute_len = 4 + data.len();
+        let total_len = NLMSG_HDRLEN + NFGENMSG_LEN + align(attribute_len);
+        let mut message = Vec::with_capacity(total_len);
+        message.extend(u32::try_from(total_len).unwrap_or_default().to_ne_bytes());
+        message.extend(NFULNL_MSG_CONFIG.to_ne_bytes());
+        let flags = u16::try_from(libc::NLM_F_REQUEST | libc::NLM_F_ACK).unwrap_or_default();
+        message.extend(flags.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes()); // sequence number
+        message.extend(0u32.to_ne_bytes()); // port id, filled in by the kernel
+        message.extend([0u8, 0u8]); // AF_UNSPEC, NFNETLINK_V0
+        message.extend(group.to_be_bytes());
+        message.extend(
+            u16::try_from(attribute_len)
+                .unwrap_or_default()
+                .to_ne_bytes(),
+        );
+        message.extend(attribute.to_ne_bytes());
+        message.extend(data);
+        message.resize(total_len, 0);
+
+        // SAFETY: `message` is a valid buffer of `message.len()` bytes.
+        let sent = unsafe { libc::send(self.fd, message.as_ptr().cast(), message.len(), 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let mut ack = [0u8; 1024];
+        let len = self.recv(&mut ack)?;
+        if len >= NLMSG_HDRLEN + 4 && u16::from_ne_bytes([ack[4], ack[5]]) == NLMSG_ERROR {
+            let code = i32::from_ne_bytes(
+                ack[NLMSG_HDRLEN..NLMSG_HDRLEN + 4]
+                    .try_into()
+                    .unwrap_or_default(),
+            );
+            if code != 0 {
+                return Err(AppError::IoError(
+                    format!("failed to configure nflog group {group}"),
+                    Some(ErrorSource::new(io::Error::from_raw_os_error(-code))),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: `buffer` is a valid, writable buffer of `buffer.len()` bytes.
+        let len = unsafe { libc::recv(self.fd, buffer.as_mut_ptr().cast(), buffer.len(), 0) };
+        usize::try_from(len).map_err(|_| io::Error::last_os_error())
+    }
+}
+
+impl Drop for NflogSocket {
+    fn drop(&mut self) {
+        // SAFETY: `fd` is owned by this socket and closed exactly once.
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use crate::error::{AppError, ErrorSource, NftablesFailure};
+use crate::nftables::serialize_ruleset;
+use crate::settings::Environment;
+use crate::utils::duration::parse_duration;
+use crate::utils::privileges::has_required_capability;
+use nftables::helper::NftablesError;
+use nftables::schema::Nftables;
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The `nft` executable, looked up in `PATH`.
+const NFT_EXECUTABLE: &str = "nft";
+/// How long an `nft` invocation may take by default before it is killed.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often a running `nft` is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Backend that submits rulesets to the kernel and reads back the active one.
+///
+/// `NftConfig` goes through an `Applier` for every `nft` invocation, so the real backend can be
+/// swapped for `MockApplier` in tests that run without root or a netfilter-capable kernel.
+pub trait Applier: Debug + Send + Sync {
+    /// Applies the given ruleset.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the ruleset is rejected.
+    fn apply(&self, ruleset: &Nftables<'_>) -> Result<(), AppError>;
+
+    /// Returns the currently active ruleset.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the ruleset cannot be listed.
+    fn current_ruleset(&self) -> Result<Nftables<'static>, AppError>;
+}
+
+/// Applies rulesets by invoking the `nft` executable.
+///
+/// Every invocation is supervised: an `nft` that does not finish within the timeout, e.g., stuck on a huge
+/// ruleset on a loaded system, is killed and reported as `NftablesFailure::Timeout` instead of blocking the
+/// update loop forever.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NftApplier {
+    /// The `nft` executable, looked up in `PATH` unless it is a path.
+    program: OsString,
+    /// How long an invocation may take before it is killed; unlimited when `None`.
+    timeout: Option<Duration>,
+}
+
+impl Default for NftApplier {
+    fn default() -> Self {
+        Self {
+            program: OsString::from(NFT_EXECUTABLE),
+            timeout: Some(DEFAULT_TIMEOUT),
+        }
+    }
+}
+
+impl NftApplier {
+    /// Reads the timeout of the `nft` invocations from `NFTBLOCKD_APPLY_TIMEOUT`; `0` disables it.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the timeout is not a valid duration.
+    pub fn from_env(environment: &Environment) -> Result<Self, AppError> {
+        let timeout = environment
+            .var("NFTBLOCKD_APPLY_TIMEOUT")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|timeout| parse_duration(&timeout))
+            .transpose()?;
+        Ok(match timeout {
+            Some(timeout) => Self::default().with_timeout((!timeout.is_zero()).then_some(timeout)),
+            None => Self::default(),
+        })
+    }
+
+    /// Invokes `program` instead of the `nft` found in `PATH`.
+    #[must_use]
+    pub fn with_program(mut self, program: impl Into<OsString>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Kills an invocation that takes longer than `timeout`; invocations are unlimited when `None`.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs `nft` with `args` and `payload` on its standard input, and returns its standard output.
+    ///
+    /// The output is read while `nft` runs, so that a large ruleset listing cannot fill the pipe and stall it.
+    fn run(&self, args: &[&str], payload: Option<String>, hint: &str) -> Result<String, AppError> {
+        let execution = |inner| NftablesError::NftExecution {
+            program: self.program.clone(),
+            inner,
+        };
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(if payload.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(execution)?;
+        let writer =
+            child.stdin.take().zip(payload).map(|(mut stdin, payload)| {
+                thread::spawn(move || stdin.write_all(payload.as_bytes()))
+            });
+        let stdout = child.stdout.take().map(read_pipe);
+        let stderr = child.stderr.take().map(read_pipe);
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(execution)? {
+                break status;
+            }
+            if let Some(timeout) = self.timeout
+                && started.elapsed() >= timeout
+            {
+                // The pipes close with the process, which ends the reading threads.
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AppError::NftablesError(
+                    NftablesFailure::Timeout,
+                    format!(
+                        "{} did not finish {hint} within {timeout:?} and was killed",
+                        self.program.display()
+                    ),
+                    None,
+                ));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        let written = writer.map_or(Ok(()), |writer| {
+            writer
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("the writer panicked")))
+        });
+        let output = |pipe: Option<JoinHandle<Vec<u8>>>| {
+            let bytes = pipe.and_then(|pipe| pipe.join().ok()).unwrap_or_default();
+            String::from_utf8(bytes).map_err(|inner| NftablesError::NftOutputEncoding {
+                program: self.program.clone(),
+                inner,
+            })
+        };
+        let (stdout, stderr) = (output(stdout)?, output(stderr)?);
+        if !status.success() {
+            // The message of `nft` tells what it rejected, e.g., the line of a syntax error.
+            let message = stderr.trim().to_string();
+            let error = NftablesError::NftFailed {
+                program: self.program.clone(),
+                hint: hint.to_string(),
+                stdout,
+                stderr,
+            };
+            if message.is_empty() {
+                return Err(error.into());
+            }
+            return Err(AppError::NftablesError(
+                NftablesFailure::classify(&error),
+                format!("{error}: {message}"),
+                Some(ErrorSource::new(error)),
+            ));
+        }
+        // `nft` exits before reading all of its input only when it fails.
+        written.map_err(execution)?;
+        Ok(stdout)
+    }
+}
+
+impl Applier for NftApplier {
+    fn apply(&self, ruleset: &Nftables<'_>) -> Result<(), AppError> {
+        let payload = serde_json::to_string(ruleset)?;
+        self.run(&["-j", "-f", "-"], Some(payload), "applying ruleset")
+            .map(|_| ())
+            .map_err(explain_missing_capability)
+    }
+
+    fn current_ruleset(&self) -> Result<Nftables<'static>, AppError> {
+        let output = self
+            .run(
+                &["-j", "list", "ruleset"],
+                None,
+                "getting the current ruleset",
+            )
+            .map_err(explain_missing_capability)?;
+        serde_json::from_str(&output).map_err(|e| NftablesError::NftInvalidJson(e).into())
+    }
+}
+
+/// Reads a pipe of a child process to its end on a separate thread.
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+/// Blames the missing capability instead of the raw `nft` error, which rarely names it.
+fn explain_missing_capability(error: AppError) -> AppError {
+    match error {
+        AppError::NftablesError(NftablesFailure::ApplyFailed, message, source)
+            if !has_required_capability() =>
+        {
+            AppError::NftablesError(NftablesFailure::PermissionDenied, message, source)
+        }
+        error => error,
+    }
+}
+
+/// In-memory backend recording every submitted ruleset.
+///
+/// Rulesets are stored in their canonical JSON form (see `serialize_ruleset`).
+/// The listed ruleset is always empty.
+#[derive(Debug, Default)]
+pub struct MockApplier {
+    applied: Mutex<Vec<String>>,
+    failure: Mutex<Option<AppError>>,
+}
+
+impl MockApplier {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes all subsequent calls fail with `error`, or succeed again when `None` is given.
+    pub fn fail_with(&self, error: Option<AppError>) {
+        *self
+            .failure
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = error;
+    }
+
+    /// Returns all rulesets applied so far, oldest first.
+    #[must_use]
+    pub fn applied(&self) -> Vec<String> {
+        self.applied
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    fn check_failure(&self) -> Result<(), AppError> {
+        match self
+            .failure
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+        {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Applier for MockApplier {
+    fn apply(&self, ruleset: &Nftables<'_>) -> Result<(), AppError> {
+        self.check_failure()?;
+        let ruleset = serialize_ruleset(ruleset)?;
+        self.applied
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(ruleset);
+        Ok(())
+    }
+
+    fn current_ruleset(&self) -> Result<Nftables<'static>, AppError> {
+        self.check_failure()?;
+        Ok(Nftables {
+            objects: Cow::Owned(Vec::new()),
+        })
+    }
+}
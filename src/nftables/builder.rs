@@ -1,17 +1,26 @@
-use nftables::expr::{Expression, NamedExpression, Payload, PayloadField};
-use nftables::schema::NfCmd::Delete;
-use nftables::schema::NfListObject::{Chain, Element, Rule, Set, Table};
-use nftables::schema::{NfObject, Nftables, SetType};
-use nftables::stmt::{Counter, Log, Match, Operator, Statement};
+use crate::error::AppError;
+use crate::nftables::element_sort_key;
+use crate::nftables::extra_rules::ExtraRule;
+use ipnetwork::IpNetwork;
+use nftables::expr::{
+    Expression, Meta, MetaKey, NamedExpression, Payload, PayloadField, Prefix, Range, SetItem,
+};
+use nftables::schema::NfCmd::{Delete, Flush};
+use nftables::schema::NfListObject::{Chain, Element, Quota, Rule, Set, Table};
+use nftables::schema::{FlushObject, NfObject, Nftables, SetType};
+use nftables::stmt::{
+    Counter, Limit, Log, Mangle, Match, Meter, Operator, QuotaOrQuotaRef, Statement,
+};
 use nftables::types::{NfChainPolicy, NfFamily, NfHook};
 use nftables::{schema, types};
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use std::fmt::Display;
 
 pub type SetElements<'a> = Vec<Expression<'a>>;
 
 /// Represents the direction of a rule in the firewall chain (source or destination).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RuleDirection {
     /// Source address (saddr).
     Saddr,
@@ -19,6 +28,220 @@ pub enum RuleDirection {
     Daddr,
 }
 
+/// Which traffic the rules of a blocklist apply to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Packets from the listed addresses, matched by source address in the prerouting chain.
+    Ingress,
+    /// Packets to the listed addresses, matched by destination address in the postrouting chain.
+    Egress,
+    /// Both ingress and egress.
+    #[default]
+    Both,
+}
+
+impl Direction {
+    /// Parses `ingress`, `egress`, or `both`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "ingress" => Ok(Self::Ingress),
+            "egress" => Ok(Self::Egress),
+            "both" => Ok(Self::Both),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_DIRECTION: {value}; expected ingress, egress, or both"
+            ))),
+        }
+    }
+
+    /// Whether packets from the listed addresses are matched.
+    #[must_use]
+    pub fn ingress(self) -> bool {
+        self != Self::Egress
+    }
+
+    /// Whether packets to the listed addresses are matched.
+    #[must_use]
+    pub fn egress(self) -> bool {
+        self != Self::Ingress
+    }
+}
+
+/// Family of the blocklist table, which determines the traffic it sees and the addresses it can match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableFamily {
+    /// IPv4 traffic only; the IPv6 sets and rules are left out.
+    Ip,
+    /// IPv6 traffic only; the IPv4 sets and rules are left out.
+    Ip6,
+    /// Both IPv4 and IPv6 traffic.
+    #[default]
+    Inet,
+    /// Bridged traffic, matched by its IPv4 and IPv6 addresses.
+    Bridge,
+}
+
+impl TableFamily {
+    /// Parses `ip`, `ip6`, `inet`, or `bridge`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "ip" => Ok(Self::Ip),
+            "ip6" => Ok(Self::Ip6),
+            "inet" => Ok(Self::Inet),
+            "bridge" => Ok(Self::Bridge),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_TABLE_FAMILY: {value}; expected ip, ip6, inet, or bridge"
+            ))),
+        }
+    }
+
+    /// Whether the table can match IPv4 addresses.
+    #[must_use]
+    pub fn ipv4(self) -> bool {
+        self != Self::Ip6
+    }
+
+    /// Whether the table can match IPv6 addresses.
+    #[must_use]
+    pub fn ipv6(self) -> bool {
+        self != Self::Ip
+    }
+
+    /// Whether the table can hold a set of `set_type`.
+    #[must_use]
+    pub fn supports(self, set_type: &SetType) -> bool {
+        match set_type {
+            SetType::Ipv4Addr => self.ipv4(),
+            SetType::Ipv6Addr => self.ipv6(),
+            _ => true,
+        }
+    }
+}
+
+/// Address families that are blocked, independently of the family of the table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamilies {
+    /// IPv4 only; the IPv6 sets and rules are left out.
+    Ipv4,
+    /// IPv6 only; the IPv4 sets and rules are left out.
+    Ipv6,
+    /// Both IPv4 and IPv6.
+    #[default]
+    Both,
+}
+
+impl AddressFamilies {
+    /// Parses `ipv4`, `ipv6`, or `both`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "ipv4" => Ok(Self::Ipv4),
+            "ipv6" => Ok(Self::Ipv6),
+            "both" => Ok(Self::Both),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_ADDRESS_FAMILIES: {value}; expected ipv4, ipv6, or both"
+            ))),
+        }
+    }
+
+    /// Whether IPv4 addresses are blocked.
+    #[must_use]
+    pub fn ipv4(self) -> bool {
+        self != Self::Ipv6
+    }
+
+    /// Whether IPv6 addresses are blocked.
+    #[must_use]
+    pub fn ipv6(self) -> bool {
+        self != Self::Ipv4
+    }
+}
+
+impl From<TableFamily> for NfFamily {
+    fn from(family: TableFamily) -> Self {
+        match family {
+            TableFamily::Ip => NfFamily::IP,
+            TableFamily::Ip6 => NfFamily::IP6,
+            TableFamily::Inet => NfFamily::INet,
+            TableFamily::Bridge => NfFamily::Bridge,
+        }
+    }
+}
+
+/// Rate every address of a rate-limited source may send or receive at before the excess is dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Packets per `per`.
+    pub rate: u32,
+    /// Unit of time of `rate`: `second`, `minute`, `hour`, or `day`.
+    pub per: String,
+    /// Packets allowed above the rate in a burst.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Parses a rate such as `10/second` and a burst of packets.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the rate is not a positive number of packets per unit of time.
+    pub fn parse(rate: &str, burst: u32) -> Result<Self, AppError> {
+        let invalid = || {
+            AppError::ParseError(format!(
+                "invalid NFTBLOCKD_RATELIMIT_RATE: {rate}; expected packets per unit of time such as `10/second`"
+            ))
+        };
+        let (packets, per) = rate.split_once('/').ok_or_else(invalid)?;
+        let packets = packets
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|packets| *packets > 0)
+            .ok_or_else(invalid)?;
+        let per = per.trim().to_ascii_lowercase();
+        if !matches!(per.as_str(), "second" | "minute" | "hour" | "day") {
+            return Err(invalid());
+        }
+        Ok(Self {
+            rate: packets,
+            per,
+            burst,
+        })
+    }
+}
+
+/// How updates are loaded into the kernel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApplyStrategy {
+    /// Every update deletes and recreates the table, resetting the rule counters and handles.
+    #[default]
+    Replace,
+    /// The table, chains, and rules are created once and kept; updates flush and refill the sets.
+    Refill,
+}
+
+impl ApplyStrategy {
+    /// Parses `replace` or `refill`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "replace" => Ok(Self::Replace),
+            "refill" => Ok(Self::Refill),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_APPLY_STRATEGY: {value}; expected replace or refill"
+            ))),
+        }
+    }
+}
+
 /// Represents the protocol type (IPv4 or IPv6) for a rule.
 #[derive(Debug, Clone, Default)]
 pub enum RuleProto {
@@ -53,9 +276,159 @@ impl Display for RuleProto {
     }
 }
 
+/// Days of the week as `nft` names them, starting with Sunday.
+const DAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Hours of the day and days of the week a source is enforced in, in the local time of the host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// Start and end of the enforced hours, e.g., `18:00` and `08:00`; a window ending before it starts
+    /// spans midnight.
+    pub hours: Option<(String, String)>,
+    /// Enforced days, e.g., `Monday`; every day when empty.
+    pub days: Vec<String>,
+}
+
+impl TimeWindow {
+    /// Parses hours such as `18:00-08:00` and days such as `mon-fri,sun`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when neither is set, i.e., the source is always enforced.
+    ///
+    /// # Errors
+    /// Will return `AppError` for an invalid time or day.
+    pub fn parse(hours: Option<&str>, days: Option<&str>) -> Result<Option<Self>, AppError> {
+        if hours.is_none() && days.is_none() {
+            return Ok(None);
+        }
+        let hours = hours
+            .map(|value| {
+                let invalid = || {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_ENFORCE_HOURS: {value}; expected a window such as `18:00-08:00`"
+                    ))
+                };
+                let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+                let (start, end) = (
+                    parse_time(start).ok_or_else(invalid)?,
+                    parse_time(end).ok_or_else(invalid)?,
+                );
+                if start == end {
+                    return Err(invalid());
+                }
+                Ok((start, end))
+            })
+            .transpose()?;
+        let days = days
+            .map(|value| {
+                let invalid = |day: &str| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_ENFORCE_DAYS: {day}; expected days such as `mon-fri,sun`"
+                    ))
+                };
+                let day = |day: &str| {
+                    let day = day.trim().to_ascii_lowercase();
+                    DAYS.iter().position(|name| {
+                        day.len() >= 3 && name.to_ascii_lowercase().starts_with(&day)
+                    })
+                };
+                let mut days = Vec::new();
+                for range in value.split(',') {
+                    let (first, last) = range.split_once('-').unwrap_or((range, range));
+                    let first = day(first).ok_or_else(|| invalid(range))?;
+                    let last = day(last).ok_or_else(|| invalid(range))?;
+                    // A range may wrap around the end of the week, e.g., `fri-mon`.
+                    let mut current = first;
+                    loop {
+                        if !days.contains(&current) {
+                            days.push(current);
+                        }
+                        if current == last {
+                            break;
+                        }
+                        current = (current + 1) % DAYS.len();
+                    }
+                }
+                days.sort_unstable();
+                Ok::<_, AppError>(days.into_iter().map(|day| DAYS[day].to_string()).collect())
+            })
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Some(Self { hours, days }))
+    }
+
+    /// Converts the window into the matches of a rule.
+    fn statements<'a>(&self) -> Vec<Statement<'a>> {
+        let meta = |key| Expression::Named(NamedExpression::Meta(Meta { key }));
+        let mut statements = Vec::new();
+        if let Some((start, end)) = &self.hours {
+            let range = |low: &str, high: &str| {
+                Expression::Range(Box::new(Range {
+                    range: [
+                        Expression::String(Cow::Owned(low.to_string())),
+                        Expression::String(Cow::Owned(high.to_string())),
+                    ],
+                }))
+            };
+            // A window spanning midnight matches outside of the hours it leaves out.
+            statements.push(Statement::Match(if start < end {
+                Match {
+                    left: meta(MetaKey::Hour),
+                    right: range(start, end),
+                    op: Operator::EQ,
+                }
+            } else {
+                Match {
+                    left: meta(MetaKey::Hour),
+                    right: range(end, start),
+                    op: Operator::NEQ,
+                }
+            }));
+        }
+        if !self.days.is_empty() {
+            statements.push(Statement::Match(Match {
+                left: meta(MetaKey::Day),
+                right: Expression::Named(NamedExpression::Set(
+                    self.days
+                        .iter()
+                        .map(|day| SetItem::Element(Expression::String(Cow::Owned(day.clone()))))
+                        .collect(),
+                )),
+                op: Operator::EQ,
+            }));
+        }
+        statements
+    }
+}
+
+/// Parses a time of the day such as `8:00` or `18:30` into the `HH:MM` form of `nft`.
+fn parse_time(value: &str) -> Option<String> {
+    let (hour, minute) = value.trim().split_once(':')?;
+    let hour = hour.parse::<u8>().ok().filter(|hour| *hour < 24)?;
+    let minute = minute.parse::<u8>().ok().filter(|minute| *minute < 60)?;
+    Some(format!("{hour:02}:{minute:02}"))
+}
+
 #[derive(Debug, Default)]
 pub struct NftRulesetBuilder<'a> {
     pub objects: Vec<NfObject<'a>>,
+    /// When set, logging rules send packets to this nflog group instead of the kernel log.
+    pub log_group: Option<u32>,
+    /// When set, blocklist and rate limit rules only match within this window.
+    pub time_window: Option<TimeWindow>,
+    /// Family of the tables; sets and rules of address families it cannot match are left out.
+    pub family: TableFamily,
+    /// Address families that are blocked; the sets and rules of the others are left out.
+    pub families: AddressFamilies,
 }
 
 impl<'a> NftRulesetBuilder<'a> {
@@ -63,9 +436,61 @@ impl<'a> NftRulesetBuilder<'a> {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
+            log_group: None,
+            time_window: None,
+            family: TableFamily::default(),
+            families: AddressFamilies::default(),
+        }
+    }
+
+    /// Builds the objects in tables of the given family instead of `inet`.
+    #[must_use]
+    pub fn with_family(mut self, family: TableFamily) -> Self {
+        self.family = family;
+        self
+    }
+
+    /// Leaves out the sets and rules of the address families that are not blocked.
+    #[must_use]
+    pub fn with_address_families(mut self, families: AddressFamilies) -> Self {
+        self.families = families;
+        self
+    }
+
+    /// Whether the sets and rules of IPv4 addresses are built.
+    fn ipv4(&self) -> bool {
+        self.family.ipv4() && self.families.ipv4()
+    }
+
+    /// Whether the sets and rules of IPv6 addresses are built.
+    fn ipv6(&self) -> bool {
+        self.family.ipv6() && self.families.ipv6()
+    }
+
+    /// Whether a set of `set_type` is built.
+    fn supports(&self, set_type: &SetType) -> bool {
+        match set_type {
+            SetType::Ipv4Addr => self.ipv4(),
+            SetType::Ipv6Addr => self.ipv6(),
+            _ => true,
         }
     }
 
+    /// Sends the packets of logging rules to the given nflog group instead of the kernel log.
+    #[must_use]
+    pub fn with_log_group(mut self, log_group: Option<u32>) -> Self {
+        self.log_group = log_group;
+        self
+    }
+
+    /// Restricts the rules built next with `build_rule` and `build_rate_limit_rule` to the given window;
+    /// `None` lifts the restriction.
+    #[must_use]
+    pub fn with_time_window(mut self, time_window: Option<TimeWindow>) -> Self {
+        self.time_window = time_window;
+        self
+    }
+
     /// Deletes an existing table in `nftables`. This operation removes the table
     /// and all related chains, sets, and rules.
     ///
@@ -78,7 +503,7 @@ impl<'a> NftRulesetBuilder<'a> {
     pub fn delete_table(mut self, table_name: &'a str) -> Self {
         self.objects
             .push(NfObject::CmdObject(Delete(Table(schema::Table {
-                family: NfFamily::INet,
+                family: self.family.into(),
                 name: table_name.into(),
                 handle: None,
             }))));
@@ -95,7 +520,7 @@ impl<'a> NftRulesetBuilder<'a> {
     #[must_use]
     pub fn build_table(mut self, table_name: &'a str) -> Self {
         self.objects.push(NfObject::ListObject(Table(schema::Table {
-            family: NfFamily::INet, // Use the `inet` family, which supports both IPv4 and IPv6.
+            family: self.family.into(),
             name: table_name.into(),
             ..Default::default()
         })));
@@ -121,7 +546,7 @@ impl<'a> NftRulesetBuilder<'a> {
         priority: i32,
     ) -> Self {
         self.objects.push(NfObject::ListObject(Chain(schema::Chain {
-            family: NfFamily::INet,
+            family: self.family.into(),
             table: table_name.into(),
             name: chain_name.into(),
             newname: None,
@@ -146,16 +571,111 @@ impl<'a> NftRulesetBuilder<'a> {
     /// An `NfObject` representing the creation of the set.
     #[must_use]
     pub fn build_set(mut self, table_name: &'a str, set_name: String, set_type: &SetType) -> Self {
+        if !self.supports(set_type) {
+            return self;
+        }
         self.objects
             .push(NfObject::ListObject(Set(Box::new(schema::Set {
-                family: NfFamily::INet,
+                family: self.family.into(),
                 table: table_name.into(),
                 name: set_name.into(),
                 auto_merge: Some(true),
                 handle: None,
                 set_type: schema::SetTypeValue::Single(*set_type),
                 policy: None,
-                flags: Some(HashSet::from([schema::SetFlag::Interval])),
+                flags: Some(BTreeSet::from([schema::SetFlag::Interval])),
+                elem: None,
+                timeout: None,
+                gc_interval: None,
+                size: None,
+                comment: None,
+            }))));
+        self
+    }
+
+    /// Removes all elements from an existing set, keeping the set and the rules referencing it.
+    #[must_use]
+    pub fn flush_set(mut self, table_name: &'a str, set_name: String, set_type: &SetType) -> Self {
+        if !self.supports(set_type) {
+            return self;
+        }
+        self.objects
+            .push(NfObject::CmdObject(Flush(FlushObject::Set(Box::new(
+                schema::Set {
+                    family: self.family.into(),
+                    table: table_name.into(),
+                    name: set_name.into(),
+                    auto_merge: None,
+                    handle: None,
+                    set_type: schema::SetTypeValue::Single(*set_type),
+                    policy: None,
+                    flags: None,
+                    elem: None,
+                    timeout: None,
+                    gc_interval: None,
+                    size: None,
+                    comment: None,
+                },
+            )))));
+        self
+    }
+
+    /// Creates a set of single addresses that expire after the timeout of each element.
+    /// Such sets are filled at runtime, e.g., with quarantined addresses.
+    #[must_use]
+    pub fn build_timeout_set(
+        mut self,
+        table_name: &'a str,
+        set_name: String,
+        set_type: &SetType,
+    ) -> Self {
+        if !self.supports(set_type) {
+            return self;
+        }
+        self.objects
+            .push(NfObject::ListObject(Set(Box::new(schema::Set {
+                family: self.family.into(),
+                table: table_name.into(),
+                name: set_name.into(),
+                auto_merge: None,
+                handle: None,
+                set_type: schema::SetTypeValue::Single(*set_type),
+                policy: None,
+                flags: Some(BTreeSet::from([schema::SetFlag::Timeout])),
+                elem: None,
+                timeout: None,
+                gc_interval: None,
+                size: None,
+                comment: None,
+            }))));
+        self
+    }
+
+    /// Creates a set of networks that expire after the timeout of each element, or never without one.
+    /// Such sets are filled at runtime, e.g., with manually added entries.
+    #[must_use]
+    pub fn build_interval_timeout_set(
+        mut self,
+        table_name: &'a str,
+        set_name: String,
+        set_type: &SetType,
+    ) -> Self {
+        if !self.supports(set_type) {
+            return self;
+        }
+        self.objects
+            .push(NfObject::ListObject(Set(Box::new(schema::Set {
+                family: self.family.into(),
+                table: table_name.into(),
+                name: set_name.into(),
+                auto_merge: None,
+                handle: None,
+                set_type: schema::SetTypeValue::Single(*set_type),
+                policy: None,
+                flags: Some(BTreeSet::from([
+                    schema::SetFlag::Interval,
+                    schema::SetFlag::Timeout,
+                ])),
                 elem: None,
                 timeout: None,
                 gc_interval: None,
@@ -170,6 +690,7 @@ impl<'a> NftRulesetBuilder<'a> {
     /// # Parameters
     /// - `table_name`: The name of the table.
     /// - `set_name`: The name of the set being updated.
+    /// - `set_type`: The data type of the set; nothing is inserted if the family cannot hold it.
     /// - `set_elements`: The elements to insert into the set (as expressions).
     ///
     /// # Returns
@@ -179,14 +700,26 @@ impl<'a> NftRulesetBuilder<'a> {
         mut self,
         table_name: &'a str,
         set_name: String,
+        set_type: &SetType,
         set_elements: &'a Vec<Expression<'a>>,
     ) -> Self {
+        if !self.supports(set_type) {
+            return self;
+        }
+        // Elements are emitted sorted, so the ruleset does not depend on the order of the fetched feeds.
+        let elem = if set_elements.is_sorted_by_key(element_sort_key) {
+            Cow::Borrowed(set_elements.as_slice())
+        } else {
+            let mut sorted = set_elements.clone();
+            sorted.sort_by_cached_key(element_sort_key);
+            Cow::Owned(sorted)
+        };
         self.objects
             .push(NfObject::ListObject(Element(schema::Element {
-                family: NfFamily::INet,
+                family: self.family.into(),
                 table: table_name.into(),
                 name: set_name.into(),
-                elem: Cow::Borrowed(set_elements),
+                elem,
             })));
         self
     }
@@ -204,7 +737,8 @@ impl<'a> NftRulesetBuilder<'a> {
     /// - `comment`: A descriptive comment about the purpose of the rule.
     ///
     /// # Returns
-    /// An `NfObject` representing the rule.
+    /// An `NfObject` representing the rule; nothing is added if the family cannot match `rule_proto`
+    /// or it is not blocked.
     #[allow(clippy::too_many_arguments)]
     #[must_use]
     pub fn build_rule(
@@ -218,6 +752,14 @@ impl<'a> NftRulesetBuilder<'a> {
         verdict: Statement<'a>,
         comment: &'a str,
     ) -> Self {
+        let supported = match rule_proto {
+            RuleProto::Ip => self.ipv4(),
+            RuleProto::Ip6 => self.ipv6(),
+            RuleProto::Other => true,
+        };
+        if !supported {
+            return self;
+        }
         // Match condition against the specified `set_name`.
         let mut expressions = vec![Statement::Match(Match {
             left: Expression::Named(NamedExpression::Payload(Payload::PayloadField(
@@ -229,14 +771,22 @@ impl<'a> NftRulesetBuilder<'a> {
             right: Expression::String(Cow::Owned(format!("@{set_name}"))),
             op: Operator::EQ,
         })];
+        if let Some(time_window) = &self.time_window {
+            expressions.extend(time_window.statements());
+        }
 
         // Optionally, add a log statement to the rule.
         if log {
+            let action = if matches!(verdict, Statement::Continue(_)) {
+                "monitored"
+            } else {
+                "dropped"
+            };
             expressions.push(Statement::Log(Some(Log {
                 prefix: log.then(|| {
-                    Cow::Owned(format!("{table_name};{chain_name};{set_name};dropped: ",))
+                    Cow::Owned(format!("{table_name};{chain_name};{set_name};{action}: ",))
                 }),
-                group: None,
+                group: self.log_group,
                 snaplen: None,
                 queue_threshold: None,
                 level: None,
@@ -249,7 +799,7 @@ impl<'a> NftRulesetBuilder<'a> {
         expressions.extend(vec![Statement::Counter(Counter::Anonymous(None)), verdict]);
         // Return the completed `NfObject` for the rule.
         let rule = NfObject::ListObject(Rule(schema::Rule {
-            family: NfFamily::INet,
+            family: self.family.into(),
             table: table_name.into(),
             chain: chain_name.into(),
             expr: Cow::Owned(expressions),
@@ -261,6 +811,343 @@ impl<'a> NftRulesetBuilder<'a> {
         self
     }
 
+    /// Creates a rule dropping the packets of every address in `set_name` above its own rate, tracked
+    /// in a meter keyed by the address.
+    ///
+    /// # Parameters
+    /// - `table_name`: The name of the table containing the chain.
+    /// - `chain_name`: The name of the chain the rule is added to.
+    /// - `set_name`: The set the addresses are matched against.
+    /// - `rule_proto`: The protocol of the addresses in the set.
+    /// - `rule_direction`: Whether the source or the destination address is matched and metered.
+    /// - `log`: Whether the dropped excess is logged.
+    /// - `rate_limit`: The rate and burst of every address.
+    /// - `comment`: The comment of the rule.
+    ///
+    /// # Returns
+    /// An `NfObject` representing the rule; nothing is added if the family cannot match `rule_proto`
+    /// or it is not blocked.
+    #[allow(clippy::too_many_arguments)]
+    #[must_use]
+    pub fn build_rate_limit_rule(
+        mut self,
+        table_name: &'a str,
+        chain_name: &'a str,
+        set_name: String,
+        rule_proto: RuleProto,
+        rule_direction: RuleDirection,
+        log: bool,
+        rate_limit: &'a RateLimit,
+        comment: &'a str,
+    ) -> Self {
+        let supported = match rule_proto {
+            RuleProto::Ip => self.ipv4(),
+            RuleProto::Ip6 => self.ipv6(),
+            RuleProto::Other => true,
+        };
+        if !supported {
+            return self;
+        }
+        let address = Expression::Named(NamedExpression::Payload(Payload::PayloadField(
+            PayloadField {
+                protocol: rule_proto.to_string().into(),
+                field: rule_direction.to_string().into(),
+            },
+        )));
+        let mut expressions = vec![
+            Statement::Match(Match {
+                left: address.clone(),
+                right: Expression::String(Cow::Owned(format!("@{set_name}"))),
+                op: Operator::EQ,
+            }),
+            // Every address gets its own limit, which matches once the address exceeds it.
+            Statement::Meter(Meter {
+                name: Cow::Owned(format!("{set_name}_{rule_direction}_meter")),
+                key: address,
+                stmt: Box::new(Statement::Limit(Limit {
+                    rate: rate_limit.rate,
+                    rate_unit: Some("packets".into()),
+                    per: Some(rate_limit.per.as_str().into()),
+                    burst: Some(rate_limit.burst),
+                    burst_unit: None,
+                    inv: Some(true),
+                })),
+            }),
+        ];
+        // Outside of the window, the meter is not even updated.
+        if let Some(time_window) = &self.time_window {
+            expressions.splice(1..1, time_window.statements());
+        }
+        if log {
+            expressions.push(Statement::Log(Some(Log {
+                prefix: Some(Cow::Owned(format!(
+                    "{table_name};{chain_name};{set_name};rate limited: "
+                ))),
+                group: self.log_group,
+                snaplen: None,
+                queue_threshold: None,
+                level: None,
+                flags: None,
+            })));
+        }
+        expressions.extend([
+            Statement::Counter(Counter::Anonymous(None)),
+            Statement::Drop(None),
+        ]);
+        self.objects.push(NfObject::ListObject(Rule(schema::Rule {
+            family: self.family.into(),
+            table: table_name.into(),
+            chain: chain_name.into(),
+            expr: Cow::Owned(expressions),
+            handle: None,
+            index: None,
+            comment: Some(Cow::from(comment)),
+        })));
+        self
+    }
+
+    /// Creates a named quota object, which matches once `bytes` passed through the rules referencing it.
+    ///
+    /// # Parameters
+    /// - `table_name`: The name of the table the quota belongs to.
+    /// - `quota_name`: The name of the quota to create.
+    /// - `bytes`: The byte budget.
+    ///
+    /// # Returns
+    /// An `NfObject` representing the creation of the quota.
+    #[must_use]
+    pub fn build_quota(mut self, table_name: &'a str, quota_name: &'a str, bytes: u32) -> Self {
+        self.objects.push(NfObject::ListObject(Quota(schema::Quota {
+            family: self.family.into(),
+            table: table_name.into(),
+            name: quota_name.into(),
+            handle: None,
+            bytes: Some(bytes),
+            used: None,
+            inv: Some(true), // Match once the budget is exceeded.
+        })));
+        self
+    }
+
+    /// Creates a rule dropping the packets of the addresses in `set_name` once the quota `quota_name`
+    /// is exhausted.
+    ///
+    /// # Parameters
+    /// - `table_name`: The name of the table containing the chain.
+    /// - `chain_name`: The name of the chain the rule is added to.
+    /// - `set_name`: The set the addresses are matched against.
+    /// - `rule_proto`: The protocol of the addresses in the set.
+    /// - `rule_direction`: Whether the source or the destination address is matched.
+    /// - `quota_name`: The name of the quota created with `build_quota`.
+    /// - `comment`: The comment of the rule.
+    ///
+    /// # Returns
+    /// An `NfObject` representing the rule; nothing is added if the family cannot match `rule_proto`
+    /// or it is not blocked.
+    #[allow(clippy::too_many_arguments)]
+    #[must_use]
+    pub fn build_quota_rule(
+        mut self,
+        table_name: &'a str,
+        chain_name: &'a str,
+        set_name: String,
+        rule_proto: RuleProto,
+        rule_direction: RuleDirection,
+        quota_name: &'a str,
+        comment: &'a str,
+    ) -> Self {
+        let supported = match rule_proto {
+            RuleProto::Ip => self.ipv4(),
+            RuleProto::Ip6 => self.ipv6(),
+            RuleProto::Other => true,
+        };
+        if !supported {
+            return self;
+        }
+        let expressions = vec![
+            Statement::Match(Match {
+                left: Expression::Named(NamedExpression::Payload(Payload::PayloadField(
+                    PayloadField {
+                        protocol: rule_proto.to_string().into(),
+                        field: rule_direction.to_string().into(),
+                    },
+                ))),
+                right: Expression::String(Cow::Owned(format!("@{set_name}"))),
+                op: Operator::EQ,
+            }),
+            Statement::Quota(QuotaOrQuotaRef::QuotaRef(quota_name.into())),
+            Statement::Counter(Counter::Anonymous(None)),
+            Statement::Drop(None),
+        ];
+        self.objects.push(NfObject::ListObject(Rule(schema::Rule {
+            family: self.family.into(),
+            table: table_name.into(),
+            chain: chain_name.into(),
+            expr: Cow::Owned(expressions),
+            handle: None,
+            index: None,
+            comment: Some(Cow::from(comment)),
+        })));
+        self
+    }
+
+    /// Creates a static rule declared in the extra rules file.
+    ///
+    /// # Parameters
+    /// - `table_name`: The name of the table containing the chain.
+    /// - `chain_name`: The name of the chain the rule is added to.
+    /// - `rule`: The declared rule.
+    ///
+    /// # Returns
+    /// An `NfObject` representing the rule; nothing is added if the family cannot match the referenced set
+    /// or it is not blocked.
+    #[must_use]
+    pub fn build_extra_rule(
+        mut self,
+        table_name: &'a str,
+        chain_name: &'a str,
+        rule: &'a ExtraRule,
+    ) -> Self {
+        let mut expressions = Vec::new();
+        if let Some((direction, set_name)) = &rule.set {
+            let rule_proto = rule.set_proto();
+            let supported = match rule_proto {
+                RuleProto::Ip => self.ipv4(),
+                RuleProto::Ip6 => self.ipv6(),
+                RuleProto::Other => true,
+            };
+            if !supported {
+                return self;
+            }
+            expressions.push(Statement::Match(Match {
+                left: Expression::Named(NamedExpression::Payload(Payload::PayloadField(
+                    PayloadField {
+                        protocol: rule_proto.to_string().into(),
+                        field: direction.to_string().into(),
+                    },
+                ))),
+                right: Expression::String(Cow::Owned(format!("@{set_name}"))),
+                op: Operator::EQ,
+            }));
+        }
+        if let Some(proto) = rule.proto {
+            let port = |(low, high): (u16, u16)| {
+                if low == high {
+                    Expression::Number(u32::from(low))
+                } else {
+                    Expression::Range(Box::new(Range {
+                        range: [
+                            Expression::Number(u32::from(low)),
+                            Expression::Number(u32::from(high)),
+                        ],
+                    }))
+                }
+            };
+            expressions.push(Statement::Match(match rule.ports.as_slice() {
+                [] => Match {
+                    left: Expression::Named(NamedExpression::Meta(Meta {
+                        key: MetaKey::L4proto,
+                    })),
+                    right: Expression::String(Cow::Owned(proto.to_string())),
+                    op: Operator::EQ,
+                },
+                ports => Match {
+                    left: Expression::Named(NamedExpression::Payload(Payload::PayloadField(
+                        PayloadField {
+                            protocol: proto.to_string().into(),
+                            field: if rule.source_ports { "sport" } else { "dport" }.into(),
+                        },
+                    ))),
+                    right: match ports {
+                        [single] => port(*single),
+                        ports => Expression::Named(NamedExpression::Set(
+                            ports
+                                .iter()
+                                .map(|range| SetItem::Element(port(*range)))
+                                .collect(),
+                        )),
+                    },
+                    op: Operator::EQ,
+                },
+            }));
+        }
+        expressions.extend([
+            Statement::Counter(Counter::Anonymous(None)),
+            rule.verdict.statement(),
+        ]);
+        self.objects.push(NfObject::ListObject(Rule(schema::Rule {
+            family: self.family.into(),
+            table: table_name.into(),
+            chain: chain_name.into(),
+            expr: Cow::Owned(expressions),
+            handle: None,
+            index: None,
+            comment: Some(Cow::Owned(rule.comment())),
+        })));
+        self
+    }
+
+    /// Creates a rule setting `meta nftrace` on the packets whose address matches `network`,
+    /// so that `nft monitor trace` shows every chain and rule they traverse.
+    ///
+    /// # Parameters
+    /// - `table_name`: The name of the table containing the chain.
+    /// - `chain_name`: The name of the chain the rule is added to.
+    /// - `network`: The traced address or network.
+    /// - `rule_direction`: Whether the source or the destination address is matched.
+    ///
+    /// # Returns
+    /// An `NfObject` representing the rule; nothing is added if the family cannot match `network`.
+    #[must_use]
+    pub fn build_trace_rule(
+        mut self,
+        table_name: &'a str,
+        chain_name: &'a str,
+        network: &IpNetwork,
+        rule_direction: RuleDirection,
+    ) -> Self {
+        let (rule_proto, supported) = match network {
+            IpNetwork::V4(_) => (RuleProto::Ip, self.family.ipv4()),
+            IpNetwork::V6(_) => (RuleProto::Ip6, self.family.ipv6()),
+        };
+        if !supported {
+            return self;
+        }
+        let expressions = vec![
+            Statement::Match(Match {
+                left: Expression::Named(NamedExpression::Payload(Payload::PayloadField(
+                    PayloadField {
+                        protocol: rule_proto.to_string().into(),
+                        field: rule_direction.to_string().into(),
+                    },
+                ))),
+                right: Expression::Named(NamedExpression::Prefix(Prefix {
+                    addr: Box::new(Expression::String(Cow::Owned(
+                        network.network().to_string(),
+                    ))),
+                    len: u32::from(network.prefix()),
+                })),
+                op: Operator::EQ,
+            }),
+            Statement::Mangle(Mangle {
+                key: Expression::Named(NamedExpression::Meta(Meta {
+                    key: MetaKey::Nftrace,
+                })),
+                value: Expression::Number(1),
+            }),
+        ];
+        self.objects.push(NfObject::ListObject(Rule(schema::Rule {
+            family: self.family.into(),
+            table: table_name.into(),
+            chain: chain_name.into(),
+            expr: Cow::Owned(expressions),
+            handle: None,
+            index: None,
+            comment: Some(Cow::Owned(format!("trace {network}"))),
+        })));
+        self
+    }
+
     #[must_use]
     pub fn build_ruleset(self) -> Nftables<'a> {
         Nftables {
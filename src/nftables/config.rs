@@ -1,17 +1,38 @@
 use crate::error::AppError;
-use crate::nftables::builder::{NftRulesetBuilder, RuleDirection, RuleProto, SetElements};
-use crate::set::custom_set::CustomSet;
-use crate::utils::read_ip_set_file;
+use crate::nflog::NflogPacket;
+use crate::nftables::applier::{Applier, NftApplier};
+use crate::nftables::builder::{
+    AddressFamilies, ApplyStrategy, Direction, NftRulesetBuilder, RateLimit, RuleDirection,
+    RuleProto, SetElements, TableFamily, TimeWindow,
+};
+use crate::nftables::diff::{SetDiff, diff_rulesets};
+use crate::nftables::extra_rules::{
+    EXTRA_RULE_COMMENT, ExtraChain, ExtraRule, extra_rules_from_env,
+};
+use crate::set::custom_set::{CustomSet, read_custom_feed};
+use crate::set::feed::FeedFormat;
+use crate::set::fetch_policy::source_var;
+use crate::set::manual::ManualEntry;
+use crate::set::overrides::{Overrides, merge_entries};
+use crate::settings::Environment;
+use crate::utils::parse_size;
 use crate::utils::stats::{ChainDropStats, RuleInfo, Stats};
-use crate::utils::subnet::parse_from_string;
-use nftables::helper;
-use nftables::schema::{Nftables, SetType};
+use crate::utils::subnet::{Strictness, parse_from_string};
+use ipnetwork::IpNetwork;
+use nftables::expr::{Elem, Expression, NamedExpression, Prefix};
+use nftables::schema::{NfListObject, NfObject, Nftables, SetType};
 use nftables::stmt::Statement;
 use nftables::types::NfHook;
-use std::env;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
-use tracing::{debug, info, trace};
+use tracing::{debug, info, trace, warn};
+
+/// Hook priority of the trace chains, before the `raw` chains and the chains of the blocklist.
+const TRACE_PRIORITY: i32 = -350;
 
 /// Defines the configuration structure for managing `nftables`.
 /// This includes tables, chains, sets, and rules used for blocking traffic.
@@ -19,63 +40,312 @@ use tracing::{debug, info, trace};
 pub struct NftConfig<'a> {
     /// Name of the table to contain the blocklist.
     pub table_name: String,
+    /// Family of the table; sets and rules of address families it cannot match are left out.
+    pub family: TableFamily,
+    /// Address families that are blocked; the sets and rules of the others are left out.
+    pub address_families: AddressFamilies,
     /// Name of the `prerouting` chain used for ingress traffic.
     pub prerouting_chain: String,
     /// Name of the `postrouting` chain used for egress traffic.
     pub postrouting_chain: String,
     /// Name of the blocklist set for IPs.
     pub blocklist_set_name: String,
+    /// Traffic the IPv4 blocklist applies to.
+    pub ipv4_direction: Direction,
+    /// Traffic the IPv6 blocklist applies to.
+    pub ipv6_direction: Direction,
+    /// Rate above which the addresses of the IPv4 blocklist are dropped; they are dropped outright when `None`.
+    pub ipv4_rate_limit: Option<RateLimit>,
+    /// Rate above which the addresses of the IPv6 blocklist are dropped; they are dropped outright when `None`.
+    pub ipv6_rate_limit: Option<RateLimit>,
+    /// Hours and days the IPv4 blocklist is enforced in; it is always enforced when `None`.
+    pub ipv4_time_window: Option<TimeWindow>,
+    /// Hours and days the IPv6 blocklist is enforced in; it is always enforced when `None`.
+    pub ipv6_time_window: Option<TimeWindow>,
+    /// Name of the monitor set for IPs that are counted and logged but not dropped.
+    pub monitor_set_name: String,
+    /// Bytes the monitored IPs may exchange before they are dropped; they are never dropped when `None`.
+    pub monitor_quota: Option<u32>,
+    /// Name of the quota object shared by the monitor sets.
+    pub monitor_quota_name: String,
+    /// Name of the quarantine set for monitored IPs promoted at runtime; no quarantine set is created when `None`.
+    pub quarantine_set_name: Option<String>,
+    /// Name of the manual set for entries added with `nftblockd add`; no manual set is created when `None`.
+    pub manual_set_name: Option<String>,
     pub anti_lockout_set: CustomSet<'a>,
     pub custom_blocklist_set: CustomSet<'a>,
+    /// Static rules declared in the extra rules file, added after the anti-lockout rules.
+    pub extra_rules: Vec<ExtraRule>,
+    /// nflog group receiving the dropped packets; the kernel log is used when `None`.
+    pub log_group: Option<u16>,
+    /// Whether updates recreate the table or only refill its sets.
+    pub apply_strategy: ApplyStrategy,
+    /// Creates the blocklist sets with the `timeout` flag, so that their elements can expire,
+    /// e.g., when a source provides the expiry of every entry.
+    pub element_timeouts: bool,
+    /// Backend used to apply and list rulesets.
+    pub applier: Arc<dyn Applier>,
+}
+
+impl Default for NftConfig<'_> {
+    /// Creates an `NftConfig` with the default names and empty anti-lockout and custom blocklist sets.
+    /// No environment variables are read.
+    fn default() -> Self {
+        NftConfig {
+            table_name: "nftblockd".to_string(),
+            family: TableFamily::default(),
+            address_families: AddressFamilies::default(),
+            prerouting_chain: "prerouting".to_string(),
+            postrouting_chain: "postrouting".to_string(),
+            blocklist_set_name: "blocklist_set".to_string(),
+            ipv4_direction: Direction::default(),
+            ipv6_direction: Direction::default(),
+            ipv4_rate_limit: None,
+            ipv6_rate_limit: None,
+            ipv4_time_window: None,
+            ipv6_time_window: None,
+            monitor_set_name: "monitor_set".to_string(),
+            monitor_quota: None,
+            monitor_quota_name: "monitor_quota".to_string(),
+            quarantine_set_name: None,
+            manual_set_name: None,
+            anti_lockout_set: CustomSet::empty("anti_lockout_set".to_string()),
+            custom_blocklist_set: CustomSet::empty("custom_blocklist_set".to_string()),
+            extra_rules: Vec::new(),
+            log_group: None,
+            apply_strategy: ApplyStrategy::default(),
+            element_timeouts: false,
+            applier: Arc::new(NftApplier::default()),
+        }
+    }
 }
 
 impl<'a> NftConfig<'a> {
-    /// Creates a new `NftConfig` by fetching configuration values from environment variables.
+    /// Creates a new `NftConfig` by fetching configuration values from `environment`.
     ///
     /// # Returns
     /// A populated `NftConfig` instance with default values for unspecified environment variables.
     ///
     /// # Errors
     /// Returns an `AppError` if anti-lockout rules fail to load/parse.
-    pub fn new(delimiter: Option<&str>) -> Result<Self, AppError> {
+    pub fn new(environment: &Environment, delimiter: Option<&str>) -> Result<Self, AppError> {
+        let overrides = Overrides::from_env(environment)?;
         let anti_lockout_set = CustomSet::new(
-            env::var("NFTBLOCKD_ANTI_LOCKOUT_SET_NAME").unwrap_or("anti_lockout_set".to_string()),
-            parse_from_string(env::var("NFTBLOCKD_ANTI_LOCKOUT_IPV4").ok().as_ref(), None),
-            parse_from_string(env::var("NFTBLOCKD_ANTI_LOCKOUT_IPV6").ok().as_ref(), None),
+            environment
+                .var("NFTBLOCKD_ANTI_LOCKOUT_SET_NAME")
+                .unwrap_or("anti_lockout_set".to_string()),
+            merge_entries(
+                parse_from_string(
+                    environment.var("NFTBLOCKD_ANTI_LOCKOUT_IPV4").ok().as_ref(),
+                    None,
+                ),
+                overrides.never_block(false),
+            ),
+            merge_entries(
+                parse_from_string(
+                    environment.var("NFTBLOCKD_ANTI_LOCKOUT_IPV6").ok().as_ref(),
+                    None,
+                ),
+                overrides.never_block(true),
+            ),
         )?;
 
+        let strictness = Strictness::from_env(environment, "CUSTOM_BLOCKLIST", Strictness::Strict)?;
+        let custom_ipv4 = read_custom_feed(
+            environment
+                .var("NFTBLOCKD_CUSTOM_BLOCKLIST_PATH_IPV4")
+                .ok()
+                .as_deref(),
+            delimiter,
+            false,
+            strictness,
+        )?;
+        let custom_ipv6 = read_custom_feed(
+            environment
+                .var("NFTBLOCKD_CUSTOM_BLOCKLIST_PATH_IPV6")
+                .ok()
+                .as_deref(),
+            delimiter,
+            true,
+            strictness,
+        )?;
         let custom_blocklist_set = CustomSet::new(
-            env::var("NFTBLOCKD_CUSTOM_BLOCKLIST_SET_NAME")
+            environment
+                .var("NFTBLOCKD_CUSTOM_BLOCKLIST_SET_NAME")
                 .unwrap_or("custom_blocklist_set".to_string()),
-            parse_from_string(
-                read_ip_set_file(
-                    env::var("NFTBLOCKD_CUSTOM_BLOCKLIST_PATH_IPV4")
-                        .ok()
-                        .as_ref(),
-                )?,
-                delimiter,
-            ),
-            parse_from_string(
-                read_ip_set_file(
-                    env::var("NFTBLOCKD_CUSTOM_BLOCKLIST_PATH_IPV6")
-                        .ok()
-                        .as_ref(),
-                )?,
-                delimiter,
-            ),
-        )?;
+            merge_entries(custom_ipv4.to_entries(), overrides.block(false)),
+            merge_entries(custom_ipv6.to_entries(), overrides.block(true)),
+        )?
+        .with_annotations(
+            custom_ipv4
+                .expiry
+                .into_iter()
+                .chain(custom_ipv6.expiry)
+                .collect(),
+            &custom_ipv4
+                .comments
+                .into_iter()
+                .chain(custom_ipv6.comments)
+                .collect(),
+        );
 
-        Ok(NftConfig {
-            table_name: env::var("NFTBLOCKD_TABLE_NAME").unwrap_or("nftblockd".to_string()),
-            prerouting_chain: env::var("NFTBLOCKD_PREROUTING_CHAIN_NAME")
+        let config = NftConfig {
+            table_name: environment.var("NFTBLOCKD_TABLE_NAME").unwrap_or("nftblockd".to_string()),
+            family: environment.var("NFTBLOCKD_TABLE_FAMILY")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|f| TableFamily::parse(&f))
+                .transpose()?
+                .unwrap_or_default(),
+            address_families: AddressFamilies::default(),
+            prerouting_chain: environment.var("NFTBLOCKD_PREROUTING_CHAIN_NAME")
                 .unwrap_or("prerouting".to_string()),
-            postrouting_chain: env::var("NFTBLOCKD_POSTROUTING_CHAIN_NAME")
+            postrouting_chain: environment.var("NFTBLOCKD_POSTROUTING_CHAIN_NAME")
                 .unwrap_or("postrouting".to_string()),
-            blocklist_set_name: env::var("NFTBLOCKD_BLOCKLIST_SET_NAME")
+            blocklist_set_name: environment.var("NFTBLOCKD_BLOCKLIST_SET_NAME")
                 .unwrap_or("blocklist_set".to_string()),
+            ipv4_direction: source_var(environment, "IPV4", "DIRECTION")
+                .map(|d| Direction::parse(&d))
+                .transpose()?
+                .unwrap_or_default(),
+            ipv6_direction: source_var(environment, "IPV6", "DIRECTION")
+                .map(|d| Direction::parse(&d))
+                .transpose()?
+                .unwrap_or_default(),
+            ipv4_rate_limit: rate_limit(environment, "IPV4")?,
+            ipv6_rate_limit: rate_limit(environment, "IPV6")?,
+            ipv4_time_window: TimeWindow::parse(
+                source_var(environment, "IPV4", "ENFORCE_HOURS").as_deref(),
+                source_var(environment, "IPV4", "ENFORCE_DAYS").as_deref(),
+            )?,
+            ipv6_time_window: TimeWindow::parse(
+                source_var(environment, "IPV6", "ENFORCE_HOURS").as_deref(),
+                source_var(environment, "IPV6", "ENFORCE_DAYS").as_deref(),
+            )?,
+            monitor_set_name: environment.var("NFTBLOCKD_MONITOR_SET_NAME")
+                .unwrap_or("monitor_set".to_string()),
+            monitor_quota: environment.var("NFTBLOCKD_MONITOR_QUOTA")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|v| {
+                    parse_size(&v)
+                        .and_then(|bytes| u32::try_from(bytes).ok())
+                        .ok_or_else(|| {
+                            AppError::ParseError(format!(
+                                "invalid NFTBLOCKD_MONITOR_QUOTA: {v}; expected bytes below 4G such as `50M`"
+                            ))
+                        })
+                })
+                .transpose()?,
+            monitor_quota_name: environment.var("NFTBLOCKD_MONITOR_QUOTA_NAME")
+                .unwrap_or("monitor_quota".to_string()),
+            quarantine_set_name: environment.var("NFTBLOCKD_QUARANTINE_THRESHOLD")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|_| {
+                    environment.var("NFTBLOCKD_QUARANTINE_SET_NAME")
+                        .unwrap_or("quarantine_set".to_string())
+                }),
+            manual_set_name: Some(
+                environment.var("NFTBLOCKD_MANUAL_SET_NAME").unwrap_or("manual_set".to_string()),
+            ),
             anti_lockout_set,
             custom_blocklist_set,
-        })
+            extra_rules: extra_rules_from_env(environment)?,
+            log_group: environment.var("NFTBLOCKD_NFLOG_GROUP")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|g| g.parse::<u16>())
+                .transpose()?,
+            apply_strategy: environment.var("NFTBLOCKD_APPLY_STRATEGY")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|s| ApplyStrategy::parse(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            element_timeouts: FeedFormat::from_env(environment, "IPV4")?.expires()
+                || FeedFormat::from_env(environment, "IPV6")?.expires(),
+            applier: Arc::new(NftApplier::from_env(environment)?),
+        };
+        config.validate_extra_rules()?;
+        Ok(config)
+    }
+
+    /// Checks that the extra rules only reference sets of the table.
+    ///
+    /// # Errors
+    /// Returns an `AppError` naming the first rule referencing an unknown set.
+    pub fn validate_extra_rules(&self) -> Result<(), AppError> {
+        let known = [
+            Some(&self.blocklist_set_name),
+            Some(&self.anti_lockout_set.set_name),
+            Some(&self.custom_blocklist_set.set_name),
+            self.quarantine_set_name.as_ref(),
+            self.manual_set_name.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|set_name| [format!("{set_name}_ipv4"), format!("{set_name}_ipv6")])
+        .collect::<HashSet<_>>();
+        match self.extra_rules.iter().find_map(|rule| {
+            rule.set
+                .as_ref()
+                .filter(|(_, set_name)| !known.contains(set_name))
+                .map(|(_, set_name)| (rule, set_name))
+        }) {
+            Some((rule, set_name)) => Err(AppError::ParseError(format!(
+                "invalid extra rule: {}: the table has no `{set_name}` set",
+                rule.line
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Namespaces the table with the name of an `instance`, so that several instances can coexist on one host.
+    /// The sets and chains live in the table, so they are isolated along with it.
+    #[must_use]
+    pub fn with_instance(mut self, instance: Option<&str>) -> Self {
+        if let Some(instance) = instance {
+            self.table_name = format!("{}_{instance}", self.table_name);
+        }
+        self
+    }
+
+    /// Blocks only the given address families, leaving out the sets and rules of the others.
+    /// An `inet` table becomes an `ip` or `ip6` table when only one family is blocked, so that its chains
+    /// do not see the traffic of the other one.
+    #[must_use]
+    pub fn with_address_families(mut self, families: AddressFamilies) -> Self {
+        self.address_families = families;
+        if self.family == TableFamily::Inet {
+            self.family = match families {
+                AddressFamilies::Ipv4 => TableFamily::Ip,
+                AddressFamilies::Ipv6 => TableFamily::Ip6,
+                AddressFamilies::Both => TableFamily::Inet,
+            };
+        }
+        self
+    }
+
+    /// Adds the quarantine sets, whose addresses are dropped until their timeout expires.
+    #[must_use]
+    pub fn with_quarantine_set(mut self, set_name: Option<String>) -> Self {
+        self.quarantine_set_name = set_name;
+        self
+    }
+
+    /// Adds the manual sets, filled with the entries added with `nftblockd add`.
+    #[must_use]
+    pub fn with_manual_set(mut self, set_name: Option<String>) -> Self {
+        self.manual_set_name = set_name;
+        self
+    }
+
+    /// Replaces the backend used to apply and list rulesets.
+    #[must_use]
+    pub fn with_applier(mut self, applier: Arc<dyn Applier>) -> Self {
+        self.applier = applier;
+        self
     }
 
     /// Deletes the specified `nftables` table and its contents by applying the delete operation.
@@ -84,9 +354,10 @@ impl<'a> NftConfig<'a> {
     /// Returns an `AppError` if the table cannot be deleted.
     pub fn delete_table_and_apply(&self) -> Result<(), AppError> {
         let ruleset = NftRulesetBuilder::new()
+            .with_family(self.family)
             .delete_table(&self.table_name)
             .build_ruleset();
-        helper::apply_ruleset(&ruleset)?;
+        self.applier.apply(&ruleset)?;
         info!(
             "the `{}` table and all its contents have been deleted",
             self.table_name
@@ -94,6 +365,41 @@ impl<'a> NftConfig<'a> {
         Ok(())
     }
 
+    /// Empties the blocklist, custom blocklist, quarantine, and manual sets, keeping the table, its chains and
+    /// counters, and the anti-lockout sets in place.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the sets cannot be flushed, e.g., because the table does not exist.
+    pub fn flush_sets_and_apply(&self) -> Result<(), AppError> {
+        let mut builder = self.builder();
+        for set_name in [
+            &self.blocklist_set_name,
+            &self.custom_blocklist_set.set_name,
+        ]
+        .into_iter()
+        .chain(&self.quarantine_set_name)
+        .chain(&self.manual_set_name)
+        {
+            builder = builder
+                .flush_set(
+                    &self.table_name,
+                    format!("{set_name}_ipv4"),
+                    &SetType::Ipv4Addr,
+                )
+                .flush_set(
+                    &self.table_name,
+                    format!("{set_name}_ipv6"),
+                    &SetType::Ipv6Addr,
+                );
+        }
+        self.applier.apply(&builder.build_ruleset())?;
+        warn!(
+            "the blocklist sets of the `{}` table have been flushed; nothing they listed is dropped",
+            self.table_name
+        );
+        Ok(())
+    }
+
     /// Generates the complete `nftables` ruleset for the current configuration.
     /// This includes table, sets, chains, and rules for IPv4 and IPv6 blocklists and anti-lockout rules.
     ///
@@ -103,13 +409,157 @@ impl<'a> NftConfig<'a> {
     ///
     /// # Returns
     /// A fully constructed `Nftables` structure containing all objects.
-    #[allow(clippy::too_many_lines)]
     #[must_use]
     pub fn generate_ruleset(
         &'a self,
         ipv4_elements: &'a Option<SetElements<'a>>,
         ipv6_elements: &'a Option<SetElements<'a>>,
     ) -> Nftables<'a> {
+        self.ruleset_builder(ipv4_elements, ipv6_elements)
+            .build_ruleset()
+    }
+
+    /// Generates the ruleset like `generate_ruleset`, adding monitor sets whose elements are counted
+    /// and logged but never dropped. The monitor sets are left out when both are `None`.
+    ///
+    /// # Parameters
+    /// - `ipv4_elements`: Optional IPv4 blocklist elements to include in the ruleset.
+    /// - `ipv6_elements`: Optional IPv6 blocklist elements to include in the ruleset.
+    /// - `monitor_ipv4`: Optional IPv4 elements to monitor.
+    /// - `monitor_ipv6`: Optional IPv6 elements to monitor.
+    ///
+    /// # Returns
+    /// A fully constructed `Nftables` structure containing all objects.
+    #[must_use]
+    pub fn generate_monitored_ruleset(
+        &'a self,
+        ipv4_elements: &'a Option<SetElements<'a>>,
+        ipv6_elements: &'a Option<SetElements<'a>>,
+        monitor_ipv4: &'a Option<SetElements<'a>>,
+        monitor_ipv6: &'a Option<SetElements<'a>>,
+    ) -> Nftables<'a> {
+        let mut builder = self.ruleset_builder(ipv4_elements, ipv6_elements);
+        if monitor_ipv4.is_none() && monitor_ipv6.is_none() {
+            return builder.build_ruleset();
+        }
+        let table = self.table_name.as_str();
+        let ipv4_monitor_set_name = format!("{}_ipv4", self.monitor_set_name);
+        let ipv6_monitor_set_name = format!("{}_ipv6", self.monitor_set_name);
+        builder = builder
+            .build_set(table, ipv4_monitor_set_name.clone(), &SetType::Ipv4Addr)
+            .build_set(table, ipv6_monitor_set_name.clone(), &SetType::Ipv6Addr)
+            .build_rule(
+                table,
+                self.prerouting_chain.as_str(),
+                ipv4_monitor_set_name.clone(),
+                RuleProto::Ip,
+                RuleDirection::Saddr,
+                true,
+                Statement::Continue(None),
+                "prerouting ipv4 monitor rule",
+            )
+            .build_rule(
+                table,
+                self.prerouting_chain.as_str(),
+                ipv6_monitor_set_name.clone(),
+                RuleProto::Ip6,
+                RuleDirection::Saddr,
+                true,
+                Statement::Continue(None),
+                "prerouting ipv6 monitor rule",
+            )
+            .build_rule(
+                table,
+                self.postrouting_chain.as_str(),
+                ipv4_monitor_set_name.clone(),
+                RuleProto::Ip,
+                RuleDirection::Daddr,
+                true,
+                Statement::Continue(None),
+                "postrouting ipv4 monitor rule",
+            )
+            .build_rule(
+                table,
+                self.postrouting_chain.as_str(),
+                ipv6_monitor_set_name.clone(),
+                RuleProto::Ip6,
+                RuleDirection::Daddr,
+                true,
+                Statement::Continue(None),
+                "postrouting ipv6 monitor rule",
+            );
+
+        // Monitored addresses get a byte budget before they are dropped like blocked ones.
+        if let Some(bytes) = self.monitor_quota {
+            let quota = self.monitor_quota_name.as_str();
+            builder = builder
+                .build_quota(table, quota, bytes)
+                .build_quota_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv4_monitor_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Saddr,
+                    quota,
+                    "prerouting ipv4 monitor quota rule",
+                )
+                .build_quota_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv6_monitor_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Saddr,
+                    quota,
+                    "prerouting ipv6 monitor quota rule",
+                )
+                .build_quota_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv4_monitor_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Daddr,
+                    quota,
+                    "postrouting ipv4 monitor quota rule",
+                )
+                .build_quota_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv6_monitor_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Daddr,
+                    quota,
+                    "postrouting ipv6 monitor quota rule",
+                );
+        }
+
+        if let Some(ipv4_elements) = monitor_ipv4 {
+            builder = builder.build_set_elements(
+                table,
+                ipv4_monitor_set_name,
+                &SetType::Ipv4Addr,
+                ipv4_elements,
+            );
+        }
+
+        if let Some(ipv6_elements) = monitor_ipv6 {
+            builder = builder.build_set_elements(
+                table,
+                ipv6_monitor_set_name,
+                &SetType::Ipv6Addr,
+                ipv6_elements,
+            );
+        }
+
+        builder.build_ruleset()
+    }
+
+    /// Builds the table, sets, chains, and rules shared by all generated rulesets.
+    #[allow(clippy::too_many_lines)]
+    fn ruleset_builder(
+        &'a self,
+        ipv4_elements: &'a Option<SetElements<'a>>,
+        ipv6_elements: &'a Option<SetElements<'a>>,
+    ) -> NftRulesetBuilder<'a> {
         let ipv4_blocklist_set_name = format!("{}_ipv4", self.blocklist_set_name);
         let ipv6_blocklist_set_name = format!("{}_ipv6", self.blocklist_set_name);
         let ipv4_anti_lockout_set_name = format!("{}_ipv4", self.anti_lockout_set.set_name);
@@ -119,7 +569,9 @@ impl<'a> NftConfig<'a> {
 
         let table = self.table_name.as_str();
 
-        let mut builder = NftRulesetBuilder::new()
+        let mut builder = self
+            .builder()
+            .with_log_group(self.log_group.map(u32::from))
             .build_table(table)
             .delete_table(table)
             .build_table(table)
@@ -144,9 +596,25 @@ impl<'a> NftConfig<'a> {
                 table,
                 ipv6_anti_lockout_set_name.clone(),
                 &SetType::Ipv6Addr,
-            )
-            .build_set(table, ipv4_blocklist_set_name.clone(), &SetType::Ipv4Addr)
-            .build_set(table, ipv6_blocklist_set_name.clone(), &SetType::Ipv6Addr)
+            );
+        builder = if self.element_timeouts {
+            builder
+                .build_interval_timeout_set(
+                    table,
+                    ipv4_blocklist_set_name.clone(),
+                    &SetType::Ipv4Addr,
+                )
+                .build_interval_timeout_set(
+                    table,
+                    ipv6_blocklist_set_name.clone(),
+                    &SetType::Ipv6Addr,
+                )
+        } else {
+            builder
+                .build_set(table, ipv4_blocklist_set_name.clone(), &SetType::Ipv4Addr)
+                .build_set(table, ipv6_blocklist_set_name.clone(), &SetType::Ipv6Addr)
+        };
+        builder = builder
             .build_set(
                 table,
                 ipv4_custom_blocklist_set_name.clone(),
@@ -196,7 +664,15 @@ impl<'a> NftConfig<'a> {
                 false,
                 Statement::Accept(None),
                 "postrouting ipv6 anti-lockout rule",
-            )
+            );
+        for rule in &self.extra_rules {
+            let chain = match rule.chain {
+                ExtraChain::Prerouting => self.prerouting_chain.as_str(),
+                ExtraChain::Postrouting => self.postrouting_chain.as_str(),
+            };
+            builder = builder.build_extra_rule(table, chain, rule);
+        }
+        builder = builder
             .build_rule(
                 table,
                 self.prerouting_chain.as_str(),
@@ -236,75 +712,622 @@ impl<'a> NftConfig<'a> {
                 false,
                 Statement::Drop(None),
                 "postrouting ipv6 custom blocklist rule",
-            )
-            .build_rule(
-                table,
-                self.prerouting_chain.as_str(),
-                ipv4_blocklist_set_name.clone(),
-                RuleProto::Ip,
-                RuleDirection::Saddr,
-                true,
-                Statement::Drop(None),
-                "prerouting ipv4 blocklist rule",
-            )
-            .build_rule(
-                table,
-                self.prerouting_chain.as_str(),
-                ipv6_blocklist_set_name.clone(),
-                RuleProto::Ip6,
-                RuleDirection::Saddr,
-                true,
-                Statement::Drop(None),
-                "prerouting ipv6 blocklist rule",
-            )
-            .build_rule(
-                table,
-                self.postrouting_chain.as_str(),
-                ipv4_blocklist_set_name.clone(),
-                RuleProto::Ip,
-                RuleDirection::Daddr,
-                true,
-                Statement::Drop(None),
-                "postrouting ipv4 blocklist rule",
-            )
-            .build_rule(
-                table,
-                self.postrouting_chain.as_str(),
-                ipv6_blocklist_set_name.clone(),
-                RuleProto::Ip6,
-                RuleDirection::Daddr,
-                true,
-                Statement::Drop(None),
-                "postrouting ipv6 blocklist rule",
             );
 
+        builder = builder.with_time_window(self.ipv4_time_window.clone());
+        if self.ipv4_direction.ingress() {
+            builder = match &self.ipv4_rate_limit {
+                Some(rate_limit) => builder.build_rate_limit_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv4_blocklist_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Saddr,
+                    true,
+                    rate_limit,
+                    "prerouting ipv4 rate limit rule",
+                ),
+                None => builder.build_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv4_blocklist_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Saddr,
+                    true,
+                    Statement::Drop(None),
+                    "prerouting ipv4 blocklist rule",
+                ),
+            };
+        }
+
+        builder = builder.with_time_window(self.ipv6_time_window.clone());
+        if self.ipv6_direction.ingress() {
+            builder = match &self.ipv6_rate_limit {
+                Some(rate_limit) => builder.build_rate_limit_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv6_blocklist_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Saddr,
+                    true,
+                    rate_limit,
+                    "prerouting ipv6 rate limit rule",
+                ),
+                None => builder.build_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv6_blocklist_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Saddr,
+                    true,
+                    Statement::Drop(None),
+                    "prerouting ipv6 blocklist rule",
+                ),
+            };
+        }
+
+        builder = builder.with_time_window(self.ipv4_time_window.clone());
+        if self.ipv4_direction.egress() {
+            builder = match &self.ipv4_rate_limit {
+                Some(rate_limit) => builder.build_rate_limit_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv4_blocklist_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Daddr,
+                    true,
+                    rate_limit,
+                    "postrouting ipv4 rate limit rule",
+                ),
+                None => builder.build_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv4_blocklist_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Daddr,
+                    true,
+                    Statement::Drop(None),
+                    "postrouting ipv4 blocklist rule",
+                ),
+            };
+        }
+
+        builder = builder.with_time_window(self.ipv6_time_window.clone());
+        if self.ipv6_direction.egress() {
+            builder = match &self.ipv6_rate_limit {
+                Some(rate_limit) => builder.build_rate_limit_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv6_blocklist_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Daddr,
+                    true,
+                    rate_limit,
+                    "postrouting ipv6 rate limit rule",
+                ),
+                None => builder.build_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv6_blocklist_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Daddr,
+                    true,
+                    Statement::Drop(None),
+                    "postrouting ipv6 blocklist rule",
+                ),
+            };
+        }
+        builder = builder.with_time_window(None);
+
         if let Some(ipv4_elements) = &self.anti_lockout_set.ipv4_elements {
-            builder = builder.build_set_elements(table, ipv4_anti_lockout_set_name, ipv4_elements);
+            builder = builder.build_set_elements(
+                table,
+                ipv4_anti_lockout_set_name,
+                &SetType::Ipv4Addr,
+                ipv4_elements,
+            );
         }
 
         if let Some(ipv6_elements) = &self.anti_lockout_set.ipv6_elements {
-            builder = builder.build_set_elements(table, ipv6_anti_lockout_set_name, ipv6_elements);
+            builder = builder.build_set_elements(
+                table,
+                ipv6_anti_lockout_set_name,
+                &SetType::Ipv6Addr,
+                ipv6_elements,
+            );
         }
 
         if let Some(ipv4_elements) = &self.custom_blocklist_set.ipv4_elements {
-            builder =
-                builder.build_set_elements(table, ipv4_custom_blocklist_set_name, ipv4_elements);
+            builder = builder.build_set_elements(
+                table,
+                ipv4_custom_blocklist_set_name,
+                &SetType::Ipv4Addr,
+                ipv4_elements,
+            );
         }
 
         if let Some(ipv6_elements) = &self.custom_blocklist_set.ipv6_elements {
-            builder =
-                builder.build_set_elements(table, ipv6_custom_blocklist_set_name, ipv6_elements);
+            builder = builder.build_set_elements(
+                table,
+                ipv6_custom_blocklist_set_name,
+                &SetType::Ipv6Addr,
+                ipv6_elements,
+            );
         }
 
         if let Some(ipv4_elements) = ipv4_elements {
-            builder = builder.build_set_elements(table, ipv4_blocklist_set_name, ipv4_elements);
+            builder = builder.build_set_elements(
+                table,
+                ipv4_blocklist_set_name,
+                &SetType::Ipv4Addr,
+                ipv4_elements,
+            );
         }
 
         if let Some(ipv6_elements) = ipv6_elements {
-            builder = builder.build_set_elements(table, ipv6_blocklist_set_name, ipv6_elements);
+            builder = builder.build_set_elements(
+                table,
+                ipv6_blocklist_set_name,
+                &SetType::Ipv6Addr,
+                ipv6_elements,
+            );
         }
 
-        builder.build_ruleset()
+        if let Some(quarantine_set_name) = &self.quarantine_set_name {
+            let ipv4_quarantine_set_name = format!("{quarantine_set_name}_ipv4");
+            let ipv6_quarantine_set_name = format!("{quarantine_set_name}_ipv6");
+            builder = builder
+                .build_timeout_set(table, ipv4_quarantine_set_name.clone(), &SetType::Ipv4Addr)
+                .build_timeout_set(table, ipv6_quarantine_set_name.clone(), &SetType::Ipv6Addr)
+                .build_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv4_quarantine_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Saddr,
+                    true,
+                    Statement::Drop(None),
+                    "prerouting ipv4 quarantine rule",
+                )
+                .build_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv6_quarantine_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Saddr,
+                    true,
+                    Statement::Drop(None),
+                    "prerouting ipv6 quarantine rule",
+                )
+                .build_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv4_quarantine_set_name,
+                    RuleProto::Ip,
+                    RuleDirection::Daddr,
+                    true,
+                    Statement::Drop(None),
+                    "postrouting ipv4 quarantine rule",
+                )
+                .build_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv6_quarantine_set_name,
+                    RuleProto::Ip6,
+                    RuleDirection::Daddr,
+                    true,
+                    Statement::Drop(None),
+                    "postrouting ipv6 quarantine rule",
+                );
+        }
+
+        if let Some(manual_set_name) = &self.manual_set_name {
+            let ipv4_manual_set_name = format!("{manual_set_name}_ipv4");
+            let ipv6_manual_set_name = format!("{manual_set_name}_ipv6");
+            builder = builder
+                .build_interval_timeout_set(table, ipv4_manual_set_name.clone(), &SetType::Ipv4Addr)
+                .build_interval_timeout_set(table, ipv6_manual_set_name.clone(), &SetType::Ipv6Addr)
+                .build_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv4_manual_set_name.clone(),
+                    RuleProto::Ip,
+                    RuleDirection::Saddr,
+                    true,
+                    Statement::Drop(None),
+                    "prerouting ipv4 manual rule",
+                )
+                .build_rule(
+                    table,
+                    self.prerouting_chain.as_str(),
+                    ipv6_manual_set_name.clone(),
+                    RuleProto::Ip6,
+                    RuleDirection::Saddr,
+                    true,
+                    Statement::Drop(None),
+                    "prerouting ipv6 manual rule",
+                )
+                .build_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv4_manual_set_name,
+                    RuleProto::Ip,
+                    RuleDirection::Daddr,
+                    true,
+                    Statement::Drop(None),
+                    "postrouting ipv4 manual rule",
+                )
+                .build_rule(
+                    table,
+                    self.postrouting_chain.as_str(),
+                    ipv6_manual_set_name,
+                    RuleProto::Ip6,
+                    RuleDirection::Daddr,
+                    true,
+                    Statement::Drop(None),
+                    "postrouting ipv6 manual rule",
+                );
+        }
+
+        builder
+    }
+
+    /// Generates a ruleset that only flushes and refills the sets of the live table, leaving its chains,
+    /// rules, counters, and handles untouched. The quarantine and manual sets are kept as they are.
+    ///
+    /// # Parameters
+    /// - `live`: The active ruleset.
+    /// - `ipv4_elements`: Optional IPv4 blocklist elements.
+    /// - `ipv6_elements`: Optional IPv6 blocklist elements.
+    /// - `monitor_ipv4`: Optional IPv4 elements to monitor.
+    /// - `monitor_ipv6`: Optional IPv6 elements to monitor.
+    ///
+    /// # Returns
+    /// `None` if the live table lacks any of the sets, e.g., because it does not exist yet,
+    /// in which case the complete ruleset has to be applied instead.
+    #[must_use]
+    pub fn generate_refill_ruleset(
+        &'a self,
+        live: &Nftables<'_>,
+        ipv4_elements: &'a Option<SetElements<'a>>,
+        ipv6_elements: &'a Option<SetElements<'a>>,
+        monitor_ipv4: &'a Option<SetElements<'a>>,
+        monitor_ipv6: &'a Option<SetElements<'a>>,
+    ) -> Option<Nftables<'a>> {
+        let live_sets = live
+            .objects
+            .iter()
+            .filter_map(|object| match object {
+                NfObject::ListObject(NfListObject::Set(set)) if set.table == self.table_name => {
+                    Some(set.name.to_string())
+                }
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let mut refilled = vec![
+            (&self.blocklist_set_name, ipv4_elements, ipv6_elements),
+            (
+                &self.anti_lockout_set.set_name,
+                &self.anti_lockout_set.ipv4_elements,
+                &self.anti_lockout_set.ipv6_elements,
+            ),
+            (
+                &self.custom_blocklist_set.set_name,
+                &self.custom_blocklist_set.ipv4_elements,
+                &self.custom_blocklist_set.ipv6_elements,
+            ),
+        ];
+        // Monitor sets no longer needed are emptied, as their rules cannot be removed without a replace.
+        if monitor_ipv4.is_some()
+            || monitor_ipv6.is_some()
+            || self
+                .set_names(&self.monitor_set_name)
+                .any(|set_name| live_sets.contains(&set_name))
+        {
+            refilled.push((&self.monitor_set_name, monitor_ipv4, monitor_ipv6));
+            let live_quota = live.objects.iter().any(|object| {
+                matches!(object, NfObject::ListObject(NfListObject::Quota(quota))
+                    if quota.table == self.table_name && quota.name == self.monitor_quota_name)
+            });
+            if self.monitor_quota.is_some() && !live_quota {
+                debug!(
+                    "the live `{}` table lacks the `{}` quota; it has to be replaced",
+                    self.table_name, self.monitor_quota_name
+                );
+                return None;
+            }
+        }
+        let missing = refilled
+            .iter()
+            .map(|(set_name, _, _)| *set_name)
+            .chain(&self.quarantine_set_name)
+            .chain(&self.manual_set_name)
+            .flat_map(|set_name| self.set_names(set_name))
+            .find(|set_name| !live_sets.contains(set_name));
+        if let Some(missing) = missing {
+            debug!(
+                "the live `{}` table lacks the `{missing}` set; it has to be replaced",
+                self.table_name
+            );
+            return None;
+        }
+        // Refills leave the rules alone, so changed extra rules need a replace.
+        let mut live_extra_rules = live
+            .objects
+            .iter()
+            .filter_map(|object| match object {
+                NfObject::ListObject(NfListObject::Rule(rule)) if rule.table == self.table_name => {
+                    rule.comment
+                        .as_deref()
+                        .filter(|comment| comment.starts_with(EXTRA_RULE_COMMENT))
+                        .map(ToString::to_string)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut extra_rules = self
+            .extra_rules
+            .iter()
+            .filter(|rule| match rule.set_proto() {
+                RuleProto::Ip => self.family.ipv4() && self.address_families.ipv4(),
+                RuleProto::Ip6 => self.family.ipv6() && self.address_families.ipv6(),
+                RuleProto::Other => true,
+            })
+            .map(ExtraRule::comment)
+            .collect::<Vec<_>>();
+        extra_rules.sort();
+        live_extra_rules.sort();
+        if extra_rules != live_extra_rules {
+            debug!(
+                "the extra rules of the live `{}` table changed; it has to be replaced",
+                self.table_name
+            );
+            return None;
+        }
+
+        let table = self.table_name.as_str();
+        let mut builder = self.builder();
+        for (set_name, ipv4, ipv6) in refilled {
+            let ipv4_set_name = format!("{set_name}_ipv4");
+            let ipv6_set_name = format!("{set_name}_ipv6");
+            builder = builder
+                .flush_set(table, ipv4_set_name.clone(), &SetType::Ipv4Addr)
+                .flush_set(table, ipv6_set_name.clone(), &SetType::Ipv6Addr);
+            if let Some(elements) = ipv4
+                && !elements.is_empty()
+            {
+                builder =
+                    builder.build_set_elements(table, ipv4_set_name, &SetType::Ipv4Addr, elements);
+            }
+            if let Some(elements) = ipv6
+                && !elements.is_empty()
+            {
+                builder =
+                    builder.build_set_elements(table, ipv6_set_name, &SetType::Ipv6Addr, elements);
+            }
+        }
+        Some(builder.build_ruleset())
+    }
+
+    /// Creates a builder for the family of the table and the blocked address families.
+    fn builder(&self) -> NftRulesetBuilder<'a> {
+        NftRulesetBuilder::new()
+            .with_family(self.family)
+            .with_address_families(self.address_families)
+    }
+
+    /// Returns the names of the IPv4 and IPv6 sets of `set_name` that the family of the table can hold
+    /// and that are blocked.
+    fn set_names(&self, set_name: &str) -> impl Iterator<Item = String> {
+        [
+            (
+                format!("{set_name}_ipv4"),
+                self.family.ipv4() && self.address_families.ipv4(),
+            ),
+            (
+                format!("{set_name}_ipv6"),
+                self.family.ipv6() && self.address_families.ipv6(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, supported)| supported.then_some(name))
+    }
+
+    /// Adds addresses to the live quarantine sets, each expiring after its timeout.
+    ///
+    /// # Parameters
+    /// - `entries`: The addresses and how long they stay quarantined.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if no quarantine set is configured or the elements are rejected.
+    pub fn apply_quarantine(&self, entries: &[(IpAddr, Duration)]) -> Result<(), AppError> {
+        let Some(quarantine_set_name) = &self.quarantine_set_name else {
+            return Err(AppError::NftblockdError(
+                "no quarantine set is configured".to_string(),
+            ));
+        };
+        let element = |addr: &IpAddr, ttl: &Duration| {
+            Expression::Named(NamedExpression::Elem(Elem {
+                val: Box::new(Expression::String(Cow::Owned(addr.to_string()))),
+                timeout: Some(u32::try_from(ttl.as_secs().max(1)).unwrap_or(u32::MAX)),
+                ..Elem::default()
+            }))
+        };
+        let (ipv4, ipv6): (Vec<_>, Vec<_>) = entries.iter().partition(|(addr, _)| addr.is_ipv4());
+        let ipv4 = ipv4
+            .iter()
+            .map(|(addr, ttl)| element(addr, ttl))
+            .collect::<Vec<_>>();
+        let ipv6 = ipv6
+            .iter()
+            .map(|(addr, ttl)| element(addr, ttl))
+            .collect::<Vec<_>>();
+        let mut builder = self.builder();
+        if !ipv4.is_empty() {
+            builder = builder.build_set_elements(
+                &self.table_name,
+                format!("{quarantine_set_name}_ipv4"),
+                &SetType::Ipv4Addr,
+                &ipv4,
+            );
+        }
+        if !ipv6.is_empty() {
+            builder = builder.build_set_elements(
+                &self.table_name,
+                format!("{quarantine_set_name}_ipv6"),
+                &SetType::Ipv6Addr,
+                &ipv6,
+            );
+        }
+        self.applier.apply(&builder.build_ruleset())
+    }
+
+    /// Replaces the contents of the live manual sets with `entries` in one transaction.
+    /// Expired entries are left out; the others expire in the kernel after their remaining time.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if no manual set is configured or the table does not exist.
+    pub fn apply_manual(&self, entries: &[ManualEntry]) -> Result<(), AppError> {
+        let Some(manual_set_name) = &self.manual_set_name else {
+            return Err(AppError::NftblockdError(
+                "no manual set is configured".to_string(),
+            ));
+        };
+        let now = SystemTime::now();
+        let mut ipv4 = Vec::new();
+        let mut ipv6 = Vec::new();
+        for entry in entries {
+            let remaining = entry.remaining(now);
+            if remaining == Some(Duration::ZERO) {
+                continue;
+            }
+            let Some((addr, len)) = entry.network.split_once('/') else {
+                continue;
+            };
+            let element = Expression::Named(NamedExpression::Elem(Elem {
+                val: Box::new(Expression::Named(NamedExpression::Prefix(Prefix {
+                    addr: Box::new(Expression::String(Cow::Owned(addr.to_string()))),
+                    len: len.parse::<u32>()?,
+                }))),
+                timeout: remaining
+                    .map(|ttl| u32::try_from(ttl.as_secs().max(1)).unwrap_or(u32::MAX)),
+                comment: entry.comment.clone().map(Cow::Owned),
+                ..Elem::default()
+            }));
+            if addr.contains(':') {
+                ipv6.push(element);
+            } else {
+                ipv4.push(element);
+            }
+        }
+        let ipv4_manual_set_name = format!("{manual_set_name}_ipv4");
+        let ipv6_manual_set_name = format!("{manual_set_name}_ipv6");
+        let mut builder = self
+            .builder()
+            .flush_set(
+                &self.table_name,
+                ipv4_manual_set_name.clone(),
+                &SetType::Ipv4Addr,
+            )
+            .flush_set(
+                &self.table_name,
+                ipv6_manual_set_name.clone(),
+                &SetType::Ipv6Addr,
+            );
+        if !ipv4.is_empty() {
+            builder = builder.build_set_elements(
+                &self.table_name,
+                ipv4_manual_set_name,
+                &SetType::Ipv4Addr,
+                &ipv4,
+            );
+        }
+        if !ipv6.is_empty() {
+            builder = builder.build_set_elements(
+                &self.table_name,
+                ipv6_manual_set_name,
+                &SetType::Ipv6Addr,
+                &ipv6,
+            );
+        }
+        self.applier.apply(&builder.build_ruleset())
+    }
+
+    /// Returns the name of the table holding the trace rules of `apply_trace`.
+    #[must_use]
+    pub fn trace_table_name(&self) -> String {
+        format!("{}_trace", self.table_name)
+    }
+
+    /// Marks the packets from and to `network` for `nft monitor trace`, replacing the network traced before.
+    ///
+    /// The rules are kept in a separate table hooked in before the chains of the blocklist, so that updates
+    /// recreating the blocklist table keep them and the trace shows every chain and rule of the blocklist.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the table cannot be created, e.g., because the family of the blocklist table
+    /// cannot match `network`.
+    pub fn apply_trace(&self, network: &IpNetwork) -> Result<(), AppError> {
+        let supported = match network {
+            IpNetwork::V4(_) => self.family.ipv4(),
+            IpNetwork::V6(_) => self.family.ipv6(),
+        };
+        if !supported {
+            return Err(AppError::NftblockdError(format!(
+                "the `{}` table cannot match {network}",
+                self.table_name
+            )));
+        }
+        let table = self.trace_table_name();
+        let ruleset = NftRulesetBuilder::new()
+            .with_family(self.family)
+            .build_table(&table)
+            .delete_table(&table)
+            .build_table(&table)
+            .build_chain(&table, "prerouting", NfHook::Prerouting, TRACE_PRIORITY)
+            .build_chain(&table, "output", NfHook::Output, TRACE_PRIORITY)
+            .build_trace_rule(&table, "prerouting", network, RuleDirection::Saddr)
+            .build_trace_rule(&table, "prerouting", network, RuleDirection::Daddr)
+            .build_trace_rule(&table, "output", network, RuleDirection::Daddr)
+            .build_ruleset();
+        self.applier.apply(&ruleset)
+    }
+
+    /// Removes the trace rules of `apply_trace`; nothing happens when there are none.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the table cannot be deleted.
+    pub fn remove_trace(&self) -> Result<(), AppError> {
+        let table = self.trace_table_name();
+        let ruleset = NftRulesetBuilder::new()
+            .with_family(self.family)
+            .build_table(&table)
+            .delete_table(&table)
+            .build_ruleset();
+        self.applier.apply(&ruleset)
+    }
+
+    /// Returns the address that matched a monitor set, i.e., the source of packets logged in
+    /// the prerouting chain and the destination of those logged in the postrouting chain.
+    ///
+    /// # Returns
+    /// `None` if the packet was not logged by a monitor rule of this configuration.
+    #[must_use]
+    pub fn monitored_address(&self, packet: &NflogPacket) -> Option<IpAddr> {
+        let monitor_set = packet
+            .set_name
+            .strip_prefix(self.monitor_set_name.as_str())
+            .is_some_and(|suffix| suffix == "_ipv4" || suffix == "_ipv6");
+        if !monitor_set {
+            return None;
+        }
+        if packet.chain == self.prerouting_chain {
+            Some(packet.src)
+        } else if packet.chain == self.postrouting_chain {
+            Some(packet.dst)
+        } else {
+            None
+        }
     }
 
     /// Applies the generated `nftables` ruleset to the system.
@@ -329,21 +1352,115 @@ impl<'a> NftConfig<'a> {
         ipv4_elements: &Option<SetElements<'a>>,
         ipv6_elements: &Option<SetElements<'a>>,
     ) -> Result<(), AppError> {
-        let ruleset = self.generate_ruleset(ipv4_elements, ipv6_elements);
+        self.apply_monitored_nft(ipv4_elements, ipv6_elements, &None, &None)
+    }
+
+    /// Applies the ruleset generated by `generate_monitored_ruleset`.
+    ///
+    /// With `ApplyStrategy::Refill`, only the sets of the live table are refilled
+    /// (see `generate_refill_ruleset`); the complete ruleset is applied when the table is missing or outdated.
+    ///
+    /// # Parameters
+    /// - `ipv4_elements`: Optional set of IPv4 blocklist elements.
+    /// - `ipv6_elements`: Optional set of IPv6 blocklist elements.
+    /// - `monitor_ipv4`: Optional set of IPv4 elements to monitor.
+    /// - `monitor_ipv6`: Optional set of IPv6 elements to monitor.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the ruleset is rejected.
+    pub fn apply_monitored_nft(
+        &self,
+        ipv4_elements: &Option<SetElements<'a>>,
+        ipv6_elements: &Option<SetElements<'a>>,
+        monitor_ipv4: &Option<SetElements<'a>>,
+        monitor_ipv6: &Option<SetElements<'a>>,
+    ) -> Result<(), AppError> {
+        if self.apply_strategy == ApplyStrategy::Refill {
+            let live = self.applier.current_ruleset()?;
+            if let Some(ruleset) = self.generate_refill_ruleset(
+                &live,
+                ipv4_elements,
+                ipv6_elements,
+                monitor_ipv4,
+                monitor_ipv6,
+            ) {
+                debug!(
+                    "Kernel sets: {}",
+                    serde_json::to_string_pretty(&ruleset)
+                        .unwrap_or("Could not convert ruleset to JSON".to_string())
+                );
+                return self.applier.apply(&ruleset);
+            }
+            info!(
+                "creating the `{}` table; later updates only refill its sets",
+                self.table_name
+            );
+        }
+        let ruleset = self.generate_monitored_ruleset(
+            ipv4_elements,
+            ipv6_elements,
+            monitor_ipv4,
+            monitor_ipv6,
+        );
         debug!(
             "Kernel ruleset: {}",
             serde_json::to_string_pretty(&ruleset)
                 .unwrap_or("Could not convert ruleset to JSON".to_string())
         );
 
-        helper::apply_ruleset(&ruleset)?;
+        self.applier.apply(&ruleset)?;
         Ok(())
     }
 
-    #[allow(clippy::single_match)]
+    /// Compares the live sets with the sets that `apply_nft` would load, without applying anything.
+    ///
+    /// # Parameters
+    /// - `ipv4_elements`: Optional set of IPv4 blocklist elements.
+    /// - `ipv6_elements`: Optional set of IPv6 blocklist elements.
+    ///
+    /// # Returns
+    /// A `SetDiff` for every set in the table.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the live ruleset cannot be listed.
+    pub fn diff_nft(
+        &self,
+        ipv4_elements: &Option<SetElements<'a>>,
+        ipv6_elements: &Option<SetElements<'a>>,
+    ) -> Result<Vec<SetDiff>, AppError> {
+        let live = self.applier.current_ruleset()?;
+        let desired = self.generate_ruleset(ipv4_elements, ipv6_elements);
+        Ok(diff_rulesets(&self.table_name, &live, &desired))
+    }
+
+    /// Adds the current counters of the drop rules to the accumulated `stats`.
+    ///
+    /// Called right before a new ruleset is applied, as applying it resets the counters.
+    /// Does nothing with `ApplyStrategy::Refill`, which keeps the counters.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the live ruleset cannot be listed.
     pub async fn generate_stats(&self, stats: Arc<RwLock<Stats>>) -> Result<(), AppError> {
-        let ruleset = helper::get_current_ruleset()?;
-        debug!(
+        if self.apply_strategy == ApplyStrategy::Refill {
+            return Ok(());
+        }
+        let scraped = self.scrape_stats()?;
+        *stats.write().await += scraped;
+        Ok(())
+    }
+
+    /// Reads the counters of the drop rules in the live ruleset.
+    ///
+    /// # Returns
+    /// The packets and bytes dropped by the blocklist and the custom blocklist rules since the ruleset was applied.
+    ///
+    /// # Errors
+    /// Returns an `AppError` if the live ruleset cannot be listed.
+    #[allow(clippy::single_match)]
+    pub fn scrape_stats(&self) -> Result<Stats, AppError> {
+        let mut stats = Stats::default();
+        let ruleset = self.applier.current_ruleset()?;
+        trace!(
             "RULESET: {}",
             serde_json::to_string_pretty(&ruleset)
                 .unwrap_or("Could not convert ruleset to JSON".to_string())
@@ -364,16 +1481,14 @@ impl<'a> NftConfig<'a> {
                         .set_name
                         .starts_with(format!("@{}", self.blocklist_set_name).as_str())
                     {
-                        debug!("Adding rule stats to main_blocklist_drop_stats: {rule_info:?}");
-                        let rule_stats = ChainDropStats::from(rule_info);
-                        stats.write().await.main_blocklist_drop_stats += rule_stats;
+                        trace!("Adding rule stats to main_blocklist_drop_stats: {rule_info:?}");
+                        stats.main_blocklist_drop_stats += ChainDropStats::from(rule_info);
                     } else if rule_info
                         .set_name
                         .starts_with(format!("@{}", self.custom_blocklist_set.set_name).as_str())
                     {
-                        debug!("Adding rule stats to custom_blocklist_drop_stats: {rule_info:?}");
-                        let rule_stats = ChainDropStats::from(rule_info);
-                        stats.write().await.custom_blocklist_drop_stats += rule_stats;
+                        trace!("Adding rule stats to custom_blocklist_drop_stats: {rule_info:?}");
+                        stats.custom_blocklist_drop_stats += ChainDropStats::from(rule_info);
                     } else {
                         trace!("Set check; skipping: {rule_info:?}");
                     }
@@ -381,6 +1496,26 @@ impl<'a> NftConfig<'a> {
                 _ => {}
             }
         }
-        Ok(())
+        Ok(stats)
+    }
+}
+
+/// Reads the rate limit of `source` (`IPV4` or `IPV6`) when its action is `ratelimit`.
+///
+/// # Errors
+/// Will return `AppError` when the rate or the burst cannot be parsed.
+fn rate_limit(environment: &Environment, source: &str) -> Result<Option<RateLimit>, AppError> {
+    if !source_var(environment, source, "ACTION")
+        .is_some_and(|action| action.eq_ignore_ascii_case("ratelimit"))
+    {
+        return Ok(None);
     }
+    let burst = source_var(environment, source, "RATELIMIT_BURST")
+        .unwrap_or("5".to_string())
+        .parse::<u32>()?;
+    RateLimit::parse(
+        &source_var(environment, source, "RATELIMIT_RATE").unwrap_or("10/second".to_string()),
+        burst,
+    )
+    .map(Some)
 }
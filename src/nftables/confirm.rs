@@ -0,0 +1,78 @@
+use crate::error::AppError;
+use std::time::{Duration, SystemTime};
+
+/// Tracks rulesets applied provisionally until the operator confirms them ("commit-confirm").
+///
+/// Only changes to the configured part of the ruleset (chains, rules, anti-lockout and custom sets)
+/// need a confirmation; new elements fetched from the sources are accepted as they come.
+/// Rulesets are kept in their canonical JSON form (see `serialize_ruleset`).
+#[derive(Debug, Default)]
+pub struct Confirmation {
+    /// Configured part and full ruleset of the last confirmed ruleset.
+    confirmed: Option<(String, String)>,
+    /// Configured part and full ruleset of the provisional ruleset, with the confirmation deadline.
+    pending: Option<(String, String, SystemTime)>,
+}
+
+impl Confirmation {
+    /// Records an applied ruleset.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The configured part of the ruleset, i.e., the ruleset without the fetched elements.
+    /// * `ruleset` - The full ruleset that was applied.
+    /// * `timeout` - The time the operator has to confirm a changed configuration.
+    ///
+    /// # Returns
+    ///
+    /// The confirmation deadline when the ruleset is provisional, or `None` when it needs no confirmation.
+    pub fn applied(
+        &mut self,
+        key: String,
+        ruleset: String,
+        timeout: Duration,
+    ) -> Option<SystemTime> {
+        match (&self.confirmed, &self.pending) {
+            (None, _) => {}
+            (Some((confirmed, _)), _) if *confirmed == key => {}
+            (_, Some((pending, _, deadline))) if *pending == key => {
+                let deadline = *deadline;
+                self.pending = Some((key, ruleset, deadline));
+                return Some(deadline);
+            }
+            _ => {
+                let deadline = SystemTime::now() + timeout;
+                self.pending = Some((key, ruleset, deadline));
+                return Some(deadline);
+            }
+        }
+        self.confirmed = Some((key, ruleset));
+        self.pending = None;
+        None
+    }
+
+    /// Returns the deadline of the provisional ruleset, if there is one.
+    #[must_use]
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.pending.as_ref().map(|(_, _, deadline)| *deadline)
+    }
+
+    /// Accepts the provisional ruleset.
+    ///
+    /// # Errors
+    /// Will return `AppError` when no ruleset is waiting for a confirmation.
+    pub fn confirm(&mut self) -> Result<(), AppError> {
+        let (key, ruleset, _) = self.pending.take().ok_or_else(|| {
+            AppError::NftblockdError("no ruleset is waiting for a confirmation".to_string())
+        })?;
+        self.confirmed = Some((key, ruleset));
+        Ok(())
+    }
+
+    /// Drops the provisional ruleset and returns the last confirmed one to restore.
+    pub fn rollback(&mut self) -> Option<String> {
+        self.pending
+            .take()
+            .and_then(|_| self.confirmed.as_ref().map(|(_, ruleset)| ruleset.clone()))
+    }
+}
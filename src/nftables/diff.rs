@@ -0,0 +1,111 @@
+use crate::nftables::element_label;
+use nftables::schema::{NfListObject, NfObject, Nftables};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+/// Differences between the live and the desired elements of a single set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetDiff {
+    /// Name of the set.
+    pub set_name: String,
+    /// Elements that would be added to the live set.
+    pub added: Vec<String>,
+    /// Elements that would be removed from the live set.
+    pub removed: Vec<String>,
+}
+
+impl SetDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Display for SetDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "set {}: +{} -{}",
+            self.set_name,
+            self.added.len(),
+            self.removed.len()
+        )?;
+        for element in &self.added {
+            write!(f, "\n+ {element}")?;
+        }
+        for element in &self.removed {
+            write!(f, "\n- {element}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the set elements of `table_name` in the `live` ruleset with those in the `desired` ruleset.
+///
+/// Elements are compared by their `nft` representation (see `element_label`).
+///
+/// # Returns
+/// A `SetDiff` for every set present in either ruleset, ordered by set name.
+/// Sets without differences are included with empty `added` and `removed` lists.
+#[must_use]
+pub fn diff_rulesets(
+    table_name: &str,
+    live: &Nftables<'_>,
+    desired: &Nftables<'_>,
+) -> Vec<SetDiff> {
+    let live = set_elements(table_name, live);
+    let mut desired = set_elements(table_name, desired);
+
+    let mut diffs = live
+        .into_iter()
+        .map(|(set_name, live_elements)| {
+            let desired_elements = desired.remove(&set_name).unwrap_or_default();
+            SetDiff {
+                added: desired_elements
+                    .difference(&live_elements)
+                    .cloned()
+                    .collect(),
+                removed: live_elements
+                    .difference(&desired_elements)
+                    .cloned()
+                    .collect(),
+                set_name,
+            }
+        })
+        .collect::<Vec<_>>();
+    diffs.extend(desired.into_iter().map(|(set_name, elements)| SetDiff {
+        set_name,
+        added: elements.into_iter().collect(),
+        removed: Vec::new(),
+    }));
+    diffs.sort_by(|a, b| a.set_name.cmp(&b.set_name));
+    diffs
+}
+
+/// Collects the elements of every set in `table_name`, whether declared inline or added separately.
+#[must_use]
+pub fn set_elements(
+    table_name: &str,
+    ruleset: &Nftables<'_>,
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut sets: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for object in ruleset.objects.iter() {
+        let NfObject::ListObject(object) = object else {
+            continue;
+        };
+        let (name, elements) = match object {
+            NfListObject::Set(set) if set.table == table_name => (
+                set.name.to_string(),
+                set.elem.as_deref().unwrap_or_default(),
+            ),
+            NfListObject::Element(element) if element.table == table_name => {
+                (element.name.to_string(), element.elem.as_ref())
+            }
+            _ => continue,
+        };
+        sets.entry(name)
+            .or_default()
+            .extend(elements.iter().filter_map(element_label));
+    }
+    sets
+}
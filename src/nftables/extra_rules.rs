@@ -0,0 +1,245 @@
+use crate::error::AppError;
+use crate::nftables::builder::{RuleDirection, RuleProto};
+use crate::settings::Environment;
+use crate::utils::read_ip_set_file;
+use nftables::stmt::Statement;
+use std::fmt::Display;
+
+/// Prefix of the comments of extra rules.
+pub const EXTRA_RULE_COMMENT: &str = "extra rule: ";
+
+/// Chain of the managed table an extra rule is added to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraChain {
+    Prerouting,
+    Postrouting,
+}
+
+/// Transport protocol matched by an extra rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraProto {
+    Tcp,
+    Udp,
+}
+
+/// Verdict of an extra rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraVerdict {
+    Accept,
+    Drop,
+    Reject,
+}
+
+/// A static rule of the managed table, declared in the extra rules file.
+///
+/// Every line of the file is a rule in a constrained subset of the `nft` syntax: a chain, an optional
+/// protocol with destination or source ports, an optional set reference, and a verdict; `#` comments
+/// are allowed:
+///
+/// ```text
+/// prerouting tcp dport 22,8000-8080 saddr @anti_lockout_set_ipv4 accept
+/// postrouting udp dport 53 drop  # no external resolvers
+/// prerouting saddr @blocklist_set_ipv6 reject
+/// ```
+///
+/// Only the sets of the managed table can be referenced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraRule {
+    pub chain: ExtraChain,
+    pub proto: Option<ExtraProto>,
+    /// Whether `ports` are matched against the source port rather than the destination port.
+    pub source_ports: bool,
+    /// Inclusive port ranges; a single port is a range of one.
+    pub ports: Vec<(u16, u16)>,
+    /// Address matched against a set of the table, e.g., `saddr` and `anti_lockout_set_ipv4`.
+    pub set: Option<(RuleDirection, String)>,
+    pub verdict: ExtraVerdict,
+    /// The line of the file, kept in the comment of the rule.
+    pub line: String,
+}
+
+impl ExtraProto {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tcp" => Some(Self::Tcp),
+            "udp" => Some(Self::Udp),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ExtraProto {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExtraProto::Tcp => write!(f, "tcp"),
+            ExtraProto::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+impl ExtraVerdict {
+    /// Converts the verdict into the statement ending the rule.
+    #[must_use]
+    pub fn statement<'a>(self) -> Statement<'a> {
+        match self {
+            ExtraVerdict::Accept => Statement::Accept(None),
+            ExtraVerdict::Drop => Statement::Drop(None),
+            ExtraVerdict::Reject => Statement::Reject(None),
+        }
+    }
+}
+
+impl ExtraRule {
+    /// Parses one rule of the extra rules file.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the rule does not follow the schema.
+    pub fn parse(line: &str) -> Result<Self, AppError> {
+        let mut tokens = line.split_whitespace();
+        let chain = match tokens.next() {
+            Some("prerouting") => ExtraChain::Prerouting,
+            Some("postrouting") => ExtraChain::Postrouting,
+            other => {
+                return Err(AppError::ParseError(format!(
+                    "invalid chain: {}; expected prerouting or postrouting",
+                    other.unwrap_or_default()
+                )));
+            }
+        };
+        let mut rule = Self {
+            chain,
+            proto: None,
+            source_ports: false,
+            ports: Vec::new(),
+            set: None,
+            verdict: ExtraVerdict::Drop,
+            line: line.to_string(),
+        };
+        let mut next = tokens.next();
+        if let Some(proto) = next.and_then(ExtraProto::parse) {
+            rule.proto = Some(proto);
+            next = tokens.next();
+            if let Some(field @ ("dport" | "sport")) = next {
+                rule.source_ports = field == "sport";
+                rule.ports = parse_ports(tokens.next().unwrap_or_default())?;
+                next = tokens.next();
+            }
+        }
+        if let Some(field @ ("saddr" | "daddr")) = next {
+            let direction = if field == "saddr" {
+                RuleDirection::Saddr
+            } else {
+                RuleDirection::Daddr
+            };
+            let set = tokens
+                .next()
+                .and_then(|set| set.strip_prefix('@'))
+                .filter(|set| !set.is_empty())
+                .ok_or_else(|| {
+                    AppError::ParseError(format!("{field} must be followed by a set, e.g., @name"))
+                })?;
+            rule.set = Some((direction, set.to_string()));
+            next = tokens.next();
+        }
+        rule.verdict = match next {
+            Some("accept") => ExtraVerdict::Accept,
+            Some("drop") => ExtraVerdict::Drop,
+            Some("reject") => ExtraVerdict::Reject,
+            other => {
+                return Err(AppError::ParseError(format!(
+                    "invalid verdict: {}; expected accept, drop, or reject",
+                    other.unwrap_or_default()
+                )));
+            }
+        };
+        if let Some(token) = tokens.next() {
+            return Err(AppError::ParseError(format!(
+                "unexpected {token} after the verdict"
+            )));
+        }
+        if rule.proto.is_none() && rule.set.is_none() {
+            return Err(AppError::ParseError(
+                "a rule must match a protocol or a set".to_string(),
+            ));
+        }
+        Ok(rule)
+    }
+
+    /// Comment of the rule in the table, which tells it apart from the rules of `nftblockd`.
+    #[must_use]
+    pub fn comment(&self) -> String {
+        format!("{EXTRA_RULE_COMMENT}{}", self.line)
+    }
+
+    /// Protocol of the addresses of the referenced set, derived from the family suffix of its name.
+    #[must_use]
+    pub fn set_proto(&self) -> RuleProto {
+        match &self.set {
+            Some((_, set)) if set.ends_with("_ipv4") => RuleProto::Ip,
+            Some((_, set)) if set.ends_with("_ipv6") => RuleProto::Ip6,
+            _ => RuleProto::Other,
+        }
+    }
+}
+
+/// Parses a comma-separated list of ports and port ranges, e.g., `22,8000-8080`.
+fn parse_ports(value: &str) -> Result<Vec<(u16, u16)>, AppError> {
+    value
+        .split(',')
+        .map(|port| {
+            let (low, high) = port.split_once('-').unwrap_or((port, port));
+            let parse = |port: &str| {
+                port.parse::<u16>()
+                    .ok()
+                    .filter(|port| *port > 0)
+                    .ok_or_else(|| AppError::ParseError(format!("invalid port: {value}")))
+            };
+            let (low, high) = (parse(low)?, parse(high)?);
+            if low > high {
+                return Err(AppError::ParseError(format!("invalid port range: {port}")));
+            }
+            Ok((low, high))
+        })
+        .collect()
+}
+
+/// Parses the contents of an extra rules file.
+///
+/// # Errors
+/// Will return `AppError` with the line number of the first invalid rule.
+pub fn parse_extra_rules(data: &str) -> Result<Vec<ExtraRule>, AppError> {
+    data.lines()
+        .enumerate()
+        .filter_map(|(number, line)| {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            (!line.is_empty()).then(|| {
+                ExtraRule::parse(line)
+                    .map_err(|e| AppError::ParseError(format!("line {}: {e}", number + 1)))
+            })
+        })
+        .collect()
+}
+
+/// Reads the extra rules file set in `NFTBLOCKD_EXTRA_RULES_PATH`.
+///
+/// # Returns
+///
+/// No rules when the variable is unset.
+///
+/// # Errors
+/// Will return `AppError` when the file cannot be read or parsed.
+pub fn extra_rules_from_env(environment: &Environment) -> Result<Vec<ExtraRule>, AppError> {
+    let path = environment
+        .var("NFTBLOCKD_EXTRA_RULES_PATH")
+        .ok()
+        .filter(|s| !s.is_empty());
+    match read_ip_set_file(path.as_ref())? {
+        Some(data) => parse_extra_rules(&data).map_err(|e| {
+            AppError::ParseError(format!(
+                "invalid extra rules file: {}: {e}",
+                path.unwrap_or_default()
+            ))
+        }),
+        None => Ok(Vec::new()),
+    }
+}
@@ -1,9 +1,21 @@
 use log::warn;
+use nftables::expr::{Elem, Expression, NamedExpression};
+use nftables::schema::Nftables;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::SystemTime;
 
+use crate::error::AppError;
 use crate::nftables::config::NftConfig;
 
+pub mod applier;
 pub mod builder;
 pub mod config;
+pub mod confirm;
+pub mod diff;
+pub mod extra_rules;
+pub mod reconcile;
 
 pub fn flush_table(config: &NftConfig<'_>) {
     let _ = config.delete_table_and_apply().map_err(|e| {
@@ -13,3 +25,136 @@ pub fn flush_table(config: &NftConfig<'_>) {
         );
     });
 }
+
+/// Serializes a ruleset into a pretty-printed JSON document.
+///
+/// `generate_ruleset` already emits set flags and set elements in a canonical order,
+/// so the output does not depend on the order of the fetched feeds and is suitable for snapshot comparisons.
+///
+/// # Errors
+/// Returns an `AppError` if the ruleset cannot be serialized.
+pub fn serialize_ruleset(ruleset: &Nftables<'_>) -> Result<String, AppError> {
+    Ok(serde_json::to_string_pretty(ruleset)?)
+}
+
+/// Computes the sort key of a set element: its (start) address, then its prefix length.
+/// Elements that are not addresses are ordered by their debug representation.
+pub(crate) fn element_sort_key(element: &Expression<'_>) -> (Option<IpAddr>, u32, String) {
+    let (addr, len) = match element {
+        Expression::Named(NamedExpression::Elem(elem)) => return element_sort_key(&elem.val),
+        Expression::String(addr) => (Some(addr.as_ref()), 0),
+        Expression::Named(NamedExpression::Prefix(prefix)) => match prefix.addr.as_ref() {
+            Expression::String(addr) => (Some(addr.as_ref()), prefix.len),
+            _ => (None, prefix.len),
+        },
+        Expression::Range(range) => match &range.range {
+            [Expression::String(start), _] => (Some(start.as_ref()), 0),
+            _ => (None, 0),
+        },
+        _ => (None, 0),
+    };
+    (
+        addr.and_then(|a| a.parse::<IpAddr>().ok()),
+        len,
+        element_label(element).unwrap_or_else(|| format!("{element:?}")),
+    )
+}
+
+/// Formats a set element the way `nft` prints it: `addr/len` for prefixes, `start-end` for ranges,
+/// and a bare address for single hosts (including full-length prefixes).
+///
+/// # Returns
+/// `None` if the element is not an address, prefix, or range.
+#[must_use]
+pub fn element_label(element: &Expression<'_>) -> Option<String> {
+    match element {
+        Expression::String(addr) => Some(addr.to_string()),
+        Expression::Named(NamedExpression::Prefix(prefix)) => {
+            let Expression::String(addr) = prefix.addr.as_ref() else {
+                return None;
+            };
+            let max_len = if addr.contains(':') { 128 } else { 32 };
+            if prefix.len == max_len {
+                Some(addr.to_string())
+            } else {
+                Some(format!("{addr}/{}", prefix.len))
+            }
+        }
+        Expression::Range(range) => {
+            let [Expression::String(start), Expression::String(end)] = &range.range else {
+                return None;
+            };
+            Some(format!("{start}-{end}"))
+        }
+        Expression::Named(NamedExpression::Elem(elem)) => element_label(&elem.val),
+        _ => None,
+    }
+}
+
+/// Sets the timeout of every element listed in `expiry` to the time remaining until it expires,
+/// leaving out the elements that already expired. The other elements never expire.
+///
+/// Elements are matched by their `nft` representation (see `element_label`).
+#[must_use]
+pub fn expire_elements<'a>(
+    elements: Vec<Expression<'a>>,
+    expiry: &HashMap<String, SystemTime>,
+    now: SystemTime,
+) -> Vec<Expression<'a>> {
+    elements
+        .into_iter()
+        .filter_map(|element| {
+            let Some(expires) = element_label(&element).and_then(|label| expiry.get(&label)) else {
+                return Some(element);
+            };
+            let remaining = expires.duration_since(now).unwrap_or_default().as_secs();
+            if remaining == 0 {
+                return None;
+            }
+            let timeout = Some(u32::try_from(remaining).unwrap_or(u32::MAX));
+            Some(match element {
+                Expression::Named(NamedExpression::Elem(mut elem)) => {
+                    elem.timeout = timeout;
+                    Expression::Named(NamedExpression::Elem(elem))
+                }
+                element => Expression::Named(NamedExpression::Elem(Elem {
+                    val: Box::new(element),
+                    timeout,
+                    ..Elem::default()
+                })),
+            })
+        })
+        .collect()
+}
+
+/// Sets the comment of every element listed in `comments`, e.g., from an annotated list, replacing any
+/// comment it had. The other elements are left as they are.
+///
+/// Elements are matched by their `nft` representation (see `element_label`).
+#[must_use]
+pub fn comment_elements<'a>(
+    elements: Vec<Expression<'a>>,
+    comments: &HashMap<String, String>,
+) -> Vec<Expression<'a>> {
+    elements
+        .into_iter()
+        .map(|element| {
+            let Some(comment) = element_label(&element).and_then(|label| comments.get(&label))
+            else {
+                return element;
+            };
+            let comment = Some(Cow::Owned(comment.clone()));
+            match element {
+                Expression::Named(NamedExpression::Elem(mut elem)) => {
+                    elem.comment = comment;
+                    Expression::Named(NamedExpression::Elem(elem))
+                }
+                element => Expression::Named(NamedExpression::Elem(Elem {
+                    val: Box::new(element),
+                    comment,
+                    ..Elem::default()
+                })),
+            }
+        })
+        .collect()
+}
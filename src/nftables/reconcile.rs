@@ -0,0 +1,279 @@
+use crate::error::{AppError, ErrorSource};
+use crate::nftables::builder::SetElements;
+use crate::nftables::diff::{SetDiff, set_elements};
+use nftables::expr::{Expression, NamedExpression};
+use nftables::schema::{NfListObject, NfObject, Nftables};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Drift between the live table and the ruleset nftblockd applies, in either direction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Drift {
+    /// Name of the table.
+    pub table_name: String,
+    /// Whether the table is missing from the live ruleset altogether.
+    pub table_missing: bool,
+    /// Chains of the desired ruleset missing from the live table.
+    pub missing_chains: Vec<String>,
+    /// Chains of the live table that the desired ruleset does not have.
+    pub extra_chains: Vec<String>,
+    /// Chains whose live number of rules differs from the desired one, with both numbers.
+    pub rule_counts: Vec<(String, usize, usize)>,
+    /// Sets of the desired ruleset missing from the live table.
+    pub missing_sets: Vec<String>,
+    /// Sets of the live table that the desired ruleset does not have.
+    pub extra_sets: Vec<String>,
+    /// Elements to add to or remove from the live sets; only the sets that differ.
+    pub sets: Vec<SetDiff>,
+}
+
+impl Drift {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        !self.table_missing
+            && self.missing_chains.is_empty()
+            && self.extra_chains.is_empty()
+            && self.rule_counts.is_empty()
+            && self.missing_sets.is_empty()
+            && self.extra_sets.is_empty()
+            && self.sets.is_empty()
+    }
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.table_missing {
+            return write!(f, "table {}: missing", self.table_name);
+        }
+        write!(f, "table {}", self.table_name)?;
+        for chain in &self.missing_chains {
+            write!(f, "\nchain {chain}: missing")?;
+        }
+        for chain in &self.extra_chains {
+            write!(f, "\nchain {chain}: not managed by nftblockd")?;
+        }
+        for (chain, live, desired) in &self.rule_counts {
+            write!(f, "\nchain {chain}: {live} rules, expected {desired}")?;
+        }
+        for set in &self.missing_sets {
+            write!(f, "\nset {set}: missing")?;
+        }
+        for set in &self.extra_sets {
+            write!(f, "\nset {set}: not managed by nftblockd")?;
+        }
+        for set in &self.sets {
+            write!(f, "\n{set}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the table `table_name` in the `live` ruleset with the `desired` ruleset.
+///
+/// Chains and sets are compared by name, rules by their number in every chain, since `nft` lists them in
+/// its own notation, and elements by their `nft` representation (see `element_label`).
+///
+/// # Arguments
+///
+/// * `ignored_sets` - Sets filled at runtime, e.g., the quarantine sets, whose elements are not compared.
+#[must_use]
+pub fn detect_drift(
+    table_name: &str,
+    live: &Nftables<'_>,
+    desired: &Nftables<'_>,
+    ignored_sets: &[String],
+) -> Drift {
+    let live_objects = TableObjects::collect(table_name, live);
+    let desired_objects = TableObjects::collect(table_name, desired);
+    let mut drift = Drift {
+        table_name: table_name.to_string(),
+        table_missing: !live_objects.table,
+        ..Drift::default()
+    };
+    if drift.table_missing {
+        return drift;
+    }
+    drift.missing_chains = difference(desired_objects.rules.keys(), live_objects.rules.keys());
+    drift.extra_chains = difference(live_objects.rules.keys(), desired_objects.rules.keys());
+    drift.rule_counts = desired_objects
+        .rules
+        .iter()
+        .filter_map(|(chain, desired)| {
+            let live = live_objects.rules.get(chain)?;
+            (live != desired).then(|| (chain.clone(), *live, *desired))
+        })
+        .collect();
+    drift.missing_sets = difference(&desired_objects.sets, &live_objects.sets);
+    drift.extra_sets = difference(&live_objects.sets, &desired_objects.sets);
+
+    let mut live_elements = set_elements(table_name, live);
+    let desired_elements = set_elements(table_name, desired);
+    drift.sets = desired_elements
+        .into_iter()
+        .filter(|(set_name, _)| {
+            live_objects.sets.contains(set_name) && !ignored_sets.contains(set_name)
+        })
+        .map(|(set_name, desired)| {
+            let live = live_elements.remove(&set_name).unwrap_or_default();
+            SetDiff {
+                added: desired.difference(&live).cloned().collect(),
+                removed: live.difference(&desired).cloned().collect(),
+                set_name,
+            }
+        })
+        .filter(|diff| !diff.is_empty())
+        .collect();
+    drift
+}
+
+/// Chains, their number of rules, and the sets of a table.
+#[derive(Debug, Default)]
+struct TableObjects {
+    table: bool,
+    rules: BTreeMap<String, usize>,
+    sets: BTreeSet<String>,
+}
+
+impl TableObjects {
+    fn collect(table_name: &str, ruleset: &Nftables<'_>) -> Self {
+        let mut objects = Self::default();
+        for object in ruleset.objects.iter() {
+            let NfObject::ListObject(object) = object else {
+                continue;
+            };
+            match object {
+                NfListObject::Table(table) if table.name == table_name => objects.table = true,
+                NfListObject::Chain(chain) if chain.table == table_name => {
+                    objects.rules.entry(chain.name.to_string()).or_default();
+                }
+                NfListObject::Rule(rule) if rule.table == table_name => {
+                    *objects.rules.entry(rule.chain.to_string()).or_default() += 1;
+                }
+                NfListObject::Set(set) if set.table == table_name => {
+                    objects.sets.insert(set.name.to_string());
+                }
+                _ => {}
+            }
+        }
+        objects
+    }
+}
+
+/// Returns the names in `names` that are not in `other`.
+fn difference<'n>(
+    names: impl IntoIterator<Item = &'n String>,
+    other: impl IntoIterator<Item = &'n String>,
+) -> Vec<String> {
+    let other = other.into_iter().collect::<BTreeSet<_>>();
+    names
+        .into_iter()
+        .filter(|name| !other.contains(name))
+        .cloned()
+        .collect()
+}
+
+/// The ruleset nftblockd last applied, persisted after every update as the source of truth of reconciliation.
+#[derive(Debug, Clone)]
+pub struct AppliedRuleset {
+    pub ruleset: Nftables<'static>,
+    /// Time the ruleset was written, which the timeouts of its elements count from.
+    pub written: SystemTime,
+}
+
+impl AppliedRuleset {
+    /// Writes the canonical JSON of `ruleset` (see `serialize_ruleset`) to `path`, replacing it atomically.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be written or renamed.
+    pub fn write(path: &Path, ruleset: &str) -> Result<(), AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!("failed to write the applied ruleset: {}", path.display()),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, ruleset).map_err(file_error)?;
+        fs::rename(&temporary, path).map_err(file_error)
+    }
+
+    /// Reads the ruleset written by `write`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be read or is not an `nft` JSON ruleset.
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!(
+                    "failed to read the applied ruleset: {}; has the daemon applied a blocklist yet?",
+                    path.display()
+                ),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        let data = fs::read_to_string(path).map_err(file_error)?;
+        let written = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(file_error)?;
+        let ruleset = serde_json::from_str::<Nftables<'static>>(&data).map_err(|e| {
+            AppError::ParseError(format!("invalid applied ruleset: {}: {e}", path.display()))
+        })?;
+        Ok(Self { ruleset, written })
+    }
+
+    /// Returns the elements of the set `set_name` of `table_name`, leaving out the elements whose timeout
+    /// passed by `now` and shortening the timeouts of the others by the time since the ruleset was written.
+    #[must_use]
+    pub fn elements(
+        &self,
+        table_name: &str,
+        set_name: &str,
+        now: SystemTime,
+    ) -> Option<SetElements<'static>> {
+        let age = now.duration_since(self.written).unwrap_or_default();
+        let elements = self
+            .ruleset
+            .objects
+            .iter()
+            .filter_map(|object| match object {
+                NfObject::ListObject(NfListObject::Set(set))
+                    if set.table == table_name && set.name == set_name =>
+                {
+                    set.elem.as_deref()
+                }
+                NfObject::ListObject(NfListObject::Element(element))
+                    if element.table == table_name && element.name == set_name =>
+                {
+                    Some(element.elem.as_ref())
+                }
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|element| age_element(element.clone(), age))
+            .collect::<Vec<_>>();
+        (!elements.is_empty()).then_some(elements)
+    }
+}
+
+/// Shortens the timeout of `element` by `age`, or returns `None` when it timed out.
+fn age_element(element: Expression<'static>, age: Duration) -> Option<Expression<'static>> {
+    match element {
+        Expression::Named(NamedExpression::Elem(mut elem)) if elem.timeout.is_some() => {
+            let age = u32::try_from(age.as_secs()).unwrap_or(u32::MAX);
+            let remaining = elem.timeout.unwrap_or_default().checked_sub(age)?;
+            if remaining == 0 {
+                return None;
+            }
+            elem.timeout = Some(remaining);
+            Some(Expression::Named(NamedExpression::Elem(elem)))
+        }
+        element => Some(element),
+    }
+}
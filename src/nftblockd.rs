@@ -1,22 +1,76 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use ipnetwork::IpNetwork;
 use log::{error, info, warn};
-use nftblockd::error::AppError;
+#[cfg(feature = "aggregator")]
+use nftblockd::aggregator::Aggregator;
+#[cfg(feature = "alerts")]
+use nftblockd::alert::smtp::SmtpAlerter;
+#[cfg(feature = "alerts")]
+use nftblockd::alert::webhook::WebhookAlerter;
+use nftblockd::error::{AppError, FailureKind, FailureReport};
+use nftblockd::events::EventStream;
+#[cfg(feature = "control-socket")]
+use nftblockd::grpc::ctl::nftblockd::status_service_client::StatusServiceClient;
+#[cfg(feature = "control-socket")]
 use nftblockd::grpc::ctl::nftblockd::status_service_server::StatusServiceServer;
 use nftblockd::grpc::server::{Command, ServiceStatusStruct};
+#[cfg(feature = "history")]
+use nftblockd::history::History;
+#[cfg(any(feature = "metrics", feature = "control-socket"))]
+use nftblockd::metrics::stats_loop;
+#[cfg(feature = "metrics")]
+use nftblockd::metrics::{serve_metrics, statsd::StatsdSink, textfile::TextfileExporter};
+#[cfg(feature = "nflog")]
+use nftblockd::nflog::{NflogPacket, nflog_reader};
+use nftblockd::nftables::applier::NftApplier;
+use nftblockd::nftables::builder::{AddressFamilies, SetElements};
 use nftblockd::nftables::config::NftConfig;
+use nftblockd::nftables::diff::SetDiff;
 use nftblockd::nftables::flush_table;
-use nftblockd::set::blocklist::{BlockList, blocklist_loop};
-use nftblockd::utils::stats::Stats;
+use nftblockd::nftables::reconcile::{AppliedRuleset, detect_drift};
+use nftblockd::set::blocklist::{BlockList, Schedule, blocklist_loop};
+use nftblockd::set::burn_in::BurnIn;
+use nftblockd::set::export::{ExportFormat, export_attributed};
+use nftblockd::set::fetch_policy::Proxies;
+use nftblockd::set::git::GitSource;
+use nftblockd::set::http_cache::HttpCache;
+use nftblockd::set::manual::ManualSet;
+use nftblockd::set::observer::UpdateObserver;
+use nftblockd::set::quarantine::Quarantine;
+use nftblockd::set::resolver::DomainResolver;
+use nftblockd::set::routing::RouteVerifier;
+use nftblockd::set::shared_fetch::SharedFetches;
+use nftblockd::set::simulation::{Simulation, read_inputs};
+use nftblockd::settings::{Environment, Settings};
+use nftblockd::utils::duration::parse_duration;
+use nftblockd::utils::instance::{Profile, socket_path, state_dir, validate_instance};
+use nftblockd::utils::kernel::ipv6_unsupported;
+use nftblockd::utils::log_file::open_log_file;
+use nftblockd::utils::privileges::{check_privileges, drop_privileges};
+use nftblockd::utils::sandbox::{SandboxConfig, apply_sandbox};
 use nftblockd::utils::status::NftblockdStatus;
-use std::env;
-use std::path::Path;
-use std::sync::Arc;
-use tokio::net::{UnixListener, UnixStream};
+use nftblockd::utils::status_file::StatusFile;
+use nftblockd::utils::watch::FileWatcher;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, PoisonError};
+#[cfg(feature = "nflog")]
+use std::time::Instant;
+#[cfg(feature = "control-socket")]
+use std::time::UNIX_EPOCH;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "control-socket")]
+use tokio::net::UnixListener;
 use tokio::signal::unix::{SignalKind, signal};
-use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
+#[cfg(feature = "control-socket")]
 use tonic::codegen::tokio_stream::wrappers::UnixListenerStream;
+#[cfg(feature = "control-socket")]
 use tonic::transport::Server;
+use tracing::Instrument;
 use tracing_subscriber::EnvFilter;
 
 #[cfg(target_env = "musl")]
@@ -45,108 +99,615 @@ struct Cli {
     #[clap(flatten)]
     url: UrlGroup,
 
-    /// Interval (in seconds) to periodically update the blocklists.
+    /// Base URL of an aggregator to replicate; its `/ipv4` and `/ipv6` lists replace the blocklist URLs.
+    #[clap(long, value_name = "PRIMARY_URL", env = "NFTBLOCKD_PRIMARY_URL")]
+    primary: Option<String>,
+
+    /// Address families to block: `ipv4`, `ipv6`, or `both`. The sets and rules of the other family are
+    /// left out and its URL is not fetched.
+    #[clap(
+        long,
+        value_name = "FAMILIES",
+        default_value = "both",
+        env = "NFTBLOCKD_ADDRESS_FAMILIES",
+        value_parser = AddressFamilies::parse
+    )]
+    families: AddressFamilies,
+
+    /// Interval to periodically update the blocklists, in seconds or as a duration such as `15m`.
     #[clap(
         short,
         long,
         value_name = "INTERVAL",
-        default_value = "30",
-        env = "NFTBLOCKD_INTERVAL"
+        default_value = "30s",
+        env = "NFTBLOCKD_INTERVAL",
+        value_parser = parse_duration
     )]
-    interval: u64,
+    interval: Duration,
 
-    /// Optional `.env` file path for loading environment variables.
+    /// Optional `.env` file paths for loading environment variables; may be repeated.
+    /// Later files override earlier ones, the process environment overrides the files,
+    /// and CLI arguments override both.
     #[clap(short, long, value_name = "ENV_FILE")]
-    env_file: Option<String>,
+    env_file: Vec<String>,
 
     /// Deletes the existing `nftables` blocklist table and then exits.
     /// This is used for cleanup.
     #[arg(short = 'd', long = "delete", action = clap::ArgAction::SetTrue)]
     delete: bool,
+
+    /// Checks that the process has the privileges the daemon needs, explains what is missing, and exits.
+    #[arg(long = "check-privileges", action = clap::ArgAction::SetTrue)]
+    check_privileges: bool,
+
+    /// Name of this instance; namespaces the table, the control socket, and the metrics,
+    /// so that several independent instances can run on one host.
+    #[arg(long, value_name = "NAME", env = "NFTBLOCKD_INSTANCE")]
+    instance: Option<String>,
+
+    /// Profile run by this daemon next to the main configuration, as `NAME=ENV_FILE[,ENV_FILE...]`; may be repeated.
+    /// Each profile gets its own table, sources, and schedule from its `.env` files, layered over the main ones,
+    /// and shares the downloads of common sources with the other profiles.
+    #[arg(
+        long = "profile",
+        value_name = "NAME=ENV_FILES",
+        env = "NFTBLOCKD_PROFILES",
+        value_delimiter = ' ',
+        value_parser = Profile::parse
+    )]
+    profiles: Vec<Profile>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+impl Cli {
+    /// Parses the command line, reading the arguments not given on it from `environment` rather than
+    /// from the environment of the process.
+    ///
+    /// # Errors
+    /// Will return `clap::Error` when an argument or a variable is invalid.
+    fn from_environment(environment: &Environment) -> Result<Self, clap::Error> {
+        let matches = with_environment(Self::command(), environment).try_get_matches()?;
+        Self::from_arg_matches(&matches)
+    }
+
+    /// Returns the address families to block; IPv6 is skipped rather than failing every apply
+    /// when both are blocked on a host whose kernel does not support it.
+    fn address_families(&self) -> AddressFamilies {
+        match self.families {
+            AddressFamilies::Both if ipv6_unsupported().is_some() => AddressFamilies::Ipv4,
+            families => families,
+        }
+    }
 }
 
+/// Makes the variables of `environment` the defaults of the arguments of `command` and its subcommands
+/// that are read from them, so that the command line still overrides them.
+fn with_environment(command: clap::Command, environment: &Environment) -> clap::Command {
+    command
+        .mut_args(
+            |arg| match arg.get_env().and_then(|name| environment.var_os(name)) {
+                Some(value) => arg.default_value(value.to_os_string()),
+                None => arg,
+            },
+        )
+        .mut_subcommands(|subcommand| with_environment(subcommand, environment))
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Asks the running daemon whether it is healthy, then exits with 0 if it is and 1 otherwise.
+    /// This is used for Docker `HEALTHCHECK` and systemd `ExecCondition`.
+    #[cfg(feature = "control-socket")]
+    Health {
+        /// Maximum age of the last applied blocklist, e.g., `90s`; defaults to three update intervals.
+        #[arg(
+            long,
+            value_name = "DURATION",
+            env = "NFTBLOCKD_HEALTH_MAX_AGE",
+            value_parser = parse_duration
+        )]
+        max_age: Option<Duration>,
+    },
+    /// Fetches the blocklists, prints how the live sets would change, and exits without applying.
+    /// This is used for reviewing changes before a manual apply.
+    Diff,
+    /// Fetches the blocklists, prints the merged and deduplicated result in the given format, and exits.
+    /// This is used for feeding the curated blocklist to other devices.
+    Export {
+        /// Format of the printed blocklist: `plain`, `csv`, `json`, or `ipset`.
+        format: ExportFormat,
+    },
+    /// Blocks an address or network until removed or until `--ttl` passes, also across restarts.
+    Add {
+        /// Address or network to block, e.g., `203.0.113.7` or `2001:db8::/32`.
+        network: String,
+        /// How long the entry stays blocked, e.g., `2h`; permanently when unset.
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        ttl: Option<Duration>,
+        /// Note stored with the entry and shown by `nft list set`, e.g., a ticket number.
+        #[arg(long)]
+        comment: Option<String>,
+    },
+    /// Removes an entry added with `add`.
+    Remove {
+        /// Address or network to unblock, as given to `add`.
+        network: String,
+    },
+    /// Fetches the blocklists and reports which addresses of a capture or a list would be dropped,
+    /// by which set and element, without applying anything.
+    /// This is used for estimating the collateral damage of a new feed before enabling it.
+    Simulate {
+        /// A pcap capture, whose packets are matched by their source and destination addresses,
+        /// or a text file, e.g., a list of addresses or a log, whose addresses are matched.
+        #[arg(long, value_name = "FILE")]
+        input: PathBuf,
+    },
+    /// Compares the live table with the ruleset the daemon last applied, rebuilt from the current configuration,
+    /// and reports the drift in either direction; exits with 1 when there is drift that was not repaired.
+    /// This is used for GitOps-style convergence checks, e.g., from a timer or a CI job.
+    Reconcile {
+        /// Applies the ruleset to remove the drift, restoring the manual entries as well.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        repair: bool,
+    },
+    /// Prints the completion script for the given shell.
+    /// This is used for packaging, e.g., `nftblockd completions bash > /usr/share/bash-completion/completions/nftblockd`.
+    Completions {
+        /// Shell to generate the completions for.
+        shell: Shell,
+    },
+    /// Prints the man page in roff format.
+    /// This is used for packaging, e.g., `nftblockd man > /usr/share/man/man8/nftblockd.8`.
+    Man,
+}
+
+#[cfg(feature = "control-socket")]
 struct SocketGuard {
     path: String,
 }
 
+#[cfg(feature = "control-socket")]
 impl Drop for SocketGuard {
     fn drop(&mut self) {
         let _ = std::fs::remove_file(&self.path);
     }
 }
 
+/// Checks the status of the running daemon over its control socket and exits with 1 when it is unhealthy.
+///
+/// # Errors
+/// Will return `AppError` when the daemon cannot be reached.
+#[cfg(feature = "control-socket")]
+async fn health(max_age: Duration, instance: Option<&str>) -> Result<(), AppError> {
+    let mut client =
+        StatusServiceClient::connect(format!("unix://{}", socket_path(instance))).await?;
+    let summary = client
+        .get_status(tonic::Request::new(()))
+        .await?
+        .into_inner();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+    match summary.check_health(now, i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX)) {
+        Ok(()) => {
+            println!("healthy");
+            Ok(())
+        }
+        Err(reason) => {
+            eprintln!("unhealthy: {reason}");
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Entry point of the `nftblockd` binary.
-/// Parses CLI arguments, initializes logging, loads the configuration (from `.env` and CLI),
+/// Exits with the code of the `FailureKind` of a fatal error and, when `NFTBLOCKD_FAILURE_REPORT` is set,
+/// writes a JSON report of the failure to that path.
+fn main() -> ExitCode {
+    let mut environment = Environment::process();
+    let Err(e) = run(&mut environment) else {
+        return ExitCode::SUCCESS;
+    };
+    eprintln!("Error: {e}");
+    if let Some(path) = environment
+        .var("NFTBLOCKD_FAILURE_REPORT")
+        .ok()
+        .filter(|s| !s.is_empty())
+        && let Err(report_error) = FailureReport::new(&e, SystemTime::now()).write(Path::new(&path))
+    {
+        eprintln!("failed to write the failure report to {path}: {report_error}");
+    }
+    ExitCode::from(e.kind().exit_code())
+}
+
+/// Parses CLI arguments, initializes logging, loads the configuration (from `.env` and CLI) into `environment`,
 /// and periodically updates the blocklists based on the configured interval.
-#[tokio::main]
-async fn main() -> Result<(), AppError> {
+fn run(environment: &mut Environment) -> Result<(), AppError> {
     // Parse CLI arguments.
     let mut cli = Cli::parse();
 
-    // Load environment variables from the specified `.env` file (if provided), then re-parse CLI.
-    if let Some(env_file) = cli.env_file {
-        dotenvy::from_filename(env_file).expect("failed to load .env file");
-        cli = Cli::parse();
+    // The packaging commands only describe the CLI, so they do not depend on the configuration.
+    match cli.command {
+        Some(CliCommand::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "nftblockd", &mut io::stdout());
+            return Ok(());
+        }
+        Some(CliCommand::Man) => {
+            clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())?;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Load the variables of the specified `.env` files (if provided), then re-parse CLI with them.
+    if !cli.env_file.is_empty() {
+        *environment = Environment::load(&cli.env_file)?;
+        cli = Cli::from_environment(environment).unwrap_or_else(|e| e.exit());
+    }
+
+    if let Some(instance) = &cli.instance {
+        validate_instance(instance)?;
+    }
+
+    #[cfg(feature = "control-socket")]
+    if let Some(CliCommand::Health { max_age }) = cli.command {
+        return runtime()?.block_on(health(
+            max_age.unwrap_or(cli.interval.saturating_mul(3)),
+            cli.instance.as_deref(),
+        ));
     }
 
-    let env = EnvFilter::try_from_env("NFTBLOCKD_LOG_LEVEL").unwrap_or(EnvFilter::new("info"));
+    let settings = Settings::from_env(environment, cli.instance.as_deref(), cli.interval)?;
+
+    let env = environment
+        .var("NFTBLOCKD_LOG_LEVEL")
+        .ok()
+        .and_then(|level| EnvFilter::try_new(level).ok())
+        .unwrap_or(EnvFilter::new("info"));
     let timer = tracing_subscriber::fmt::time::LocalTime::rfc_3339();
-    tracing_subscriber::fmt()
+    let subscriber = tracing_subscriber::fmt()
         .with_timer(timer)
         .with_target(true)
-        .with_env_filter(env)
-        .init();
-
-    let blocklist_split_string = env::var("NFTBLOCKD_BLOCKLIST_SPLIT_STRING")
-        .ok()
-        .filter(|s| !s.is_empty());
+        .with_env_filter(env);
+    // Keeps the background log writer alive; buffered lines are flushed when it is dropped.
+    let _log_guard = match &settings.log_file {
+        Some(path) => {
+            let (writer, guard) =
+                open_log_file(path, settings.log_rotation, settings.log_max_files)?;
+            subscriber.with_ansi(false).with_writer(writer).init();
+            Some(guard)
+        }
+        None => {
+            subscriber.init();
+            None
+        }
+    };
 
-    let mut config = NftConfig::new(blocklist_split_string.as_deref())?;
+    let config = NftConfig::new(environment, settings.split_string.as_deref())?
+        .with_instance(cli.instance.as_deref())
+        .with_address_families(cli.address_families());
     if cli.delete {
         flush_table(&config);
         return Ok(());
     }
+    if let Some(command @ (CliCommand::Add { .. } | CliCommand::Remove { .. })) = &cli.command {
+        return edit_manual_set(&config, &settings.manual_path, command);
+    }
+    if cli.check_privileges {
+        let checks = check_privileges(
+            &socket_path(cli.instance.as_deref()),
+            &NftApplier::from_env(environment)?,
+        );
+        for check in &checks {
+            println!("{check}");
+        }
+        if checks.iter().any(|c| !c.ok) {
+            std::process::exit(FailureKind::Privilege.exit_code().into());
+        }
+        return Ok(());
+    }
+    if let Some(CliCommand::Diff) = cli.command {
+        return runtime()?.block_on(print_diff(&cli, &settings, &config));
+    }
+    if let Some(CliCommand::Export { format }) = cli.command {
+        return runtime()?.block_on(print_export(&cli, &settings, &config, format));
+    }
+    if let Some(CliCommand::Simulate { input }) = &cli.command {
+        return runtime()?.block_on(print_simulation(&cli, &settings, &config, input));
+    }
+    if let Some(CliCommand::Reconcile { repair }) = cli.command {
+        return reconcile(&settings, &config, repair);
+    }
+
+    // The control socket is bound and the privileges are reduced before the runtime starts,
+    // since capabilities and Landlock apply per thread and are only inherited by threads created later.
+    let socket_path = socket_path(cli.instance.as_deref());
+    #[cfg(feature = "control-socket")]
+    let (listener, _guard) = (
+        Some(bind_socket(&socket_path)?),
+        SocketGuard {
+            path: socket_path.clone(),
+        },
+    );
+    #[cfg(not(feature = "control-socket"))]
+    let listener = None;
+    // The CPU quota is written to the cgroup while the process is still privileged.
+    if let Err(e) = settings.scheduling.apply() {
+        warn!("{e}");
+    }
+    if settings.drop_privileges {
+        drop_privileges(settings.user.as_deref())?;
+    }
+    if settings.sandbox {
+        apply_sandbox(&sandbox_config(&cli, environment, &socket_path)?)?;
+    }
+
+    runtime()?.block_on(run_daemon(cli, settings, config, listener))
+}
+
+/// Builds the multi-threaded runtime the daemon and the one-shot commands run on.
+fn runtime() -> Result<tokio::runtime::Runtime, AppError> {
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?)
+}
+
+/// Collects what the sandboxed daemon still needs to reach from its configuration.
+fn sandbox_config(
+    cli: &Cli,
+    environment: &Environment,
+    socket_path: &str,
+) -> Result<SandboxConfig, AppError> {
+    let mut sandbox = SandboxConfig::default()
+        .with_writable_path(state_dir(cli.instance.as_deref()))
+        .with_writable_path(Path::new(socket_path).parent().unwrap_or(Path::new("/run")));
+    // The sources of the profiles are only known once their `.env` files are loaded; allow the default ports.
+    for profile in &cli.profiles {
+        sandbox = sandbox
+            .with_writable_path(state_dir(Some(&profile.name)))
+            .with_connect_port(80)
+            .with_connect_port(443);
+    }
+    for variable in [
+        "NFTBLOCKD_LOG_FILE",
+        "NFTBLOCKD_HISTORY_DB",
+        "NFTBLOCKD_TEXTFILE_PATH",
+        "NFTBLOCKD_FAILURE_REPORT",
+        "NFTBLOCKD_EVENTS_PATH",
+        "NFTBLOCKD_STATUS_FILE",
+        "NFTBLOCKD_DNS_CACHE_PATH",
+        "NFTBLOCKD_ROUTE_CACHE_PATH",
+        "NFTBLOCKD_BURN_IN_PATH",
+        "NFTBLOCKD_RULESET_PATH",
+    ] {
+        if let Some(parent) = environment
+            .var(variable)
+            .ok()
+            .filter(|s| !s.is_empty())
+            .and_then(|p| Path::new(&p).parent().map(Path::to_path_buf))
+        {
+            sandbox = sandbox.with_writable_path(parent);
+        }
+    }
+    if let Some(dir) = environment
+        .var("NFTBLOCKD_GIT_DIR")
+        .ok()
+        .filter(|s| !s.is_empty())
+    {
+        sandbox = sandbox.with_writable_path(PathBuf::from(dir));
+    }
+    let urls = [
+        cli.url.url4.clone(),
+        cli.url.url6.clone(),
+        cli.primary.clone(),
+        environment.var("NFTBLOCKD_ALERT_WEBHOOK").ok(),
+        environment.var("NFTBLOCKD_DOH_URL").ok(),
+        environment.var("NFTBLOCKD_RIPESTAT_URL").ok(),
+    ];
+    let proxies = Proxies::from_env(environment)?.urls;
+    for url in urls.into_iter().flatten().chain(proxies) {
+        // Git sources connect to their remote, which may be cloned over SSH.
+        let url = if GitSource::is_git(&url) {
+            GitSource::parse(&url)?.remote
+        } else {
+            url
+        };
+        let url = reqwest::Url::parse(&url)
+            .map_err(|e| AppError::ParseError(format!("invalid URL: {url}: {e}")))?;
+        if let Some(port) = url
+            .port_or_known_default()
+            .or((url.scheme() == "ssh").then_some(22))
+        {
+            sandbox = sandbox.with_connect_port(port);
+        }
+    }
+    // The route verification queries RIPEstat over HTTPS unless another endpoint is configured.
+    if environment
+        .var("NFTBLOCKD_ROUTE_CHECK")
+        .is_ok_and(|check| !check.is_empty() && check != "off")
+        && environment.var("NFTBLOCKD_RIPESTAT_URL").is_err()
+    {
+        sandbox = sandbox.with_connect_port(443);
+    }
+    if let Some(port) = environment
+        .var("NFTBLOCKD_SMTP_PORT")
+        .ok()
+        .and_then(|p| p.parse::<u16>().ok())
+    {
+        sandbox = sandbox.with_connect_port(port);
+    } else if environment.var("NFTBLOCKD_SMTP_HOST").is_ok() {
+        sandbox = sandbox.with_connect_port(465).with_connect_port(587);
+    }
+    for variable in ["NFTBLOCKD_METRICS_ADDR", "NFTBLOCKD_SERVE_ADDR"] {
+        if let Some(addr) = environment
+            .var(variable)
+            .ok()
+            .and_then(|a| a.parse::<SocketAddr>().ok())
+        {
+            sandbox = sandbox.with_bind_port(addr.port());
+        }
+    }
+    Ok(sandbox)
+}
 
+/// Runs the daemon: serves the control socket, when bound, and periodically updates the blocklists
+/// until a signal arrives.
+#[cfg_attr(not(feature = "control-socket"), allow(unused_variables))]
+async fn run_daemon(
+    mut cli: Cli,
+    mut settings: Settings,
+    mut config: NftConfig<'static>,
+    listener: Option<std::os::unix::net::UnixListener>,
+) -> Result<(), AppError> {
     // Check that at least one URL (IPv4 or IPv6) is specified; otherwise, exit early.
-    if cli.url.url4.is_none() && cli.url.url6.is_none() {
+    if cli.url.url4.is_none() && cli.url.url6.is_none() && cli.primary.is_none() {
         warn!("no blocklist url provided");
     }
 
     let mut channel = tokio::sync::mpsc::channel::<Command>(100);
 
-    let status = Arc::new(ServiceStatusStruct {
-        status: Arc::new(RwLock::new(NftblockdStatus::default())),
-        stats: Arc::new(RwLock::new(Stats::default())),
-        command_channel: channel.0.clone(),
-    });
+    // Observers living as long as the daemon, added to the blocklist on every (re)configuration.
+    #[cfg_attr(not(feature = "aggregator"), allow(unused_mut))]
+    let mut observers: Vec<Arc<dyn UpdateObserver>> = Vec::new();
+    let status = ServiceStatusStruct::new(channel.0.clone())
+        .with_instance(cli.instance.clone())
+        .with_notes(
+            ipv6_unsupported()
+                .filter(|_| cli.address_families() != cli.families)
+                .map(|reason| format!("IPv6 is skipped: {reason}"))
+                .into_iter()
+                .collect(),
+        )
+        .with_quarantine(Quarantine::from_env(&settings.environment)?);
+    #[cfg(feature = "aggregator")]
+    let status = {
+        let aggregator = Aggregator::from_env(&settings.environment)?.map(|(addr, aggregator)| {
+            let server = aggregator.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.serve(addr, CancellationToken::new()).await {
+                    error!("Error serving the blocklist: {e}");
+                }
+            });
+            aggregator
+        });
+        if let Some(aggregator) = &aggregator {
+            observers.push(Arc::new(aggregator.clone()));
+        }
+        status.with_aggregator(aggregator)
+    };
+    #[cfg(not(feature = "aggregator"))]
+    if settings
+        .environment
+        .var("NFTBLOCKD_SERVE_ADDR")
+        .is_ok_and(|s| !s.is_empty())
+    {
+        warn!(
+            "nftblockd was built without the `aggregator` feature; NFTBLOCKD_SERVE_ADDR is ignored"
+        );
+    }
+    let status = Arc::new(status);
 
-    let status_clone = status.clone();
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = settings.metrics_addr {
+        let status = status.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_metrics(addr, status, CancellationToken::new()).await {
+                error!("Error serving metrics: {e}");
+            }
+        });
+    }
+    #[cfg(not(feature = "metrics"))]
+    if settings.metrics_addr.is_some() {
+        warn!(
+            "nftblockd was built without the `metrics` feature; NFTBLOCKD_METRICS_ADDR is ignored"
+        );
+    }
 
-    let socket = bind_socket("/run/nftblockd.sock").await?;
-    let _guard = SocketGuard {
-        path: "/run/nftblockd.sock".into(),
-    };
-    tokio::spawn(async move {
-        if let Err(e) = Server::builder()
-            .add_service(StatusServiceServer::from_arc(status_clone))
-            .serve_with_incoming(socket)
-            .await
-        {
-            error!("Error creating server: {e}");
-        }
-    });
+    #[cfg(feature = "nflog")]
+    if let Some(group) = config.log_group {
+        let offenders = status.offenders.clone();
+        let quarantine = status.quarantine.clone();
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || {
+            let promote = |packet: &NflogPacket| {
+                let (Some(quarantine), Some(addr)) =
+                    (&quarantine, config.monitored_address(packet))
+                else {
+                    return;
+                };
+                let mut quarantine = quarantine.lock().unwrap_or_else(PoisonError::into_inner);
+                if quarantine.record(addr, Instant::now()) {
+                    warn!(
+                        "{addr} hit the monitor set {} times within {} s; quarantined for {} s",
+                        quarantine.threshold,
+                        quarantine.window.as_secs(),
+                        quarantine.ttl.as_secs()
+                    );
+                    if let Err(e) = config.apply_quarantine(&[(addr, quarantine.ttl)]) {
+                        error!("failed to quarantine {addr}: {e}");
+                    }
+                }
+            };
+            if let Err(e) = nflog_reader(group, offenders, promote) {
+                error!("Error reading from nflog group {group}: {e}");
+            }
+        });
+    } else if status.quarantine.is_some() {
+        warn!(
+            "the quarantine needs NFTBLOCKD_NFLOG_GROUP to observe the monitor sets; nothing is quarantined"
+        );
+    }
+    #[cfg(not(feature = "nflog"))]
+    if config.log_group.is_some() {
+        warn!(
+            "nftblockd was built without the `nflog` feature; the packets logged to NFTBLOCKD_NFLOG_GROUP are not read and nothing is quarantined"
+        );
+    }
+
+    #[cfg(feature = "control-socket")]
+    if let Some(listener) = listener {
+        let status = status.clone();
+        let socket = UnixListenerStream::new(UnixListener::from_std(listener)?);
+        tokio::spawn(async move {
+            if let Err(e) = Server::builder()
+                .add_service(StatusServiceServer::from_arc(status))
+                .serve_with_incoming(socket)
+                .await
+            {
+                error!("Error creating server: {e}");
+            }
+        });
+    }
 
     info!("initialized");
 
+    // The fetches may take long or fail while the network is coming up; protect the host with the last
+    // applied ruleset meanwhile.
+    if settings.restore_on_start
+        && let Err(e) = restore_applied_ruleset(&settings, &config)
+    {
+        warn!("failed to restore the applied ruleset: {e}");
+    }
+
+    let mut fetches = shared_fetches(&cli);
     let mut cancellation_token = CancellationToken::new();
     config = spawn_blocklist_loop(
         &cli,
+        &settings,
         status.clone(),
         cancellation_token.clone(),
-        blocklist_split_string.as_deref(),
+        &observers,
+        fetches.as_ref(),
     )?;
+    let mut tenants = spawn_profiles(&cli, &status, fetches.as_ref())?;
     let mut sigterm = signal(SignalKind::terminate())?;
     let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
+    let mut changes = watch_config(&cli, &settings)?;
+    let mut paused = false;
+    let mut trace_expiry: Option<tokio::task::JoinHandle<()>> = None;
     loop {
         tokio::select! {
             cmd = channel.1.recv() => {
@@ -154,33 +715,743 @@ async fn main() -> Result<(), AppError> {
                 Some(Command::Flush { respond_to }) => {
                     cancellation_token.cancel();
                     flush_table(&config);
+                    stop_profiles(&tenants, true);
                     respond_to.send(Ok(())).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?;
                 }
+                Some(Command::FlushSets { respond_to }) => {
+                    let flushed = flush_sets(&status, &config, &cancellation_token).await;
+                    let flushed = flushed.and(flush_profile_sets(&tenants).await);
+                    paused = true;
+                    respond_to.send(flushed).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?;
+                }
                 Some(Command::Reload { respond_to }) => {
                     cancellation_token.cancel();
                     cancellation_token = CancellationToken::new();
-                    match spawn_blocklist_loop(&cli, status.clone(), cancellation_token.clone(), blocklist_split_string.as_deref()) {
-                        Ok(_) => respond_to.send(Ok(())).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?,
-                        Err(e) => respond_to.send(Err(e)).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?,
+                    stop_profiles(&tenants, false);
+                    let reloaded = spawn_blocklist_loop(&cli, &settings, status.clone(), cancellation_token.clone(), &observers, fetches.as_ref())
+                        .and_then(|_| spawn_profiles(&cli, &status, fetches.as_ref()))
+                        .map(|new_tenants| tenants = new_tenants);
+                    respond_to.send(reloaded).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?;
+                }
+                Some(Command::Pause { disable_rules, respond_to }) => {
+                    pause(&status, &config, &cancellation_token, disable_rules).await;
+                    pause_profiles(&tenants, disable_rules).await;
+                    paused = true;
+                    respond_to.send(Ok(())).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?;
+                }
+                Some(Command::Resume { respond_to }) => {
+                    let resumed = if paused {
+                        cancellation_token = CancellationToken::new();
+                        resume(&cli, &settings, &status, &cancellation_token, &observers, fetches.as_ref()).await
+                            .and_then(|()| spawn_profiles(&cli, &status, fetches.as_ref()))
+                            .map(|new_tenants| tenants = new_tenants)
+                    } else {
+                        Err(AppError::NftblockdError("updates are not paused".to_string()))
+                    };
+                    paused &= resumed.is_err();
+                    respond_to.send(resumed).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?;
+                }
+                Some(Command::Trace { network, respond_to }) => {
+                    if let Some(expiry) = trace_expiry.take() {
+                        expiry.abort();
                     }
+                    let traced = trace(&config, network).map(|expiry| trace_expiry = expiry);
+                    respond_to.send(traced).map_err(|_| AppError::NftblockdError("failed to send response to a gRPC client".to_string()))?;
                 }
             _ => {}}
             }
+            Some(path) = changes.recv() => {
+                // Editors often write a file in several steps; wait for them to settle.
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                while changes.try_recv().is_ok() {}
+                info!("{} changed, reloading the configuration", path.display());
+                match reload_config(&cli) {
+                    Ok((new_cli, new_settings)) if paused => {
+                        info!("the new configuration is applied when updates are resumed");
+                        fetches = shared_fetches(&new_cli);
+                        cli = new_cli;
+                        settings = new_settings;
+                    }
+                    Ok((new_cli, new_settings)) => {
+                        let new_token = CancellationToken::new();
+                        let new_fetches = shared_fetches(&new_cli);
+                        match spawn_blocklist_loop(&new_cli, &new_settings, status.clone(), new_token.clone(), &observers, new_fetches.as_ref()) {
+                            Ok(new_config) => {
+                                cancellation_token.cancel();
+                                cancellation_token = new_token;
+                                config = new_config;
+                                stop_profiles(&tenants, false);
+                                match spawn_profiles(&new_cli, &status, new_fetches.as_ref()) {
+                                    Ok(new_tenants) => tenants = new_tenants,
+                                    Err(e) => error!("the profiles are stopped until the next configuration change: {e}"),
+                                }
+                                fetches = new_fetches;
+                                cli = new_cli;
+                                settings = new_settings;
+                                info!("configuration reloaded");
+                            }
+                            Err(e) => error!("rejected the configuration change, keeping the running configuration: {e}"),
+                        }
+                    }
+                    Err(e) => error!("rejected the configuration change, keeping the running configuration: {e}"),
+                }
+            },
+            _ = sigusr1.recv() => {
+                if paused {
+                    info!("received SIGUSR1, resuming updates");
+                    cancellation_token = CancellationToken::new();
+                    match resume(&cli, &settings, &status, &cancellation_token, &observers, fetches.as_ref()).await
+                        .and_then(|()| spawn_profiles(&cli, &status, fetches.as_ref()))
+                    {
+                        Ok(new_tenants) => {
+                            tenants = new_tenants;
+                            paused = false;
+                        }
+                        Err(e) => error!("failed to resume updates: {e}"),
+                    }
+                } else {
+                    info!("received SIGUSR1, pausing updates");
+                    pause(&status, &config, &cancellation_token, settings.pause_disables_rules).await;
+                    pause_profiles(&tenants, settings.pause_disables_rules).await;
+                    paused = true;
+                }
+            },
             _ = sigterm.recv() => {
                 info!("received SIGTERM, shutting down");
+                stop_trace(&config, trace_expiry);
                 return Ok(());
             },
             _ = sigint.recv() => {
                 info!("received SIGINT, shutting down");
+                stop_trace(&config, trace_expiry);
                 return Ok(());
             },
         }
     }
 }
 
-async fn bind_socket(path: &str) -> Result<UnixListenerStream, AppError> {
+/// Starts tracing the packets of `network` with `meta nftrace` and spawns the task removing the trace rules
+/// after its timeout, or removes them right away when `network` is `None`.
+///
+/// # Errors
+/// Will return `AppError` when the trace rules cannot be applied or removed.
+fn trace(
+    config: &NftConfig<'static>,
+    network: Option<(IpNetwork, Duration)>,
+) -> Result<Option<tokio::task::JoinHandle<()>>, AppError> {
+    let Some((network, timeout)) = network else {
+        config.remove_trace()?;
+        info!("stopped tracing");
+        return Ok(None);
+    };
+    config.apply_trace(&network)?;
+    info!(
+        "tracing {network} in the `{}` table for {} s",
+        config.trace_table_name(),
+        timeout.as_secs()
+    );
+    let config = config.clone();
+    Ok(Some(tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        match config.remove_trace() {
+            Ok(()) => info!("stopped tracing {network} after {} s", timeout.as_secs()),
+            Err(e) => warn!("failed to remove the trace rules of {network}: {e}"),
+        }
+    })))
+}
+
+/// Removes the trace rules left by `trace` when shutting down.
+fn stop_trace(config: &NftConfig<'static>, expiry: Option<tokio::task::JoinHandle<()>>) {
+    let Some(expiry) = expiry else {
+        return;
+    };
+    if expiry.is_finished() {
+        return;
+    }
+    expiry.abort();
+    if let Err(e) = config.remove_trace() {
+        warn!("failed to remove the trace rules: {e}");
+    }
+}
+
+/// Watches the `.env` files and the custom blocklist files when `NFTBLOCKD_WATCH_CONFIG` is set,
+/// returning a channel that receives the path of every changed file.
+///
+/// # Errors
+/// Will return `AppError` when the files cannot be watched.
+fn watch_config(
+    cli: &Cli,
+    settings: &Settings,
+) -> Result<tokio::sync::mpsc::Receiver<PathBuf>, AppError> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(16);
+    if !settings.watch_config {
+        return Ok(receiver);
+    }
+    let files = cli
+        .env_file
+        .clone()
+        .into_iter()
+        .chain(cli.profiles.iter().flat_map(|p| p.env_files.clone()))
+        .chain(
+            [
+                "NFTBLOCKD_CUSTOM_BLOCKLIST_PATH_IPV4",
+                "NFTBLOCKD_CUSTOM_BLOCKLIST_PATH_IPV6",
+                "NFTBLOCKD_OVERRIDES_PATH",
+                "NFTBLOCKD_POLICY_PATH",
+                "NFTBLOCKD_EXTRA_RULES_PATH",
+            ]
+            .into_iter()
+            .filter_map(|name| {
+                settings
+                    .environment
+                    .var(name)
+                    .ok()
+                    .filter(|s| !s.is_empty())
+            }),
+        )
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        warn!(
+            "NFTBLOCKD_WATCH_CONFIG is set, but there is no .env file, custom blocklist file, overrides file, or extra rules file to watch"
+        );
+        return Ok(receiver);
+    }
+    let mut watcher = FileWatcher::new(files)?;
+    std::thread::spawn(move || {
+        loop {
+            match watcher.wait() {
+                Ok(path) => {
+                    if sender.blocking_send(path).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    error!("stopped watching the configuration: {e}");
+                    return;
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Re-reads the `.env` files and parses the command line and the settings again.
+///
+/// # Errors
+/// Will return `AppError` when a file or the resulting configuration is invalid.
+fn reload_config(cli: &Cli) -> Result<(Cli, Settings), AppError> {
+    let environment = Environment::load(&cli.env_file)?;
+    let new_cli = Cli::from_environment(&environment)
+        .map_err(|e| AppError::ParseError(format!("invalid configuration: {e}")))?;
+    if new_cli.instance != cli.instance {
+        return Err(AppError::ParseError(
+            "the instance cannot be changed while running".to_string(),
+        ));
+    }
+    let settings = Settings::from_env(&environment, new_cli.instance.as_deref(), new_cli.interval)?;
+    Ok((new_cli, settings))
+}
+
+/// Adds or removes a manual entry, stores the manual set, and updates the live manual sets.
+/// When the table does not exist yet, the entries are applied once the daemon starts.
+///
+/// # Errors
+/// Will return `AppError` when the entry is invalid or the manual set cannot be stored.
+fn edit_manual_set(
+    config: &NftConfig<'_>,
+    path: &Path,
+    command: &CliCommand,
+) -> Result<(), AppError> {
+    let mut manual = ManualSet::load(path)?;
+    match command {
+        CliCommand::Add {
+            network,
+            ttl,
+            comment,
+        } => {
+            let entry = manual.add(network, *ttl, comment.clone(), SystemTime::now())?;
+            manual.save()?;
+            match ttl {
+                Some(ttl) => println!("added {} for {} s", entry.network, ttl.as_secs()),
+                None => println!("added {}", entry.network),
+            }
+        }
+        CliCommand::Remove { network } => {
+            if !manual.remove(network)? {
+                return Err(AppError::NftblockdError(format!(
+                    "{network} is not in the manual set"
+                )));
+            }
+            manual.save()?;
+            println!("removed {network}");
+        }
+        #[cfg(feature = "control-socket")]
+        CliCommand::Health { .. } => return Ok(()),
+        CliCommand::Diff
+        | CliCommand::Export { .. }
+        | CliCommand::Simulate { .. }
+        | CliCommand::Reconcile { .. }
+        | CliCommand::Completions { .. }
+        | CliCommand::Man => {
+            return Ok(());
+        }
+    }
+    if let Err(e) = config.apply_manual(&manual.active(SystemTime::now())) {
+        warn!("the manual set is applied when nftblockd starts: {e}");
+    }
+    Ok(())
+}
+
+/// Stops the blocklist loop and, with `disable_rules`, removes the table until updates are resumed.
+async fn pause(
+    status: &ServiceStatusStruct,
+    config: &NftConfig<'_>,
+    cancellation_token: &CancellationToken,
+    disable_rules: bool,
+) {
+    cancellation_token.cancel();
+    if disable_rules {
+        warn!("updates paused and the table removed; no traffic is dropped until resumed");
+        flush_table(config);
+    } else {
+        info!("updates paused; the last applied blocklist stays in place");
+    }
+    *status.status.write().await = NftblockdStatus::Paused;
+}
+
+/// Stops the blocklist loop and empties the blocklist sets until updates are resumed.
+/// The table, its chains and counters, and the anti-lockout sets stay in place.
+///
+/// # Errors
+/// Will return `AppError` when the sets cannot be flushed; updates stay paused regardless.
+async fn flush_sets(
+    status: &ServiceStatusStruct,
+    config: &NftConfig<'_>,
+    cancellation_token: &CancellationToken,
+) -> Result<(), AppError> {
+    cancellation_token.cancel();
+    *status.status.write().await = NftblockdStatus::Paused;
+    config.flush_sets_and_apply()
+}
+
+/// Restarts the blocklist loop after a pause; the first cycle re-applies the table.
+///
+/// # Errors
+/// Will return `AppError` when the blocklist loop cannot be configured.
+async fn resume(
+    cli: &Cli,
+    settings: &Settings,
+    status: &Arc<ServiceStatusStruct>,
+    cancellation_token: &CancellationToken,
+    observers: &[Arc<dyn UpdateObserver>],
+    fetches: Option<&Arc<SharedFetches>>,
+) -> Result<(), AppError> {
+    *status.status.write().await = NftblockdStatus::Pending;
+    if let Err(e) = spawn_blocklist_loop(
+        cli,
+        settings,
+        status.clone(),
+        cancellation_token.clone(),
+        observers,
+        fetches,
+    ) {
+        *status.status.write().await = NftblockdStatus::Paused;
+        return Err(e);
+    }
+    info!("updates resumed");
+    Ok(())
+}
+
+/// Creates the feeds shared by the daemon and its profiles, or `None` without profiles.
+///
+/// Feeds are reused within half an interval, so that an update never sees the data of the previous one.
+fn shared_fetches(cli: &Cli) -> Option<Arc<SharedFetches>> {
+    (!cli.profiles.is_empty()).then(|| Arc::new(SharedFetches::new(cli.interval / 2)))
+}
+
+/// A profile run next to the main configuration, with its own table, sources, and schedule.
+struct Tenant {
+    config: NftConfig<'static>,
+    status: Arc<ServiceStatusStruct>,
+    cancellation_token: CancellationToken,
+}
+
+/// Starts the blocklist loops of the profiles of `cli`, each configured from its own variables: its `.env`
+/// files layered over the main ones. When a profile is invalid, the loops already started are stopped again.
+///
+/// # Errors
+/// Will return `AppError` when a profile cannot be configured.
+fn spawn_profiles(
+    cli: &Cli,
+    status: &ServiceStatusStruct,
+    fetches: Option<&Arc<SharedFetches>>,
+) -> Result<Vec<Tenant>, AppError> {
+    let mut tenants = Vec::new();
+    for (index, profile) in cli.profiles.iter().enumerate() {
+        let duplicate = cli.instance.as_ref() == Some(&profile.name)
+            || cli.profiles[..index].iter().any(|p| p.name == profile.name);
+        let tenant = if duplicate {
+            Err(AppError::ParseError(
+                "the name is already used by the daemon or another profile".to_string(),
+            ))
+        } else {
+            profile
+                .environment(&cli.env_file)
+                .and_then(|environment| spawn_profile(profile, &environment, status, fetches))
+        };
+        match tenant {
+            Ok(tenant) => tenants.push(tenant),
+            Err(e) => {
+                stop_profiles(&tenants, false);
+                status
+                    .profiles
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clear();
+                return Err(AppError::ParseError(format!(
+                    "invalid profile {}: {e}",
+                    profile.name
+                )));
+            }
+        }
+    }
+    *status
+        .profiles
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) =
+        tenants.iter().map(|tenant| tenant.status.clone()).collect();
+    Ok(tenants)
+}
+
+/// Starts the blocklist loop of `profile` from the variables of its `.env` files in `environment`,
+/// labeled with the instance of the `daemon`.
+///
+/// # Errors
+/// Will return `AppError` when the configuration of the profile is invalid.
+fn spawn_profile(
+    profile: &Profile,
+    environment: &Environment,
+    daemon: &ServiceStatusStruct,
+    fetches: Option<&Arc<SharedFetches>>,
+) -> Result<Tenant, AppError> {
+    let mut cli = Cli::from_environment(environment)
+        .map_err(|e| AppError::ParseError(format!("invalid configuration: {e}")))?;
+    cli.instance = Some(profile.name.clone());
+    cli.profiles.clear();
+    let settings = Settings::from_env(environment, cli.instance.as_deref(), cli.interval)?;
+    let status = Arc::new(
+        ServiceStatusStruct::new(daemon.command_channel.clone())
+            .with_instance(daemon.instance.clone())
+            .with_profile(cli.instance.clone()),
+    );
+    let cancellation_token = CancellationToken::new();
+    let config = spawn_blocklist_loop(
+        &cli,
+        &settings,
+        status.clone(),
+        cancellation_token.clone(),
+        &[],
+        fetches,
+    )?;
+    info!(
+        "profile {} manages the table {}",
+        profile.name, config.table_name
+    );
+    Ok(Tenant {
+        config,
+        status,
+        cancellation_token,
+    })
+}
+
+/// Stops the blocklist loops of the profiles and, with `flush`, deletes their tables.
+fn stop_profiles(tenants: &[Tenant], flush: bool) {
+    for tenant in tenants {
+        tenant.cancellation_token.cancel();
+        if flush {
+            flush_table(&tenant.config);
+        }
+    }
+}
+
+/// Pauses the blocklist loops of the profiles like the main one.
+async fn pause_profiles(tenants: &[Tenant], disable_rules: bool) {
+    for tenant in tenants {
+        pause(
+            &tenant.status,
+            &tenant.config,
+            &tenant.cancellation_token,
+            disable_rules,
+        )
+        .await;
+    }
+}
+
+/// Empties the blocklist sets of every profile, even when those of another profile cannot be flushed.
+///
+/// # Errors
+/// Will return the first `AppError` of a profile whose sets cannot be flushed.
+async fn flush_profile_sets(tenants: &[Tenant]) -> Result<(), AppError> {
+    let mut flushed = Ok(());
+    for tenant in tenants {
+        let result = flush_sets(&tenant.status, &tenant.config, &tenant.cancellation_token).await;
+        flushed = flushed.and(result);
+    }
+    flushed
+}
+
+/// Prints the differences between the live sets and the freshly fetched blocklists.
+async fn print_diff(
+    cli: &Cli,
+    settings: &Settings,
+    config: &NftConfig<'_>,
+) -> Result<(), AppError> {
+    let blocklist = build_blocklist(cli, settings)?;
+    let (ipv4, ipv6) = blocklist.fetch_elements().await?;
+    let diffs = config.diff_nft(&ipv4, &ipv6)?;
+    if diffs.iter().all(SetDiff::is_empty) {
+        println!("no changes");
+    }
+    for diff in diffs.iter().filter(|d| !d.is_empty()) {
+        println!("{diff}");
+    }
+    Ok(())
+}
+
+/// Reports the drift between the live table and the last applied ruleset, whose blocklist and monitor
+/// elements are loaded into the ruleset of the current configuration, and with `repair`, applies it.
+///
+/// # Errors
+/// Will return `AppError` when the applied ruleset cannot be read, the live ruleset cannot be listed,
+/// or the repair is rejected.
+fn reconcile(settings: &Settings, config: &NftConfig<'_>, repair: bool) -> Result<(), AppError> {
+    let applied = AppliedRuleset::load(&settings.ruleset_path)?;
+    let now = SystemTime::now();
+    let table = config.table_name.as_str();
+    let [ipv4, ipv6, monitor_ipv4, monitor_ipv6] = applied_elements(&applied, config, now);
+    let desired = config.generate_monitored_ruleset(&ipv4, &ipv6, &monitor_ipv4, &monitor_ipv6);
+    // Quarantined addresses come and go at runtime and are not part of the applied ruleset.
+    let ignored = config
+        .quarantine_set_name
+        .iter()
+        .flat_map(|name| [format!("{name}_ipv4"), format!("{name}_ipv6")])
+        .collect::<Vec<_>>();
+    let drift = detect_drift(
+        table,
+        &config.applier.current_ruleset()?,
+        &desired,
+        &ignored,
+    );
+    if drift.is_empty() {
+        println!("no drift");
+        return Ok(());
+    }
+    println!("{drift}");
+    if !repair {
+        std::process::exit(1);
+    }
+    config.applier.apply(&desired)?;
+    restore_manual(settings, config, now)?;
+    println!("repaired");
+    Ok(())
+}
+
+/// Returns the elements of the IPv4 and IPv6 blocklist sets and monitor sets of the `applied` ruleset,
+/// in this order, leaving out the elements whose timeout passed by `now`.
+fn applied_elements(
+    applied: &AppliedRuleset,
+    config: &NftConfig<'_>,
+    now: SystemTime,
+) -> [Option<SetElements<'static>>; 4] {
+    let table = config.table_name.as_str();
+    let elements = |set_name: &str, family: &str| {
+        applied.elements(table, &format!("{set_name}_{family}"), now)
+    };
+    [
+        elements(&config.blocklist_set_name, "ipv4"),
+        elements(&config.blocklist_set_name, "ipv6"),
+        elements(&config.monitor_set_name, "ipv4"),
+        elements(&config.monitor_set_name, "ipv6"),
+    ]
+}
+
+/// Restores the entries added with `nftblockd add` into the manual sets.
+fn restore_manual(
+    settings: &Settings,
+    config: &NftConfig<'_>,
+    now: SystemTime,
+) -> Result<(), AppError> {
+    if config.manual_set_name.is_some() {
+        let active = ManualSet::load(&settings.manual_path)?.active(now);
+        if !active.is_empty() {
+            config.apply_manual(&active)?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies the last applied ruleset with the current configuration, so that the host is protected again
+/// right after a restart; the first fetch then runs in the background, however long it takes or fails.
+///
+/// # Errors
+/// Will return `AppError` when the applied ruleset cannot be read or applied.
+fn restore_applied_ruleset(settings: &Settings, config: &NftConfig<'_>) -> Result<(), AppError> {
+    if !settings.ruleset_path.exists() {
+        info!("no ruleset was applied yet; the table is created by the first update");
+        return Ok(());
+    }
+    let applied = AppliedRuleset::load(&settings.ruleset_path)?;
+    let now = SystemTime::now();
+    let [ipv4, ipv6, monitor_ipv4, monitor_ipv6] = applied_elements(&applied, config, now);
+    config.applier.apply(&config.generate_monitored_ruleset(
+        &ipv4,
+        &ipv6,
+        &monitor_ipv4,
+        &monitor_ipv6,
+    ))?;
+    restore_manual(settings, config, now)?;
+    info!(
+        "restored {} elements of the ruleset applied {} s ago; fetching the sources in the background",
+        [&ipv4, &ipv6, &monitor_ipv4, &monitor_ipv6]
+            .into_iter()
+            .flatten()
+            .map(Vec::len)
+            .sum::<usize>(),
+        now.duration_since(applied.written)
+            .unwrap_or_default()
+            .as_secs()
+    );
+    Ok(())
+}
+
+/// Prints the merged and deduplicated blocklists in the given format, along with the sources of every entry.
+async fn print_export(
+    cli: &Cli,
+    settings: &Settings,
+    config: &NftConfig<'_>,
+    format: ExportFormat,
+) -> Result<(), AppError> {
+    let blocklist = build_blocklist(cli, settings)?;
+    let (ipv4, ipv6) = blocklist.fetch_elements().await?;
+    print!(
+        "{}",
+        export_attributed(
+            format,
+            &config.blocklist_set_name,
+            &ipv4,
+            &ipv6,
+            Some(&blocklist.attribution())
+        )?
+    );
+    Ok(())
+}
+
+/// Prints what the rules would do with the addresses of `input`, matching the anti-lockout, custom blocklist,
+/// and freshly fetched blocklist sets in the order of the rules.
+async fn print_simulation(
+    cli: &Cli,
+    settings: &Settings,
+    config: &NftConfig<'_>,
+    input: &Path,
+) -> Result<(), AppError> {
+    let inputs = read_inputs(input)?;
+    let blocklist = build_blocklist(cli, settings)?;
+    let (ipv4, ipv6) = blocklist.fetch_elements().await?;
+    let mut simulation = Simulation::new();
+    for (set, drop) in [
+        (&config.anti_lockout_set, false),
+        (&config.custom_blocklist_set, true),
+    ] {
+        simulation = simulation
+            .with_set(
+                format!("{}_ipv4", set.set_name),
+                drop,
+                set.ipv4_elements.as_ref(),
+            )
+            .with_set(
+                format!("{}_ipv6", set.set_name),
+                drop,
+                set.ipv6_elements.as_ref(),
+            );
+    }
+    simulation = simulation
+        .with_set(
+            format!("{}_ipv4", config.blocklist_set_name),
+            true,
+            ipv4.as_ref().as_ref(),
+        )
+        .with_set(
+            format!("{}_ipv6", config.blocklist_set_name),
+            true,
+            ipv6.as_ref().as_ref(),
+        );
+    print!("{}", simulation.run(&inputs));
+    Ok(())
+}
+
+/// Creates the `BlockList` from the CLI; a configured primary replaces the blocklist URLs.
+fn build_blocklist(cli: &Cli, settings: &Settings) -> Result<BlockList, AppError> {
+    // The URL of a family that is not blocked is left out rather than fetched for nothing.
+    let families = cli.address_families();
+    let (ipv4, ipv6) = (families.ipv4(), families.ipv6());
+    for (url, name, blocked) in [(&cli.url.url4, "IPv4", ipv4), (&cli.url.url6, "IPv6", ipv6)] {
+        if url.is_some() && !blocked {
+            warn!(
+                "the {name} blocklist URL is ignored, as NFTBLOCKD_ADDRESS_FAMILIES excludes {name}"
+            );
+        }
+    }
+    let blocklist = match cli.primary.as_deref().map(|p| p.trim_end_matches('/')) {
+        None => BlockList::new(
+            &settings.environment,
+            cli.url.url4.clone().filter(|_| ipv4),
+            cli.url.url6.clone().filter(|_| ipv6),
+            settings.split_string.as_deref(),
+        )?,
+        Some(primary) => {
+            let name = settings.replica_name.clone();
+            info!("replicating the blocklist of {primary} as `{name}`");
+            BlockList::new(
+                &settings.environment,
+                ipv4.then(|| format!("{primary}/ipv4")),
+                ipv6.then(|| format!("{primary}/ipv6")),
+                None,
+            )?
+            .with_replica(name, settings.primary_token.as_deref())
+        }
+    };
+    blocklist.validate_sources()?;
+    let mut blocklist = blocklist.with_scheduling(settings.scheduling);
+    if blocklist.resolves_domains() {
+        let resolver =
+            DomainResolver::from_env(&settings.environment, Some(settings.dns_cache_path.clone()))?;
+        blocklist = blocklist.with_resolver(resolver);
+    }
+    if let Some(verifier) = RouteVerifier::from_env(
+        &settings.environment,
+        Some(settings.route_cache_path.clone()),
+    )? {
+        blocklist = blocklist.with_route_verifier(verifier);
+    }
+    if let Some(burn_in) =
+        BurnIn::from_env(&settings.environment, Some(settings.burn_in_path.clone()))?
+    {
+        blocklist = blocklist.with_burn_in(burn_in);
+    }
+    if let Some(http_cache) = HttpCache::from_env(
+        &settings.environment,
+        Some(settings.http_cache_path.clone()),
+    )? {
+        blocklist = blocklist.with_http_cache(http_cache);
+    }
+    Ok(blocklist)
+}
+
+#[cfg(feature = "control-socket")]
+fn bind_socket(path: &str) -> Result<std::os::unix::net::UnixListener, AppError> {
     if Path::new(path).exists() {
-        if UnixStream::connect(path).await.is_ok() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
             return Err(AppError::NftblockdError(
                 "nftblockd is already running".into(),
             ));
@@ -189,43 +1460,130 @@ async fn bind_socket(path: &str) -> Result<UnixListenerStream, AppError> {
         std::fs::remove_file(path)?;
     }
 
-    let uds = UnixListener::bind(path)?;
-
-    Ok(UnixListenerStream::new(uds))
+    let listener = std::os::unix::net::UnixListener::bind(path)?;
+    // Required by `tokio::net::UnixListener::from_std`.
+    listener.set_nonblocking(true)?;
+    Ok(listener)
 }
 
 fn spawn_blocklist_loop<'a>(
     cli: &Cli,
+    settings: &Settings,
     status: Arc<ServiceStatusStruct>,
     cancellation_token: CancellationToken,
-    blocklist_split_string: Option<&str>,
+    observers: &[Arc<dyn UpdateObserver>],
+    fetches: Option<&Arc<SharedFetches>>,
 ) -> Result<NftConfig<'a>, AppError> {
-    let retry_interval = env::var("NFTBLOCKD_RETRY_INTERVAL")
-        .unwrap_or("2".to_string())
-        .parse::<u64>()?;
-
-    let retry_count = env::var("NFTBLOCKD_RETRY_COUNT")
-        .unwrap_or("10".to_string())
-        .parse::<u64>()?;
-    let blocklist = BlockList::new(
-        cli.url.url4.clone(),
-        cli.url.url6.clone(),
-        blocklist_split_string,
-    )?;
-    let refresh_interval = cli.interval;
-    let config = NftConfig::new(blocklist_split_string)?;
-    let config_local = config.clone();
-    tokio::spawn(async move {
-        blocklist_loop(
-            status,
-            blocklist,
-            config_local,
-            refresh_interval,
-            retry_count,
-            retry_interval,
-            cancellation_token,
-        )
-        .await;
+    let mut blocklist = build_blocklist(cli, settings)?
+        .with_manual_set(settings.manual_path.clone())
+        .with_ruleset_path(settings.ruleset_path.clone())
+        .with_git_dir(settings.git_dir.clone());
+    let duplicates = blocklist.duplicate_sources();
+    for source in &duplicates {
+        info!("{source} is configured by several sources; it is fetched once per update");
+    }
+    // Without profiles, the feeds are only shared within this blocklist, and only if a source is configured twice.
+    let fetches = fetches.cloned().or_else(|| {
+        (!duplicates.is_empty()).then(|| Arc::new(SharedFetches::new(cli.interval / 2)))
     });
+    blocklist = blocklist.with_shared_fetches(fetches);
+    #[cfg(feature = "alerts")]
+    if let Some(alerter) = SmtpAlerter::from_env(&settings.environment)? {
+        blocklist = blocklist.with_observer(Arc::new(alerter));
+    }
+    #[cfg(feature = "alerts")]
+    if let Some(alerter) = WebhookAlerter::from_env(&settings.environment)? {
+        blocklist = blocklist.with_observer(Arc::new(alerter));
+    }
+    #[cfg(not(feature = "alerts"))]
+    for variable in ["NFTBLOCKD_SMTP_HOST", "NFTBLOCKD_ALERT_WEBHOOK"] {
+        if settings
+            .environment
+            .var(variable)
+            .is_ok_and(|s| !s.is_empty())
+        {
+            warn!("nftblockd was built without the `alerts` feature; {variable} is ignored");
+        }
+    }
+    #[cfg(feature = "history")]
+    if let Some(path) = &settings.history_db {
+        blocklist = blocklist.with_observer(Arc::new(
+            History::open(path.clone())?
+                .with_escalation(settings.escalate_after, settings.manual_path.clone()),
+        ));
+    }
+    #[cfg(not(feature = "history"))]
+    if settings.history_db.is_some() {
+        warn!("nftblockd was built without the `history` feature; NFTBLOCKD_HISTORY_DB is ignored");
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(sink) = StatsdSink::from_env(&settings.environment, status.instance.as_deref())? {
+        blocklist = blocklist.with_observer(Arc::new(sink.with_profile(status.profile.as_deref())));
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(exporter) =
+        TextfileExporter::from_env(&settings.environment, status.instance.as_deref())
+    {
+        blocklist =
+            blocklist.with_observer(Arc::new(exporter.with_profile(status.profile.as_deref())));
+    }
+    if let Some(events) = EventStream::from_env(&settings.environment, status.instance.as_deref()) {
+        blocklist =
+            blocklist.with_observer(Arc::new(events.with_profile(status.profile.as_deref())));
+    }
+    if let Some(file) = StatusFile::from_env(&settings.environment, status.instance.as_deref()) {
+        blocklist = blocklist.with_observer(Arc::new(file.with_profile(status.profile.as_deref())));
+    }
+    for observer in observers {
+        blocklist = blocklist.with_observer(observer.clone());
+    }
+    let schedule = Schedule::new(cli.interval, settings.retry_count, settings.retry_interval)
+        .with_jitter(settings.interval_jitter)
+        .with_initial_jitter(settings.initial_jitter);
+    let config = NftConfig::new(&settings.environment, settings.split_string.as_deref())?
+        .with_instance(cli.instance.as_deref())
+        .with_address_families(cli.address_families());
+    // The log lines of the loops carry the instance and the profile they belong to.
+    let span = if status.instance.is_some() || status.profile.is_some() {
+        let span = tracing::info_span!(
+            "update",
+            instance = tracing::field::Empty,
+            profile = tracing::field::Empty
+        );
+        if let Some(instance) = &status.instance {
+            span.record("instance", instance.as_str());
+        }
+        if let Some(profile) = &status.profile {
+            span.record("profile", profile.as_str());
+        }
+        span
+    } else {
+        tracing::Span::none()
+    };
+    // The live drop counters are only read by the metrics and the control socket.
+    #[cfg(any(feature = "metrics", feature = "control-socket"))]
+    tokio::spawn(
+        stats_loop(
+            status.clone(),
+            config.clone(),
+            settings.stats_interval,
+            cancellation_token.clone(),
+        )
+        .instrument(span.clone()),
+    );
+    let config_local = config.clone();
+    tokio::spawn(
+        async move {
+            blocklist_loop(
+                status,
+                blocklist,
+                config_local,
+                schedule,
+                cancellation_token,
+            )
+            .await;
+        }
+        .instrument(span),
+    );
     Ok(config)
 }
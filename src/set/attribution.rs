@@ -0,0 +1,72 @@
+use crate::nftables::builder::SetElements;
+use crate::nftables::element_label;
+use crate::utils::subnet::normalize_notation;
+use ipnetwork::IpNetwork;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Sources that contributed each element of a set, keyed by the element as `nft` prints it
+/// (see `element_label`).
+pub type Attribution = BTreeMap<String, BTreeSet<String>>;
+
+/// Attributes every element to the sources listing it or an entry it covers, e.g., after the entries of
+/// several feeds were merged and deduplicated into `elements`.
+///
+/// Entries that no element covers, e.g., those below the consensus threshold, are not attributed.
+///
+/// # Arguments
+///
+/// * `elements` - The deduplicated set elements.
+/// * `sources` - The entries of every source by its URL.
+#[must_use]
+pub fn attribute(elements: &SetElements<'_>, sources: &[(&str, &[&str])]) -> Attribution {
+    let mut spans = elements
+        .iter()
+        .filter_map(element_label)
+        .filter_map(|label| span(&label).map(|span| (span, label)))
+        .collect::<Vec<_>>();
+    spans.sort();
+    let mut attribution = Attribution::new();
+    for (source, entries) in sources {
+        for entry in *entries {
+            let Some((ipv6, start, end)) = span(&normalize_notation(entry.trim())) else {
+                continue;
+            };
+            // The elements are disjoint, so only the last one starting at or before the entry can cover it.
+            let index = spans.partition_point(|((v6, s, _), _)| (*v6, *s) <= (ipv6, start));
+            if let Some(((v6, _, e), label)) = index.checked_sub(1).map(|i| &spans[i])
+                && *v6 == ipv6
+                && *e >= end
+            {
+                attribution
+                    .entry(label.clone())
+                    .or_default()
+                    .insert((*source).to_string());
+            }
+        }
+    }
+    attribution
+}
+
+/// The family and the first and last address of a network, an address, or a range.
+pub fn span(entry: &str) -> Option<(bool, u128, u128)> {
+    if let Some((start, end)) = entry.split_once('-') {
+        let (start, end) = (IpAddr::from_str(start).ok()?, IpAddr::from_str(end).ok()?);
+        return (start.is_ipv6() == end.is_ipv6())
+            .then(|| (start.is_ipv6(), bits(start), bits(end)));
+    }
+    let network = IpNetwork::from_str(entry).ok()?;
+    Some((
+        network.is_ipv6(),
+        bits(network.network()),
+        bits(network.broadcast()),
+    ))
+}
+
+fn bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u128::from(addr.to_bits()),
+        IpAddr::V6(addr) => addr.to_bits(),
+    }
+}
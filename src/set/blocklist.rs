@@ -1,25 +1,287 @@
 use crate::error::AppError;
-use crate::grpc::server::ServiceStatusStruct;
-use crate::nftables::builder::SetElements;
+use crate::grpc::server::{Command, ServiceStatusStruct};
+use crate::nftables::builder::{ApplyStrategy, RuleProto, SetElements};
 use crate::nftables::config::NftConfig;
-use crate::nftables::flush_table;
+use crate::nftables::reconcile::AppliedRuleset;
+use crate::nftables::{comment_elements, expire_elements, flush_table, serialize_ruleset};
+use crate::set::attribution::{Attribution, attribute};
+use crate::set::burn_in::BurnIn;
+use crate::set::change_rate::ChangeRate;
+use crate::set::cloud::CloudFilter;
+use crate::set::consensus::Consensus;
+use crate::set::dual_stack::{DualStackAction, DualStackCheck, FamilyState};
+use crate::set::element_cache::{ElementCache, SharedSetElements};
+use crate::set::feed::{Feed, FeedFormat, Splitter};
+use crate::set::fetch_policy::{FetchPolicy, parse_http_date, parse_retry_after, source_var};
+use crate::set::git::GitSource;
+use crate::set::http_cache::HttpCache;
+use crate::set::impact::{affected_flows, read_flows};
+use crate::set::manual::ManualSet;
+use crate::set::observer::{UpdateObserver, UpdateReport};
+use crate::set::policy::ElementPolicy;
+use crate::set::resolver::DomainResolver;
+use crate::set::routing::{RouteAction, RouteVerifier};
+use crate::set::shared_fetch::{SharedFetch, SharedFetches};
+use crate::set::simulation::Simulation;
+use crate::set::tor;
+use crate::set::trace::EntryTrace;
+use crate::set::url_template::expand_url;
+use crate::settings::Environment;
+use crate::utils::duration::parse_duration;
+use crate::utils::memory::{self, MemoryLimit, PeakSampler};
+use crate::utils::prefix_set::PrefixSet;
+use crate::utils::read_ip_set_file;
+use crate::utils::scheduling::Scheduling;
+use crate::utils::stats::{SourceStats, Stats};
 use crate::utils::status::NftblockdStatus;
-use crate::utils::subnet::{SubnetList, parse_from_string};
-use log::{error, info, warn};
+use crate::utils::subnet::{OverflowPolicy, Rejected, Strictness, SubnetList, ValidatedSubnetList};
+use log::{debug, error, info, warn};
+use nftables::schema::Nftables;
 use rand::RngExt;
-use std::collections::HashMap;
-use std::env;
-use std::sync::Arc;
-use std::time::Duration;
+use reqwest::header::{AUTHORIZATION, ETAG, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use time::OffsetDateTime;
+use time::macros::format_description;
 use tokio_util::sync::CancellationToken;
 
+/// Header carrying the name of a replica polling an aggregator.
+pub const REPLICA_HEADER: &str = "x-nftblockd-replica";
+/// Header carrying the `ETag` of the list a replica last applied.
+pub const APPLIED_HEADER: &str = "x-nftblockd-applied";
+/// Longest comment `nft` accepts on a set element.
+const MAX_COMMENT_LEN: usize = 128;
+/// Most active flows listed when an update would block their peers.
+const MAX_LISTED_FLOWS: usize = 20;
+
+/// Time spent in the phases of fetching the blocklists, summed over both families.
+#[derive(Debug, Default)]
+struct PhaseTimings {
+    fetch: Duration,
+    parse: Duration,
+}
+
+/// Outcome of a (conditional) blocklist fetch.
+enum Fetched {
+    /// The parsed blocklist, possibly shared with other sources, and its `ETag`, if the server sent one.
+    Modified(Arc<Feed>, Option<String>),
+    /// The blocklist has not changed since the cached `ETag`.
+    NotModified,
+    /// The source is overloaded or rate limited and asked not to be fetched before the given time.
+    Deferred(SystemTime),
+}
+
+/// Whether an update is applied when only some sources could be fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApplyPolicy {
+    /// Any failing source aborts the whole update.
+    #[default]
+    Strict,
+    /// A failing source keeps the last elements of its family; the update aborts when there are none.
+    PerFamily,
+    /// A failing source is skipped and its family is applied without it, even if that empties the family.
+    PerSource,
+}
+
+impl ApplyPolicy {
+    /// Parses `strict`, `per-family`, or `per-source`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "per-family" => Ok(Self::PerFamily),
+            "per-source" => Ok(Self::PerSource),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_APPLY_POLICY: {value}; expected strict, per-family, or per-source"
+            ))),
+        }
+    }
+}
+
+/// What happens to the packets matching the entries of a source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SourceAction {
+    /// The entries are loaded into the blocklist set and matching packets are dropped.
+    #[default]
+    Drop,
+    /// The entries are loaded into the monitor set; matching packets are counted and logged, but not dropped.
+    Log,
+    /// The entries are loaded into the blocklist set, but only the packets of an address above its rate
+    /// are dropped (see `NftConfig::ipv4_rate_limit`).
+    Ratelimit,
+}
+
+impl SourceAction {
+    /// Parses `drop`, `log`, or `ratelimit`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "drop" => Ok(Self::Drop),
+            "log" => Ok(Self::Log),
+            "ratelimit" => Ok(Self::Ratelimit),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_ACTION: {value}; expected drop, log, or ratelimit"
+            ))),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BlockList {
     pub headers: Option<HashMap<String, String>>,
-    pub timeout: Duration,
     pub ipv4_endpoint: Option<String>,
     pub ipv6_endpoint: Option<String>,
-    pub split_string: Option<String>,
+    pub observers: Vec<Arc<dyn UpdateObserver>>,
+    /// Name reported to the primary when running as a replica of an aggregator.
+    pub replica_name: Option<String>,
+    /// Restricts the scheme, redirects, and timeouts of the IPv4 source.
+    pub ipv4_policy: FetchPolicy,
+    /// Restricts the scheme, redirects, and timeouts of the IPv6 source.
+    pub ipv6_policy: FetchPolicy,
+    /// Whether an update is applied when only some sources could be fetched.
+    pub apply_policy: ApplyPolicy,
+    /// Whether the IPv4 source is enforced or only monitored.
+    pub ipv4_action: SourceAction,
+    /// Whether the IPv6 source is enforced or only monitored.
+    pub ipv6_action: SourceAction,
+    /// Whether `ipv4_action` was configured explicitly, which skips the burn-in of a new source.
+    ipv4_action_explicit: bool,
+    /// Whether `ipv6_action` was configured explicitly, which skips the burn-in of a new source.
+    ipv6_action_explicit: bool,
+    /// Observes new sources in log mode before enforcing them; off when `None`.
+    burn_in: Option<Arc<BurnIn>>,
+    /// Responses kept for as long as their caching headers declare them fresh; off when `None`.
+    http_cache: Option<Arc<HttpCache>>,
+    /// Soft cap on the memory used while parsing the sources; unlimited when `None`.
+    pub memory_limit: Option<MemoryLimit>,
+    /// Priorities of the threads validating and deduplicating the sources.
+    scheduling: Scheduling,
+    /// Peak memory of the running update, sampled when the peak of the process cannot be reset.
+    peak_sampler: Arc<PeakSampler>,
+    /// Variables the `{env:NAME}` placeholders of the source URLs are expanded from.
+    environment: Environment,
+    /// Format of the IPv4 blocklist.
+    pub ipv4_format: FeedFormat,
+    /// Format of the IPv6 blocklist.
+    pub ipv6_format: FeedFormat,
+    /// How the entries of the IPv4 blocklist are separated.
+    pub ipv4_splitter: Splitter,
+    /// How the entries of the IPv6 blocklist are separated.
+    pub ipv6_splitter: Splitter,
+    /// How invalid entries of the IPv4 blocklist are handled.
+    pub ipv4_strictness: Strictness,
+    /// How invalid entries of the IPv6 blocklist are handled.
+    pub ipv6_strictness: Strictness,
+    /// Whether the IPv4 blocklist lists hostnames, which are resolved into their IPv4 addresses.
+    pub ipv4_resolve_domains: bool,
+    /// Whether the IPv6 blocklist lists hostnames, which are resolved into their IPv6 addresses.
+    pub ipv6_resolve_domains: bool,
+    /// Resolves the hostnames of the sources with `ipv4_resolve_domains` or `ipv6_resolve_domains`.
+    resolver: Option<Arc<DomainResolver>>,
+    /// Verifies that the entries of the sources are routed; off when `None`.
+    route_verifier: Option<Arc<RouteVerifier>>,
+    /// File the invalid entries skipped in the blocklists are written to after every update.
+    pub invalid_entries_path: Option<PathBuf>,
+    /// Invalid entries kept per blocklist for the `invalid_entries_path`.
+    pub invalid_entries_max_samples: usize,
+    /// Conntrack table or flow export checked for active flows the pending update would block.
+    pub impact_flows: Option<PathBuf>,
+    /// Most elements a blocklist set may hold, e.g., to protect the kernel memory of a small router.
+    pub max_set_size: Option<usize>,
+    /// What happens to a blocklist with more elements than `max_set_size`.
+    pub overflow_policy: OverflowPolicy,
+    /// Maximum age of the data of a source before the staleness is escalated.
+    pub max_data_age: Option<Duration>,
+    /// Empties the family of a stale source instead of relying on outdated data.
+    pub clear_stale: bool,
+    /// Time to confirm a changed configuration before it is rolled back; confirmations are off when `None`.
+    pub confirm_timeout: Option<Duration>,
+    /// Comments every element with its source and the time its data was fetched.
+    pub element_comments: bool,
+    /// Requires entries to be listed by several feeds before they are blocked.
+    pub consensus: Option<Consensus>,
+    /// Restricts the scheme, redirects, and timeouts of the consensus feeds.
+    pub consensus_policy: FetchPolicy,
+    /// How the entries of the consensus feeds are separated.
+    pub consensus_splitter: Splitter,
+    /// Rules the final sets must satisfy, or the update fails before anything is applied.
+    pub element_policy: Option<ElementPolicy>,
+    /// Compares the IPv4 and IPv6 lists when both families are configured; off when `None`.
+    pub dual_stack: Option<DualStackCheck>,
+    /// Ports the exits of a Tor source must allow to be listed; every exit when `None`.
+    pub tor_ports: Option<Vec<u16>>,
+    /// Services and regions the ranges of a cloud provider are restricted to.
+    pub cloud_filter: CloudFilter,
+    /// Networks whose way through every update is logged; off when `None`.
+    pub entry_trace: Option<EntryTrace>,
+    /// File with the entries added with `nftblockd add`, restored into the manual sets after every apply.
+    pub manual_path: Option<PathBuf>,
+    /// File the applied ruleset is written to after every apply, as the source of truth of `reconcile`.
+    pub ruleset_path: Option<PathBuf>,
+    /// Directory the git sources are checked out to.
+    pub git_dir: Option<PathBuf>,
+    /// URL or file with IPv4 anti-lockout entries, added to the configured ones on every update.
+    pub anti_lockout_ipv4: Option<String>,
+    /// URL or file with IPv6 anti-lockout entries, added to the configured ones on every update.
+    pub anti_lockout_ipv6: Option<String>,
+    /// Restricts the scheme, redirects, and timeouts of the anti-lockout URLs.
+    pub anti_lockout_policy: FetchPolicy,
+    /// Format of the anti-lockout sources; the expiry of their entries is ignored.
+    pub anti_lockout_format: FeedFormat,
+    /// How the entries of the anti-lockout sources are separated.
+    pub anti_lockout_splitter: Splitter,
+    /// How invalid entries of the anti-lockout sources are handled.
+    pub anti_lockout_strictness: Strictness,
+    ipv4_cache: ElementCache,
+    ipv6_cache: ElementCache,
+    /// Sources that answered `429` or `503` with a `Retry-After`, and until when they are not fetched.
+    deferred: Arc<Mutex<BTreeMap<String, SystemTime>>>,
+    /// Time every source was last fetched successfully.
+    refreshed: Arc<Mutex<BTreeMap<String, SystemTime>>>,
+    /// The `Last-Modified` time every source reported for its list.
+    last_modified: Arc<Mutex<BTreeMap<String, SystemTime>>>,
+    /// IPv4 and IPv6 elements of the last apply, kept while the dual-stack check holds the lists.
+    applied_elements: Arc<Mutex<(SharedSetElements, SharedSetElements)>>,
+    /// Sources whose staleness has already been escalated.
+    stale: Arc<Mutex<BTreeSet<String>>>,
+    /// Elements and failed fetches of every source.
+    source_stats: Arc<Mutex<BTreeMap<String, SourceStats>>>,
+    /// Entries of every consensus feed from its last successful fetch.
+    consensus_lists: Arc<Mutex<BTreeMap<String, Vec<String>>>>,
+    /// Entries of every anti-lockout source from its last successful fetch.
+    anti_lockout_lists: Arc<Mutex<BTreeMap<String, Vec<String>>>>,
+    /// Added and removed entries of the recent fetches of every source; the detection is off when `None`.
+    change_rate: Option<Arc<Mutex<ChangeRate>>>,
+    /// Responses shared with the other sources and profiles of the daemon.
+    shared_fetches: Option<Arc<SharedFetches>>,
+    /// IPv4 entries below the consensus threshold, to be monitored.
+    ipv4_monitor: Arc<Mutex<SharedSetElements>>,
+    /// IPv6 entries below the consensus threshold, to be monitored.
+    ipv6_monitor: Arc<Mutex<SharedSetElements>>,
+    /// Invalid entries skipped in the current IPv4 blocklist.
+    ipv4_rejected: Arc<Mutex<Rejected>>,
+    /// Invalid entries skipped in the current IPv6 blocklist.
+    ipv6_rejected: Arc<Mutex<Rejected>>,
+    /// Expiry of the IPv4 entries from the last successful fetch.
+    ipv4_expiry: Arc<Mutex<HashMap<String, SystemTime>>>,
+    /// Expiry of the IPv6 entries from the last successful fetch.
+    ipv6_expiry: Arc<Mutex<HashMap<String, SystemTime>>>,
+    /// Comments of the annotated IPv4 entries from the last successful fetch.
+    ipv4_comments: Arc<Mutex<HashMap<String, String>>>,
+    /// Comments of the annotated IPv6 entries from the last successful fetch.
+    ipv6_comments: Arc<Mutex<HashMap<String, String>>>,
+    /// Sources that contributed each element of the current IPv4 blocklist.
+    ipv4_attribution: Arc<Mutex<Attribution>>,
+    /// Sources that contributed each element of the current IPv6 blocklist.
+    ipv6_attribution: Arc<Mutex<Attribution>>,
 }
 
 // headers with json in env
@@ -33,7 +295,7 @@ impl BlockList {
     ///
     /// # Arguments
     ///
-    /// * `headers` - An optional JSON string that contains HTTP headers in key-value format.
+    /// * `environment` - The variables the settings of the blocklist are read from.
     /// * `ipv4_endpoint` - An optional string representing the IPv4 blocklist URL.
     /// * `ipv6_endpoint` - An optional string representing the IPv6 blocklist URL.
     /// * `split_string` - An optional delimiter used to split the blocklist contents.
@@ -45,69 +307,1435 @@ impl BlockList {
     /// # Errors
     /// Will return `AppError` when parsing headers fails
     pub fn new(
+        environment: &Environment,
         ipv4_endpoint: Option<String>,
         ipv6_endpoint: Option<String>,
         split_string: Option<&str>,
     ) -> Result<BlockList, AppError> {
-        let headers = env::var("NFTBLOCKD_REQUEST_HEADERS")
+        let headers = environment
+            .var("NFTBLOCKD_REQUEST_HEADERS")
             .ok()
             .filter(|s| !s.is_empty());
-        let timeout = env::var("NFTBLOCKD_REQUEST_TIMEOUT")
-            .unwrap_or("10".to_string())
-            .parse::<u64>()?;
         let headers: Option<HashMap<String, String>> = headers
             .map(|h| serde_json::from_str(h.as_str()))
             .transpose()?;
         Ok(Self {
             headers,
-            timeout: Duration::from_secs(timeout),
+            environment: environment.clone(),
             ipv4_endpoint,
             ipv6_endpoint,
-            split_string: split_string.map(ToString::to_string),
+            observers: Vec::new(),
+            replica_name: None,
+            ipv4_policy: FetchPolicy::from_env(environment, "IPV4")?,
+            ipv6_policy: FetchPolicy::from_env(environment, "IPV6")?,
+            apply_policy: environment
+                .var("NFTBLOCKD_APPLY_POLICY")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|p| ApplyPolicy::parse(&p))
+                .transpose()?
+                .unwrap_or_default(),
+            ipv4_action: source_var(environment, "IPV4", "ACTION")
+                .map(|a| SourceAction::parse(&a))
+                .transpose()?
+                .unwrap_or_default(),
+            ipv6_action: source_var(environment, "IPV6", "ACTION")
+                .map(|a| SourceAction::parse(&a))
+                .transpose()?
+                .unwrap_or_default(),
+            ipv4_action_explicit: source_var(environment, "IPV4", "ACTION").is_some(),
+            ipv6_action_explicit: source_var(environment, "IPV6", "ACTION").is_some(),
+            burn_in: None,
+            http_cache: None,
+            memory_limit: MemoryLimit::from_env(environment)?,
+            scheduling: Scheduling::default(),
+            peak_sampler: Arc::new(PeakSampler::default()),
+            ipv4_format: FeedFormat::from_env(environment, "IPV4")?,
+            ipv6_format: FeedFormat::from_env(environment, "IPV6")?,
+            ipv4_splitter: Splitter::from_env(environment, "IPV4", split_string)?,
+            ipv6_splitter: Splitter::from_env(environment, "IPV6", split_string)?,
+            ipv4_strictness: Strictness::from_env(environment, "IPV4", Strictness::Lenient)?,
+            ipv6_strictness: Strictness::from_env(environment, "IPV6", Strictness::Lenient)?,
+            ipv4_resolve_domains: resolve_domains_var(environment, "IPV4")?,
+            ipv6_resolve_domains: resolve_domains_var(environment, "IPV6")?,
+            resolver: None,
+            route_verifier: None,
+            invalid_entries_path: environment
+                .var("NFTBLOCKD_INVALID_ENTRIES_PATH")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+            impact_flows: environment
+                .var("NFTBLOCKD_IMPACT_FLOWS")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+            invalid_entries_max_samples: environment
+                .var("NFTBLOCKD_INVALID_ENTRIES_MAX_SAMPLES")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<usize>())
+                .transpose()
+                .map_err(|e| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_INVALID_ENTRIES_MAX_SAMPLES: {e}"
+                    ))
+                })?
+                .unwrap_or(100),
+            max_set_size: environment
+                .var("NFTBLOCKD_MAX_SET_SIZE")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<usize>())
+                .transpose()
+                .map_err(|e| {
+                    AppError::ParseError(format!("invalid NFTBLOCKD_MAX_SET_SIZE: {e}"))
+                })?,
+            overflow_policy: environment
+                .var("NFTBLOCKD_OVERFLOW_POLICY")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|p| OverflowPolicy::parse(&p))
+                .transpose()?
+                .unwrap_or_default(),
+            ipv4_cache: ElementCache::default(),
+            ipv6_cache: ElementCache::default(),
+            deferred: Arc::new(Mutex::new(BTreeMap::new())),
+            max_data_age: environment
+                .var("NFTBLOCKD_MAX_DATA_AGE")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|age| parse_duration(&age))
+                .transpose()?,
+            clear_stale: environment
+                .var("NFTBLOCKD_CLEAR_STALE")
+                .unwrap_or("false".to_string())
+                .parse::<bool>()
+                .map_err(|e| AppError::ParseError(format!("invalid NFTBLOCKD_CLEAR_STALE: {e}")))?,
+            confirm_timeout: environment
+                .var("NFTBLOCKD_CONFIRM_TIMEOUT")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|timeout| parse_duration(&timeout))
+                .transpose()?,
+            consensus: Consensus::from_env(environment)?,
+            consensus_policy: FetchPolicy::from_env(environment, "CONSENSUS")?,
+            consensus_splitter: Splitter::from_env(environment, "CONSENSUS", split_string)?,
+            element_policy: ElementPolicy::from_env(environment)?,
+            dual_stack: DualStackCheck::from_env(environment)?,
+            tor_ports: tor::ports_from_env(environment)?,
+            cloud_filter: CloudFilter::from_env(environment),
+            entry_trace: EntryTrace::from_env(environment)?,
+            manual_path: None,
+            ruleset_path: None,
+            git_dir: None,
+            anti_lockout_ipv4: environment
+                .var("NFTBLOCKD_ANTI_LOCKOUT_SOURCE_IPV4")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            anti_lockout_ipv6: environment
+                .var("NFTBLOCKD_ANTI_LOCKOUT_SOURCE_IPV6")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            anti_lockout_policy: FetchPolicy::from_env(environment, "ANTI_LOCKOUT")?,
+            anti_lockout_format: FeedFormat::from_env(environment, "ANTI_LOCKOUT")?,
+            anti_lockout_splitter: Splitter::from_env(environment, "ANTI_LOCKOUT", split_string)?,
+            anti_lockout_strictness: Strictness::from_env(
+                environment,
+                "ANTI_LOCKOUT",
+                Strictness::Strict,
+            )?,
+            element_comments: environment
+                .var("NFTBLOCKD_ELEMENT_COMMENTS")
+                .unwrap_or("false".to_string())
+                .parse::<bool>()
+                .map_err(|e| {
+                    AppError::ParseError(format!("invalid NFTBLOCKD_ELEMENT_COMMENTS: {e}"))
+                })?,
+            refreshed: Arc::new(Mutex::new(BTreeMap::new())),
+            last_modified: Arc::new(Mutex::new(BTreeMap::new())),
+            applied_elements: Arc::new(Mutex::new((Arc::new(None), Arc::new(None)))),
+            stale: Arc::new(Mutex::new(BTreeSet::new())),
+            source_stats: Arc::new(Mutex::new(BTreeMap::new())),
+            consensus_lists: Arc::new(Mutex::new(BTreeMap::new())),
+            anti_lockout_lists: Arc::new(Mutex::new(BTreeMap::new())),
+            change_rate: ChangeRate::from_env(environment)?.map(|rate| Arc::new(Mutex::new(rate))),
+            shared_fetches: None,
+            ipv4_monitor: Arc::new(Mutex::new(Arc::new(None))),
+            ipv6_monitor: Arc::new(Mutex::new(Arc::new(None))),
+            ipv4_rejected: Arc::new(Mutex::new(Rejected::default())),
+            ipv6_rejected: Arc::new(Mutex::new(Rejected::default())),
+            ipv4_expiry: Arc::new(Mutex::new(HashMap::new())),
+            ipv6_expiry: Arc::new(Mutex::new(HashMap::new())),
+            ipv4_comments: Arc::new(Mutex::new(HashMap::new())),
+            ipv6_comments: Arc::new(Mutex::new(HashMap::new())),
+            ipv4_attribution: Arc::new(Mutex::new(Attribution::new())),
+            ipv6_attribution: Arc::new(Mutex::new(Attribution::new())),
+        })
+    }
+
+    /// Registers an observer that gets notified about update lifecycle events.
+    ///
+    /// # Arguments
+    ///
+    /// * `observer` - The hooks to call on fetch start, successful apply, and error.
+    ///
+    /// # Returns
+    ///
+    /// The `BlockList` with the observer registered.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn UpdateObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Shares the responses of the sources with the other profiles of the daemon.
+    #[must_use]
+    pub fn with_shared_fetches(mut self, shared_fetches: Option<Arc<SharedFetches>>) -> Self {
+        self.shared_fetches = shared_fetches;
+        self
+    }
+
+    /// Validates and deduplicates the sources with the priorities of `scheduling`.
+    #[must_use]
+    pub fn with_scheduling(mut self, scheduling: Scheduling) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    /// Reuses the responses of the sources with `http_cache` while their caching headers declare them fresh.
+    #[must_use]
+    pub fn with_http_cache(mut self, http_cache: HttpCache) -> Self {
+        self.http_cache = Some(Arc::new(http_cache));
+        self
+    }
+
+    /// Resolves the hostnames listed by the sources with `resolver`.
+    #[must_use]
+    pub fn with_resolver(mut self, resolver: DomainResolver) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Verifies with `verifier` that the entries of the sources are covered by an announced route.
+    #[must_use]
+    pub fn with_route_verifier(mut self, verifier: RouteVerifier) -> Self {
+        self.route_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Observes the sources configured for the first time in log mode for the period of `burn_in`.
+    #[must_use]
+    pub fn with_burn_in(mut self, burn_in: BurnIn) -> Self {
+        self.burn_in = Some(Arc::new(burn_in));
+        self
+    }
+
+    /// Whether a source lists hostnames to be resolved.
+    #[must_use]
+    pub fn resolves_domains(&self) -> bool {
+        (self.ipv4_resolve_domains && self.ipv4_endpoint.is_some())
+            || (self.ipv6_resolve_domains && self.ipv6_endpoint.is_some())
+    }
+
+    /// Restores the entries stored in `path` into the manual sets after every apply.
+    #[must_use]
+    pub fn with_manual_set(mut self, path: PathBuf) -> Self {
+        self.manual_path = Some(path);
+        self
+    }
+
+    /// Writes the applied ruleset to `path` after every apply.
+    #[must_use]
+    pub fn with_ruleset_path(mut self, path: PathBuf) -> Self {
+        self.ruleset_path = Some(path);
+        self
+    }
+
+    /// Checks the git sources out to `dir`.
+    #[must_use]
+    pub fn with_git_dir(mut self, dir: PathBuf) -> Self {
+        self.git_dir = Some(dir);
+        self
+    }
+
+    /// Configures the `BlockList` as a replica of an aggregator.
+    ///
+    /// Replicas authenticate with `token` and report `name` along with the `ETag`s
+    /// of the lists they applied, so the primary can show the fleet convergence.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the replica shown by the primary.
+    /// * `token` - The bearer token of the primary, if required.
+    ///
+    /// # Returns
+    ///
+    /// The `BlockList` configured as a replica.
+    #[must_use]
+    pub fn with_replica(mut self, name: String, token: Option<&str>) -> Self {
+        if let Some(token) = token {
+            self.headers
+                .get_or_insert_default()
+                .insert(AUTHORIZATION.to_string(), format!("Bearer {token}"));
+        }
+        self.replica_name = Some(name);
+        self
+    }
+
+    /// Checks the configured sources against the `FetchPolicy`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a source is not allowed, e.g., uses plain HTTP.
+    pub fn validate_sources(&self) -> Result<(), AppError> {
+        [
+            (&self.ipv4_endpoint, &self.ipv4_policy),
+            (&self.ipv6_endpoint, &self.ipv6_policy),
+        ]
+        .into_iter()
+        .filter_map(|(endpoint, policy)| Some((endpoint.as_deref()?, policy)))
+        .chain(
+            self.consensus
+                .iter()
+                .flat_map(|consensus| consensus.feeds.keys())
+                .map(|feed| (feed.as_str(), &self.consensus_policy)),
+        )
+        .chain(
+            self.anti_lockout_urls()
+                .map(|source| (source, &self.anti_lockout_policy)),
+        )
+        .try_for_each(|(source, policy)| {
+            policy.check_source(&expand_url(
+                &self.environment,
+                source,
+                OffsetDateTime::now_utc(),
+            )?)
         })
     }
 
+    /// Returns the configured source URLs with their placeholders expanded.
+    fn sources(&self) -> Vec<String> {
+        let now = OffsetDateTime::now_utc();
+        [&self.ipv4_endpoint, &self.ipv6_endpoint]
+            .into_iter()
+            .filter_map(Option::as_deref)
+            .chain(
+                self.consensus
+                    .iter()
+                    .flat_map(|consensus| consensus.feeds.keys().map(String::as_str)),
+            )
+            .chain(self.anti_lockout_urls())
+            .filter_map(|endpoint| expand_url(&self.environment, endpoint, now).ok())
+            .collect()
+    }
+
+    /// Returns the source URLs configured more than once, e.g., for both families or also as a consensus feed;
+    /// each of them is fetched once per update and the response is shared.
+    #[must_use]
+    pub fn duplicate_sources(&self) -> Vec<String> {
+        let mut seen = BTreeSet::new();
+        let mut duplicates = BTreeSet::new();
+        for source in self.sources() {
+            if !seen.insert(source.clone()) {
+                duplicates.insert(source);
+            }
+        }
+        duplicates.into_iter().collect()
+    }
+
+    /// Returns the anti-lockout sources that are fetched over HTTP rather than read from a file.
+    fn anti_lockout_urls(&self) -> impl Iterator<Item = &str> {
+        [&self.anti_lockout_ipv4, &self.anti_lockout_ipv6]
+            .into_iter()
+            .filter_map(Option::as_deref)
+            .filter(|source| is_url(source))
+    }
+
+    /// Returns the sources currently deferred by a `Retry-After`, and until when.
+    #[must_use]
+    pub fn deferred(&self) -> BTreeMap<String, SystemTime> {
+        let now = SystemTime::now();
+        let mut deferred = self.deferred.lock().unwrap_or_else(PoisonError::into_inner);
+        deferred.retain(|_, until| *until > now);
+        deferred.clone()
+    }
+
+    /// Returns the time every source was last fetched successfully.
+    #[must_use]
+    pub fn refreshed(&self) -> BTreeMap<String, SystemTime> {
+        self.refreshed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns the elements and failed fetches of every source.
+    #[must_use]
+    pub fn source_stats(&self) -> BTreeMap<String, SourceStats> {
+        self.source_stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Counts a failed fetch of `url` and notifies the observers.
+    fn source_failed(&self, url: &str, error: &AppError) {
+        self.source_stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(url.to_string())
+            .or_default()
+            .failures += 1;
+        for observer in &self.observers {
+            observer.on_source_failed(url, error);
+        }
+    }
+
+    /// Records the unrouted entries of a fresh fetch of `url`.
+    fn record_unrouted(&self, url: &str, action: RouteAction, unrouted: &[String]) {
+        self.source_stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(url.to_string())
+            .or_default()
+            .unrouted = unrouted.len();
+        if unrouted.is_empty() {
+            return;
+        }
+        let samples = unrouted
+            .iter()
+            .take(5)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        match action {
+            RouteAction::Drop => warn!(
+                "left out {} unrouted entries of {url}, e.g., {samples}",
+                unrouted.len()
+            ),
+            _ => info!(
+                "{} entries of {url} are not routed, e.g., {samples}",
+                unrouted.len()
+            ),
+        }
+    }
+
+    /// Returns the action of `endpoint`, which is `SourceAction::Log` during the burn-in of a new source
+    /// unless its action was configured explicitly.
+    fn effective_action(
+        &self,
+        endpoint: Option<&str>,
+        action: SourceAction,
+        explicit: bool,
+    ) -> SourceAction {
+        let (Some(burn_in), Some(endpoint), false) = (&self.burn_in, endpoint, explicit) else {
+            return action;
+        };
+        match burn_in.remaining(endpoint, SystemTime::now()) {
+            Some(remaining) => {
+                info!(
+                    "{endpoint} is a new source; observing it in log mode for another {} s before enforcing it",
+                    remaining.as_secs()
+                );
+                SourceAction::Log
+            }
+            None => action,
+        }
+    }
+
+    fn mark_refreshed(&self, url: &str) {
+        self.refreshed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(url.to_string(), SystemTime::now());
+    }
+
+    /// Records the entries of a fresh fetch of `url` and escalates an anomalous change rate.
+    fn record_changes(&self, url: &str, entries: &[&str]) {
+        let Some(anomaly) = self.change_rate.as_ref().and_then(|rate| {
+            rate.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(url, entries)
+        }) else {
+            return;
+        };
+        warn!("anomalous change rate: {anomaly}; the feed may be corrupted");
+        for observer in &self.observers {
+            observer.on_change_anomaly(&anomaly);
+        }
+    }
+
+    /// Returns the monitored elements of the family of `proto`.
+    fn monitor(&self, proto: &RuleProto) -> &Mutex<SharedSetElements> {
+        match proto {
+            RuleProto::Ip6 => &self.ipv6_monitor,
+            _ => &self.ipv4_monitor,
+        }
+    }
+
+    /// Returns the invalid entries skipped in the current blocklist of the family of `proto`.
+    fn rejected(&self, proto: &RuleProto) -> &Mutex<Rejected> {
+        match proto {
+            RuleProto::Ip6 => &self.ipv6_rejected,
+            _ => &self.ipv4_rejected,
+        }
+    }
+
+    /// Writes the invalid entries skipped in the current blocklists to `path`, replacing it atomically.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be written or renamed.
+    fn write_rejected(&self, path: &Path) -> Result<(), AppError> {
+        let mut out = String::new();
+        for (endpoint, proto) in [
+            (&self.ipv4_endpoint, RuleProto::Ip),
+            (&self.ipv6_endpoint, RuleProto::Ip6),
+        ] {
+            let Some(endpoint) = endpoint else {
+                continue;
+            };
+            let rejected = self
+                .rejected(&proto)
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
+            let _ = writeln!(
+                out,
+                "# {endpoint}: {} invalid entries, {} shown",
+                rejected.count,
+                rejected.samples.len()
+            );
+            for sample in &rejected.samples {
+                let _ = writeln!(out, "{sample}");
+            }
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, out)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Returns the expiry of the entries of the family of `proto`.
+    fn expiry(&self, proto: &RuleProto) -> &Mutex<HashMap<String, SystemTime>> {
+        match proto {
+            RuleProto::Ip6 => &self.ipv6_expiry,
+            _ => &self.ipv4_expiry,
+        }
+    }
+
+    /// Returns the comments of the annotated entries of the family of `proto`.
+    fn comments(&self, proto: &RuleProto) -> &Mutex<HashMap<String, String>> {
+        match proto {
+            RuleProto::Ip6 => &self.ipv6_comments,
+            _ => &self.ipv4_comments,
+        }
+    }
+
+    /// Returns the sources that contributed the elements of the family of `proto`.
+    fn attribution_of(&self, proto: &RuleProto) -> &Mutex<Attribution> {
+        match proto {
+            RuleProto::Ip6 => &self.ipv6_attribution,
+            _ => &self.ipv4_attribution,
+        }
+    }
+
+    /// Returns the sources that contributed each element of the current IPv4 and IPv6 blocklists,
+    /// keyed by the element as `nft` prints it. An element covering entries of several feeds, e.g.,
+    /// a network merged with the addresses listed by the consensus feeds, is attributed to all of them.
+    #[must_use]
+    pub fn attribution(&self) -> Attribution {
+        let mut attribution = self
+            .ipv4_attribution
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        attribution.extend(
+            self.ipv6_attribution
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        );
+        attribution
+    }
+
+    /// Sets the timeouts of the elements whose entries expire, counted from now, and the comments of the
+    /// annotated entries, so that the cached elements of an unchanged blocklist keep their original expiry.
+    fn expire(&self, proto: &RuleProto, elements: SharedSetElements) -> SharedSetElements {
+        let expiry = self
+            .expiry(proto)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let comments = self
+            .comments(proto)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match elements.as_ref() {
+            Some(list) if !expiry.is_empty() || !comments.is_empty() => {
+                let mut list = list.clone();
+                if !comments.is_empty() {
+                    list = comment_elements(list, &comments);
+                }
+                if !expiry.is_empty() {
+                    list = expire_elements(list, &expiry, SystemTime::now());
+                }
+                Arc::new(Some(list))
+            }
+            _ => elements,
+        }
+    }
+
+    /// Returns the IPv4 and IPv6 entries below the consensus threshold that are monitored instead of dropped.
+    #[must_use]
+    pub fn monitored(&self) -> (SharedSetElements, SharedSetElements) {
+        (
+            self.ipv4_monitor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            self.ipv6_monitor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        )
+    }
+
+    /// Fetches the consensus feeds.
+    ///
+    /// A failing feed aborts the update with the `Strict` apply policy; otherwise, its last entries are used.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a feed cannot be fetched and the apply policy is `Strict`.
+    async fn fetch_consensus_feeds(&self, consensus: &Consensus) -> Result<(), AppError> {
+        for url in consensus.feeds.keys() {
+            if self.deferred().contains_key(url) {
+                info!("fetch of {url} is deferred by its Retry-After; reusing its last entries");
+                continue;
+            }
+            let fetched = self
+                .fetch_blocklist(
+                    url,
+                    &ElementCache::default(),
+                    &self.consensus_policy,
+                    FeedFormat::Text,
+                    &self.consensus_splitter,
+                )
+                .await;
+            let entries = match fetched {
+                Ok(Fetched::Modified(feed, _)) => feed.to_entries().unwrap_or_default(),
+                Ok(Fetched::NotModified) => continue,
+                Ok(Fetched::Deferred(until)) => {
+                    warn!("{url} asked to retry later; reusing its last entries");
+                    self.deferred
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(url.clone(), until);
+                    continue;
+                }
+                Err(e) if self.apply_policy == ApplyPolicy::Strict => {
+                    self.source_failed(url, &e);
+                    return Err(e);
+                }
+                Err(e) => {
+                    warn!(
+                        "failed to fetch the consensus feed {url}: {e}; reusing its last entries"
+                    );
+                    self.source_failed(url, &e);
+                    self.notify_error(&e);
+                    continue;
+                }
+            };
+            self.mark_refreshed(url);
+            self.consensus_lists
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(url.clone(), entries);
+        }
+        Ok(())
+    }
+
+    /// Fetches the anti-lockout sources and returns `config` with their entries added to its anti-lockout set.
+    ///
+    /// A source that cannot be fetched keeps its last entries. Without any, the update fails
+    /// rather than risk locking out the addresses the source lists.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a source without previous entries cannot be fetched or its entries are invalid.
+    async fn refresh_anti_lockout<'a>(
+        &self,
+        config: &NftConfig<'a>,
+    ) -> Result<NftConfig<'a>, AppError> {
+        let mut entries = [Vec::new(), Vec::new()];
+        for ((source, ipv6), entries) in [
+            (&self.anti_lockout_ipv4, false),
+            (&self.anti_lockout_ipv6, true),
+        ]
+        .into_iter()
+        .zip(&mut entries)
+        {
+            let Some(source) = source else {
+                continue;
+            };
+            match self.fetch_anti_lockout(source, ipv6).await {
+                Ok(Some(fetched)) => {
+                    self.mark_refreshed(source);
+                    self.anti_lockout_lists
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(source.clone(), fetched);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    if !self
+                        .anti_lockout_lists
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .contains_key(source)
+                    {
+                        return Err(e);
+                    }
+                    warn!(
+                        "failed to fetch the anti-lockout source {source}: {e}; reusing its last entries"
+                    );
+                    self.notify_error(&e);
+                }
+            }
+            if let Some(last) = self
+                .anti_lockout_lists
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(source)
+            {
+                entries.extend_from_slice(last);
+            }
+        }
+        let [ipv4, ipv6] = entries;
+        let mut config = config.clone();
+        config.anti_lockout_set =
+            config
+                .anti_lockout_set
+                .with_entries(&ipv4, &ipv6, self.anti_lockout_strictness)?;
+        Ok(config)
+    }
+
+    /// Fetches the IPv4 or, if `ipv6`, IPv6 entries of an anti-lockout source, a URL or a file.
+    ///
+    /// # Returns
+    /// `None` when the source is deferred by its `Retry-After` and its last entries are to be kept.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the source cannot be fetched or parsed.
+    async fn fetch_anti_lockout(
+        &self,
+        source: &str,
+        ipv6: bool,
+    ) -> Result<Option<Vec<String>>, AppError> {
+        if self.deferred().contains_key(source) {
+            info!("fetch of {source} is deferred by its Retry-After; reusing its last entries");
+            return Ok(None);
+        }
+        if !is_url(source) {
+            let body = read_ip_set_file(Some(source))?.unwrap_or_default();
+            let mut feed = self.parse_feed(
+                source,
+                body,
+                self.anti_lockout_format,
+                &self.anti_lockout_splitter,
+            )?;
+            if self.anti_lockout_format.mixes_families() {
+                feed.retain_family(ipv6);
+            }
+            return Ok(Some(feed.to_entries().unwrap_or_default()));
+        }
+        let fetched = self
+            .fetch_blocklist(
+                source,
+                &ElementCache::default(),
+                &self.anti_lockout_policy,
+                self.anti_lockout_format,
+                &self.anti_lockout_splitter,
+            )
+            .await?;
+        match fetched {
+            Fetched::Modified(mut feed, _) => {
+                if self.anti_lockout_format.mixes_families() {
+                    Arc::make_mut(&mut feed).retain_family(ipv6);
+                }
+                Ok(Some(feed.to_entries().unwrap_or_default()))
+            }
+            Fetched::NotModified => Ok(None),
+            Fetched::Deferred(until) => {
+                warn!("{source} asked to retry later; reusing its last entries");
+                self.deferred
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(source.to_string(), until);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Escalates sources whose data is older than `max_data_age` and, with `clear_stale`, empties their family.
+    fn check_stale(&self, ipv4: &mut SharedSetElements, ipv6: &mut SharedSetElements) {
+        let Some(max_age) = self.max_data_age else {
+            return;
+        };
+        let refreshed = self.refreshed();
+        let mut stale = self.stale.lock().unwrap_or_else(PoisonError::into_inner);
+        for (endpoint, elements) in [(&self.ipv4_endpoint, ipv4), (&self.ipv6_endpoint, ipv6)] {
+            let Some(endpoint) = endpoint else {
+                continue;
+            };
+            let age = refreshed
+                .get(endpoint)
+                .and_then(|t| t.elapsed().ok())
+                .unwrap_or_default();
+            if age <= max_age {
+                stale.remove(endpoint);
+                continue;
+            }
+            error!(
+                "the data of {endpoint} is {} s old, exceeding the maximum age of {} s",
+                age.as_secs(),
+                max_age.as_secs()
+            );
+            if stale.insert(endpoint.clone()) {
+                for observer in &self.observers {
+                    observer.on_stale(endpoint, age);
+                }
+            }
+            if self.clear_stale {
+                warn!("clearing the elements of the stale source {endpoint}");
+                *elements = Arc::new(None);
+            }
+        }
+    }
+
+    /// Compares the IPv4 and IPv6 lists when both families are configured and, with
+    /// `DualStackAction::Hold`, replaces inconsistent lists with the elements of the last apply.
+    fn check_dual_stack(&self, ipv4: &mut SharedSetElements, ipv6: &mut SharedSetElements) {
+        let (Some(check), Some(ipv4_endpoint), Some(ipv6_endpoint)) =
+            (&self.dual_stack, &self.ipv4_endpoint, &self.ipv6_endpoint)
+        else {
+            return;
+        };
+        let inconsistencies = {
+            let last_modified = self
+                .last_modified
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            check.inconsistencies(
+                FamilyState {
+                    elements: Option::as_ref(ipv4).map_or(0, Vec::len),
+                    last_modified: last_modified.get(ipv4_endpoint).copied(),
+                },
+                FamilyState {
+                    elements: Option::as_ref(ipv6).map_or(0, Vec::len),
+                    last_modified: last_modified.get(ipv6_endpoint).copied(),
+                },
+            )
+        };
+        if inconsistencies.is_empty() {
+            return;
+        }
+        for inconsistency in &inconsistencies {
+            warn!(
+                "inconsistent dual-stack lists of {ipv4_endpoint} and {ipv6_endpoint}: {inconsistency}"
+            );
+        }
+        if check.action != DualStackAction::Hold {
+            return;
+        }
+        let applied = self
+            .applied_elements
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if applied.0.is_none() && applied.1.is_none() {
+            warn!("nothing was applied yet; applying the inconsistent lists");
+            return;
+        }
+        warn!("holding the elements of the last apply until the lists are consistent again");
+        (*ipv4, *ipv6) = applied;
+    }
+
+    /// Notifies all registered observers about a failed update.
+    pub fn notify_error(&self, error: &AppError) {
+        for observer in &self.observers {
+            observer.on_error(error);
+        }
+    }
+
+    /// Notifies all registered observers that the retry budget has been exhausted.
+    pub fn notify_retries_exhausted(&self, error: &AppError) {
+        for observer in &self.observers {
+            observer.on_retries_exhausted(error);
+        }
+    }
+
+    /// Notifies all registered observers that updates were paused.
+    pub fn notify_updates_stopped(&self, error: &AppError) {
+        for observer in &self.observers {
+            observer.on_updates_stopped(error);
+        }
+    }
+
     /// Fetches and parses a blocklist from the specified endpoint.
     ///
-    /// This function sends an HTTP GET request to the given endpoint. If headers
+    /// This function expands the placeholders of the endpoint and sends an HTTP GET request to it. If headers
     /// are specified in the `BlockList` object, they are applied to the request.
-    /// The response body is read and processed using the delimiter specified by `split_string`
-    /// before being returned.
+    /// If the `cache` holds an `ETag`, the request is conditional and a `304 Not Modified`
+    /// response yields `Fetched::NotModified`. A `429` or `503` carrying a `Retry-After` yields `Fetched::Deferred`.
+    /// Otherwise, the response body is split into its entries by `splitter`.
+    /// Responses with a non-success status code are treated as errors.
     ///
     /// # Arguments
     ///
-    /// * `endpoint` - A string reference to the endpoint URL from which to fetch the blocklist.
+    /// * `endpoint` - A string reference to the endpoint URL from which to fetch the blocklist, possibly with placeholders.
+    /// * `cache` - The element cache of the blocklist providing the `ETag`s.
+    /// * `policy` - Restricts the scheme, redirects, and timeouts of the source.
+    /// * `format` - The format of the blocklist.
+    /// * `splitter` - Separates the entries of a text blocklist.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing an optional `Vec<String>` with blocklist entries, or an `AppError`
+    /// Returns a `Result` containing the `Fetched` blocklist, or an `AppError`
     /// if the request or parsing fails.
     /// # Errors
     /// Will return `AppError` when fetching blocklist fails
-    async fn fetch_blocklist(&self, endpoint: &str) -> Result<Option<Vec<String>>, AppError> {
-        let client = reqwest::Client::builder().timeout(self.timeout).build()?;
+    async fn fetch_blocklist(
+        &self,
+        endpoint: &str,
+        cache: &ElementCache,
+        policy: &FetchPolicy,
+        format: FeedFormat,
+        splitter: &Splitter,
+    ) -> Result<Fetched, AppError> {
+        for observer in &self.observers {
+            observer.on_fetch_start(endpoint);
+        }
+        if GitSource::is_git(endpoint) {
+            return self
+                .fetch_git(endpoint, cache, policy, format, splitter)
+                .await;
+        }
+
+        let sources = self.sources();
+        let client = policy
+            .apply(
+                reqwest::Client::builder(),
+                sources.iter().map(String::as_str),
+            )
+            .build()?;
 
-        let mut req = client.get(endpoint);
+        let url = expand_url(&self.environment, endpoint, OffsetDateTime::now_utc())?;
+        // Placeholders may expand to secrets, e.g., API keys, which must not end up in logs and alerts.
+        let templated = url != endpoint;
+        let scrub = |e: reqwest::Error| if templated { e.without_url() } else { e };
+        let mut req = client.get(&url);
 
         if let Some(headers) = &self.headers {
             for (k, v) in headers {
                 req = req.header(k, v);
             }
         }
+        // With a consensus, the entries are scored against the feeds on every update,
+        // and listed hostnames are resolved again on every update.
+        if let Some(etag) = cache.etag()
+            && self.consensus.is_none()
+            && !self.resolves_domains()
+        {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(name) = &self.replica_name {
+            req = req.header(REPLICA_HEADER, name);
+            if let Some(applied) = cache.applied_etag() {
+                req = req.header(APPLIED_HEADER, applied);
+            }
+        }
 
-        let body = req.send().await?.text().await?;
-
-        let blocklist = parse_from_string(Some(body.trim()).as_ref(), self.split_string.as_deref());
+        // Sources parsing the same URL differently must not share the feed.
+        let parsing = format!(
+            "{format:?} {splitter:?} {:?} {:?}",
+            self.tor_ports, self.cloud_filter
+        );
+        let mut shared = match &self.shared_fetches {
+            Some(shared) => Some(shared.lock(&url, self.headers.as_ref(), &parsing).await),
+            None => None,
+        };
+        if let Some((feed, etag)) = shared.as_ref().and_then(SharedFetch::fresh) {
+            info!("reusing the feed of {endpoint} fetched by another source or profile");
+            for observer in &self.observers {
+                observer.on_fetched(endpoint, Some(feed.len()));
+            }
+            return Ok(Fetched::Modified(feed, etag));
+        }
+        let reused = self.http_cache.as_ref().and_then(|http_cache| {
+            let response = http_cache.fresh(&url, self.headers.as_ref(), SystemTime::now())?;
+            info!("reusing the cached response of {endpoint}, which is still fresh");
+            Some(response)
+        });
+        let (body, etag) = match reused {
+            Some(response) => response,
+            None => {
+                let response = req.send().await.map_err(scrub)?;
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    info!("blocklist not modified: {endpoint}");
+                    for observer in &self.observers {
+                        observer.on_fetched(endpoint, None);
+                    }
+                    return Ok(Fetched::NotModified);
+                }
+                if matches!(
+                    response.status(),
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                ) && let Some(until) = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| parse_retry_after(v, SystemTime::now()))
+                {
+                    return Ok(Fetched::Deferred(until));
+                }
+                // Non-success responses (e.g., error pages) must not be parsed as a blocklist.
+                let response = response.error_for_status().map_err(scrub)?;
+                if let Some(modified) = response
+                    .headers()
+                    .get(LAST_MODIFIED)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_http_date)
+                {
+                    self.last_modified
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(endpoint.to_string(), modified);
+                }
+                let etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(ToString::to_string);
+                let response_headers = response.headers().clone();
+                let body = if policy.rate_limit.is_some() || self.memory_limit.is_some() {
+                    self.read_body(response, policy.rate_limit, endpoint, scrub)
+                        .await?
+                } else {
+                    response.text().await.map_err(scrub)?
+                };
+                if let Some(http_cache) = &self.http_cache
+                    && let Some(freshness) = http_cache.store(
+                        &url,
+                        self.headers.as_ref(),
+                        &response_headers,
+                        &body,
+                        etag.as_deref(),
+                        SystemTime::now(),
+                    )
+                {
+                    debug!("caching the response of {endpoint} for {freshness:?}");
+                }
+                (body, etag)
+            }
+        };
+        let feed = Arc::new(self.parse_feed(endpoint, body, format, splitter)?);
+        if let Some(shared) = &mut shared {
+            shared.store(feed.clone(), etag.as_deref());
+        }
+        drop(shared);
 
         info!("blocklist fetched from: {endpoint}");
-        Ok(blocklist)
+        for observer in &self.observers {
+            observer.on_fetched(endpoint, Some(feed.len()));
+        }
+        Ok(Fetched::Modified(feed, etag))
+    }
+
+    /// Parses a fetched blocklist, applying `NFTBLOCKD_TOR_PORTS` to the Tor exits
+    /// and the cloud filter to the ranges of a cloud provider.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the blocklist cannot be parsed or the parsed entries, which take about
+    /// as much memory as `body`, would exceed the memory limit.
+    fn parse_feed(
+        &self,
+        source: &str,
+        body: String,
+        format: FeedFormat,
+        splitter: &Splitter,
+    ) -> Result<Feed, AppError> {
+        if let Some(limit) = &self.memory_limit {
+            let reserved = u64::try_from(body.len()).unwrap_or(u64::MAX);
+            limit.check_reserve(reserved, &format!("parsing {source}"))?;
+        }
+        match format {
+            FeedFormat::Tor => return Ok(Feed::parse_tor(&body, self.tor_ports.as_deref())),
+            FeedFormat::Cloud(provider) => {
+                return Feed::parse_cloud(&body, provider, &self.cloud_filter);
+            }
+            _ => {}
+        }
+        Feed::parse_split(body, format, splitter, SystemTime::now())
+    }
+
+    /// Reads a blocklist from a git repository, using the hash of the checked-out commit as its `ETag`.
+    async fn fetch_git(
+        &self,
+        endpoint: &str,
+        cache: &ElementCache,
+        policy: &FetchPolicy,
+        format: FeedFormat,
+        splitter: &Splitter,
+    ) -> Result<Fetched, AppError> {
+        let source = GitSource::parse(endpoint)?;
+        let dir = self
+            .git_dir
+            .as_deref()
+            .ok_or(AppError::ConfigError(vec![format!(
+                "no git directory configured for {endpoint}"
+            )]))?;
+        let (body, commit) = source.fetch(dir, policy.deadline).await?;
+        if cache.etag().as_ref() == Some(&commit)
+            && self.consensus.is_none()
+            && !self.resolves_domains()
+        {
+            info!("blocklist not modified: {endpoint} is still at commit {commit}");
+            for observer in &self.observers {
+                observer.on_fetched(endpoint, None);
+            }
+            return Ok(Fetched::NotModified);
+        }
+        let feed = self.parse_feed(endpoint, body, format, splitter)?;
+        info!("blocklist fetched from: {endpoint} at commit {commit}");
+        for observer in &self.observers {
+            observer.on_fetched(endpoint, Some(feed.len()));
+        }
+        Ok(Fetched::Modified(Arc::new(feed), Some(commit)))
+    }
+
+    /// Fetches a blocklist and transforms it into nftables expressions, reusing the cached ones when possible.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The endpoint URL of the blocklist.
+    /// * `cache` - The element cache of the blocklist.
+    /// * `policy` - Restricts the scheme, redirects, and timeouts of the source.
+    /// * `proto` - The family of the blocklist.
+    ///
+    /// # Errors
+    /// Will return `AppError` when fetching or parsing the blocklist fails
+    async fn update_family(
+        &self,
+        url: &str,
+        cache: &ElementCache,
+        policy: &FetchPolicy,
+        proto: &RuleProto,
+        timings: &mut PhaseTimings,
+    ) -> Result<SharedSetElements, AppError> {
+        let subnet_list: fn(Vec<String>) -> SubnetList = match proto {
+            RuleProto::Ip6 => SubnetList::IPv6,
+            _ => SubnetList::IPv4,
+        };
+        if self.deferred().contains_key(url) {
+            info!(
+                "fetch of {url} is deferred by its Retry-After or freshness; reusing the last elements"
+            );
+            return cache.cached().ok_or(AppError::RequestError(
+                format!("{url} is deferred by its Retry-After or freshness, but nothing is cached"),
+                None,
+            ));
+        }
+        let (format, splitter) = match proto {
+            RuleProto::Ip6 => (self.ipv6_format, &self.ipv6_splitter),
+            _ => (self.ipv4_format, &self.ipv4_splitter),
+        };
+        let resolve_domains = match proto {
+            RuleProto::Ip6 => self.ipv6_resolve_domains,
+            _ => self.ipv4_resolve_domains,
+        };
+        let started = Instant::now();
+        let mut fetched = self
+            .fetch_blocklist(url, cache, policy, format, splitter)
+            .await;
+        // Extracted entries of the other family belong to the other set rather than being invalid.
+        if format.mixes_families()
+            && let Ok(Fetched::Modified(feed, _)) = &mut fetched
+        {
+            Arc::make_mut(feed).retain_family(matches!(proto, RuleProto::Ip6));
+        }
+        if resolve_domains
+            && let Some(resolver) = &self.resolver
+            && let Ok(Fetched::Modified(feed, _)) = &mut fetched
+            && !feed.is_empty()
+        {
+            let entries = feed.entries().map(ToString::to_string).collect();
+            let resolved = resolver
+                .resolve(entries, matches!(proto, RuleProto::Ip6))
+                .await;
+            Arc::make_mut(feed).set_entries(resolved);
+        }
+        if let Some(verifier) = &self.route_verifier
+            && let Ok(Fetched::Modified(feed, _)) = &mut fetched
+            && !feed.is_empty()
+        {
+            let entries = feed.entries().map(ToString::to_string).collect();
+            let verified = verifier.verify(entries).await;
+            self.record_unrouted(url, verifier.action, &verified.unrouted);
+            Arc::make_mut(feed).set_entries(verified.entries);
+        }
+        timings.fetch += started.elapsed();
+        match fetched? {
+            Fetched::Deferred(until) => {
+                let seconds = until
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default()
+                    .as_secs();
+                warn!("{url} asked to retry after {seconds} s; deferring only this source");
+                self.deferred
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(url.to_string(), until);
+                cache.cached().ok_or(AppError::RequestError(
+                    format!("{url} asked to retry after {seconds} s, but nothing is cached"),
+                    None,
+                ))
+            }
+            Fetched::NotModified => {
+                let elements = cache.cached().ok_or(AppError::RequestError(
+                    format!("{url} answered 304 Not Modified, but nothing is cached"),
+                    None,
+                ))?;
+                self.mark_refreshed(url);
+                Ok(elements)
+            }
+            Fetched::Modified(feed, etag) if !feed.is_empty() => {
+                // Nothing newer is published before then, e.g., the next Tor consensus.
+                if let Some(fresh_until) = feed.fresh_until.filter(|t| *t > SystemTime::now()) {
+                    self.deferred
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(url.to_string(), fresh_until);
+                }
+                *self
+                    .expiry(proto)
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = feed.expiry.clone();
+                *self
+                    .comments(proto)
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = feed.comments.clone();
+                // The entries are borrowed from the fetched body for validation, scoring, and attribution.
+                let listed = feed.entries().collect::<Vec<_>>();
+                self.record_changes(url, &listed);
+                if let Some(trace) = &self.entry_trace {
+                    trace.listed(url, &listed);
+                }
+                self.check_memory(&format!("parsing {url}"))?;
+                let started = Instant::now();
+                // Without a consensus, nothing is scored and the broadest entries are kept.
+                let scores = match &self.consensus {
+                    Some(consensus)
+                        if self.max_set_size.is_some()
+                            && self.overflow_policy == OverflowPolicy::PreferScored =>
+                    {
+                        consensus.scores(
+                            &listed,
+                            &self
+                                .consensus_lists
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner),
+                            matches!(proto, RuleProto::Ip6),
+                        )
+                    }
+                    _ => BTreeMap::new(),
+                };
+                let blocked = match &self.consensus {
+                    Some(consensus) => {
+                        let (blocked, below) = consensus.split(
+                            &listed,
+                            &self
+                                .consensus_lists
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner),
+                            matches!(proto, RuleProto::Ip6),
+                        );
+                        if let Some(trace) = &self.entry_trace {
+                            trace.left_out(url, &below, "below the consensus threshold");
+                        }
+                        let monitored = if consensus.monitor && !below.is_empty() {
+                            monitor_elements(subnet_list(below))
+                        } else {
+                            Arc::new(None)
+                        };
+                        *self
+                            .monitor(proto)
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner) = monitored;
+                        Some(blocked)
+                    }
+                    None => None,
+                };
+                let blocked = blocked
+                    .as_ref()
+                    .map(|blocked| blocked.iter().map(String::as_str).collect::<Vec<_>>());
+                let blocklist = blocked.as_deref().unwrap_or(&listed);
+                let comment = self
+                    .element_comments
+                    .then(|| provenance(url, OffsetDateTime::now_utc()));
+                let strictness = match proto {
+                    RuleProto::Ip6 => self.ipv6_strictness,
+                    _ => self.ipv4_strictness,
+                };
+                // The comment carries the fetch time, so commented elements are only reused for a `304 Not Modified`.
+                let context = (&comment, strictness, &scores);
+                let elements = cache.get_or_generate(blocklist, &context, |list| {
+                    self.scheduling.run(|| {
+                        let mut rejected = Rejected::new(self.invalid_entries_max_samples);
+                        let validated = ValidatedSubnetList::validate_entries(
+                            list.iter().copied(),
+                            matches!(proto, RuleProto::Ip6),
+                            strictness,
+                            &mut rejected,
+                        );
+                        *self
+                            .rejected(proto)
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner) = rejected;
+                        let validated = validated?;
+                        self.check_memory(&format!("validating {url}"))?;
+                        let deduplicated = match self.max_set_size {
+                            Some(max) => {
+                                let prioritizer = self.overflow_policy.prioritizer(scores.clone());
+                                let (deduplicated, dropped) =
+                                    validated.deduplicate_prioritized(max, prioritizer.as_deref())?;
+                                if dropped > 0 {
+                                    warn!(
+                                        "left out {dropped} elements of {url} over the maximum set size of {max}"
+                                    );
+                                }
+                                deduplicated
+                            }
+                            None => validated.deduplicate()?,
+                        };
+                        self.check_memory(&format!("deduplicating {url}"))?;
+                        let elements = deduplicated.into_prefix_set().map(|set| match &comment {
+                            Some(comment) => set.with_comment(comment),
+                            None => set,
+                        });
+                        Ok(elements.map(PrefixSet::into_elements))
+                    })
+                });
+                let elements = elements?;
+                if let Some(trace) = &self.entry_trace {
+                    trace.elements(
+                        &format!("the validated and deduplicated list of {url}"),
+                        &elements,
+                    );
+                }
+                // Attributed after deduplication, so that a network covering the entries of other
+                // sources keeps all of them.
+                let feeds = self
+                    .consensus_lists
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                let feed_entries = feeds
+                    .iter()
+                    .filter(|_| self.consensus.is_some())
+                    .map(|(feed, entries)| {
+                        let entries = entries.iter().map(String::as_str).collect::<Vec<_>>();
+                        (feed.as_str(), entries)
+                    })
+                    .collect::<Vec<_>>();
+                let sources = std::iter::once((url, listed.as_slice()))
+                    .chain(
+                        feed_entries
+                            .iter()
+                            .map(|(feed, entries)| (*feed, entries.as_slice())),
+                    )
+                    .collect::<Vec<_>>();
+                *self
+                    .attribution_of(proto)
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = elements
+                    .as_ref()
+                    .as_ref()
+                    .map(|elements| attribute(elements, &sources))
+                    .unwrap_or_default();
+                drop(feeds);
+                timings.parse += started.elapsed();
+                cache.set_etag(etag);
+                self.mark_refreshed(url);
+                Ok(elements)
+            }
+            Fetched::Modified(..) => {
+                warn!("empty blocklist fetched from: {url}");
+                *self
+                    .rejected(proto)
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Rejected::default();
+                self.attribution_of(proto)
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clear();
+                self.mark_refreshed(url);
+                Ok(Arc::new(None))
+            }
+        }
+    }
+
+    /// Samples the memory of the update and aborts it when the process uses more memory than `memory_limit`
+    /// while `stage` runs.
+    fn check_memory(&self, stage: &str) -> Result<(), AppError> {
+        let resident = self.peak_sampler.sample();
+        match (&self.memory_limit, resident) {
+            (Some(limit), Some(resident)) => limit.check_usage(resident, stage),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the body of `response`, at no more than `rate_limit` bytes per second when set.
+    ///
+    /// The body counts against `memory_limit` while it is downloaded: the download is aborted as soon as
+    /// the body alone exceeds the limit, and the memory of the process is checked after every MiB.
+    ///
+    /// # Errors
+    /// Will return `AppError` when reading the body fails or the memory limit is exceeded.
+    async fn read_body(
+        &self,
+        mut response: Response,
+        rate_limit: Option<u64>,
+        endpoint: &str,
+        scrub: impl Fn(reqwest::Error) -> reqwest::Error,
+    ) -> Result<String, AppError> {
+        const CHECK_EVERY: usize = 1 << 20;
+        let stage = format!("downloading {endpoint}");
+        let started = Instant::now();
+        let mut body = Vec::new();
+        let mut checked = 0;
+        while let Some(chunk) = response.chunk().await.map_err(&scrub)? {
+            body.extend_from_slice(&chunk);
+            let received = u64::try_from(body.len()).unwrap_or(u64::MAX);
+            if let Some(limit) = &self.memory_limit {
+                limit.check_usage(received, &stage)?;
+            }
+            if body.len() - checked >= CHECK_EVERY {
+                checked = body.len();
+                self.check_memory(&stage)?;
+            }
+            if let Some(rate_limit) = rate_limit {
+                let expected = Duration::from_millis(received.saturating_mul(1000) / rate_limit);
+                if let Some(ahead) = expected.checked_sub(started.elapsed()) {
+                    tokio::time::sleep(ahead).await;
+                }
+            }
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
     }
 
     /// Updates the IPv4 blocklist and transforms it into nftables expressions.
     ///
     /// This function fetches the IPv4 blocklist using the `ipv4_endpoint`. If a blocklist is
     /// successfully retrieved, it is validated, deduplicated, and transformed into nftables-compatible
-    /// expressions.
+    /// expressions. If the blocklist did not change since the last cycle, the cached expressions are reused.
     ///
     /// # Returns
     ///
@@ -115,28 +1743,28 @@ impl BlockList {
     /// or an `AppError` if any step during the process fails.
     /// # Errors
     /// Will return `AppError` when parsing subnets fails
-    async fn update_ipv4<'a>(&self) -> Result<Option<SetElements<'a>>, AppError> {
+    async fn update_ipv4(&self, timings: &mut PhaseTimings) -> Result<SharedSetElements, AppError> {
         let Some(url) = self.ipv4_endpoint.as_deref() else {
-            return Ok(None);
+            return Ok(Arc::new(None));
         };
-        if let Some(blocklist_ipv4) = self.fetch_blocklist(url).await? {
-            let elems = SubnetList::IPv4(blocklist_ipv4)
-                .validate_blocklist(false)?
-                .deduplicate()?
-                .transform_to_nft_expressions()
-                .get_elements();
-            Ok(elems)
-        } else {
-            warn!("empty IPv4 blocklist fetched from: {url}");
-            Ok(None)
-        }
+        let elements = self
+            .update_family(
+                url,
+                &self.ipv4_cache,
+                &self.ipv4_policy,
+                &RuleProto::Ip,
+                timings,
+            )
+            .await
+            .inspect_err(|e| self.source_failed(url, e))?;
+        Ok(self.expire(&RuleProto::Ip, elements))
     }
 
     /// Updates the IPv6 blocklist and transforms it into nftables expressions.
     ///
     /// This function fetches the IPv6 blocklist using the `ipv6_endpoint`. If a blocklist is
     /// successfully retrieved, it is validated, deduplicated, and transformed into nftables-compatible
-    /// expressions.
+    /// expressions. If the blocklist did not change since the last cycle, the cached expressions are reused.
     ///
     /// # Returns
     ///
@@ -144,21 +1772,88 @@ impl BlockList {
     /// or an `AppError` if any step during the process fails.
     /// # Errors
     /// Will return `AppError` when parsing subnets fails
-    async fn update_ipv6<'a>(&self) -> Result<Option<SetElements<'a>>, AppError> {
+    async fn update_ipv6(&self, timings: &mut PhaseTimings) -> Result<SharedSetElements, AppError> {
         let Some(url) = self.ipv6_endpoint.as_deref() else {
-            return Ok(None);
+            return Ok(Arc::new(None));
         };
-        if let Some(blocklist_ipv6) = self.fetch_blocklist(url).await? {
-            let elems = SubnetList::IPv6(blocklist_ipv6)
-                .validate_blocklist(false)?
-                .deduplicate()?
-                .transform_to_nft_expressions()
-                .get_elements();
-            Ok(elems)
-        } else {
-            warn!("empty IPv6 blocklist fetched from: {url}");
-            Ok(None)
+        let elements = self
+            .update_family(
+                url,
+                &self.ipv6_cache,
+                &self.ipv6_policy,
+                &RuleProto::Ip6,
+                timings,
+            )
+            .await
+            .inspect_err(|e| self.source_failed(url, e))?;
+        Ok(self.expire(&RuleProto::Ip6, elements))
+    }
+
+    /// Fetches both blocklists and transforms them into set elements without applying them.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the IPv4 and IPv6 set elements, or an `AppError` if fetching or parsing fails.
+    /// # Errors
+    /// Will return `AppError` when fetching or parsing a blocklist fails
+    pub async fn fetch_elements(&self) -> Result<(SharedSetElements, SharedSetElements), AppError> {
+        self.fetch_elements_timed(&mut PhaseTimings::default())
+            .await
+    }
+
+    async fn fetch_elements_timed(
+        &self,
+        timings: &mut PhaseTimings,
+    ) -> Result<(SharedSetElements, SharedSetElements), AppError> {
+        Ok((
+            self.update_ipv4(timings).await?,
+            self.update_ipv6(timings).await?,
+        ))
+    }
+
+    /// Fetches both blocklists, tolerating a failing source according to `policy`.
+    ///
+    /// A failing source is reported to the observers; the update fails only when every configured source failed
+    /// or when `policy` is `PerFamily` and the family of the failing source has no previous elements.
+    ///
+    /// # Errors
+    /// Will return `AppError` when no source could be fetched or a failing family cannot be kept.
+    async fn fetch_elements_partial(
+        &self,
+        policy: ApplyPolicy,
+        timings: &mut PhaseTimings,
+    ) -> Result<(SharedSetElements, SharedSetElements), AppError> {
+        let ipv4 = self.update_ipv4(timings).await;
+        let ipv6 = self.update_ipv6(timings).await;
+        // A family without a source yields no elements, which must not count as a successful fetch.
+        let fetched = |result: &Result<SharedSetElements, AppError>, endpoint: &Option<String>| {
+            endpoint.is_some() && result.is_ok()
+        };
+        if !fetched(&ipv4, &self.ipv4_endpoint)
+            && !fetched(&ipv6, &self.ipv6_endpoint)
+            && let Err(e) = ipv4.as_ref().and(ipv6.as_ref())
+        {
+            return Err(e.clone());
         }
+        let tolerate = |fetched: Result<SharedSetElements, AppError>,
+                        cache: &ElementCache,
+                        proto: &RuleProto| {
+            fetched.or_else(|e| {
+                warn!("applying the other blocklist despite a failed source: {e}");
+                self.notify_error(&e);
+                match policy {
+                    ApplyPolicy::PerFamily => cache
+                        .cached()
+                        .map(|elements| self.expire(proto, elements))
+                        .ok_or(e),
+                    _ => Ok(Arc::new(None)),
+                }
+            })
+        };
+        Ok((
+            tolerate(ipv4, &self.ipv4_cache, &RuleProto::Ip)?,
+            tolerate(ipv6, &self.ipv6_cache, &RuleProto::Ip6)?,
+        ))
     }
 
     /// Applies the updated blocklists to the nftables configuration.
@@ -173,14 +1868,23 @@ impl BlockList {
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating success (`Ok(())`) or an `AppError` if any part of the process fails.
+    /// Returns a `Result` containing the `UpdateReport` of the applied update or an `AppError` if any part of the process fails.
     /// # Errors
     /// Will return `AppError` when updating nftables fails
     pub async fn update(
         &self,
         config: &NftConfig<'_>,
         status: Arc<ServiceStatusStruct>,
-    ) -> Result<(), AppError> {
+    ) -> Result<UpdateReport, AppError> {
+        let started = Instant::now();
+        let peak_reset = memory::reset_peak();
+        if !peak_reset {
+            debug!("the peak memory cannot be reset; it is sampled during the update instead");
+        }
+        self.peak_sampler.reset();
+        for observer in &self.observers {
+            observer.on_cycle_start();
+        }
         info!("Generating stats");
         config.generate_stats(status.stats.clone()).await?;
 
@@ -189,13 +1893,506 @@ impl BlockList {
         }
 
         info!("Pulling and parsing blocklist");
-        let ipv4 = self.update_ipv4().await?;
-        let ipv6 = self.update_ipv6().await?;
+        let mut timings = PhaseTimings::default();
+        let refreshed;
+        let configured = config;
+        let config = if self.anti_lockout_ipv4.is_some() || self.anti_lockout_ipv6.is_some() {
+            let started = Instant::now();
+            let fetched = self.refresh_anti_lockout(config).await;
+            timings.fetch += started.elapsed();
+            refreshed = fetched?;
+            &refreshed
+        } else {
+            config
+        };
+        // Entries of the custom blocklist whose TTL passed are left out from the next update.
+        let unexpired;
+        let config = if config.custom_blocklist_set.expires() {
+            let mut current = config.clone();
+            current.custom_blocklist_set = config.custom_blocklist_set.unexpired(SystemTime::now());
+            unexpired = current;
+            &unexpired
+        } else {
+            config
+        };
+        if let Some(consensus) = &self.consensus {
+            let started = Instant::now();
+            let fetched = self.fetch_consensus_feeds(consensus).await;
+            timings.fetch += started.elapsed();
+            if let Err(e) = fetched {
+                *status.deferred.write().await = self.deferred();
+                return Err(e);
+            }
+        }
+        let fetched = match self.apply_policy {
+            ApplyPolicy::Strict => self.fetch_elements_timed(&mut timings).await,
+            policy => self.fetch_elements_partial(policy, &mut timings).await,
+        };
+        *status.deferred.write().await = self.deferred();
+        *status.refreshed.write().await = self.refreshed();
+        *status.sources.write().await = self.source_stats();
+        let (mut ipv4, mut ipv6) = fetched?;
+        self.check_stale(&mut ipv4, &mut ipv6);
+        self.check_dual_stack(&mut ipv4, &mut ipv6);
+        let checked = (ipv4.clone(), ipv6.clone());
 
+        let (mut monitor_ipv4, mut monitor_ipv6) = self.monitored();
+        // Sources in log mode replace the entries below the consensus threshold of their family.
+        let ipv4_action = self.effective_action(
+            self.ipv4_endpoint.as_deref(),
+            self.ipv4_action,
+            self.ipv4_action_explicit,
+        );
+        let ipv6_action = self.effective_action(
+            self.ipv6_endpoint.as_deref(),
+            self.ipv6_action,
+            self.ipv6_action_explicit,
+        );
+        if ipv4_action == SourceAction::Log {
+            monitor_ipv4 = std::mem::replace(&mut ipv4, Arc::new(None));
+        }
+        if ipv6_action == SourceAction::Log {
+            monitor_ipv6 = std::mem::replace(&mut ipv6, Arc::new(None));
+        }
+        // The ruleset without the fetched elements tells configuration changes from feed updates,
+        // so it leaves out the fetched anti-lockout entries as well.
+        let provisional = self
+            .confirm_timeout
+            .map(|timeout| {
+                Ok::<_, AppError>((
+                    serialize_ruleset(&configured.generate_ruleset(&None, &None))?,
+                    serialize_ruleset(&config.generate_monitored_ruleset(
+                        &ipv4,
+                        &ipv6,
+                        &monitor_ipv4,
+                        &monitor_ipv6,
+                    ))?,
+                    timeout,
+                ))
+            })
+            .transpose()?;
+
+        if let Some(policy) = &self.element_policy {
+            policy.enforce(&ipv4, &ipv6)?;
+        }
+        if let Some(path) = &self.impact_flows {
+            warn_impact(path, config, &ipv4, &ipv6);
+        }
         info!("Applying nftables ruleset");
-        config.apply_nft(&ipv4, &ipv6)?;
+        let apply_started = Instant::now();
+        config.apply_monitored_nft(&ipv4, &ipv6, &monitor_ipv4, &monitor_ipv6)?;
+        if let Some(trace) = &self.entry_trace {
+            for (set_name, elements) in [
+                (&config.blocklist_set_name, &ipv4),
+                (&config.blocklist_set_name, &ipv6),
+                (&config.monitor_set_name, &monitor_ipv4),
+                (&config.monitor_set_name, &monitor_ipv6),
+            ] {
+                trace.elements(&format!("the applied set {set_name}"), elements);
+            }
+        }
+        // The new table starts with empty quarantine sets; restore the addresses still quarantined.
+        if let Some(quarantine) = &status.quarantine
+            && config.quarantine_set_name.is_some()
+        {
+            let active = quarantine
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .active(SystemTime::now());
+            if !active.is_empty() {
+                config.apply_quarantine(&active)?;
+            }
+        }
+        // Likewise for the manual entries, which are kept on disk.
+        if let Some(path) = &self.manual_path
+            && config.manual_set_name.is_some()
+        {
+            let active = ManualSet::load(path)?.active(SystemTime::now());
+            if !active.is_empty() {
+                config.apply_manual(&active)?;
+            }
+        }
+        let apply_duration = apply_started.elapsed();
+        if let Some((key, ruleset, timeout)) = provisional
+            && status
+                .confirmation
+                .write()
+                .await
+                .applied(key, ruleset, timeout)
+                .is_some()
+        {
+            warn!(
+                "the configuration changed; confirm the ruleset with `nftblockdctl confirm` within {} s or it is rolled back",
+                timeout.as_secs()
+            );
+        }
+        // The new ruleset starts with zeroed counters, unless only its sets were refilled.
+        if config.apply_strategy == ApplyStrategy::Replace {
+            *status.live_stats.write().await = Stats::default();
+        }
+        *status.last_applied.write().await = Some(SystemTime::now());
         info!("the `{}` table successfully loaded", config.table_name);
-        Ok(())
+        self.ipv4_cache.mark_applied();
+        self.ipv6_cache.mark_applied();
+        *self
+            .applied_elements
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = checked;
+        let revisions = [
+            (&self.ipv4_endpoint, &self.ipv4_cache),
+            (&self.ipv6_endpoint, &self.ipv6_cache),
+        ]
+        .into_iter()
+        .filter_map(|(endpoint, cache)| {
+            let endpoint = endpoint.as_ref().filter(|e| GitSource::is_git(e))?;
+            Some((endpoint.clone(), cache.applied_etag()?))
+        })
+        .collect::<BTreeMap<_, _>>();
+        for (endpoint, commit) in &revisions {
+            info!("applied {endpoint} at commit {commit}");
+        }
+
+        let report = UpdateReport {
+            table_name: config.table_name.clone(),
+            ipv4_elements: Option::as_ref(&ipv4).map_or(0, Vec::len),
+            ipv6_elements: Option::as_ref(&ipv6).map_or(0, Vec::len),
+            duration: started.elapsed(),
+            fetch_duration: timings.fetch,
+            parse_duration: timings.parse,
+            apply_duration,
+            ipv4_invalid_entries: self
+                .ipv4_rejected
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .count,
+            ipv6_invalid_entries: self
+                .ipv6_rejected
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .count,
+            revisions,
+            peak_memory: if peak_reset {
+                memory::peak_resident()
+            } else {
+                self.peak_sampler.sample();
+                self.peak_sampler.peak()
+            },
+        };
+        if let Some(peak) = report.peak_memory {
+            info!(
+                "the update peaked at {} of memory",
+                memory::format_mib(peak)
+            );
+        }
+        if let Some(path) = &self.ruleset_path
+            && let Err(e) = serialize_ruleset(&config.generate_monitored_ruleset(
+                &ipv4,
+                &ipv6,
+                &monitor_ipv4,
+                &monitor_ipv6,
+            ))
+            .and_then(|ruleset| AppliedRuleset::write(path, &ruleset))
+        {
+            warn!("{e}");
+        }
+        if let Some(path) = &self.invalid_entries_path
+            && let Err(e) = self.write_rejected(path)
+        {
+            warn!(
+                "failed to write the invalid entries to {}: {e}",
+                path.display()
+            );
+        }
+        let sources = [
+            (&self.ipv4_endpoint, RuleProto::Ip, &ipv4),
+            (&self.ipv6_endpoint, RuleProto::Ip6, &ipv6),
+        ];
+        {
+            let mut source_stats = self
+                .source_stats
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            for (endpoint, proto, elements) in &sources {
+                let Some(endpoint) = endpoint else {
+                    continue;
+                };
+                let stats = source_stats.entry(endpoint.clone()).or_default();
+                let count = Option::as_ref(elements).map_or(0, Vec::len);
+                match proto {
+                    RuleProto::Ip6 => stats.ipv6_elements = count,
+                    _ => stats.ipv4_elements = count,
+                }
+            }
+        }
+        *status.sources.write().await = self.source_stats();
+        // A source without elements is reported with an empty set, so observers drop what it listed before.
+        let empty = SetElements::new();
+        for observer in &self.observers {
+            observer.on_applied(&report);
+            for (endpoint, proto, elements) in &sources {
+                if let Some(endpoint) = endpoint {
+                    observer.on_blocked(
+                        endpoint,
+                        proto,
+                        Option::as_ref(elements).unwrap_or(&empty),
+                    );
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Reads whether `source` lists hostnames from `NFTBLOCKD_{source}_RESOLVE_DOMAINS` or `NFTBLOCKD_RESOLVE_DOMAINS`.
+///
+/// # Errors
+/// Will return `AppError` when the value is not a boolean.
+fn resolve_domains_var(environment: &Environment, source: &str) -> Result<bool, AppError> {
+    source_var(environment, source, "RESOLVE_DOMAINS")
+        .map(|value| {
+            value.parse::<bool>().map_err(|e| {
+                AppError::ParseError(format!("invalid NFTBLOCKD_{source}_RESOLVE_DOMAINS: {e}"))
+            })
+        })
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Whether a source is fetched over HTTP rather than read from a file.
+fn is_url(source: &str) -> bool {
+    source.contains("://")
+}
+
+/// Warns about the active flows of `path` whose peers the pending blocklist elements would block.
+fn warn_impact(
+    path: &Path,
+    config: &NftConfig<'_>,
+    ipv4: &SharedSetElements,
+    ipv6: &SharedSetElements,
+) {
+    let flows = match read_flows(path) {
+        Ok(flows) => flows,
+        Err(e) => {
+            warn!("failed to read the flows of {}: {e}", path.display());
+            return;
+        }
+    };
+    let simulation = Simulation::new()
+        .with_set(
+            format!("{}_ipv4", config.blocklist_set_name),
+            true,
+            Option::as_ref(ipv4),
+        )
+        .with_set(
+            format!("{}_ipv6", config.blocklist_set_name),
+            true,
+            Option::as_ref(ipv6),
+        );
+    let affected = affected_flows(&flows, &simulation);
+    if affected.is_empty() {
+        return;
+    }
+    warn!(
+        "the update blocks the peers of {} active flows in {}",
+        affected.len(),
+        path.display()
+    );
+    for (flow, outcome) in affected.iter().take(MAX_LISTED_FLOWS) {
+        warn!("{flow} {outcome}");
+    }
+    if affected.len() > MAX_LISTED_FLOWS {
+        warn!("and {} more flows", affected.len() - MAX_LISTED_FLOWS);
+    }
+}
+
+/// Transforms the entries below the consensus threshold into set elements; invalid entries are skipped.
+fn monitor_elements(entries: SubnetList) -> SharedSetElements {
+    let elements = entries
+        .validate_blocklist(false)
+        .and_then(ValidatedSubnetList::deduplicate)
+        .map(|entries| entries.into_prefix_set().map(PrefixSet::into_elements));
+    match elements {
+        Ok(elements) => Arc::new(elements),
+        Err(e) => {
+            warn!("no entries to monitor: {e}");
+            Arc::new(None)
+        }
+    }
+}
+
+/// Describes where the elements fetched from `url` come from, e.g.,
+/// `source=lists.example.com/ipv4.txt fetched=2025-01-01T12:00:00Z`.
+///
+/// The scheme, credentials, and query are left out, as the query often carries an API key,
+/// and the source is shortened so the comment fits the limit of `nft`.
+#[must_use]
+pub fn provenance(url: &str, fetched: OffsetDateTime) -> String {
+    let source = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let source = source.split(['?', '#']).next().unwrap_or_default();
+    let source = source
+        .split_once('@')
+        .filter(|(userinfo, _)| !userinfo.contains('/'))
+        .map_or(source, |(_, rest)| rest);
+    let fetched = fetched
+        .format(format_description!(
+            "[year]-[month]-[day]T[hour]:[minute]:[second]Z"
+        ))
+        .unwrap_or_default();
+    let max_len = MAX_COMMENT_LEN.saturating_sub("source= fetched=".len() + fetched.len());
+    let mut end = source.len().min(max_len);
+    while !source.is_char_boundary(end) {
+        end -= 1;
+    }
+    let source = &source[..end];
+    format!("source={source} fetched={fetched}")
+}
+
+/// When the blocklist loop updates and retries.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    /// Interval between two regular updates.
+    pub refresh_interval: Duration,
+    /// Maximum random delay added to every regular interval, so a fleet does not fetch at the same second.
+    pub jitter: Duration,
+    /// Maximum random delay before the first update, spreading hosts that start together.
+    pub initial_jitter: Duration,
+    /// Number of failed attempts before the table is flushed; errors that are not retryable pause updates instead.
+    pub retry_count: u64,
+    /// Base interval between two attempts after a failure; randomized between half and double.
+    pub retry_interval: Duration,
+}
+
+impl Schedule {
+    /// Creates a `Schedule` without jitter.
+    #[must_use]
+    pub fn new(refresh_interval: Duration, retry_count: u64, retry_interval: Duration) -> Self {
+        Self {
+            refresh_interval,
+            jitter: Duration::ZERO,
+            initial_jitter: Duration::ZERO,
+            retry_count,
+            retry_interval,
+        }
+    }
+
+    /// Sets the maximum random delay added to every regular interval.
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the maximum random delay before the first update.
+    #[must_use]
+    pub fn with_initial_jitter(mut self, initial_jitter: Duration) -> Self {
+        self.initial_jitter = initial_jitter;
+        self
+    }
+
+    /// Returns the time until the next regular update.
+    #[must_use]
+    pub fn next_interval(&self) -> Duration {
+        self.refresh_interval + random_delay(self.jitter)
+    }
+
+    /// Decides what the blocklist loop does after `attempt` consecutive failed updates ending with `error`.
+    ///
+    /// A fatal error keeps the last applied ruleset, since flushing the table over an error that retrying
+    /// cannot fix would remove all protection. A local fault stops the loop right away, while a fatal error
+    /// caused by fetched data, such as a malformed feed, is tried again at the next regular interval.
+    #[must_use]
+    pub fn on_failure(&self, error: &AppError, attempt: u64) -> FailureAction {
+        if !error.is_retryable() && error.is_local() {
+            FailureAction::Stop
+        } else if !error.is_retryable() {
+            FailureAction::Hold
+        } else if attempt >= self.retry_count {
+            FailureAction::Flush
+        } else {
+            FailureAction::Retry
+        }
+    }
+}
+
+/// What the blocklist loop does after a failed update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureAction {
+    /// Retries the update after the retry interval.
+    Retry,
+    /// Flushes the table after the last attempt and starts counting attempts again.
+    Flush,
+    /// Keeps the last applied ruleset and tries again at the next regular interval.
+    Hold,
+    /// Pauses updates and keeps the last applied ruleset until the daemon is resumed or reloaded.
+    Stop,
+}
+
+/// Returns a random delay between zero and `max`.
+fn random_delay(max: Duration) -> Duration {
+    let ms = u64::try_from(max.as_millis()).unwrap_or(u64::MAX);
+    if ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::rng().random_range(0..=ms))
+}
+
+/// Waits until a provisionally applied ruleset is confirmed, or rolls it back when the deadline passes.
+///
+/// Returns right away when no ruleset is pending or the loop is cancelled.
+///
+/// # Errors
+/// Will return `AppError` describing the rollback when the ruleset was not confirmed in time.
+async fn await_confirmation(
+    status: &ServiceStatusStruct,
+    config: &NftConfig<'_>,
+    cancellation_token: &CancellationToken,
+) -> Result<(), AppError> {
+    loop {
+        let Some(deadline) = status.confirmation.read().await.deadline() else {
+            return Ok(());
+        };
+        let remaining = deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        if remaining.is_zero() {
+            break;
+        }
+        tokio::select! {
+            () = status.confirmed.notified() => {}
+            () = tokio::time::sleep(remaining) => {}
+            () = cancellation_token.cancelled() => return Ok(()),
+        }
+    }
+    let Some(previous) = status.confirmation.write().await.rollback() else {
+        return Ok(());
+    };
+    let restored = serde_json::from_str::<Nftables<'static>>(&previous)
+        .map_err(AppError::from)
+        .and_then(|ruleset| config.applier.apply(&ruleset));
+    match restored {
+        Ok(()) => Err(AppError::NftblockdError(
+            "the ruleset was not confirmed in time; rolled back to the last confirmed ruleset and paused updates"
+                .to_string(),
+        )),
+        Err(e) => Err(AppError::NftblockdError(format!(
+            "the ruleset was not confirmed in time and rolling it back failed: {e}; paused updates"
+        ))),
+    }
+}
+
+/// Asks the daemon to pause updates, keeping the table as it is.
+async fn pause_updates(status: &ServiceStatusStruct) {
+    let (respond_to, response) = tokio::sync::oneshot::channel();
+    if status
+        .command_channel
+        .send(Command::Pause {
+            disable_rules: false,
+            respond_to,
+        })
+        .await
+        .is_ok()
+    {
+        let _ = response.await;
     }
 }
 
@@ -203,27 +2400,84 @@ pub async fn blocklist_loop(
     status: Arc<ServiceStatusStruct>,
     blocklist: BlockList,
     config: NftConfig<'_>,
-    refresh_interval: u64,
-    retry_count: u64,
-    retry_interval: u64,
+    schedule: Schedule,
     cancellation_token: CancellationToken,
 ) {
+    let Schedule {
+        retry_count,
+        retry_interval,
+        ..
+    } = schedule;
+    let initial_delay = random_delay(schedule.initial_jitter);
+    if !initial_delay.is_zero() {
+        info!(
+            "delaying the first update by {} ms",
+            initial_delay.as_millis()
+        );
+        tokio::select! {
+            () = tokio::time::sleep(initial_delay) => {}
+            () = cancellation_token.cancelled() => {
+                info!("stopping blocklist loop");
+                return;
+            }
+        }
+    }
     let mut counter = 1;
     loop {
         info!("starting updating nftables blocklist");
         match blocklist.update(&config, status.clone()).await {
-            Ok(()) => {
+            Ok(_) => {
                 info!("finished updating nftables blocklist");
                 *status.status.write().await = NftblockdStatus::Ok;
                 counter = 1;
+                if let Err(e) = await_confirmation(&status, &config, &cancellation_token).await {
+                    error!("{e}");
+                    blocklist.notify_error(&e);
+                    pause_updates(&status).await;
+                    return;
+                }
+                if cancellation_token.is_cancelled() {
+                    info!("stopping blocklist loop");
+                    return;
+                }
             }
             Err(e) => {
                 error!("{e}");
+                blocklist.notify_error(&e);
                 if !matches!(*status.status.read().await, NftblockdStatus::Failed(_)) {
                     *status.status.write().await = NftblockdStatus::PreFail(e.clone());
                 }
+                let action = schedule.on_failure(&e, counter);
+                if action == FailureAction::Stop {
+                    let err = AppError::NftblockdError(format!(
+                        "failed to update nftables blocklist; reason: {e}; the error is not retryable; keeping the last applied ruleset and pausing updates"
+                    ));
+                    error!("{err}");
+                    blocklist.notify_updates_stopped(&err);
+                    pause_updates(&status).await;
+                    *status.status.write().await = NftblockdStatus::Failed(err);
+                    return;
+                }
+                if action == FailureAction::Hold {
+                    let err = AppError::NftblockdError(format!(
+                        "failed to update nftables blocklist; reason: {e}; the error is not retryable; keeping the last applied ruleset until the next interval"
+                    ));
+                    error!("{err}");
+                    *status.status.write().await = NftblockdStatus::Failed(err);
+                    counter = 1;
+                    tokio::select! {
+                        () = tokio::time::sleep(schedule.next_interval()) => {}
+                        () = cancellation_token.cancelled() => {
+                            info!("stopping blocklist loop");
+                            return;
+                        }
+                    }
+                    continue;
+                }
 
-                let ms = retry_interval * 1000;
+                let ms = u64::try_from(retry_interval.as_millis())
+                    .unwrap_or(u64::MAX)
+                    .max(1);
                 let sleep_interval = rand::rng().random_range(ms / 2..ms * 2);
                 tokio::select! {
                     () = tokio::time::sleep(Duration::from_millis(sleep_interval)) => {}
@@ -235,11 +2489,12 @@ pub async fn blocklist_loop(
                 warn!(
                     "paused for {sleep_interval} ms; retrying; attempt {counter} out of {retry_count}"
                 );
-                if counter >= retry_count {
+                if action == FailureAction::Flush {
                     let err = AppError::NftblockdError(format!(
                         "failed to update nftables blocklist after {retry_count} retries; reason: {e}; FLUSHING TABLE!"
                     ));
                     error!("{err}");
+                    blocklist.notify_retries_exhausted(&err);
                     *status.status.write().await = NftblockdStatus::Failed(err);
                     counter = 1;
                     flush_table(&config);
@@ -249,7 +2504,7 @@ pub async fn blocklist_loop(
             }
         }
         tokio::select! {
-            () = tokio::time::sleep(Duration::from_secs(refresh_interval)) => {}
+            () = tokio::time::sleep(schedule.next_interval()) => {}
             () = cancellation_token.cancelled() => {
                 info!("stopping blocklist loop");
                 return;
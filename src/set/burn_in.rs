@@ -0,0 +1,131 @@
+use crate::error::{AppError, ErrorSource};
+use crate::settings::Environment;
+use crate::utils::duration::parse_duration;
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Runs the sources appearing in the configuration for the first time in log mode for a burn-in period.
+///
+/// A typo in a feed URL, or a feed listing far more than expected, then only shows in the monitor counters
+/// instead of cutting off traffic. The time every source was first configured is persisted, so that a restart
+/// neither starts the period over nor treats the known sources as new.
+#[derive(Debug)]
+pub struct BurnIn {
+    /// How long a new source is only observed.
+    pub period: Duration,
+    /// File the first appearance of every source is persisted to; only kept in memory when `None`.
+    path: Option<PathBuf>,
+    /// Unix timestamp every source was first configured at.
+    first_seen: Mutex<BTreeMap<String, u64>>,
+}
+
+impl BurnIn {
+    /// Creates a `BurnIn` observing new sources for `period` and persisting their first appearance to `path`.
+    #[must_use]
+    pub fn new(period: Duration, path: Option<PathBuf>) -> Self {
+        let first_seen = path.as_deref().map(load).unwrap_or_default();
+        Self {
+            period,
+            path,
+            first_seen: Mutex::new(first_seen),
+        }
+    }
+
+    /// Reads the burn-in period from `NFTBLOCKD_BURN_IN`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when the variable is unset or zero, i.e., new sources are enforced right away.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the period is not a valid duration.
+    pub fn from_env(
+        environment: &Environment,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Self>, AppError> {
+        let period = environment
+            .var("NFTBLOCKD_BURN_IN")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|period| parse_duration(&period))
+            .transpose()?
+            .filter(|period| !period.is_zero());
+        Ok(period.map(|period| Self::new(period, path)))
+    }
+
+    /// Returns how long `source` is still observed at `now`, recording it as first configured at `now`
+    /// if it is new.
+    ///
+    /// # Returns
+    ///
+    /// `None` when the burn-in period of the source is over.
+    pub fn remaining(&self, source: &str, now: SystemTime) -> Option<Duration> {
+        let now = unix_time(now);
+        let (first_seen, new) = {
+            let mut sources = self
+                .first_seen
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            match sources.get(source) {
+                Some(first_seen) => (*first_seen, false),
+                None => {
+                    sources.insert(source.to_string(), now);
+                    (now, true)
+                }
+            }
+        };
+        if new
+            && let Some(path) = &self.path
+            && let Err(e) = self.save(path)
+        {
+            warn!("{e}");
+        }
+        let elapsed = Duration::from_secs(now.saturating_sub(first_seen));
+        self.period
+            .checked_sub(elapsed)
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Persists the first appearance of every source to `path`.
+    fn save(&self, path: &Path) -> Result<(), AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!("failed to write the burn-in state: {}", path.display()),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        let data = serde_json::to_string(
+            &*self
+                .first_seen
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(file_error)?;
+        fs::rename(&tmp, path).map_err(file_error)
+    }
+}
+
+/// Reads the persisted first appearances, starting over when the file is missing or corrupt.
+fn load(path: &Path) -> BTreeMap<String, u64> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("ignoring the invalid burn-in state {}: {e}", path.display());
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
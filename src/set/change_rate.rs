@@ -0,0 +1,143 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+
+/// Changes recorded for a source before its change rate is judged.
+const MIN_SAMPLES: usize = 3;
+
+/// An update of a source that added or removed far more entries than its recent updates did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeAnomaly {
+    pub source: String,
+    /// Entries listed by the source now, but not by its previous update.
+    pub added: usize,
+    /// Entries listed by the previous update of the source, but not anymore.
+    pub removed: usize,
+    /// Average number of changed entries of the recorded updates.
+    pub average: f64,
+    /// How many times the average the update changed.
+    pub factor: f64,
+}
+
+impl ChangeAnomaly {
+    /// Number of added and removed entries.
+    #[must_use]
+    pub fn changes(&self) -> usize {
+        self.added + self.removed
+    }
+}
+
+impl Display for ChangeAnomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added {} and removed {} entries, {:.1} times its rolling average of {:.1} changes",
+            self.source, self.added, self.removed, self.factor, self.average
+        )
+    }
+}
+
+/// Tracks how many entries every source adds and removes per update and flags updates deviating from
+/// the rolling average, which usually means a corrupted feed or an incident at its provider.
+#[derive(Debug, Clone)]
+pub struct ChangeRate {
+    /// How many times the rolling average an update must change to be anomalous.
+    pub factor: f64,
+    /// Number of updates the rolling average is taken over.
+    pub window: usize,
+    /// Fewest changed entries reported as an anomaly, so that quiet sources do not alert on every blip.
+    pub min_changes: usize,
+    previous: HashMap<String, HashSet<String>>,
+    history: HashMap<String, VecDeque<usize>>,
+}
+
+impl ChangeRate {
+    #[must_use]
+    pub fn new(factor: f64, window: usize, min_changes: usize) -> Self {
+        Self {
+            factor,
+            window: window.max(1),
+            min_changes,
+            previous: HashMap::new(),
+            history: HashMap::new(),
+        }
+    }
+
+    /// Reads the settings from `NFTBLOCKD_CHANGE_RATE_*`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when `NFTBLOCKD_CHANGE_RATE_FACTOR` is not set, i.e., the detection is disabled.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a setting cannot be parsed or the factor is not above 1.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let Some(factor) = environment
+            .var("NFTBLOCKD_CHANGE_RATE_FACTOR")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        let factor = factor
+            .parse::<f64>()
+            .ok()
+            .filter(|factor| *factor > 1.0)
+            .ok_or_else(|| {
+                AppError::ParseError(format!(
+                    "invalid NFTBLOCKD_CHANGE_RATE_FACTOR: {factor}; expected a number above 1"
+                ))
+            })?;
+        Ok(Some(Self::new(
+            factor,
+            environment
+                .var("NFTBLOCKD_CHANGE_RATE_WINDOW")
+                .unwrap_or("10".to_string())
+                .parse::<usize>()?,
+            environment
+                .var("NFTBLOCKD_CHANGE_RATE_MIN")
+                .unwrap_or("100".to_string())
+                .parse::<usize>()?,
+        )))
+    }
+
+    /// Records the entries of a fresh fetch of `source` and compares its changes with the rolling average.
+    ///
+    /// The first fetch of a source only sets its baseline, and nothing is judged before a few updates
+    /// have been recorded.
+    pub fn record(&mut self, source: &str, entries: &[impl AsRef<str>]) -> Option<ChangeAnomaly> {
+        let current = entries
+            .iter()
+            .map(|entry| entry.as_ref().to_string())
+            .collect::<HashSet<_>>();
+        let previous = self.previous.insert(source.to_string(), current)?;
+        let current = &self.previous[source];
+        let added = current.difference(&previous).count();
+        let removed = previous.difference(current).count();
+
+        let history = self.history.entry(source.to_string()).or_default();
+        let anomaly = (history.len() >= MIN_SAMPLES.min(self.window))
+            .then(|| {
+                #[allow(clippy::cast_precision_loss)]
+                let average = history.iter().sum::<usize>() as f64 / history.len() as f64;
+                #[allow(clippy::cast_precision_loss)]
+                let factor = (added + removed) as f64 / average.max(1.0);
+                ChangeAnomaly {
+                    source: source.to_string(),
+                    added,
+                    removed,
+                    average,
+                    factor,
+                }
+            })
+            .filter(|anomaly| {
+                anomaly.changes() >= self.min_changes && anomaly.factor > self.factor
+            });
+        history.push_back(added + removed);
+        if history.len() > self.window {
+            history.pop_front();
+        }
+        anomaly
+    }
+}
@@ -0,0 +1,198 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// Cloud provider publishing its IP ranges as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudProvider {
+    /// `https://ip-ranges.amazonaws.com/ip-ranges.json`
+    Aws,
+    /// `https://www.gstatic.com/ipranges/cloud.json`
+    Gcp,
+    /// The weekly `ServiceTags_Public_*.json` of the Microsoft download center.
+    Azure,
+    /// `https://api.cloudflare.com/client/v4/ips`
+    Cloudflare,
+}
+
+/// Services and regions the ranges of a cloud provider are restricted to.
+///
+/// Patterns are compared case-insensitively, and a trailing `*` matches any suffix, e.g., `eu-*`.
+/// An empty list matches everything. Cloudflare publishes neither services nor regions, so its ranges are not filtered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloudFilter {
+    /// AWS `service`, GCP `service`, or Azure `systemService` or service tag, e.g., `EC2` or `AzureCloud`.
+    pub services: Vec<String>,
+    /// AWS `region`, GCP `scope`, or Azure `region`, e.g., `eu-west-1`, `europe-west1`, or `westeurope`.
+    pub regions: Vec<String>,
+}
+
+impl CloudFilter {
+    /// Reads the filter from `NFTBLOCKD_CLOUD_SERVICES` and `NFTBLOCKD_CLOUD_REGIONS`, both comma-separated.
+    #[must_use]
+    pub fn from_env(environment: &Environment) -> Self {
+        let list = |name: &str| {
+            environment
+                .var(name)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        };
+        Self {
+            services: list("NFTBLOCKD_CLOUD_SERVICES"),
+            regions: list("NFTBLOCKD_CLOUD_REGIONS"),
+        }
+    }
+
+    /// Returns whether a range of `services` in `region` passes the filter.
+    fn allows<'a>(&self, mut services: impl Iterator<Item = &'a str>, region: &str) -> bool {
+        (self.services.is_empty() || services.any(|service| matches(&self.services, service)))
+            && (self.regions.is_empty() || matches(&self.regions, region))
+    }
+}
+
+/// Returns whether `value` matches one of `patterns`, ignoring case.
+fn matches(patterns: &[String], value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.to_ascii_lowercase();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => value.starts_with(prefix),
+            None => pattern == value,
+        }
+    })
+}
+
+/// Parses the IP ranges published by `provider`, keeping those passing `filter`.
+///
+/// # Returns
+///
+/// The IPv4 and IPv6 ranges, without duplicates, in the order they are published.
+///
+/// # Errors
+/// Will return `AppError` when the body is not the JSON of the provider.
+pub fn parse_ranges(
+    body: &str,
+    provider: CloudProvider,
+    filter: &CloudFilter,
+) -> Result<Vec<String>, AppError> {
+    let mut ranges = match provider {
+        CloudProvider::Aws => {
+            let ranges = serde_json::from_str::<AwsRanges>(body)?;
+            ranges
+                .prefixes
+                .into_iter()
+                .chain(ranges.ipv6_prefixes)
+                .filter(|range| filter.allows([range.service.as_str()].into_iter(), &range.region))
+                .filter_map(|range| range.ip_prefix.or(range.ipv6_prefix))
+                .collect::<Vec<_>>()
+        }
+        CloudProvider::Gcp => serde_json::from_str::<GcpRanges>(body)?
+            .prefixes
+            .into_iter()
+            .filter(|range| filter.allows([range.service.as_str()].into_iter(), &range.scope))
+            .filter_map(|range| range.ipv4_prefix.or(range.ipv6_prefix))
+            .collect(),
+        CloudProvider::Azure => serde_json::from_str::<AzureServiceTags>(body)?
+            .values
+            .into_iter()
+            .filter(|tag| {
+                // Service tags are named like `AzureCloud.westeurope`; the name also matches without its region.
+                let tag_service = tag.name.split('.').next().unwrap_or_default();
+                let services = [
+                    tag.name.as_str(),
+                    tag_service,
+                    tag.properties.system_service.as_str(),
+                ];
+                filter.allows(services.into_iter(), &tag.properties.region)
+            })
+            .flat_map(|tag| tag.properties.address_prefixes)
+            .collect(),
+        CloudProvider::Cloudflare => {
+            let result = serde_json::from_str::<CloudflareResponse>(body)?.result;
+            result
+                .ipv4_cidrs
+                .into_iter()
+                .chain(result.ipv6_cidrs)
+                .collect()
+        }
+    };
+    let mut seen = HashSet::new();
+    ranges.retain(|range| seen.insert(range.clone()));
+    Ok(ranges)
+}
+
+#[derive(Deserialize)]
+struct AwsRanges {
+    #[serde(default)]
+    prefixes: Vec<AwsRange>,
+    #[serde(default)]
+    ipv6_prefixes: Vec<AwsRange>,
+}
+
+#[derive(Deserialize)]
+struct AwsRange {
+    ip_prefix: Option<String>,
+    ipv6_prefix: Option<String>,
+    #[serde(default)]
+    region: String,
+    #[serde(default)]
+    service: String,
+}
+
+#[derive(Deserialize)]
+struct GcpRanges {
+    #[serde(default)]
+    prefixes: Vec<GcpRange>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcpRange {
+    ipv4_prefix: Option<String>,
+    ipv6_prefix: Option<String>,
+    #[serde(default)]
+    service: String,
+    #[serde(default)]
+    scope: String,
+}
+
+#[derive(Deserialize)]
+struct AzureServiceTags {
+    #[serde(default)]
+    values: Vec<AzureServiceTag>,
+}
+
+#[derive(Deserialize)]
+struct AzureServiceTag {
+    name: String,
+    properties: AzureProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureProperties {
+    #[serde(default)]
+    region: String,
+    #[serde(default)]
+    system_service: String,
+    #[serde(default)]
+    address_prefixes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CloudflareResponse {
+    result: CloudflareRanges,
+}
+
+#[derive(Deserialize)]
+struct CloudflareRanges {
+    #[serde(default)]
+    ipv4_cidrs: Vec<String>,
+    #[serde(default)]
+    ipv6_cidrs: Vec<String>,
+}
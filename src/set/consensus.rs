@@ -0,0 +1,144 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use ipnetwork::IpNetwork;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+/// Requires an entry to be listed by several feeds before it is blocked.
+///
+/// Every feed, including the primary source of a family, adds its weight to the entries it lists.
+/// Entries whose combined weight reaches the threshold are blocked; the others are left out or,
+/// with `monitor`, only counted and logged.
+#[derive(Debug, Clone)]
+pub struct Consensus {
+    /// Additional feeds by URL, with their weights.
+    pub feeds: BTreeMap<String, u32>,
+    /// Weight of the primary source of each family.
+    pub primary_weight: u32,
+    /// Combined weight an entry needs to be blocked.
+    pub threshold: u32,
+    /// Loads the entries below the threshold into the monitor sets.
+    pub monitor: bool,
+}
+
+impl Consensus {
+    /// Reads the consensus settings from `NFTBLOCKD_CONSENSUS_*`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when no consensus feeds are configured.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a setting cannot be parsed.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let Some(feeds) = environment
+            .var("NFTBLOCKD_CONSENSUS_FEEDS")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        let feeds = serde_json::from_str(&feeds).map_err(|e| {
+            AppError::ParseError(format!(
+                "invalid NFTBLOCKD_CONSENSUS_FEEDS: {e}; expected a JSON object of URLs and weights"
+            ))
+        })?;
+        Ok(Some(Self {
+            feeds,
+            primary_weight: environment
+                .var("NFTBLOCKD_CONSENSUS_PRIMARY_WEIGHT")
+                .unwrap_or("1".to_string())
+                .parse::<u32>()?,
+            threshold: environment
+                .var("NFTBLOCKD_CONSENSUS_THRESHOLD")
+                .unwrap_or("2".to_string())
+                .parse::<u32>()?,
+            monitor: environment
+                .var("NFTBLOCKD_CONSENSUS_MONITOR")
+                .unwrap_or("false".to_string())
+                .parse::<bool>()
+                .map_err(|e| {
+                    AppError::ParseError(format!("invalid NFTBLOCKD_CONSENSUS_MONITOR: {e}"))
+                })?,
+        }))
+    }
+
+    /// Scores the entries of one family by the combined weight of the feeds listing them.
+    ///
+    /// Entries are compared as networks, so `192.0.2.1` and `192.0.2.1/32` count as the same entry,
+    /// and a feed listing an entry more than once adds its weight only once.
+    ///
+    /// # Arguments
+    ///
+    /// * `primary` - The entries of the primary source of the family.
+    /// * `feeds` - The entries of the additional feeds by URL; entries of the other family are ignored.
+    /// * `ipv6` - Whether the family is IPv6.
+    ///
+    /// # Returns
+    ///
+    /// The scores by entry in its canonical `address/prefix` form.
+    #[must_use]
+    pub fn scores(
+        &self,
+        primary: &[impl AsRef<str>],
+        feeds: &BTreeMap<String, Vec<String>>,
+        ipv6: bool,
+    ) -> BTreeMap<String, u32> {
+        let mut scores = BTreeMap::<String, u32>::new();
+        let mut add = |entries: &mut dyn Iterator<Item = &str>, weight: u32| {
+            let entries = entries
+                .filter(|entry| entry.contains(':') == ipv6)
+                .map(normalize)
+                .collect::<BTreeSet<_>>();
+            for entry in entries {
+                let score = scores.entry(entry).or_default();
+                *score = score.saturating_add(weight);
+            }
+        };
+        add(&mut primary.iter().map(AsRef::as_ref), self.primary_weight);
+        for (url, entries) in feeds {
+            add(
+                &mut entries.iter().map(String::as_str),
+                self.feeds.get(url).copied().unwrap_or(1),
+            );
+        }
+        scores
+    }
+
+    /// Splits the entries of one family into those reaching the threshold and those below it,
+    /// scoring them like `scores`.
+    ///
+    /// # Arguments
+    ///
+    /// * `primary` - The entries of the primary source of the family.
+    /// * `feeds` - The entries of the additional feeds by URL; entries of the other family are ignored.
+    /// * `ipv6` - Whether the family is IPv6.
+    ///
+    /// # Returns
+    ///
+    /// The entries to block and the entries below the threshold, both sorted.
+    #[must_use]
+    pub fn split(
+        &self,
+        primary: &[impl AsRef<str>],
+        feeds: &BTreeMap<String, Vec<String>>,
+        ipv6: bool,
+    ) -> (Vec<String>, Vec<String>) {
+        let (blocked, below): (Vec<_>, Vec<_>) = self
+            .scores(primary, feeds, ipv6)
+            .into_iter()
+            .partition(|(_, score)| *score >= self.threshold);
+        (
+            blocked.into_iter().map(|(entry, _)| entry).collect(),
+            below.into_iter().map(|(entry, _)| entry).collect(),
+        )
+    }
+}
+
+/// Writes a network in its canonical `address/prefix` form; other entries, e.g., ranges, are kept as they are.
+fn normalize(entry: &str) -> String {
+    IpNetwork::from_str(entry).map_or_else(
+        |_| entry.to_string(),
+        |network| format!("{}/{}", network.network(), network.prefix()),
+    )
+}
@@ -1,25 +1,62 @@
 use crate::error::AppError;
 use crate::nftables::builder::SetElements;
-use crate::utils::subnet::SubnetList;
+use crate::nftables::{comment_elements, element_label};
+use crate::set::feed::{Feed, expiry_key, split_annotations};
+use crate::utils::subnet::{Strictness, SubnetList, validate_subnets};
+use crate::utils::{expand_paths, read_ip_set_file};
+use ipnetwork::{Ipv4Network, Ipv6Network};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::time::SystemTime;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CustomSet<'a> {
     pub set_name: String,
     pub ipv4_elements: Option<SetElements<'a>>,
     pub ipv6_elements: Option<SetElements<'a>>,
+    /// The IPv4 and IPv6 entries the elements were built from.
+    entries: (Option<Vec<String>>, Option<Vec<String>>),
+    /// Expiry of the entries with a TTL, keyed by the entry as `nft` prints it.
+    expiry: HashMap<String, SystemTime>,
 }
 
 impl<'a> CustomSet<'a> {
+    /// Creates a `CustomSet` without any IPv4 or IPv6 elements.
+    #[must_use]
+    pub fn empty(set_name: String) -> Self {
+        Self {
+            set_name,
+            ipv4_elements: None,
+            ipv6_elements: None,
+            entries: (None, None),
+            expiry: HashMap::new(),
+        }
+    }
+
     pub fn new(
         set_name: String,
         ipv4_data: Option<Vec<String>>,
         ipv6_data: Option<Vec<String>>,
     ) -> Result<Self, AppError> {
+        Self::new_with_strictness(set_name, ipv4_data, ipv6_data, Strictness::Strict)
+    }
+
+    /// Creates a `CustomSet` like `new`, handling invalid entries according to `strictness`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when `strictness` does not tolerate the invalid entries.
+    pub fn new_with_strictness(
+        set_name: String,
+        ipv4_data: Option<Vec<String>>,
+        ipv6_data: Option<Vec<String>>,
+        strictness: Strictness,
+    ) -> Result<Self, AppError> {
+        let entries = (ipv4_data.clone(), ipv6_data.clone());
         let ipv4_elements = ipv4_data.map_or_else(
             || Ok::<Option<SetElements>, AppError>(None),
             |ips| {
                 Ok(SubnetList::IPv4(ips)
-                    .validate_blocklist(true)?
+                    .validate_with(strictness)?
                     .deduplicate()?
                     .transform_to_nft_expressions()
                     .get_elements())
@@ -30,7 +67,7 @@ impl<'a> CustomSet<'a> {
             || Ok::<Option<SetElements>, AppError>(None),
             |ips| {
                 Ok(SubnetList::IPv6(ips)
-                    .validate_blocklist(true)?
+                    .validate_with(strictness)?
                     .deduplicate()?
                     .transform_to_nft_expressions()
                     .get_elements())
@@ -41,6 +78,206 @@ impl<'a> CustomSet<'a> {
             set_name,
             ipv4_elements,
             ipv6_elements,
+            entries,
+            expiry: HashMap::new(),
         })
     }
+
+    /// Adds the TTLs and comments of an annotated list, e.g., from `read_custom_feed`.
+    /// The comments are set on the elements right away, while expired elements are left out by `unexpired`.
+    #[must_use]
+    pub fn with_annotations(
+        mut self,
+        expiry: HashMap<String, SystemTime>,
+        comments: &HashMap<String, String>,
+    ) -> Self {
+        if !comments.is_empty() {
+            self.ipv4_elements = self.ipv4_elements.map(|e| comment_elements(e, comments));
+            self.ipv6_elements = self.ipv6_elements.map(|e| comment_elements(e, comments));
+        }
+        self.expiry = expiry;
+        self
+    }
+
+    /// Whether any entry of the set expires.
+    #[must_use]
+    pub fn expires(&self) -> bool {
+        !self.expiry.is_empty()
+    }
+
+    /// Returns the set without the elements that expired by `now`.
+    #[must_use]
+    pub fn unexpired(&self, now: SystemTime) -> Self {
+        let unexpired = |elements: &Option<SetElements<'a>>| {
+            let elements = elements
+                .iter()
+                .flatten()
+                .filter(|element| {
+                    element_label(element)
+                        .and_then(|label| self.expiry.get(&label))
+                        .is_none_or(|expires| *expires > now)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            (!elements.is_empty()).then_some(elements)
+        };
+        Self {
+            ipv4_elements: unexpired(&self.ipv4_elements),
+            ipv6_elements: unexpired(&self.ipv6_elements),
+            ..self.clone()
+        }
+    }
+
+    /// Creates a `CustomSet` with the same name and entries, extended by `ipv4` and `ipv6`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when `strictness` does not tolerate the invalid entries.
+    pub fn with_entries(
+        &self,
+        ipv4: &[String],
+        ipv6: &[String],
+        strictness: Strictness,
+    ) -> Result<Self, AppError> {
+        let extend = |entries: &Option<Vec<String>>, extra: &[String]| {
+            let mut entries = entries.clone().unwrap_or_default();
+            entries.extend_from_slice(extra);
+            (!entries.is_empty()).then_some(entries)
+        };
+        Self::new_with_strictness(
+            self.set_name.clone(),
+            extend(&self.entries.0, ipv4),
+            extend(&self.entries.1, ipv6),
+            strictness,
+        )
+    }
+}
+
+/// Reads the entries of a custom blocklist file, one family per file, like `read_custom_feed`
+/// without their TTLs and comments.
+///
+/// # Errors
+/// Will return `AppError` when the file cannot be read or `strictness` does not tolerate its invalid entries.
+pub fn read_custom_file(
+    path: Option<&str>,
+    delimiter: Option<&str>,
+    ipv6: bool,
+    strictness: Strictness,
+) -> Result<Option<Vec<String>>, AppError> {
+    read_custom_feed(path, delimiter, ipv6, strictness).map(|feed| feed.to_entries())
+}
+
+/// Reads the entries of a custom blocklist file, one family per file.
+///
+/// The path may also name a directory or a glob, e.g., `/etc/nftblockd/blocklists/*.txt`, whose files
+/// are read in the order of their names; a glob matching no file yields no entries.
+///
+/// Entries are separated by `delimiter` (whitespace when `None`) within a line, and everything after a `#`
+/// is a comment. A line may end in a TTL before its comment, e.g., `192.0.2.0/24 7d # ticket 1234`; its entries
+/// expire that long after the file was last modified and carry the comment. Every entry is validated, so that
+/// an invalid one is reported with its position, e.g.,
+/// `/etc/nftblockd/custom.txt:3:12: invalid ip: 192.0.2.1/16; not a network`.
+///
+/// # Arguments
+///
+/// * `path` - The file to read; no entries are returned when `None`.
+/// * `delimiter` - The delimiter of the entries within a line.
+/// * `ipv6` - Whether the file lists IPv6 rather than IPv4 entries.
+/// * `strictness` - Whether invalid entries fail the file or are skipped with a summarized warning.
+///
+/// # Errors
+/// Will return `AppError` when the file cannot be read or `strictness` does not tolerate its invalid entries.
+pub fn read_custom_feed(
+    path: Option<&str>,
+    delimiter: Option<&str>,
+    ipv6: bool,
+    strictness: Strictness,
+) -> Result<Feed, AppError> {
+    let Some(path) = path else {
+        return Ok(Feed::default());
+    };
+    let now = SystemTime::now();
+    let mut feed = Feed::default();
+    let mut entries = Vec::new();
+    let mut invalid = Vec::new();
+    for file in expand_paths(path)? {
+        let modified = std::fs::metadata(&file)?.modified()?;
+        let file = file.display().to_string();
+        let data = read_ip_set_file(Some(&file))?.unwrap_or_default();
+        for (number, line) in data.lines().enumerate() {
+            let (line, ttl, comment) = split_annotations(line);
+            let expires = ttl.map(|ttl| modified + ttl);
+            if expires.is_some_and(|expires| expires <= now) {
+                debug!("skipping the expired entries of {file}:{}", number + 1);
+                continue;
+            }
+            for (column, entry) in tokens(line, delimiter) {
+                let entry = entry.to_string();
+                let validated = if ipv6 {
+                    validate_subnets::<Ipv6Network>(std::slice::from_ref(&entry), true).map(|_| ())
+                } else {
+                    validate_subnets::<Ipv4Network>(std::slice::from_ref(&entry), true).map(|_| ())
+                };
+                match validated {
+                    Ok(()) => {
+                        let key = expiry_key(&entry);
+                        if let Some(expires) = expires {
+                            feed.expiry.insert(key.clone(), expires);
+                        }
+                        if let Some(comment) = comment {
+                            feed.comments.insert(key, comment.to_string());
+                        }
+                        entries.push(entry);
+                    }
+                    Err(e) => {
+                        let reason = match e {
+                            AppError::ParseError(reason) => reason,
+                            e => e.to_string(),
+                        };
+                        let e = format!("{file}:{}:{column}: {reason}", number + 1);
+                        if strictness == Strictness::Strict {
+                            return Err(AppError::ParseError(e));
+                        }
+                        debug!("skipping an invalid entry: {e}");
+                        invalid.push(e);
+                    }
+                }
+            }
+        }
+    }
+    if let Strictness::WarnThreshold(threshold) = strictness
+        && invalid.len() > threshold
+    {
+        return Err(AppError::ParseError(format!(
+            "{} invalid entries of {path} exceed the threshold of {threshold}; the first is at {}",
+            invalid.len(),
+            invalid[0]
+        )));
+    }
+    if let Some(first) = invalid.first() {
+        warn!(
+            "skipped {} invalid entries of {path}, the first at {first}",
+            invalid.len()
+        );
+    }
+    feed.set_entries(entries);
+    Ok(feed)
+}
+
+/// Splits a line into its non-empty entries and their 1-based columns.
+fn tokens<'s>(line: &'s str, delimiter: Option<&str>) -> Vec<(usize, &'s str)> {
+    let pieces: Vec<&str> = match delimiter {
+        Some(delimiter) => line.split(delimiter).collect(),
+        None => line.split(char::is_whitespace).collect(),
+    };
+    pieces
+        .into_iter()
+        .filter_map(|piece| {
+            let entry = piece.trim();
+            if entry.is_empty() {
+                return None;
+            }
+            let offset = entry.as_ptr() as usize - line.as_ptr() as usize;
+            Some((line[..offset].chars().count() + 1, entry))
+        })
+        .collect()
 }
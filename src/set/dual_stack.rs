@@ -0,0 +1,167 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use crate::utils::duration::parse_duration;
+use std::fmt::Display;
+use std::time::{Duration, SystemTime};
+
+/// Default largest difference between the `Last-Modified` times of the two families.
+const MAX_SKEW: &str = "24h";
+
+/// Default number of elements of one family that makes an empty other family suspicious.
+const MIN_ELEMENTS: usize = 1000;
+
+/// What happens when the IPv4 and IPv6 lists of a dual-stack feed look inconsistent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DualStackAction {
+    /// The lists are not compared.
+    #[default]
+    Off,
+    /// The inconsistency is logged and the lists are applied anyway.
+    Warn,
+    /// The previously applied elements are kept until the lists are consistent again.
+    Hold,
+}
+
+impl DualStackAction {
+    /// Parses `off`, `warn`, or `hold`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "hold" => Ok(Self::Hold),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_DUAL_STACK_CHECK: {value}; expected off, warn, or hold"
+            ))),
+        }
+    }
+}
+
+/// The lists of one family as the consistency check sees them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FamilyState {
+    /// Number of elements of the family.
+    pub elements: usize,
+    /// The `Last-Modified` time the source reported for its list, if any.
+    pub last_modified: Option<SystemTime>,
+}
+
+/// An obvious inconsistency between the IPv4 and IPv6 lists of a feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// One list was modified `skew` later than the other; `ipv6_newer` tells which one.
+    Skew { skew: Duration, ipv6_newer: bool },
+    /// One family is empty while the other has `elements`; `ipv6_empty` tells which one is empty.
+    Empty { elements: usize, ipv6_empty: bool },
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let family = |ipv6: bool| if ipv6 { "IPv6" } else { "IPv4" };
+        match self {
+            Self::Skew { skew, ipv6_newer } => write!(
+                f,
+                "the {} list is {} s newer than the {} list",
+                family(*ipv6_newer),
+                skew.as_secs(),
+                family(!*ipv6_newer)
+            ),
+            Self::Empty {
+                elements,
+                ipv6_empty,
+            } => write!(
+                f,
+                "the {} list is empty while the {} list has {elements} elements",
+                family(*ipv6_empty),
+                family(!*ipv6_empty)
+            ),
+        }
+    }
+}
+
+/// Compares the IPv4 and IPv6 lists of a feed providing both families.
+///
+/// A feed that publishes its families separately can fail halfway, e.g., leave one list behind after an
+/// export job crashed, or serve an empty file for one of them. Either shows as lists modified far apart or
+/// as one family empty while the other is large.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DualStackCheck {
+    pub action: DualStackAction,
+    /// Largest difference between the `Last-Modified` times of the two lists.
+    pub max_skew: Duration,
+    /// Elements of one family that make an empty other family suspicious.
+    pub min_elements: usize,
+}
+
+impl DualStackCheck {
+    /// Reads the check from `NFTBLOCKD_DUAL_STACK_CHECK`, `NFTBLOCKD_DUAL_STACK_MAX_SKEW`,
+    /// and `NFTBLOCKD_DUAL_STACK_MIN_ELEMENTS`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when the check is off.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a variable cannot be parsed.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let action = environment
+            .var("NFTBLOCKD_DUAL_STACK_CHECK")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|action| DualStackAction::parse(&action))
+            .transpose()?
+            .unwrap_or_default();
+        if action == DualStackAction::Off {
+            return Ok(None);
+        }
+        let max_skew = environment
+            .var("NFTBLOCKD_DUAL_STACK_MAX_SKEW")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or(MAX_SKEW.to_string());
+        let min_elements = environment
+            .var("NFTBLOCKD_DUAL_STACK_MIN_ELEMENTS")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|value| {
+                value.parse::<usize>().map_err(|e| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_DUAL_STACK_MIN_ELEMENTS: {value}: {e}"
+                    ))
+                })
+            })
+            .transpose()?
+            .unwrap_or(MIN_ELEMENTS);
+        Ok(Some(Self {
+            action,
+            max_skew: parse_duration(&max_skew)?,
+            min_elements,
+        }))
+    }
+
+    /// Returns the inconsistencies between the two families.
+    #[must_use]
+    pub fn inconsistencies(&self, ipv4: FamilyState, ipv6: FamilyState) -> Vec<Inconsistency> {
+        let mut inconsistencies = Vec::new();
+        if let (Some(ipv4), Some(ipv6)) = (ipv4.last_modified, ipv6.last_modified) {
+            let (skew, ipv6_newer) = match ipv6.duration_since(ipv4) {
+                Ok(skew) => (skew, true),
+                Err(e) => (e.duration(), false),
+            };
+            if skew > self.max_skew {
+                inconsistencies.push(Inconsistency::Skew { skew, ipv6_newer });
+            }
+        }
+        for (empty, other, ipv6_empty) in [(ipv4, ipv6, false), (ipv6, ipv4, true)] {
+            if empty.elements == 0 && other.elements >= self.min_elements.max(1) {
+                inconsistencies.push(Inconsistency::Empty {
+                    elements: other.elements,
+                    ipv6_empty,
+                });
+            }
+        }
+        inconsistencies
+    }
+}
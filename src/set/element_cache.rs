@@ -0,0 +1,116 @@
+use crate::error::AppError;
+use crate::nftables::builder::SetElements;
+use log::debug;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Shared set elements generated from a fetched list.
+pub type SharedSetElements = Arc<Option<SetElements<'static>>>;
+
+/// The last generated elements together with the `ETag`s of the list they were generated from.
+#[derive(Debug, Default)]
+struct CachedElements {
+    hash: u64,
+    elements: SharedSetElements,
+    /// `ETag` of the cached list, sent as `If-None-Match` on the next fetch.
+    etag: Option<String>,
+    /// `ETag` of the list that was last applied successfully.
+    applied_etag: Option<String>,
+}
+
+/// Caches the set elements generated from the last fetched list.
+///
+/// Blocklists rarely change between cycles, so the elements are keyed by the hash of the
+/// fetched entries and of everything else they are generated from, and reused as long as the
+/// hash stays the same. This skips validation, deduplication, and the per-element `String`
+/// allocations of expression generation.
+/// The cache also remembers the `ETag` of the list, so unchanged lists need not be downloaded at all.
+#[derive(Debug, Clone, Default)]
+pub struct ElementCache {
+    inner: Arc<Mutex<Option<CachedElements>>>,
+}
+
+impl ElementCache {
+    /// Returns the cached elements if `list` and `context` are unchanged since the last call,
+    /// otherwise generates, caches, and returns new elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `list` - The fetched blocklist entries.
+    /// * `context` - Everything else `generate` depends on, e.g., the strictness or the element comment.
+    /// * `generate` - Transforms the entries into set elements.
+    ///
+    /// # Errors
+    /// Returns the `AppError` produced by `generate`; the cache is left untouched in that case.
+    pub fn get_or_generate<S, F>(
+        &self,
+        list: &[S],
+        context: &impl Hash,
+        generate: F,
+    ) -> Result<SharedSetElements, AppError>
+    where
+        S: Hash,
+        F: FnOnce(&[S]) -> Result<Option<SetElements<'static>>, AppError>,
+    {
+        let mut hasher = DefaultHasher::new();
+        list.hash(&mut hasher);
+        context.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut cached = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = cached.as_ref()
+            && entry.hash == hash
+        {
+            debug!("blocklist unchanged; reusing cached set elements");
+            return Ok(entry.elements.clone());
+        }
+
+        let elements = Arc::new(generate(list)?);
+        let applied_etag = cached.take().and_then(|entry| entry.applied_etag);
+        *cached = Some(CachedElements {
+            hash,
+            elements: elements.clone(),
+            etag: None,
+            applied_etag,
+        });
+        Ok(elements)
+    }
+
+    /// Returns the cached elements, if any.
+    #[must_use]
+    pub fn cached(&self) -> Option<SharedSetElements> {
+        let cached = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        cached.as_ref().map(|entry| entry.elements.clone())
+    }
+
+    /// Stores the `ETag` of the list the cached elements were generated from.
+    pub fn set_etag(&self, etag: Option<String>) {
+        let mut cached = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = cached.as_mut() {
+            entry.etag = etag;
+        }
+    }
+
+    /// Returns the `ETag` of the cached list.
+    #[must_use]
+    pub fn etag(&self) -> Option<String> {
+        let cached = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        cached.as_ref().and_then(|entry| entry.etag.clone())
+    }
+
+    /// Marks the cached list as applied.
+    pub fn mark_applied(&self) {
+        let mut cached = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = cached.as_mut() {
+            entry.applied_etag.clone_from(&entry.etag);
+        }
+    }
+
+    /// Returns the `ETag` of the list that was last applied.
+    #[must_use]
+    pub fn applied_etag(&self) -> Option<String> {
+        let cached = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        cached.as_ref().and_then(|entry| entry.applied_etag.clone())
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::AppError;
+use crate::nftables::builder::SetElements;
+use crate::nftables::element_label;
+use crate::set::attribution::Attribution;
+use ipnetwork::IpNetwork;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Output formats of the merged blocklist export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// One CIDR per line.
+    Plain,
+    /// `network,family,first_address,last_address` rows with a header, followed by `sources` when attributed.
+    Csv,
+    /// `{"ipv4": [...], "ipv6": [...]}` with CIDR strings, and `"sources"` by CIDR when attributed.
+    Json,
+    /// Commands for `ipset restore`, one `hash:net` set per family.
+    Ipset,
+}
+
+/// Exports the merged and deduplicated blocklist so that other devices can consume it.
+///
+/// Ranges are split into the smallest list of covering CIDRs, as none of the formats supports ranges.
+///
+/// # Arguments
+///
+/// * `format` - The output format.
+/// * `set_name` - Base name of the `ipset` sets; `_ipv4` and `_ipv6` are appended.
+/// * `ipv4_elements` - The IPv4 set elements.
+/// * `ipv6_elements` - The IPv6 set elements.
+///
+/// # Errors
+/// Will return `AppError` when the JSON output cannot be serialized.
+pub fn export_elements(
+    format: ExportFormat,
+    set_name: &str,
+    ipv4_elements: &Option<SetElements<'_>>,
+    ipv6_elements: &Option<SetElements<'_>>,
+) -> Result<String, AppError> {
+    export_attributed(format, set_name, ipv4_elements, ipv6_elements, None)
+}
+
+/// Exports the blocklist like `export_elements`, naming the sources of every CIDR in the `csv` and `json`
+/// formats, e.g., the feeds merged into the blocklist (see `BlockList::attribution`).
+///
+/// # Errors
+/// Will return `AppError` when the JSON output cannot be serialized.
+pub fn export_attributed(
+    format: ExportFormat,
+    set_name: &str,
+    ipv4_elements: &Option<SetElements<'_>>,
+    ipv6_elements: &Option<SetElements<'_>>,
+    attribution: Option<&Attribution>,
+) -> Result<String, AppError> {
+    let ipv4 = to_networks(ipv4_elements.as_ref());
+    let ipv6 = to_networks(ipv6_elements.as_ref());
+    // A CIDR split from a range takes on the sources of the range.
+    let sources = |label: &str| {
+        attribution
+            .and_then(|attribution| attribution.get(label))
+            .map(|sources| sources.iter().map(String::as_str).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    let mut out = String::new();
+    match format {
+        ExportFormat::Plain => {
+            for (network, _) in ipv4.iter().chain(&ipv6) {
+                let _ = writeln!(out, "{network}");
+            }
+        }
+        ExportFormat::Csv => {
+            out.push_str("network,family,first_address,last_address");
+            if attribution.is_some() {
+                out.push_str(",sources");
+            }
+            out.push('\n');
+            for (network, label) in ipv4.iter().chain(&ipv6) {
+                let family = if network.is_ipv4() { "ipv4" } else { "ipv6" };
+                let _ = write!(
+                    out,
+                    "{network},{family},{},{}",
+                    network.network(),
+                    network.broadcast()
+                );
+                if attribution.is_some() {
+                    // URLs do not contain spaces, unlike commas.
+                    let _ = write!(out, ",{}", sources(label).join(" "));
+                }
+                out.push('\n');
+            }
+        }
+        ExportFormat::Json => {
+            let cidrs = |networks: &[(IpNetwork, String)]| {
+                networks
+                    .iter()
+                    .map(|(network, _)| network.to_string())
+                    .collect::<Vec<_>>()
+            };
+            let mut value = serde_json::json!({
+                "ipv4": cidrs(&ipv4),
+                "ipv6": cidrs(&ipv6),
+            });
+            if attribution.is_some() {
+                value["sources"] = ipv4
+                    .iter()
+                    .chain(&ipv6)
+                    .map(|(network, label)| {
+                        (network.to_string(), serde_json::json!(sources(label)))
+                    })
+                    .collect::<serde_json::Map<_, _>>()
+                    .into();
+            }
+            out = serde_json::to_string_pretty(&value)?;
+            out.push('\n');
+        }
+        ExportFormat::Ipset => {
+            for (suffix, family, networks) in [("ipv4", "inet", &ipv4), ("ipv6", "inet6", &ipv6)] {
+                let name = format!("{set_name}_{suffix}");
+                let _ = writeln!(out, "create {name} hash:net family {family} -exist");
+                let _ = writeln!(out, "flush {name}");
+                for (network, _) in networks {
+                    let _ = writeln!(out, "add {name} {network} -exist");
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Exports a single family as one CIDR per line, i.e., in `ExportFormat::Plain`.
+#[must_use]
+pub fn export_plain(elements: &SetElements<'_>) -> String {
+    let mut out = String::new();
+    for (network, _) in to_networks(Some(elements)) {
+        let _ = writeln!(out, "{network}");
+    }
+    out
+}
+
+/// Converts set elements into CIDRs, splitting ranges, along with the label of the element they came from.
+fn to_networks(elements: Option<&SetElements<'_>>) -> Vec<(IpNetwork, String)> {
+    elements
+        .into_iter()
+        .flatten()
+        .filter_map(element_label)
+        .flat_map(|label| {
+            let networks = if let Some((start, end)) = label.split_once('-')
+                && let (Ok(start), Ok(end)) = (IpAddr::from_str(start), IpAddr::from_str(end))
+            {
+                range_to_networks(start, end)
+            } else {
+                IpNetwork::from_str(&label).into_iter().collect()
+            };
+            networks
+                .into_iter()
+                .map(move |network| (network, label.clone()))
+        })
+        .collect()
+}
+
+/// Splits an inclusive address range into the smallest list of CIDRs covering it exactly.
+///
+/// # Returns
+/// An empty `Vec` if the addresses are of different families or `start` is greater than `end`.
+#[must_use]
+pub fn range_to_networks(start: IpAddr, end: IpAddr) -> Vec<IpNetwork> {
+    let (mut start, end, bits) = match (start, end) {
+        (IpAddr::V4(start), IpAddr::V4(end)) => {
+            (u128::from(start.to_bits()), u128::from(end.to_bits()), 32)
+        }
+        (IpAddr::V6(start), IpAddr::V6(end)) => (start.to_bits(), end.to_bits(), 128),
+        _ => return Vec::new(),
+    };
+    let mut networks = Vec::new();
+    while start <= end {
+        // The largest block aligned at `start` that does not extend past `end`.
+        let mut size = start.trailing_zeros().min(bits);
+        while size > 0 && end - start < block_last(size) {
+            size -= 1;
+        }
+        let addr = if bits == 32 {
+            IpAddr::V4(u32::try_from(start).unwrap_or_default().into())
+        } else {
+            IpAddr::V6(start.into())
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        if let Ok(network) = IpNetwork::new(addr, (bits - size) as u8) {
+            networks.push(network);
+        }
+        let Some(next) = start
+            .checked_add(block_last(size))
+            .and_then(|s| s.checked_add(1))
+        else {
+            break;
+        };
+        start = next;
+    }
+    networks
+}
+
+/// Offset of the last address in a block of `2^size` addresses.
+fn block_last(size: u32) -> u128 {
+    1u128.checked_shl(size).map_or(u128::MAX, |block| block - 1)
+}
@@ -0,0 +1,633 @@
+use crate::error::AppError;
+use crate::set::cloud::{CloudFilter, CloudProvider, parse_ranges};
+use crate::set::fetch_policy::source_var;
+use crate::set::tor::parse_exits;
+use crate::settings::Environment;
+use crate::utils::duration::parse_duration;
+use crate::utils::subnet::{normalize_notation, split_entries};
+use ipnetwork::Ipv6Network;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::ops::Range;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// Format of the blocklist served by a source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeedFormat {
+    /// Addresses and networks separated by whitespace or `NFTBLOCKD_BLOCKLIST_SPLIT_STRING`.
+    #[default]
+    Text,
+    /// CrowdSec decisions, e.g., from `/v1/decisions` or `/v1/decisions/stream` of the local API;
+    /// every decision expires after its `duration`.
+    Crowdsec,
+    /// MISP attributes from `/attributes/restSearch`; every attribute expires at its `last_seen`.
+    Misp,
+    /// Arbitrary text, e.g., an HTML page or an advisory, scanned for IPv4 and IPv6 addresses and networks.
+    Extract,
+    /// Text with one entry per line, optionally followed by a TTL and a comment, e.g., `192.0.2.0/24 1d # scanner`;
+    /// the entry expires after its TTL and carries its comment into the set.
+    Annotated,
+    /// Text with one entry per line followed by extra columns, e.g., `192.0.2.1 42 2024-05-01`;
+    /// the first token of every line that is an address, network, or range is taken, the rest is ignored.
+    Columns,
+    /// The Tor exit list or a network status consensus, see `parse_exits`; both families are listed,
+    /// and the relays of a consensus expire at its `valid-until`.
+    Tor,
+    /// The IP ranges a cloud provider publishes as JSON, see `parse_ranges`; both families are listed.
+    Cloud(CloudProvider),
+}
+
+impl FeedFormat {
+    /// Parses `text`, `crowdsec`, `misp`, `extract`, `annotated`, `columns`, `tor`, `aws`, `gcp`, `azure`, or `cloudflare`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "crowdsec" => Ok(Self::Crowdsec),
+            "misp" => Ok(Self::Misp),
+            "extract" => Ok(Self::Extract),
+            "annotated" => Ok(Self::Annotated),
+            "columns" => Ok(Self::Columns),
+            "tor" => Ok(Self::Tor),
+            "aws" => Ok(Self::Cloud(CloudProvider::Aws)),
+            "gcp" => Ok(Self::Cloud(CloudProvider::Gcp)),
+            "azure" => Ok(Self::Cloud(CloudProvider::Azure)),
+            "cloudflare" => Ok(Self::Cloud(CloudProvider::Cloudflare)),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_FORMAT: {value}; expected text, crowdsec, misp, extract, annotated, columns, tor, aws, gcp, azure, or cloudflare"
+            ))),
+        }
+    }
+
+    /// Reads the format of `source` from `NFTBLOCKD_{source}_FORMAT` or `NFTBLOCKD_FORMAT`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the format is invalid.
+    pub fn from_env(environment: &Environment, source: &str) -> Result<Self, AppError> {
+        source_var(environment, source, "FORMAT")
+            .map(|f| Self::parse(&f))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Whether the format lists both families, so each set keeps only the entries of its own.
+    #[must_use]
+    pub fn mixes_families(self) -> bool {
+        matches!(self, Self::Extract | Self::Tor | Self::Cloud(_))
+    }
+
+    /// Whether the entries of the format carry their own expiry.
+    #[must_use]
+    pub fn expires(self) -> bool {
+        matches!(
+            self,
+            Self::Crowdsec | Self::Misp | Self::Annotated | Self::Tor
+        )
+    }
+}
+
+/// How the entries of a text blocklist are separated.
+#[derive(Debug, Clone, Default)]
+pub enum Splitter {
+    /// Any whitespace.
+    #[default]
+    Whitespace,
+    /// A literal delimiter, e.g., `NFTBLOCKD_BLOCKLIST_SPLIT_STRING`; empty entries are kept and reported invalid.
+    Literal(String),
+    /// Any of a set of characters, e.g., newlines, commas, and semicolons mixed in one feed.
+    Delimiters(Vec<char>),
+    /// Matches of a regular expression.
+    Regex(Regex),
+}
+
+impl Splitter {
+    /// Splits on `split_string`, or on whitespace when `None`.
+    #[must_use]
+    pub fn literal(split_string: Option<&str>) -> Self {
+        split_string.map_or(Self::Whitespace, |s| Self::Literal(s.to_string()))
+    }
+
+    /// Parses a comma-separated set of delimiters: `newline`, `comma`, `semicolon`, `whitespace`, `tab`, `pipe`,
+    /// or any single character.
+    ///
+    /// # Errors
+    /// Will return `AppError` for an unknown name or an empty set.
+    pub fn delimiters(value: &str) -> Result<Self, AppError> {
+        let mut delimiters = Vec::new();
+        for name in value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match name.to_ascii_lowercase().as_str() {
+                "newline" => delimiters.extend(['\n', '\r']),
+                "comma" => delimiters.push(','),
+                "semicolon" => delimiters.push(';'),
+                "whitespace" => delimiters.extend([' ', '\t', '\n', '\r', '\x0b', '\x0c']),
+                "tab" => delimiters.push('\t'),
+                "pipe" => delimiters.push('|'),
+                _ => {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => delimiters.push(c),
+                        _ => {
+                            return Err(AppError::ParseError(format!(
+                                "invalid NFTBLOCKD_SPLIT_DELIMITERS: {name}; expected newline, comma, semicolon, whitespace, tab, pipe, or a single character"
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+        if delimiters.is_empty() {
+            return Err(AppError::ParseError(
+                "NFTBLOCKD_SPLIT_DELIMITERS lists no delimiter".to_string(),
+            ));
+        }
+        delimiters.sort_unstable();
+        delimiters.dedup();
+        Ok(Self::Delimiters(delimiters))
+    }
+
+    /// Parses a regular expression matching the separators.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the expression is invalid.
+    pub fn regex(value: &str) -> Result<Self, AppError> {
+        Regex::new(value).map(Self::Regex).map_err(|e| {
+            AppError::ParseError(format!("invalid NFTBLOCKD_SPLIT_REGEX: {value}: {e}"))
+        })
+    }
+
+    /// Reads the splitter of `source` from `NFTBLOCKD_{source}_SPLIT_REGEX` or `NFTBLOCKD_SPLIT_REGEX`, then
+    /// `NFTBLOCKD_{source}_SPLIT_DELIMITERS` or `NFTBLOCKD_SPLIT_DELIMITERS`, falling back to `split_string`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the regular expression or the delimiters are invalid.
+    pub fn from_env(
+        environment: &Environment,
+        source: &str,
+        split_string: Option<&str>,
+    ) -> Result<Self, AppError> {
+        if let Some(regex) = source_var(environment, source, "SPLIT_REGEX") {
+            return Self::regex(&regex);
+        }
+        if let Some(delimiters) = source_var(environment, source, "SPLIT_DELIMITERS") {
+            return Self::delimiters(&delimiters);
+        }
+        Ok(Self::literal(split_string))
+    }
+
+    /// Splits `data` into its trimmed entries without copying them; only a literal delimiter keeps empty entries.
+    pub fn split<'a>(&'a self, data: &'a str) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+        match self {
+            Self::Whitespace => split_entries(data, None),
+            Self::Literal(split_string) => split_entries(data, Some(split_string)),
+            Self::Delimiters(delimiters) => Box::new(
+                data.split(delimiters.as_slice())
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty()),
+            ),
+            Self::Regex(regex) => Box::new(
+                regex
+                    .split(data)
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty()),
+            ),
+        }
+    }
+}
+
+/// Entries of a fetched blocklist along with when they expire.
+///
+/// The entries are spans of `text`, which is the body of a text blocklist as fetched, or the entries of any other
+/// format joined by newlines, so that they are validated in place rather than allocated one by one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Feed {
+    text: String,
+    spans: Vec<Range<usize>>,
+    /// Expiry of the entries that do not stay forever, keyed by the entry as `nft` prints it
+    /// (see `element_label`).
+    pub expiry: HashMap<String, SystemTime>,
+    /// Comments of the annotated entries, keyed like `expiry`.
+    pub comments: HashMap<String, String>,
+    /// Time before which the source publishes no newer list, e.g., the `fresh-until` of a Tor consensus.
+    pub fresh_until: Option<SystemTime>,
+}
+
+impl Feed {
+    /// Parses the body of a blocklist served in `format`.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - The fetched blocklist.
+    /// * `format` - The format of the blocklist.
+    /// * `split_string` - The delimiter of a text blocklist; whitespace when `None`.
+    /// * `now` - The time the blocklist was fetched, which relative expiries start at.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a CrowdSec or MISP response is not valid JSON.
+    pub fn parse(
+        body: impl Into<String>,
+        format: FeedFormat,
+        split_string: Option<&str>,
+        now: SystemTime,
+    ) -> Result<Self, AppError> {
+        Self::parse_split(body, format, &Splitter::literal(split_string), now)
+    }
+
+    /// Parses the body of a blocklist like `parse`, separating the entries of a text blocklist with `splitter`.
+    ///
+    /// The entries of a text or columns blocklist are kept in `body` without copying them.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a CrowdSec or MISP response is not valid JSON.
+    #[allow(clippy::too_many_lines)]
+    pub fn parse_split(
+        body: impl Into<String>,
+        format: FeedFormat,
+        splitter: &Splitter,
+        now: SystemTime,
+    ) -> Result<Self, AppError> {
+        let body = body.into();
+        let mut comments = HashMap::new();
+        let listed = match format {
+            FeedFormat::Text => {
+                let spans = splitter
+                    .split(body.trim())
+                    .map(|entry| span_of(&body, entry))
+                    .collect();
+                return Ok(Self::from_spans(body, spans));
+            }
+            FeedFormat::Extract => {
+                let mut seen = HashSet::new();
+                return Ok(Self::from_entries(
+                    extract(&body).filter(|entry| seen.insert(entry.clone())),
+                ));
+            }
+            FeedFormat::Columns => {
+                let spans = body
+                    .lines()
+                    .filter_map(first_address)
+                    .map(|entry| span_of(&body, entry))
+                    .collect();
+                return Ok(Self::from_spans(body, spans));
+            }
+            FeedFormat::Tor => return Ok(Self::parse_tor(&body, None)),
+            FeedFormat::Cloud(provider) => {
+                return Self::parse_cloud(&body, provider, &CloudFilter::default());
+            }
+            FeedFormat::Annotated => {
+                let mut listed = Vec::new();
+                for line in body.lines() {
+                    let (entries, ttl, comment) = split_annotations(line);
+                    for entry in splitter
+                        .split(entries.trim())
+                        .filter(|entry| !entry.is_empty())
+                    {
+                        if let Some(comment) = comment {
+                            comments.insert(expiry_key(entry), comment.to_string());
+                        }
+                        listed.push((Cow::Borrowed(entry), ttl.map(|ttl| now + ttl)));
+                    }
+                }
+                listed
+            }
+            FeedFormat::Crowdsec => match serde_json::from_str::<Decisions>(&body)? {
+                Decisions::List(decisions) | Decisions::Stream { new: decisions } => decisions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(Decision::bans_addresses)
+                    .map(|decision| {
+                        let expires = decision.duration.as_deref().and_then(|duration| {
+                            go_duration(duration).map(|duration| now + duration)
+                        });
+                        (Cow::Owned(decision.value), expires)
+                    })
+                    .collect::<Vec<_>>(),
+            },
+            FeedFormat::Misp => serde_json::from_str::<MispResponse>(&body)?
+                .response
+                .attributes
+                .into_iter()
+                .filter(|attribute| attribute.kind.starts_with("ip-"))
+                .map(|attribute| {
+                    let expires = attribute
+                        .last_seen
+                        .as_deref()
+                        .and_then(|last_seen| OffsetDateTime::parse(last_seen, &Rfc3339).ok())
+                        .map(SystemTime::from);
+                    // Composite attributes, e.g., `ip-dst|port`, list the address first.
+                    let value = attribute
+                        .value
+                        .split('|')
+                        .next()
+                        .unwrap_or_default()
+                        .to_string();
+                    (Cow::Owned(value), expires)
+                })
+                .collect(),
+        };
+
+        Ok(Self::from_listed(listed, comments))
+    }
+
+    /// Parses the Tor exit list or a consensus, keeping only the exits that allow one of `ports`
+    /// when given (see `parse_exits`).
+    #[must_use]
+    pub fn parse_tor(body: &str, ports: Option<&[u16]>) -> Self {
+        let exits = parse_exits(body, ports);
+        let listed = exits
+            .addresses
+            .into_iter()
+            .map(|address| (Cow::Owned(address), exits.valid_until))
+            .collect();
+        Self {
+            fresh_until: exits.fresh_until,
+            ..Self::from_listed(listed, HashMap::new())
+        }
+    }
+
+    /// Parses the IP ranges of a cloud provider, keeping those passing `filter` (see `parse_ranges`).
+    ///
+    /// # Errors
+    /// Will return `AppError` when the body is not the JSON of the provider.
+    pub fn parse_cloud(
+        body: &str,
+        provider: CloudProvider,
+        filter: &CloudFilter,
+    ) -> Result<Self, AppError> {
+        Ok(Self::from_entries(parse_ranges(body, provider, filter)?))
+    }
+
+    /// Keeps `entries` joined in one buffer rather than as a string each.
+    #[must_use]
+    pub fn from_entries<S: AsRef<str>>(entries: impl IntoIterator<Item = S>) -> Self {
+        let mut text = String::new();
+        let mut spans = Vec::new();
+        for entry in entries {
+            let entry = entry.as_ref();
+            spans.push(text.len()..text.len() + entry.len());
+            text.push_str(entry);
+            text.push('\n');
+        }
+        Self::from_spans(text, spans)
+    }
+
+    /// Keeps the entries of `text` at `spans`.
+    fn from_spans(text: String, spans: Vec<Range<usize>>) -> Self {
+        Self {
+            text,
+            spans,
+            ..Self::default()
+        }
+    }
+
+    /// Collects the listed entries with their expiry.
+    fn from_listed(
+        listed: Vec<(Cow<'_, str>, Option<SystemTime>)>,
+        comments: HashMap<String, String>,
+    ) -> Self {
+        // An entry listed several times stays until its latest expiry, or forever if any listing has none.
+        let mut latest: HashMap<String, Option<SystemTime>> = HashMap::new();
+        let mut entries = Vec::with_capacity(listed.len());
+        for (entry, expires) in listed {
+            let key = expiry_key(&entry);
+            match latest.get_mut(&key) {
+                Some(current) => {
+                    *current = current.zip(expires).map(|(a, b)| a.max(b));
+                }
+                None => {
+                    latest.insert(key, expires);
+                    entries.push(entry);
+                }
+            }
+        }
+        Self {
+            expiry: latest
+                .into_iter()
+                .filter_map(|(key, expires)| expires.map(|expires| (key, expires)))
+                .collect(),
+            comments,
+            ..Self::from_entries(entries)
+        }
+    }
+}
+
+impl Feed {
+    /// The entries, borrowed from the feed.
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.spans.iter().map(|span| &self.text[span.clone()])
+    }
+
+    /// Number of entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Whether the feed lists no entry.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Copies the entries into strings, or `None` when there is none.
+    #[must_use]
+    pub fn to_entries(&self) -> Option<Vec<String>> {
+        (!self.is_empty()).then(|| self.entries().map(ToString::to_string).collect())
+    }
+
+    /// Replaces the entries, keeping their expiry and comments, e.g., with the addresses listed hostnames resolve to.
+    pub fn set_entries<S: AsRef<str>>(&mut self, entries: impl IntoIterator<Item = S>) {
+        let Self { text, spans, .. } = Self::from_entries(entries);
+        self.text = text;
+        self.spans = spans;
+    }
+
+    /// Keeps only the entries `keep` returns `true` for.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        let text = &self.text;
+        self.spans.retain(|span| keep(&text[span.clone()]));
+    }
+
+    /// Keeps only the IPv6 entries if `ipv6`, or only the IPv4 entries otherwise,
+    /// e.g., for a page scanned with `FeedFormat::Extract` that lists both families.
+    pub fn retain_family(&mut self, ipv6: bool) {
+        self.retain(|entry| entry.contains(':') == ipv6);
+    }
+}
+
+/// The span of `entry` within `text`, which it is borrowed from.
+fn span_of(text: &str, entry: &str) -> Range<usize> {
+    let start = entry.as_ptr().addr() - text.as_ptr().addr();
+    start..start + entry.len()
+}
+
+/// Splits a line of an annotated list, `entry [ttl] [# comment]`, into its entries, TTL, and comment.
+/// The last word is the TTL when it is a duration, e.g., `1d` or `3600`; the entries are a prefix of `line`.
+#[must_use]
+pub fn split_annotations(line: &str) -> (&str, Option<Duration>, Option<&str>) {
+    let (body, comment) = match line.split_once('#') {
+        Some((body, comment)) => (body, Some(comment.trim()).filter(|c| !c.is_empty())),
+        None => (line, None),
+    };
+    let body = body.trim_end();
+    if let Some((entries, ttl)) = body.rsplit_once(char::is_whitespace)
+        && !entries.trim().is_empty()
+        && let Ok(ttl) = parse_duration(ttl)
+    {
+        return (entries, Some(ttl), comment);
+    }
+    (body, None, comment)
+}
+
+/// Returns the first whitespace-separated token of `line` that is an address, a network, or a range,
+/// skipping leading columns and ignoring anything after it or after a `#`.
+///
+/// Tokens are parsed strictly, so that a hit count such as `42` is not taken for the address `42.0.0.0`.
+#[must_use]
+pub fn first_address(line: &str) -> Option<&str> {
+    let line = line.split_once('#').map_or(line, |(line, _)| line);
+    line.split_whitespace().find(|token| is_address(token))
+}
+
+/// Whether `token` is an address, a network, or a range, in any notation `validate_subnets` accepts.
+fn is_address(token: &str) -> bool {
+    let token = normalize_notation(token);
+    let strict = |address: &str| address.parse::<IpAddr>().is_ok();
+    if let Some((start, end)) = token.split_once('-') {
+        return strict(start) && strict(end);
+    }
+    match token.split_once('/') {
+        Some((address, prefix)) => strict(address) && prefix.parse::<u8>().is_ok(),
+        None => strict(&token),
+    }
+}
+
+/// Candidate IPv4 addresses and networks, e.g., `192.0.2.1` or `192.0.2.0/24`.
+static IPV4_CANDIDATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)(?:/(?:3[0-2]|[12]?\d))?\b")
+        .expect("the IPv4 pattern is valid")
+});
+
+/// Candidate IPv6 addresses and networks, e.g., `2001:db8::1` or `2001:db8::/32`;
+/// candidates are parsed before they are extracted.
+static IPV6_CANDIDATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:[0-9a-f]{0,4}:){2,7}[0-9a-f]{0,4}(?:/\d{1,3})?")
+        .expect("the IPv6 pattern is valid")
+});
+
+/// Scans `body` for IPv4 and IPv6 addresses and networks, in the order they appear per family.
+pub fn extract(body: &str) -> impl Iterator<Item = String> {
+    let ipv4 = IPV4_CANDIDATE
+        .find_iter(body)
+        .map(|candidate| candidate.as_str().to_string());
+    let ipv6 = IPV6_CANDIDATE
+        .find_iter(body)
+        .map(|candidate| candidate.as_str())
+        // At least two groups rule out, e.g., `d::` in `std::vector`.
+        .filter(|candidate| {
+            let address = candidate.split('/').next().unwrap_or_default();
+            address.split(':').filter(|group| !group.is_empty()).count() >= 2
+        })
+        .filter(|candidate| candidate.parse::<Ipv6Network>().is_ok())
+        .map(ToString::to_string);
+    ipv4.chain(ipv6)
+}
+
+/// Formats an entry the way `element_label` formats its element: single hosts without a prefix length,
+/// and legacy notations as CIDRs (see `normalize_notation`).
+#[must_use]
+pub fn expiry_key(entry: &str) -> String {
+    let entry = normalize_notation(entry.trim());
+    let entry = entry.as_ref();
+    let host = if entry.contains(':') { "/128" } else { "/32" };
+    entry.strip_suffix(host).unwrap_or(entry).to_string()
+}
+
+/// Parses a Go duration, e.g., `3h59m58.6304s`; fractions of seconds are dropped.
+///
+/// # Returns
+/// `None` for durations that cannot be parsed, whose entries then never expire.
+fn go_duration(value: &str) -> Option<Duration> {
+    if value.starts_with('-') {
+        return Some(Duration::ZERO);
+    }
+    let mut whole = String::with_capacity(value.len());
+    let mut fraction = false;
+    for c in value.chars() {
+        match c {
+            '.' => fraction = true,
+            c if c.is_ascii_digit() => {
+                if !fraction {
+                    whole.push(c);
+                }
+            }
+            c => {
+                fraction = false;
+                whole.push(c);
+            }
+        }
+    }
+    parse_duration(&whole)
+        .inspect_err(|e| debug!("ignoring the expiry of a CrowdSec decision: {e}"))
+        .ok()
+}
+
+/// Decisions of the CrowdSec local API, either a plain list (`null` when empty) or a stream update.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Decisions {
+    List(Option<Vec<Decision>>),
+    Stream {
+        #[serde(default)]
+        new: Option<Vec<Decision>>,
+    },
+}
+
+#[derive(Deserialize)]
+struct Decision {
+    value: String,
+    #[serde(default)]
+    scope: String,
+    #[serde(rename = "type", default)]
+    kind: Option<String>,
+    duration: Option<String>,
+}
+
+impl Decision {
+    /// Whether the decision bans an address or a range, rather than, e.g., a country or a captcha.
+    fn bans_addresses(&self) -> bool {
+        let scope = self.scope.to_ascii_lowercase();
+        (scope == "ip" || scope == "range") && self.kind.as_deref().is_none_or(|kind| kind == "ban")
+    }
+}
+
+#[derive(Deserialize)]
+struct MispResponse {
+    response: MispAttributes,
+}
+
+#[derive(Deserialize)]
+struct MispAttributes {
+    #[serde(rename = "Attribute", default)]
+    attributes: Vec<MispAttribute>,
+}
+
+#[derive(Deserialize)]
+struct MispAttribute {
+    #[serde(rename = "type")]
+    kind: String,
+    value: String,
+    last_seen: Option<String>,
+}
@@ -0,0 +1,488 @@
+use crate::error::AppError;
+use crate::set::git::GitSource;
+use crate::settings::Environment;
+use crate::utils::duration::parse_duration;
+use crate::utils::parse_size;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::{ClientBuilder, NoProxy, Proxy, Url};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use time::PrimitiveDateTime;
+use time::macros::format_description;
+
+/// Default maximum number of redirects, matching the `reqwest` default.
+const MAX_REDIRECTS: usize = 10;
+
+/// Default connect and read timeout in seconds.
+const TIMEOUT: u64 = 10;
+
+/// Default deadline of a whole fetch in seconds.
+const DEADLINE: u64 = 60;
+
+/// Proxy variables in the order `reqwest` looks them up, each with its lowercase form.
+const PROXY_VARIABLES: [(&str, &str); 3] = [
+    ("HTTPS_PROXY", "https_proxy"),
+    ("HTTP_PROXY", "http_proxy"),
+    ("ALL_PROXY", "all_proxy"),
+];
+
+/// Proxies the HTTP clients connect through, read from `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY`,
+/// and `NO_PROXY` (or their lowercase forms) like `reqwest` reads them from the environment of the process,
+/// so that they can be set in the `.env` files as well.
+#[derive(Debug, Clone, Default)]
+pub struct Proxies {
+    /// URLs of the configured proxies, whose hosts are trusted like the configured sources.
+    pub urls: Vec<String>,
+    proxies: Vec<Proxy>,
+}
+
+impl Proxies {
+    /// Reads the proxies from `environment`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a proxy URL is invalid.
+    pub fn from_env(environment: &Environment) -> Result<Self, AppError> {
+        let var = |upper: &str, lower: &str| {
+            [upper, lower]
+                .into_iter()
+                .find_map(|name| environment.var(name).ok().filter(|s| !s.is_empty()))
+        };
+        let no_proxy = var("NO_PROXY", "no_proxy").and_then(|list| NoProxy::from_string(&list));
+        let mut proxies = Self::default();
+        for (upper, lower) in PROXY_VARIABLES {
+            let Some(url) = var(upper, lower) else {
+                continue;
+            };
+            let proxy = match upper {
+                "HTTPS_PROXY" => Proxy::https(&url),
+                "HTTP_PROXY" => Proxy::http(&url),
+                _ => Proxy::all(&url),
+            }
+            .map_err(|e| AppError::ParseError(format!("invalid {upper}: {url}: {e}")))?;
+            proxies.proxies.push(proxy.no_proxy(no_proxy.clone()));
+            proxies.urls.push(url);
+        }
+        Ok(proxies)
+    }
+
+    /// Makes `builder` connect through the proxies rather than those of the environment of the process.
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        self.proxies
+            .iter()
+            .cloned()
+            .fold(builder.no_proxy(), ClientBuilder::proxy)
+    }
+}
+
+/// Address family used to connect to a source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Connects over both families; the connector races IPv6 and IPv4 (happy eyeballs),
+    /// so a broken AAAA record only delays the fetch.
+    #[default]
+    Any,
+    IPv4,
+    IPv6,
+}
+
+impl AddressFamily {
+    /// Parses `any`, `ipv4`, or `ipv6`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "any" => Ok(Self::Any),
+            "ipv4" => Ok(Self::IPv4),
+            "ipv6" => Ok(Self::IPv6),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_FETCH_FAMILY: {value}; expected any, ipv4, or ipv6"
+            ))),
+        }
+    }
+
+    /// Returns whether `ip` may be connected to.
+    #[must_use]
+    pub fn allows(self, ip: IpAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::IPv4 => ip.is_ipv4(),
+            Self::IPv6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl Display for AddressFamily {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::IPv4 => write!(f, "ipv4"),
+            Self::IPv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+/// Rules restricting where blocklists may be fetched from.
+///
+/// A compromised feed must not be able to redirect the fetch into internal services, so redirects
+/// may neither downgrade to plain HTTP nor lead to private, loopback, or link-local addresses.
+/// The configured sources themselves are trusted and may live on a private network.
+#[derive(Debug, Clone)]
+pub struct FetchPolicy {
+    /// Allows plain-HTTP sources; redirects from HTTPS to HTTP are refused regardless.
+    pub allow_http: bool,
+    /// Maximum number of redirects followed; `0` disables redirects.
+    pub max_redirects: usize,
+    /// Hosts redirects may lead to, e.g., `cdn.example.com` or `*.example.com`; any public host when `None`.
+    pub redirect_hosts: Option<Vec<String>>,
+    /// Time allowed to establish the connection.
+    pub connect_timeout: Duration,
+    /// Time allowed between two reads; a stalled transfer fails after it.
+    pub read_timeout: Duration,
+    /// Time allowed for the whole fetch including the body, so a trickling response cannot stall the update cycle.
+    pub deadline: Duration,
+    /// Address family the source is fetched over.
+    pub family: AddressFamily,
+    /// Maximum download rate in bytes per second, so large feeds do not saturate small uplinks.
+    pub rate_limit: Option<u64>,
+    /// Proxies the source is fetched through.
+    pub proxies: Proxies,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        Self {
+            allow_http: false,
+            max_redirects: MAX_REDIRECTS,
+            redirect_hosts: None,
+            connect_timeout: Duration::from_secs(TIMEOUT),
+            read_timeout: Duration::from_secs(TIMEOUT),
+            deadline: Duration::from_secs(DEADLINE),
+            family: AddressFamily::Any,
+            rate_limit: None,
+            proxies: Proxies::default(),
+        }
+    }
+}
+
+impl FetchPolicy {
+    /// Creates the `FetchPolicy` of a `source` (`IPV4` or `IPV6`) from the environment.
+    ///
+    /// Every setting is read from `NFTBLOCKD_<SOURCE>_<SETTING>` first and then from `NFTBLOCKD_<SETTING>`,
+    /// e.g., `NFTBLOCKD_IPV4_MAX_REDIRECTS` overrides `NFTBLOCKD_MAX_REDIRECTS` for the IPv4 source.
+    /// The deadline falls back to the former global `NFTBLOCKD_REQUEST_TIMEOUT`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a setting cannot be parsed.
+    pub fn from_env(environment: &Environment, source: &str) -> Result<Self, AppError> {
+        let defaults = Self::default();
+        let allow_http = source_var(environment, source, "ALLOW_HTTP")
+            .map(|v| {
+                v.parse::<bool>().map_err(|e| {
+                    AppError::ParseError(format!("invalid NFTBLOCKD_ALLOW_HTTP: {v}: {e}"))
+                })
+            })
+            .transpose()?
+            .unwrap_or(defaults.allow_http);
+        let max_redirects = source_var(environment, source, "MAX_REDIRECTS")
+            .map(|v| v.parse::<usize>())
+            .transpose()?
+            .unwrap_or(defaults.max_redirects);
+        let redirect_hosts = source_var(environment, source, "REDIRECT_HOSTS").map(|hosts| {
+            hosts
+                .split(',')
+                .map(|h| h.trim().to_ascii_lowercase())
+                .filter(|h| !h.is_empty())
+                .collect()
+        });
+        let connect_timeout = source_duration(environment, source, "CONNECT_TIMEOUT")?
+            .unwrap_or(defaults.connect_timeout);
+        let read_timeout =
+            source_duration(environment, source, "READ_TIMEOUT")?.unwrap_or(defaults.read_timeout);
+        let deadline = match source_duration(environment, source, "FETCH_DEADLINE")? {
+            Some(deadline) => deadline,
+            None => source_duration(environment, source, "REQUEST_TIMEOUT")?
+                .unwrap_or(defaults.deadline),
+        };
+        let family = source_var(environment, source, "FETCH_FAMILY")
+            .map(|v| AddressFamily::parse(&v))
+            .transpose()?
+            .unwrap_or(defaults.family);
+        let rate_limit = source_var(environment, source, "FETCH_RATE_LIMIT")
+            .map(|v| {
+                parse_size(&v).ok_or_else(|| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_FETCH_RATE_LIMIT: {v}; expected bytes per second such as `256K`"
+                    ))
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            allow_http,
+            max_redirects,
+            redirect_hosts,
+            connect_timeout,
+            read_timeout,
+            deadline,
+            family,
+            rate_limit,
+            proxies: Proxies::from_env(environment)?,
+        })
+    }
+
+    /// Checks that `source` may be fetched from.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the URL is invalid, uses plain HTTP without `allow_http`, or another scheme.
+    /// Git sources may also be cloned over `ssh` or from a local `file` path.
+    pub fn check_source(&self, source: &str) -> Result<(), AppError> {
+        if GitSource::is_git(source) {
+            return match GitSource::parse(source)?.scheme() {
+                "https" | "ssh" | "file" => Ok(()),
+                "http" if self.allow_http => Ok(()),
+                "http" => Err(AppError::ParseError(format!(
+                    "refusing plain-HTTP source {source}; use HTTPS or set NFTBLOCKD_ALLOW_HTTP=true"
+                ))),
+                scheme => Err(AppError::ParseError(format!(
+                    "unsupported scheme `{scheme}` of git source {source}; expected https, ssh, or file"
+                ))),
+            };
+        }
+        let url = Url::parse(source)
+            .map_err(|e| AppError::ParseError(format!("invalid source URL: {source}: {e}")))?;
+        match url.scheme() {
+            "https" => Ok(()),
+            "http" if self.allow_http => Ok(()),
+            "http" => Err(AppError::ParseError(format!(
+                "refusing plain-HTTP source {source}; use HTTPS or set NFTBLOCKD_ALLOW_HTTP=true"
+            ))),
+            scheme => Err(AppError::ParseError(format!(
+                "unsupported scheme `{scheme}` of source {source}"
+            ))),
+        }
+    }
+
+    /// Returns whether `host` matches an entry of `redirect_hosts`; `*.example.com` matches its subdomains.
+    #[must_use]
+    pub fn allows_redirect_host(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.redirect_hosts.as_ref().is_none_or(|hosts| {
+            hosts
+                .iter()
+                .any(|allowed| match allowed.strip_prefix("*.") {
+                    Some(domain) => host.ends_with(&format!(".{domain}")),
+                    None => *allowed == host,
+                })
+        })
+    }
+
+    /// Decides whether a redirect is followed, see the rules of `FetchPolicy`.
+    fn check_redirect(
+        &self,
+        attempt: Attempt<'_>,
+        trusted: &HashSet<String>,
+    ) -> reqwest::redirect::Action {
+        if self.max_redirects == 0 {
+            return attempt.error("redirects are disabled");
+        }
+        if attempt.previous().len() > self.max_redirects {
+            let max_redirects = self.max_redirects;
+            return attempt.error(format!("more than {max_redirects} redirects"));
+        }
+        let downgrade = attempt.url().scheme() == "http"
+            && attempt
+                .previous()
+                .iter()
+                .any(|previous| previous.scheme() == "https");
+        if downgrade {
+            let target = attempt.url().to_string();
+            return attempt.error(format!(
+                "refusing redirect from HTTPS to plain HTTP: {target}"
+            ));
+        }
+        let host = attempt.url().host_str().unwrap_or_default().to_string();
+        if !self.allows_redirect_host(&host) {
+            return attempt.error(format!(
+                "refusing redirect to {host}: not an allowed redirect host"
+            ));
+        }
+        let ip = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>();
+        if let Ok(ip) = ip
+            && !is_public_ip(ip)
+            && !trusted.contains(&host)
+        {
+            return attempt.error(format!("refusing redirect to non-public address {ip}"));
+        }
+        attempt.follow()
+    }
+
+    /// Applies the timeouts, the redirect rules, the address family, the address filter, and the proxies
+    /// to `builder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The configured source URLs, whose hosts are trusted.
+    pub fn apply<'a>(
+        &self,
+        builder: ClientBuilder,
+        sources: impl IntoIterator<Item = &'a str>,
+    ) -> ClientBuilder {
+        let trusted = Arc::new(trusted_hosts(sources, &self.proxies));
+        let redirect_trusted = trusted.clone();
+        let policy = self.clone();
+        let builder = self.proxies.apply(builder);
+        let builder = match self.family {
+            AddressFamily::Any => builder,
+            AddressFamily::IPv4 => builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            AddressFamily::IPv6 => builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        };
+        builder
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .timeout(self.deadline)
+            .redirect(Policy::custom(move |attempt| {
+                policy.check_redirect(attempt, &redirect_trusted)
+            }))
+            .dns_resolver(Arc::new(PublicResolver {
+                trusted,
+                family: self.family,
+            }))
+    }
+}
+
+/// Reads `NFTBLOCKD_<SOURCE>_<NAME>`, falling back to `NFTBLOCKD_<NAME>`; empty values count as unset.
+#[must_use]
+pub fn source_var(environment: &Environment, source: &str, name: &str) -> Option<String> {
+    environment
+        .var(format!("NFTBLOCKD_{source}_{name}"))
+        .ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| {
+            environment
+                .var(format!("NFTBLOCKD_{name}"))
+                .ok()
+                .filter(|v| !v.is_empty())
+        })
+}
+
+/// Reads a duration with `source_var`.
+///
+/// # Errors
+/// Will return `AppError` when the value is not a valid duration.
+fn source_duration(
+    environment: &Environment,
+    source: &str,
+    name: &str,
+) -> Result<Option<Duration>, AppError> {
+    source_var(environment, source, name)
+        .map(|v| {
+            parse_duration(&v).map_err(|e| AppError::ParseError(format!("NFTBLOCKD_{name}: {e}")))
+        })
+        .transpose()
+}
+
+/// Parses a `Retry-After` header, either delay seconds or an HTTP date, into the time of the next attempt.
+#[must_use]
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<SystemTime> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return now.checked_add(Duration::from_secs(seconds));
+    }
+    parse_http_date(value)
+}
+
+/// Parses an HTTP date, e.g., `Thu, 01 Jan 2026 00:00:00 GMT`, as sent in `Retry-After` or `Last-Modified`.
+#[must_use]
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let format = format_description!(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+    );
+    PrimitiveDateTime::parse(value.trim(), format)
+        .ok()
+        .map(|date| SystemTime::from(date.assume_utc()))
+}
+
+/// Collects the hosts of the sources and of the configured proxies.
+fn trusted_hosts<'a>(
+    sources: impl IntoIterator<Item = &'a str>,
+    proxies: &Proxies,
+) -> HashSet<String> {
+    sources
+        .into_iter()
+        .map(ToString::to_string)
+        .chain(proxies.urls.iter().cloned())
+        .filter_map(|url| Url::parse(&url).ok()?.host_str().map(ToString::to_string))
+        .collect()
+}
+
+/// Returns whether `ip` is reachable on the public internet, i.e., not loopback, private,
+/// link-local, shared (CGNAT), unspecified, broadcast, or multicast.
+#[must_use]
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || shared)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(mapped));
+            }
+            let segment = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (segment & 0xfe00) == 0xfc00
+                || (segment & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolver refusing non-public addresses for every host except the trusted ones,
+/// so that redirects to a hostname pointing into the internal network fail.
+/// Addresses of other families than the pinned one are dropped as well.
+#[derive(Debug)]
+struct PublicResolver {
+    trusted: Arc<HashSet<String>>,
+    family: AddressFamily,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let trusted = self.trusted.contains(&host);
+        let family = self.family;
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| trusted || is_public_ip(addr.ip()))
+                .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(
+                    format!("refusing to connect to {host}: it has no public address").into(),
+                );
+            }
+            let addrs = addrs
+                .into_iter()
+                .filter(|addr| family.allows(addr.ip()))
+                .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(format!("{host} has no {family} address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::{AppError, ErrorSource};
+use log::{debug, info};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Prefix of the sources read from a git repository.
+const PREFIX: &str = "git+";
+
+/// A blocklist read from a file of a git repository, e.g., `git+https://git.example.com/lists.git#ipv4.txt`.
+///
+/// Lists kept in a repository change through its usual code review; nftblockd clones the repository
+/// once, pulls its default branch on every update, and reports the commit it applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSource {
+    /// The URL the repository is cloned from, e.g., `https://git.example.com/lists.git`
+    /// or `ssh://git@git.example.com/lists.git`.
+    pub remote: String,
+    /// Path of the list within the repository.
+    pub path: PathBuf,
+}
+
+impl GitSource {
+    /// Returns whether `source` is read from a git repository.
+    #[must_use]
+    pub fn is_git(source: &str) -> bool {
+        source.starts_with(PREFIX)
+    }
+
+    /// Parses a `git+<remote>#<path>` source.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the source lacks the path of the list, or the path leaves the repository.
+    pub fn parse(source: &str) -> Result<Self, AppError> {
+        let invalid =
+            |reason: &str| AppError::ParseError(format!("invalid git source {source}: {reason}"));
+        let (remote, path) = source
+            .strip_prefix(PREFIX)
+            .ok_or_else(|| invalid("expected git+<remote>#<path>"))?
+            .split_once('#')
+            .ok_or_else(|| invalid("missing #<path> of the list"))?;
+        let path = PathBuf::from(path);
+        if remote.is_empty()
+            || path.as_os_str().is_empty()
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(invalid("expected a relative path within the repository"));
+        }
+        Ok(Self {
+            remote: remote.to_string(),
+            path,
+        })
+    }
+
+    /// Returns the scheme of the remote, e.g., `https` or `ssh`.
+    #[must_use]
+    pub fn scheme(&self) -> &str {
+        self.remote
+            .split_once("://")
+            .map_or("", |(scheme, _)| scheme)
+    }
+
+    /// Returns the directory within `dir` the repository is checked out to.
+    #[must_use]
+    pub fn checkout(&self, dir: &Path) -> PathBuf {
+        let name = self
+            .remote
+            .split_once("://")
+            .map_or(self.remote.as_str(), |(_, rest)| rest)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        dir.join(name)
+    }
+
+    /// Clones the repository into `dir`, or pulls its default branch when already cloned, and reads the list.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory the repositories are checked out to.
+    /// * `timeout` - Time allowed for the clone or pull.
+    ///
+    /// # Returns
+    ///
+    /// The content of the list and the hash of the commit it was read at.
+    ///
+    /// # Errors
+    /// Will return `AppError` when `git` fails or the list cannot be read.
+    pub async fn fetch(&self, dir: &Path, timeout: Duration) -> Result<(String, String), AppError> {
+        let checkout = self.checkout(dir);
+        if checkout.join(".git").is_dir() {
+            debug!("pulling {}", self.remote);
+            git(
+                &checkout,
+                &["fetch", "--depth", "1", "origin", "HEAD"],
+                timeout,
+            )
+            .await?;
+            git(&checkout, &["reset", "--hard", "FETCH_HEAD"], timeout).await?;
+        } else {
+            info!("cloning {}", self.remote);
+            std::fs::create_dir_all(dir).map_err(|e| {
+                AppError::FileError(
+                    format!("failed to create the git directory: {}", dir.display()),
+                    Some(ErrorSource::new(e)),
+                )
+            })?;
+            let _ = std::fs::remove_dir_all(&checkout);
+            let target = checkout.to_string_lossy();
+            git(
+                dir,
+                &["clone", "--depth", "1", "--", &self.remote, &target],
+                timeout,
+            )
+            .await?;
+        }
+        let commit = git(&checkout, &["rev-parse", "HEAD"], timeout).await?;
+        let list = checkout.join(&self.path);
+        let body = tokio::fs::read_to_string(&list).await.map_err(|e| {
+            AppError::FileError(
+                format!(
+                    "failed to read {} from {}",
+                    self.path.display(),
+                    self.remote
+                ),
+                Some(ErrorSource::new(e)),
+            )
+        })?;
+        Ok((body, commit))
+    }
+}
+
+/// Runs `git` with `args` in `dir` and returns its trimmed standard output.
+///
+/// Prompts are disabled, so that a remote asking for credentials fails instead of hanging the update.
+async fn git(dir: &Path, args: &[&str], timeout: Duration) -> Result<String, AppError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(timeout, output)
+        .await
+        .map_err(|_| {
+            AppError::RequestError(
+                format!("git {} timed out after {} s", args[0], timeout.as_secs()),
+                None,
+            )
+        })?
+        .map_err(|e| {
+            AppError::RequestError(
+                format!("failed to run git {}", args[0]),
+                Some(ErrorSource::new(e)),
+            )
+        })?;
+    if !output.status.success() {
+        return Err(AppError::RequestError(
+            format!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            None,
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,214 @@
+use crate::error::{AppError, ErrorSource};
+use crate::set::fetch_policy::parse_http_date;
+use crate::settings::Environment;
+use log::warn;
+use reqwest::header::{AGE, CACHE_CONTROL, DATE, EXPIRES, HeaderMap};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A response body with its `ETag` and the time it stops being fresh.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Stored {
+    /// Unix timestamp after which the source is downloaded again.
+    expires: u64,
+    etag: Option<String>,
+    body: String,
+}
+
+/// Keeps the responses of the sources for as long as their caching headers declare them fresh.
+///
+/// A source answering with `Cache-Control: max-age` or `Expires` is then not downloaded again before the
+/// provider intends it to be, even with a shorter update interval, and across restarts, since the responses are
+/// persisted. Responses marked `no-store` or `no-cache`, or without any freshness, are never reused.
+#[derive(Debug)]
+pub struct HttpCache {
+    /// File the responses are persisted to; they are only kept in memory when `None`.
+    path: Option<PathBuf>,
+    responses: Mutex<HashMap<String, Stored>>,
+}
+
+impl HttpCache {
+    /// Creates an `HttpCache` persisting its responses to `path`.
+    #[must_use]
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let responses = path.as_deref().map(load).unwrap_or_default();
+        Self {
+            path,
+            responses: Mutex::new(responses),
+        }
+    }
+
+    /// Creates an `HttpCache` when `NFTBLOCKD_HTTP_CACHE` is `true`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the variable is not a boolean.
+    pub fn from_env(
+        environment: &Environment,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Self>, AppError> {
+        let enabled = environment
+            .var("NFTBLOCKD_HTTP_CACHE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|value| {
+                value.parse::<bool>().map_err(|e| {
+                    AppError::ParseError(format!("invalid NFTBLOCKD_HTTP_CACHE: {value}: {e}"))
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        Ok(enabled.then(|| Self::new(path)))
+    }
+
+    /// Returns the body and the `ETag` of the response of `url` requested with `headers` while it is fresh at `now`.
+    #[must_use]
+    pub fn fresh(
+        &self,
+        url: &str,
+        headers: Option<&HashMap<String, String>>,
+        now: SystemTime,
+    ) -> Option<(String, Option<String>)> {
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&key(url, headers))
+            .filter(|stored| stored.expires > unix_time(now))
+            .map(|stored| (stored.body.clone(), stored.etag.clone()))
+    }
+
+    /// Stores the response of `url` requested with `headers` if its `response_headers` declare it fresh at `now`,
+    /// and persists the cache.
+    ///
+    /// # Returns
+    ///
+    /// How long the response is fresh, or `None` when it is not cached.
+    pub fn store(
+        &self,
+        url: &str,
+        headers: Option<&HashMap<String, String>>,
+        response_headers: &HeaderMap,
+        body: &str,
+        etag: Option<&str>,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        let freshness = freshness(response_headers, now)?;
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                key(url, headers),
+                Stored {
+                    expires: unix_time(now + freshness),
+                    etag: etag.map(ToString::to_string),
+                    body: body.to_string(),
+                },
+            );
+        if let Some(path) = &self.path
+            && let Err(e) = self.save(path, now)
+        {
+            warn!("{e}");
+        }
+        Some(freshness)
+    }
+
+    /// Persists the responses to `path`, leaving out the ones no longer fresh.
+    fn save(&self, path: &Path, now: SystemTime) -> Result<(), AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!("failed to write the HTTP cache: {}", path.display()),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        let data = {
+            let mut responses = self
+                .responses
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            responses.retain(|_, stored| stored.expires > unix_time(now));
+            serde_json::to_string(&*responses)?
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(file_error)?;
+        fs::rename(&tmp, path).map_err(file_error)
+    }
+}
+
+/// Returns how long a response with `headers` received at `now` stays fresh.
+///
+/// `Cache-Control: max-age` takes precedence over `Expires`, and the `Age` the response already spent in
+/// other caches is deducted from it; `Expires` is taken relative to the `Date` of the response when present,
+/// so that a skewed clock of the server does not matter.
+///
+/// # Returns
+///
+/// `None` when the response is marked `no-store` or `no-cache`, declares no freshness, or is already stale.
+#[must_use]
+pub fn freshness(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let directives = header(CACHE_CONTROL)
+        .unwrap_or_default()
+        .split(',')
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    if directives
+        .iter()
+        .any(|directive| directive == "no-store" || directive == "no-cache")
+    {
+        return None;
+    }
+    let max_age = directives.iter().find_map(|directive| {
+        directive
+            .strip_prefix("max-age=")
+            .and_then(|seconds| seconds.trim_matches('"').parse::<u64>().ok())
+    });
+    let freshness = match max_age {
+        Some(max_age) => {
+            let age = header(AGE)
+                .and_then(|age| age.trim().parse::<u64>().ok())
+                .unwrap_or_default();
+            Duration::from_secs(max_age.saturating_sub(age))
+        }
+        None => {
+            let expires = parse_http_date(header(EXPIRES)?)?;
+            let date = header(DATE).and_then(parse_http_date).unwrap_or(now);
+            expires.duration_since(date).ok()?
+        }
+    };
+    Some(freshness).filter(|freshness| !freshness.is_zero())
+}
+
+/// Identifies a request by its URL and headers; the headers are hashed, so that no credentials are persisted.
+fn key(url: &str, headers: Option<&HashMap<String, String>>) -> String {
+    let mut hasher = DefaultHasher::new();
+    headers
+        .map(|headers| headers.iter().collect::<BTreeMap<_, _>>())
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{url} {:016x}", hasher.finish())
+}
+
+/// Reads the persisted responses, starting over when the file is missing or corrupt.
+fn load(path: &Path) -> HashMap<String, Stored> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("ignoring the invalid HTTP cache {}: {e}", path.display());
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
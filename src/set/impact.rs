@@ -0,0 +1,142 @@
+use crate::error::AppError;
+use crate::set::simulation::{Outcome, Simulation};
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// A connection tracked by the kernel or exported by a flow collector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// Protocol of the flow, e.g., `tcp`; empty when unknown.
+    pub protocol: String,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// State of a tracked TCP connection, e.g., `ESTABLISHED`.
+    pub state: Option<String>,
+}
+
+impl Display for Flow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} -> {}",
+            self.protocol, self.source, self.destination
+        )?;
+        if let Some(state) = &self.state {
+            write!(f, " {state}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the connections of `/proc/net/nf_conntrack` or of `conntrack -L`, taking the original direction
+/// of every connection; lines without addresses are skipped.
+#[must_use]
+pub fn parse_conntrack(data: &str) -> Vec<Flow> {
+    data.lines()
+        .filter_map(|line| {
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            let address = |key: &str| {
+                tokens
+                    .iter()
+                    .find_map(|token| token.strip_prefix(key))
+                    .and_then(|address| address.parse::<IpAddr>().ok())
+            };
+            // `/proc/net/nf_conntrack` prefixes the family, e.g., `ipv4 2 tcp 6 ...`.
+            let protocol = tokens
+                .iter()
+                .find(|token| {
+                    token
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+                        && !token.starts_with(|c: char| c.is_ascii_digit())
+                        && !matches!(**token, "ipv4" | "ipv6")
+                })
+                .map_or_else(String::new, ToString::to_string);
+            let state = tokens
+                .iter()
+                .take_while(|token| !token.contains('='))
+                .find(|token| {
+                    token.len() > 1 && token.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+                })
+                .map(ToString::to_string);
+            Some(Flow {
+                protocol,
+                source: address("src=")?,
+                destination: address("dst=")?,
+                state,
+            })
+        })
+        .collect()
+}
+
+/// Parses a flow export in CSV, e.g., of `nfdump -o csv` or an sFlow collector, by the columns named in its header:
+/// `sa`, `src`, `srcaddr`, `src_addr`, or `source` for the source; `da`, `dst`, `dstaddr`, `dst_addr`,
+/// or `destination` for the destination; and optionally `pr`, `proto`, or `protocol`.
+/// Rows without valid addresses, e.g., a trailing summary, are skipped.
+///
+/// # Errors
+/// Will return `AppError` when the header names no source or destination column.
+pub fn parse_flow_csv(data: &str) -> Result<Vec<Flow>, AppError> {
+    let mut lines = data.lines();
+    let header = lines
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(|column| column.trim().trim_matches('"').to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let column = |names: &[&str]| header.iter().position(|c| names.contains(&c.as_str()));
+    let (Some(source), Some(destination)) = (
+        column(&["sa", "src", "srcaddr", "src_addr", "source"]),
+        column(&["da", "dst", "dstaddr", "dst_addr", "destination"]),
+    ) else {
+        return Err(AppError::ParseError(
+            "the flow CSV names no source or destination address column".to_string(),
+        ));
+    };
+    let protocol = column(&["pr", "proto", "protocol"]);
+    Ok(lines
+        .filter_map(|line| {
+            let fields = line
+                .split(',')
+                .map(|field| field.trim().trim_matches('"'))
+                .collect::<Vec<_>>();
+            Some(Flow {
+                protocol: protocol
+                    .and_then(|p| fields.get(p))
+                    .map_or_else(String::new, |p| p.to_ascii_lowercase()),
+                source: fields.get(source)?.parse().ok()?,
+                destination: fields.get(destination)?.parse().ok()?,
+                state: None,
+            })
+        })
+        .collect())
+}
+
+/// Reads the flows of `path`, a flow export when it ends in `.csv` and a conntrack table otherwise.
+///
+/// # Errors
+/// Will return `AppError` when the file cannot be read or parsed.
+pub fn read_flows(path: &Path) -> Result<Vec<Flow>, AppError> {
+    let data = std::fs::read_to_string(path)?;
+    if path.extension().is_some_and(|extension| extension == "csv") {
+        parse_flow_csv(&data)
+    } else {
+        Ok(parse_conntrack(&data))
+    }
+}
+
+/// Returns the flows whose source or destination `simulation` drops, with the set and element dropping it.
+#[must_use]
+pub fn affected_flows<'f>(flows: &'f [Flow], simulation: &Simulation) -> Vec<(&'f Flow, Outcome)> {
+    flows
+        .iter()
+        .filter_map(|flow| {
+            [flow.source, flow.destination]
+                .into_iter()
+                .map(|address| simulation.outcome(address))
+                .find(|outcome| matches!(outcome, Outcome::Dropped { .. }))
+                .map(|outcome| (flow, outcome))
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use crate::error::{AppError, ErrorSource};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A manually blocked address or network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManualEntry {
+    /// The blocked network in its canonical `address/prefix` form.
+    pub network: String,
+    /// Unix timestamp at which the entry expires; never when `None`.
+    pub expires: Option<u64>,
+    /// Free-form note, e.g., a ticket number, shown by `nft list set`.
+    pub comment: Option<String>,
+}
+
+impl ManualEntry {
+    /// Returns the time the entry stays blocked, `None` for a permanent entry, or `Some(Duration::ZERO)`
+    /// once it has expired.
+    #[must_use]
+    pub fn remaining(&self, now: SystemTime) -> Option<Duration> {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.expires
+            .map(|expires| Duration::from_secs(expires).saturating_sub(now))
+    }
+}
+
+/// Entries added with `nftblockd add`, persisted as JSON so that they survive restarts and re-applies.
+#[derive(Debug, Clone)]
+pub struct ManualSet {
+    path: PathBuf,
+    entries: Vec<ManualEntry>,
+}
+
+impl ManualSet {
+    /// Loads the entries stored at `path`; a missing file is an empty set.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be read or parsed.
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let entries = match fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| {
+                AppError::ParseError(format!("invalid manual set file: {}: {e}", path.display()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(AppError::FileError(
+                    format!("failed to read the manual set file: {}", path.display()),
+                    Some(ErrorSource::new(e)),
+                ));
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// Returns all stored entries, including the expired ones not yet removed.
+    #[must_use]
+    pub fn entries(&self) -> &[ManualEntry] {
+        &self.entries
+    }
+
+    /// Returns the entries that have not expired yet.
+    #[must_use]
+    pub fn active(&self, now: SystemTime) -> Vec<ManualEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.remaining(now) != Some(Duration::ZERO))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether there is an entry for `network`, expired or not.
+    #[must_use]
+    pub fn contains(&self, network: &str) -> bool {
+        normalize(network).is_ok_and(|network| self.entries.iter().any(|e| e.network == network))
+    }
+
+    /// Adds an entry, replacing an existing entry for the same network, and drops the expired ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - An address or network, e.g., `203.0.113.7` or `2001:db8::/32`.
+    /// * `ttl` - How long the entry stays blocked; permanently when `None`.
+    /// * `comment` - Free-form note stored with the entry.
+    /// * `now` - The current time.
+    ///
+    /// # Errors
+    /// Will return `AppError` when `network` is not an address or network.
+    pub fn add(
+        &mut self,
+        network: &str,
+        ttl: Option<Duration>,
+        comment: Option<String>,
+        now: SystemTime,
+    ) -> Result<ManualEntry, AppError> {
+        let network = normalize(network)?;
+        let expires = ttl.map(|ttl| {
+            (now + ttl)
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        });
+        let entry = ManualEntry {
+            network,
+            expires,
+            comment,
+        };
+        self.entries = self.active(now);
+        self.entries.retain(|e| e.network != entry.network);
+        self.entries.push(entry.clone());
+        self.entries.sort_by(|a, b| a.network.cmp(&b.network));
+        Ok(entry)
+    }
+
+    /// Removes the entry for `network`.
+    ///
+    /// # Returns
+    ///
+    /// `false` when there was no such entry.
+    ///
+    /// # Errors
+    /// Will return `AppError` when `network` is not an address or network.
+    pub fn remove(&mut self, network: &str) -> Result<bool, AppError> {
+        let network = normalize(network)?;
+        let before = self.entries.len();
+        self.entries.retain(|e| e.network != network);
+        Ok(self.entries.len() != before)
+    }
+
+    /// Writes the entries to the file, replacing it atomically.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be written.
+    pub fn save(&self) -> Result<(), AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!(
+                    "failed to write the manual set file: {}",
+                    self.path.display()
+                ),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.entries)?).map_err(file_error)?;
+        fs::rename(&tmp, &self.path).map_err(file_error)
+    }
+}
+
+/// Parses an address or network into its canonical `address/prefix` form.
+fn normalize(network: &str) -> Result<String, AppError> {
+    let parsed = IpNetwork::from_str(network.trim())
+        .map_err(|e| AppError::ParseError(format!("invalid network: {network}: {e}")))?;
+    Ok(format!("{}/{}", parsed.network(), parsed.prefix()))
+}
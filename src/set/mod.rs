@@ -1,2 +1,27 @@
+pub mod attribution;
 pub mod blocklist;
+pub mod burn_in;
+pub mod change_rate;
+pub mod cloud;
+pub mod consensus;
 pub mod custom_set;
+pub mod dual_stack;
+pub mod element_cache;
+pub mod export;
+pub mod feed;
+pub mod fetch_policy;
+pub mod git;
+pub mod http_cache;
+pub mod impact;
+pub mod manual;
+pub mod observer;
+pub mod overrides;
+pub mod policy;
+pub mod quarantine;
+pub mod resolver;
+pub mod routing;
+pub mod shared_fetch;
+pub mod simulation;
+pub mod tor;
+pub mod trace;
+pub mod url_template;
@@ -0,0 +1,75 @@
+use crate::error::AppError;
+use crate::nftables::builder::{RuleProto, SetElements};
+use crate::set::change_rate::ChangeAnomaly;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Summary of a successfully applied blocklist update.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateReport {
+    /// Name of the table the ruleset was applied to.
+    pub table_name: String,
+    /// Number of IPv4 elements loaded into the blocklist set.
+    pub ipv4_elements: usize,
+    /// Number of IPv6 elements loaded into the blocklist set.
+    pub ipv6_elements: usize,
+    /// Time taken by the whole update (fetch, parse and apply).
+    pub duration: Duration,
+    /// Time spent downloading the blocklists.
+    pub fetch_duration: Duration,
+    /// Time spent validating, deduplicating and transforming the blocklists.
+    pub parse_duration: Duration,
+    /// Time spent applying the ruleset.
+    pub apply_duration: Duration,
+    /// Number of invalid entries skipped in the IPv4 blocklist.
+    pub ipv4_invalid_entries: usize,
+    /// Number of invalid entries skipped in the IPv6 blocklist.
+    pub ipv6_invalid_entries: usize,
+    /// Commits the git sources were applied at, by source.
+    pub revisions: BTreeMap<String, String>,
+    /// Peak resident memory of the process during the update in bytes; `None` when it cannot be read.
+    pub peak_memory: Option<u64>,
+}
+
+/// Hooks invoked by `BlockList` during the update lifecycle.
+///
+/// Every method has a no-op default, so implementors only override the events they care about.
+/// Hooks are called synchronously from the update loop and should return quickly.
+pub trait UpdateObserver: Send + Sync {
+    /// Called when an update cycle starts, before any blocklist is fetched.
+    fn on_cycle_start(&self) {}
+
+    /// Called right before a blocklist is fetched from `endpoint`.
+    fn on_fetch_start(&self, _endpoint: &str) {}
+
+    /// Called after a blocklist has been fetched from `endpoint` with the number of its `entries`,
+    /// or `None` when the source reported it as not modified.
+    fn on_fetched(&self, _endpoint: &str, _entries: Option<usize>) {}
+
+    /// Called after the ruleset has been successfully applied.
+    fn on_applied(&self, _report: &UpdateReport) {}
+
+    /// Called after the ruleset has been applied, once for every source, with the elements it contributed,
+    /// which are empty when the source listed nothing. `proto` tells whether the elements are IPv4
+    /// (`RuleProto::Ip`) or IPv6 (`RuleProto::Ip6`).
+    fn on_blocked(&self, _source: &str, _proto: &RuleProto, _elements: &SetElements<'_>) {}
+
+    /// Called when an update attempt fails.
+    fn on_error(&self, _error: &AppError) {}
+
+    /// Called when fetching or parsing the blocklist of `source` fails, before the update fails or goes on
+    /// without it.
+    fn on_source_failed(&self, _source: &str, _error: &AppError) {}
+
+    /// Called when the retry budget is exhausted and the table is about to be flushed.
+    fn on_retries_exhausted(&self, _error: &AppError) {}
+
+    /// Called when an error that retrying cannot fix paused updates; the last applied ruleset is kept.
+    fn on_updates_stopped(&self, _error: &AppError) {}
+
+    /// Called once when the data of `source` becomes older than the configured maximum age.
+    fn on_stale(&self, _source: &str, _age: Duration) {}
+
+    /// Called when a fresh fetch of a source changed far more entries than its rolling average.
+    fn on_change_anomaly(&self, _anomaly: &ChangeAnomaly) {}
+}
@@ -0,0 +1,115 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use crate::utils::read_ip_set_file;
+
+/// Addresses pinned or exempted by the operator regardless of what the feeds list.
+///
+/// The overrides file has a `block:` and a `never-block:` section with one address, network, or range
+/// per line; list markers (`- `) and `#` comments are allowed:
+///
+/// ```text
+/// block:
+///   - 203.0.113.7
+/// never-block:
+///   - 192.0.2.0/24  # monitoring
+/// ```
+///
+/// `block` entries are merged into the custom blocklist and `never-block` entries into the anti-lockout set,
+/// whose accept rules come before every drop rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    /// Entries always dropped.
+    pub block: Vec<String>,
+    /// Entries never dropped.
+    pub never_block: Vec<String>,
+}
+
+impl Overrides {
+    /// Reads the overrides file set in `NFTBLOCKD_OVERRIDES_PATH`.
+    ///
+    /// # Returns
+    ///
+    /// Empty overrides when the variable is unset.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be read or parsed.
+    pub fn from_env(environment: &Environment) -> Result<Self, AppError> {
+        let path = environment
+            .var("NFTBLOCKD_OVERRIDES_PATH")
+            .ok()
+            .filter(|s| !s.is_empty());
+        match read_ip_set_file(path.as_ref())? {
+            Some(data) => Self::parse(&data).map_err(|e| {
+                AppError::ParseError(format!(
+                    "invalid overrides file: {}: {e}",
+                    path.unwrap_or_default()
+                ))
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parses the contents of an overrides file.
+    ///
+    /// # Errors
+    /// Will return `AppError` for an entry outside of a section.
+    pub fn parse(data: &str) -> Result<Self, AppError> {
+        let mut overrides = Self::default();
+        let mut section = None;
+        for (number, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            match line {
+                "" => {}
+                "block:" => section = Some(&mut overrides.block),
+                "never-block:" => section = Some(&mut overrides.never_block),
+                entry => {
+                    let entry = entry.strip_prefix('-').unwrap_or(entry).trim();
+                    let Some(section) = section.as_deref_mut() else {
+                        return Err(AppError::ParseError(format!(
+                            "line {}: {entry} is outside of a `block:` or `never-block:` section",
+                            number + 1
+                        )));
+                    };
+                    section.push(entry.to_string());
+                }
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// Returns the `block` entries of one family.
+    #[must_use]
+    pub fn block(&self, ipv6: bool) -> Vec<String> {
+        family(&self.block, ipv6)
+    }
+
+    /// Returns the `never-block` entries of one family.
+    #[must_use]
+    pub fn never_block(&self, ipv6: bool) -> Vec<String> {
+        family(&self.never_block, ipv6)
+    }
+}
+
+/// Appends the override entries to the configured ones.
+#[must_use]
+pub fn merge_entries(
+    configured: Option<Vec<String>>,
+    overrides: Vec<String>,
+) -> Option<Vec<String>> {
+    match configured {
+        Some(mut configured) => {
+            configured.extend(overrides);
+            Some(configured)
+        }
+        None if overrides.is_empty() => None,
+        None => Some(overrides),
+    }
+}
+
+fn family(entries: &[String], ipv6: bool) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|entry| entry.contains(':') == ipv6)
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,254 @@
+use crate::error::AppError;
+use crate::nftables::builder::SetElements;
+use crate::nftables::element_label;
+use crate::settings::Environment;
+use crate::utils::read_ip_set_file;
+use ipnetwork::IpNetwork;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Violations quoted in the error failing the update; all of them are logged.
+const QUOTED_VIOLATIONS: usize = 10;
+
+/// Rules the final blocklist sets must satisfy before they are applied.
+///
+/// The policy file has one `key: value` rule per line; `#` comments are allowed and `never-block` may be repeated:
+///
+/// ```text
+/// never-block: 192.0.2.0/24  # monitoring
+/// max-elements: 200000
+/// min-prefix-ipv4: 10
+/// min-prefix-ipv6: 32
+/// ```
+///
+/// Unlike the `never-block` entries of the overrides file, which punch holes into the blocklist, a violated
+/// policy fails the update, so that a feed listing a protected network, or growing far beyond what is expected,
+/// is noticed instead of being silently fixed up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ElementPolicy {
+    /// Networks no element may overlap.
+    pub never_block: Vec<IpNetwork>,
+    /// Maximum number of elements of both sets together.
+    pub max_elements: Option<usize>,
+    /// Shortest IPv4 prefix an element may have, e.g., `10` refuses a `/8`.
+    pub min_prefix_ipv4: Option<u8>,
+    /// Shortest IPv6 prefix an element may have.
+    pub min_prefix_ipv6: Option<u8>,
+}
+
+/// A rule of the `ElementPolicy` broken by the blocklist sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// `element` overlaps the protected `network`.
+    NeverBlock { element: String, network: IpNetwork },
+    /// The sets hold `elements`, more than `max`.
+    MaxElements { elements: usize, max: usize },
+    /// `element` is broader than a `/min` prefix.
+    MinPrefix { element: String, min: u8 },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NeverBlock { element, network } => {
+                write!(f, "{element} overlaps the never-block network {network}")
+            }
+            Self::MaxElements { elements, max } => {
+                write!(f, "{elements} elements exceed max-elements {max}")
+            }
+            Self::MinPrefix { element, min } => {
+                write!(f, "{element} is broader than the minimum prefix /{min}")
+            }
+        }
+    }
+}
+
+impl ElementPolicy {
+    /// Reads the policy file set in `NFTBLOCKD_POLICY_PATH`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when the variable is unset.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be read or parsed.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let path = environment
+            .var("NFTBLOCKD_POLICY_PATH")
+            .ok()
+            .filter(|s| !s.is_empty());
+        read_ip_set_file(path.as_ref())?
+            .map(|data| {
+                Self::parse(&data).map_err(|e| {
+                    AppError::ParseError(format!(
+                        "invalid policy file: {}: {e}",
+                        path.unwrap_or_default()
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Parses the contents of a policy file.
+    ///
+    /// # Errors
+    /// Will return `AppError` for an unknown rule or an invalid value.
+    pub fn parse(data: &str) -> Result<Self, AppError> {
+        let mut policy = Self::default();
+        for (number, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |reason: &str| {
+                AppError::ParseError(format!("line {}: {line}: {reason}", number + 1))
+            };
+            let (key, value) = line
+                .split_once(':')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| invalid("expected `rule: value`"))?;
+            match key {
+                "never-block" => policy
+                    .never_block
+                    .push(IpNetwork::from_str(value).map_err(|_| invalid("invalid network"))?),
+                "max-elements" => {
+                    policy.max_elements =
+                        Some(value.parse().map_err(|_| invalid("invalid number"))?);
+                }
+                "min-prefix-ipv4" => {
+                    policy.min_prefix_ipv4 = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|len| *len <= 32)
+                            .ok_or_else(|| invalid("expected a prefix length up to 32"))?,
+                    );
+                }
+                "min-prefix-ipv6" => {
+                    policy.min_prefix_ipv6 = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|len| *len <= 128)
+                            .ok_or_else(|| invalid("expected a prefix length up to 128"))?,
+                    );
+                }
+                _ => {
+                    return Err(invalid(
+                        "unknown rule; expected never-block, max-elements, min-prefix-ipv4, or min-prefix-ipv6",
+                    ));
+                }
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Evaluates the policy against the elements of the IPv4 and IPv6 sets.
+    ///
+    /// # Returns
+    ///
+    /// Every violation, in the order of the elements.
+    #[must_use]
+    pub fn check(
+        &self,
+        ipv4: &Option<SetElements<'_>>,
+        ipv6: &Option<SetElements<'_>>,
+    ) -> Vec<Violation> {
+        let elements = ipv4.iter().chain(ipv6).flatten().collect::<Vec<_>>();
+        let mut violations = Vec::new();
+        if let Some(max) = self.max_elements
+            && elements.len() > max
+        {
+            violations.push(Violation::MaxElements {
+                elements: elements.len(),
+                max,
+            });
+        }
+        for element in elements {
+            let Some((element, start, end)) = element_label(element).and_then(|label| {
+                let (start, end) = bounds(&label)?;
+                Some((label, start, end))
+            }) else {
+                continue;
+            };
+            for network in &self.never_block {
+                if network.is_ipv4() == start.is_ipv4()
+                    && start <= network.broadcast()
+                    && network.network() <= end
+                {
+                    violations.push(Violation::NeverBlock {
+                        element: element.clone(),
+                        network: *network,
+                    });
+                }
+            }
+            let (min, bits) = if start.is_ipv4() {
+                (self.min_prefix_ipv4, 32)
+            } else {
+                (self.min_prefix_ipv6, 128)
+            };
+            if let Some(min) = min
+                && addresses(start, end)
+                    > 1u128
+                        .checked_shl(bits - u32::from(min))
+                        .unwrap_or(u128::MAX)
+            {
+                violations.push(Violation::MinPrefix { element, min });
+            }
+        }
+        violations
+    }
+
+    /// Fails when the elements violate the policy, logging every violation.
+    ///
+    /// # Errors
+    /// Will return `AppError` quoting the first violations.
+    pub fn enforce(
+        &self,
+        ipv4: &Option<SetElements<'_>>,
+        ipv6: &Option<SetElements<'_>>,
+    ) -> Result<(), AppError> {
+        let violations = self.check(ipv4, ipv6);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        for violation in &violations {
+            log::error!("policy violation: {violation}");
+        }
+        let quoted = violations
+            .iter()
+            .take(QUOTED_VIOLATIONS)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        let more = violations.len().saturating_sub(QUOTED_VIOLATIONS);
+        Err(AppError::NftblockdError(format!(
+            "the blocklist violates the policy {} times, refusing to apply it: {quoted}{}",
+            violations.len(),
+            if more > 0 {
+                format!("; and {more} more")
+            } else {
+                String::new()
+            }
+        )))
+    }
+}
+
+/// Returns the first and last address of an element label, i.e., an address, a network, or a range.
+fn bounds(label: &str) -> Option<(IpAddr, IpAddr)> {
+    if let Some((start, end)) = label.split_once('-') {
+        return Some((IpAddr::from_str(start).ok()?, IpAddr::from_str(end).ok()?));
+    }
+    let network = IpNetwork::from_str(label).ok()?;
+    Some((network.network(), network.broadcast()))
+}
+
+/// Returns the number of addresses from `start` to `end`, saturating for the whole IPv6 space.
+fn addresses(start: IpAddr, end: IpAddr) -> u128 {
+    let number = |ip: IpAddr| match ip {
+        IpAddr::V4(ip) => u128::from(u32::from(ip)),
+        IpAddr::V6(ip) => u128::from(ip),
+    };
+    number(end).saturating_sub(number(start)).saturating_add(1)
+}
@@ -0,0 +1,105 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use crate::utils::duration::env_duration;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Upper bound of tracked addresses, so a scan from a large network cannot exhaust memory.
+const MAX_TRACKED_ADDRESSES: usize = 100_000;
+
+/// Promotes monitored addresses that keep hitting traffic into the enforced quarantine set for a limited time.
+///
+/// Hits are the packets logged over nflog by the monitor rules. An address reaching `threshold` hits
+/// within `window` is quarantined for `ttl`; the quarantine set drops its traffic until the entry expires.
+#[derive(Debug)]
+pub struct Quarantine {
+    /// Hits within `window` that promote an address.
+    pub threshold: u64,
+    /// Period over which the hits of an address are counted.
+    pub window: Duration,
+    /// How long a promoted address stays quarantined.
+    pub ttl: Duration,
+    /// Start of the current window and the hits in it, per monitored address.
+    hits: HashMap<IpAddr, (Instant, u64)>,
+    /// Quarantined addresses and when they are released.
+    quarantined: BTreeMap<IpAddr, SystemTime>,
+}
+
+impl Quarantine {
+    /// Creates an empty quarantine.
+    #[must_use]
+    pub fn new(threshold: u64, window: Duration, ttl: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            ttl,
+            hits: HashMap::new(),
+            quarantined: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the quarantine settings from `NFTBLOCKD_QUARANTINE_*`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when `NFTBLOCKD_QUARANTINE_THRESHOLD` is unset.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a setting cannot be parsed.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        let Some(threshold) = environment
+            .var("NFTBLOCKD_QUARANTINE_THRESHOLD")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self::new(
+            threshold.parse::<u64>()?,
+            env_duration(environment, "NFTBLOCKD_QUARANTINE_WINDOW", "1m")?,
+            env_duration(environment, "NFTBLOCKD_QUARANTINE_TTL", "1h")?,
+        )))
+    }
+
+    /// Records a hit of a monitored address.
+    ///
+    /// # Returns
+    ///
+    /// `true` when the address has just been promoted into the quarantine.
+    pub fn record(&mut self, addr: IpAddr, now: Instant) -> bool {
+        if self
+            .quarantined
+            .get(&addr)
+            .is_some_and(|until| *until > SystemTime::now())
+        {
+            return false;
+        }
+        if self.hits.len() >= MAX_TRACKED_ADDRESSES && !self.hits.contains_key(&addr) {
+            let window = self.window;
+            self.hits
+                .retain(|_, (started, _)| now.duration_since(*started) < window);
+        }
+        let (started, count) = self.hits.entry(addr).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+        *count += 1;
+        if *count < self.threshold {
+            return false;
+        }
+        self.hits.remove(&addr);
+        self.quarantined.insert(addr, SystemTime::now() + self.ttl);
+        true
+    }
+
+    /// Returns the quarantined addresses with the time they stay quarantined, releasing the expired ones.
+    pub fn active(&mut self, now: SystemTime) -> Vec<(IpAddr, Duration)> {
+        self.quarantined.retain(|_, until| *until > now);
+        self.quarantined
+            .iter()
+            .filter_map(|(addr, until)| Some((*addr, until.duration_since(now).ok()?)))
+            .collect()
+    }
+}
@@ -0,0 +1,317 @@
+use crate::error::{AppError, ErrorSource};
+use crate::set::fetch_policy::Proxies;
+use crate::settings::Environment;
+use log::{debug, info, warn};
+use reqwest::Url;
+use reqwest::header::ACCEPT;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+
+/// Shortest time a resolution is cached, whatever the TTL of its records.
+const MIN_TTL: Duration = Duration::from_secs(60);
+/// Longest time a resolution is cached, whatever the TTL of its records.
+const MAX_TTL: Duration = Duration::from_secs(86_400);
+/// Names resolved at the same time.
+const CONCURRENCY: usize = 16;
+/// DNS record types of the JSON API.
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Addresses a name resolved to and when they expire.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Resolution {
+    addresses: Vec<IpAddr>,
+    /// Unix timestamp after which the name is resolved again.
+    expires: u64,
+}
+
+/// Response of the JSON API of a DNS-over-HTTPS resolver, e.g., `https://cloudflare-dns.com/dns-query`.
+#[derive(Debug, Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u16,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    kind: u16,
+    #[serde(rename = "TTL")]
+    ttl: u64,
+    data: String,
+}
+
+/// Resolves the hostnames listed by domain-based sources into addresses.
+///
+/// With `NFTBLOCKD_DOH_URL`, names are resolved over DNS-over-HTTPS, so that the local resolver can neither
+/// spoof nor block the resolution of a blocklist; otherwise the system resolver is used. Resolutions are kept
+/// for the TTL of their records and persisted, so that a restart does not resolve every name again and a
+/// resolver outage falls back to the last known addresses.
+#[derive(Debug)]
+pub struct DomainResolver {
+    /// The DNS-over-HTTPS endpoint and its client; the system resolver is used when `None`.
+    doh: Option<(reqwest::Client, Url)>,
+    /// File the resolutions are persisted to; they are only kept in memory when `None`.
+    cache_path: Option<PathBuf>,
+    cache: Mutex<HashMap<String, Resolution>>,
+}
+
+impl DomainResolver {
+    /// Creates a `DomainResolver` using the DNS-over-HTTPS resolver in `NFTBLOCKD_DOH_URL`, if set,
+    /// and persisting its resolutions to `cache_path`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the URL is invalid or not HTTPS (except on a loopback address),
+    /// or the HTTP client cannot be built.
+    pub fn from_env(
+        environment: &Environment,
+        cache_path: Option<PathBuf>,
+    ) -> Result<Self, AppError> {
+        let doh = environment
+            .var("NFTBLOCKD_DOH_URL")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|url| {
+                let invalid = |reason: &str| {
+                    AppError::ParseError(format!("invalid NFTBLOCKD_DOH_URL: {url}: {reason}"))
+                };
+                let parsed = Url::parse(&url).map_err(|e| invalid(&e.to_string()))?;
+                let loopback = match parsed.host_str() {
+                    Some("localhost") => true,
+                    Some(host) => host
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .parse::<IpAddr>()
+                        .is_ok_and(|ip| ip.is_loopback()),
+                    None => false,
+                };
+                if parsed.scheme() != "https" && !loopback {
+                    return Err(invalid("expected an https URL"));
+                }
+                let client = Proxies::from_env(environment)?
+                    .apply(reqwest::Client::builder())
+                    .timeout(Duration::from_secs(10))
+                    .build()?;
+                info!("resolving domain-based sources over DNS-over-HTTPS");
+                Ok((client, parsed))
+            })
+            .transpose()?;
+        let cache = cache_path.as_deref().map(load).unwrap_or_default();
+        Ok(Self {
+            doh,
+            cache_path,
+            cache: Mutex::new(cache),
+        })
+    }
+
+    /// Resolves every hostname in `entries` into its IPv6 addresses if `ipv6`, or its IPv4 addresses otherwise.
+    /// Entries that are not hostnames, e.g., addresses, networks, and ranges, are kept as they are.
+    ///
+    /// A name that cannot be resolved keeps its last known addresses, even if they expired, and is left out
+    /// with a warning if it has none.
+    pub async fn resolve(&self, entries: Vec<String>, ipv6: bool) -> Vec<String> {
+        let kind = if ipv6 { "AAAA" } else { "A" };
+        let now = unix_time(SystemTime::now());
+        let mut resolved = Vec::with_capacity(entries.len());
+        let mut pending = Vec::new();
+        {
+            let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            for entry in entries {
+                if !is_hostname(&entry) {
+                    resolved.push(entry);
+                    continue;
+                }
+                let name = entry.trim_end_matches('.').to_ascii_lowercase();
+                match cache.get(&cache_key(kind, &name)) {
+                    Some(resolution) if resolution.expires > now => {
+                        resolved.extend(resolution.addresses.iter().map(ToString::to_string))
+                    }
+                    _ => pending.push(name),
+                }
+            }
+        }
+        pending.sort_unstable();
+        pending.dedup();
+        if pending.is_empty() {
+            return resolved;
+        }
+        debug!("resolving {} names ({kind})", pending.len());
+
+        let mut failed = 0;
+        for chunk in pending.chunks(CONCURRENCY) {
+            let mut lookups = JoinSet::new();
+            for name in chunk {
+                let doh = self.doh.clone();
+                let name = name.clone();
+                lookups.spawn(async move {
+                    let result = match doh {
+                        Some((client, url)) => resolve_doh(&client, &url, &name, ipv6).await,
+                        None => resolve_system(&name, ipv6).await,
+                    };
+                    (name, result)
+                });
+            }
+            let mut results = Vec::with_capacity(chunk.len());
+            while let Some(lookup) = lookups.join_next().await {
+                if let Ok(lookup) = lookup {
+                    results.push(lookup);
+                }
+            }
+            let mut stored = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            for (name, result) in results {
+                let key = cache_key(kind, &name);
+                match result {
+                    Ok((addresses, ttl)) => {
+                        resolved.extend(addresses.iter().map(ToString::to_string));
+                        stored.insert(
+                            key,
+                            Resolution {
+                                addresses,
+                                expires: now + ttl.clamp(MIN_TTL, MAX_TTL).as_secs(),
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        match stored.get(&key) {
+                            Some(stale) => {
+                                debug!("failed to resolve {name}: {e}; keeping its last addresses");
+                                resolved.extend(stale.addresses.iter().map(ToString::to_string));
+                            }
+                            None => debug!("failed to resolve {name}: {e}"),
+                        }
+                    }
+                }
+            }
+        }
+        if failed > 0 {
+            warn!(
+                "failed to resolve {failed} of {} names ({kind}); names resolved before keep their last addresses",
+                pending.len()
+            );
+        }
+        if let Some(path) = &self.cache_path
+            && let Err(e) = self.save(path)
+        {
+            warn!("{e}");
+        }
+        resolved
+    }
+
+    /// Persists the resolutions to `path`, leaving out the ones expired for longer than a day.
+    fn save(&self, path: &Path) -> Result<(), AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!("failed to write the DNS cache: {}", path.display()),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        let horizon = unix_time(SystemTime::now()).saturating_sub(MAX_TTL.as_secs());
+        let data = {
+            let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            cache.retain(|_, resolution| resolution.expires > horizon);
+            serde_json::to_string(&*cache)?
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(file_error)?;
+        fs::rename(&tmp, path).map_err(file_error)
+    }
+}
+
+/// Reads the persisted resolutions, starting over when the file is missing or corrupt.
+fn load(path: &Path) -> HashMap<String, Resolution> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("ignoring the invalid DNS cache {}: {e}", path.display());
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Whether `entry` is a hostname, i.e., labels of letters, digits, `-`, and `_` with at least one letter,
+/// which tells it from an address, a network, a range, or a wildcard.
+fn is_hostname(entry: &str) -> bool {
+    let name = entry.trim_end_matches('.');
+    !name.is_empty()
+        && name.chars().any(|c| c.is_ascii_alphabetic())
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+fn cache_key(kind: &str, name: &str) -> String {
+    format!("{kind} {name}")
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Resolves `name` with the JSON API of a DNS-over-HTTPS resolver.
+async fn resolve_doh(
+    client: &reqwest::Client,
+    url: &Url,
+    name: &str,
+    ipv6: bool,
+) -> Result<(Vec<IpAddr>, Duration), AppError> {
+    let kind = if ipv6 { TYPE_AAAA } else { TYPE_A };
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("name", name)
+        .append_pair("type", if ipv6 { "AAAA" } else { "A" });
+    let response = client
+        .get(url)
+        .header(ACCEPT, "application/dns-json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<DohResponse>()
+        .await?;
+    // NXDOMAIN and NODATA are answers, so that the name is not retried on every update.
+    if response.status != 0 && response.status != 3 {
+        return Err(AppError::RequestError(
+            format!("the resolver answered with DNS status {}", response.status),
+            None,
+        ));
+    }
+    let records = response
+        .answer
+        .into_iter()
+        .filter(|answer| answer.kind == kind)
+        .filter_map(|answer| Some((answer.data.parse::<IpAddr>().ok()?, answer.ttl)))
+        .collect::<Vec<_>>();
+    let ttl = records.iter().map(|(_, ttl)| *ttl).min().unwrap_or(0);
+    Ok((
+        records.into_iter().map(|(address, _)| address).collect(),
+        Duration::from_secs(ttl),
+    ))
+}
+
+/// Resolves `name` with the system resolver, which does not tell the TTL of the records.
+async fn resolve_system(name: &str, ipv6: bool) -> Result<(Vec<IpAddr>, Duration), AppError> {
+    let mut addresses = tokio::net::lookup_host((name, 0))
+        .await?
+        .map(|address| address.ip())
+        .filter(|address| address.is_ipv6() == ipv6)
+        .collect::<Vec<_>>();
+    addresses.sort_unstable();
+    addresses.dedup();
+    Ok((addresses, MIN_TTL))
+}
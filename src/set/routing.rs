@@ -0,0 +1,368 @@
+use crate::error::{AppError, ErrorSource};
+use crate::set::fetch_policy::Proxies;
+use crate::settings::Environment;
+use ipnetwork::IpNetwork;
+use log::{debug, info, warn};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+
+/// Default endpoint of the RIPEstat prefix overview.
+const RIPESTAT_URL: &str = "https://stat.ripe.net/data/prefix-overview/data.json";
+/// Default number of entries verified per fetch, so that a large feed does not flood RIPEstat.
+const MAX_QUERIES: usize = 500;
+/// Time a routed entry is trusted to stay routed.
+const ROUTED_TTL: Duration = Duration::from_secs(86_400);
+/// Time an unrouted entry is trusted to stay unrouted; unused space is announced more often than withdrawn.
+const UNROUTED_TTL: Duration = Duration::from_secs(6 * 3600);
+/// Entries verified at the same time.
+const CONCURRENCY: usize = 8;
+
+/// What happens to the entries no route covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RouteAction {
+    /// The entries are not verified.
+    #[default]
+    Off,
+    /// The unrouted entries are counted and logged, but still blocked.
+    Report,
+    /// The unrouted entries are left out of the sets.
+    Drop,
+}
+
+impl RouteAction {
+    /// Parses `off`, `report`, or `drop`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "report" => Ok(Self::Report),
+            "drop" => Ok(Self::Drop),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_ROUTE_CHECK: {value}; expected off, report, or drop"
+            ))),
+        }
+    }
+}
+
+/// Whether an entry was announced and when the verdict expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Verdict {
+    announced: bool,
+    /// Unix timestamp after which the entry is verified again.
+    expires: u64,
+}
+
+/// Response of the RIPEstat prefix overview.
+#[derive(Debug, Deserialize)]
+struct PrefixOverview {
+    data: PrefixOverviewData,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrefixOverviewData {
+    announced: bool,
+}
+
+/// Entries of a fetch after their verification.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verified {
+    /// The entries to block, without the unrouted ones with `RouteAction::Drop`.
+    pub entries: Vec<String>,
+    /// The entries no route covers.
+    pub unrouted: Vec<String>,
+    /// The entries not verified yet, which are kept as if they were routed.
+    pub unverified: usize,
+}
+
+/// Verifies with RIPEstat that the entries of the sources are covered by a route announced in BGP.
+///
+/// Feeds often list unallocated or withdrawn space next to the addresses actually attacking; such bogon noise
+/// cannot reach the host over a routed path and only grows the sets. Verdicts are cached, routed entries for
+/// a day and unrouted ones for six hours, and persisted, so that only new entries are queried; at most
+/// `max_queries` entries are verified per fetch, and the rest are kept until a later fetch verifies them.
+#[derive(Debug)]
+pub struct RouteVerifier {
+    pub action: RouteAction,
+    /// Entries verified per fetch at most.
+    pub max_queries: usize,
+    client: reqwest::Client,
+    url: Url,
+    /// File the verdicts are persisted to; they are only kept in memory when `None`.
+    cache_path: Option<PathBuf>,
+    cache: Mutex<HashMap<String, Verdict>>,
+}
+
+impl RouteVerifier {
+    /// Creates a `RouteVerifier` querying the RIPEstat prefix overview at `url`
+    /// and persisting its verdicts to `cache_path`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the HTTP client cannot be built.
+    pub fn new(
+        action: RouteAction,
+        url: Url,
+        cache_path: Option<PathBuf>,
+    ) -> Result<Self, AppError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        let cache = cache_path.as_deref().map(load).unwrap_or_default();
+        Ok(Self {
+            action,
+            max_queries: MAX_QUERIES,
+            client,
+            url,
+            cache_path,
+            cache: Mutex::new(cache),
+        })
+    }
+
+    /// Reads the verifier from `NFTBLOCKD_ROUTE_CHECK`, `NFTBLOCKD_RIPESTAT_URL`,
+    /// and `NFTBLOCKD_ROUTE_CHECK_MAX_QUERIES`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when the verification is off.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a variable cannot be parsed or the HTTP client cannot be built.
+    pub fn from_env(
+        environment: &Environment,
+        cache_path: Option<PathBuf>,
+    ) -> Result<Option<Self>, AppError> {
+        let action = environment
+            .var("NFTBLOCKD_ROUTE_CHECK")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|action| RouteAction::parse(&action))
+            .transpose()?
+            .unwrap_or_default();
+        if action == RouteAction::Off {
+            return Ok(None);
+        }
+        let url = environment
+            .var("NFTBLOCKD_RIPESTAT_URL")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or(RIPESTAT_URL.to_string());
+        let url = Url::parse(&url).map_err(|e| {
+            AppError::ParseError(format!("invalid NFTBLOCKD_RIPESTAT_URL: {url}: {e}"))
+        })?;
+        let max_queries = environment
+            .var("NFTBLOCKD_ROUTE_CHECK_MAX_QUERIES")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|value| {
+                value.parse::<usize>().map_err(|e| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_ROUTE_CHECK_MAX_QUERIES: {value}: {e}"
+                    ))
+                })
+            })
+            .transpose()?
+            .unwrap_or(MAX_QUERIES);
+        info!("verifying that the entries of the sources are routed");
+        Ok(Some(
+            Self::new(action, url, cache_path)?
+                .with_max_queries(max_queries)
+                .with_proxies(&Proxies::from_env(environment)?)?,
+        ))
+    }
+
+    /// Queries RIPEstat through `proxies`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the HTTP client cannot be built.
+    pub fn with_proxies(mut self, proxies: &Proxies) -> Result<Self, AppError> {
+        self.client = proxies
+            .apply(reqwest::Client::builder())
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(self)
+    }
+
+    /// Sets the number of entries verified per fetch at most.
+    #[must_use]
+    pub fn with_max_queries(mut self, max_queries: usize) -> Self {
+        self.max_queries = max_queries;
+        self
+    }
+
+    /// Classifies `entries` into routed and unrouted ones.
+    ///
+    /// Entries that are not addresses or networks, e.g., ranges, are kept unverified, and so is an entry
+    /// whose query fails or exceeds `max_queries`, unless an earlier verdict is known.
+    pub async fn verify(&self, entries: Vec<String>) -> Verified {
+        let now = unix_time(SystemTime::now());
+        let mut verdicts = HashMap::new();
+        let mut pending = Vec::new();
+        {
+            let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            for entry in &entries {
+                let Some(resource) = resource(entry) else {
+                    continue;
+                };
+                match cache.get(&resource) {
+                    Some(verdict) if verdict.expires > now => {
+                        verdicts.insert(resource, verdict.announced);
+                    }
+                    _ => pending.push(resource),
+                }
+            }
+        }
+        pending.sort_unstable();
+        pending.dedup();
+        if pending.len() > self.max_queries {
+            debug!(
+                "verifying {} of {} unverified entries; the rest is verified by later fetches",
+                self.max_queries,
+                pending.len()
+            );
+            pending.truncate(self.max_queries);
+        }
+
+        let mut failed = 0;
+        for chunk in pending.chunks(CONCURRENCY) {
+            let mut queries = JoinSet::new();
+            for resource in chunk {
+                let client = self.client.clone();
+                let url = self.url.clone();
+                let resource = resource.clone();
+                queries.spawn(async move {
+                    let result = query(&client, &url, &resource).await;
+                    (resource, result)
+                });
+            }
+            let mut results = Vec::with_capacity(chunk.len());
+            while let Some(query) = queries.join_next().await {
+                if let Ok(query) = query {
+                    results.push(query);
+                }
+            }
+            let mut stored = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            for (resource, result) in results {
+                match result {
+                    Ok(announced) => {
+                        let ttl = if announced { ROUTED_TTL } else { UNROUTED_TTL };
+                        verdicts.insert(resource.clone(), announced);
+                        stored.insert(
+                            resource,
+                            Verdict {
+                                announced,
+                                expires: now + ttl.as_secs(),
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        debug!("failed to verify the route of {resource}: {e}");
+                        if let Some(stale) = stored.get(&resource) {
+                            verdicts.insert(resource, stale.announced);
+                        }
+                    }
+                }
+            }
+        }
+        if failed > 0 {
+            warn!(
+                "failed to verify the routes of {failed} of {} entries; they keep their last verdict",
+                pending.len()
+            );
+        }
+        if let Some(path) = &self.cache_path
+            && let Err(e) = self.save(path)
+        {
+            warn!("{e}");
+        }
+
+        let mut verified = Verified::default();
+        for entry in entries {
+            match resource(&entry).and_then(|resource| verdicts.get(&resource).copied()) {
+                Some(false) => {
+                    if self.action != RouteAction::Drop {
+                        verified.entries.push(entry.clone());
+                    }
+                    verified.unrouted.push(entry);
+                }
+                Some(true) => verified.entries.push(entry),
+                None => {
+                    verified.unverified += 1;
+                    verified.entries.push(entry);
+                }
+            }
+        }
+        verified
+    }
+
+    /// Persists the verdicts to `path`, leaving out the ones expired for longer than a day.
+    fn save(&self, path: &Path) -> Result<(), AppError> {
+        let file_error = |e: std::io::Error| {
+            AppError::FileError(
+                format!("failed to write the route cache: {}", path.display()),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        let horizon = unix_time(SystemTime::now()).saturating_sub(ROUTED_TTL.as_secs());
+        let data = {
+            let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            cache.retain(|_, verdict| verdict.expires > horizon);
+            serde_json::to_string(&*cache)?
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(file_error)?;
+        fs::rename(&tmp, path).map_err(file_error)
+    }
+}
+
+/// Reads the persisted verdicts, starting over when the file is missing or corrupt.
+fn load(path: &Path) -> HashMap<String, Verdict> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("ignoring the invalid route cache {}: {e}", path.display());
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// The network `entry` is verified as, e.g., `192.0.2.1/32`; `None` when it is not an address or a network.
+fn resource(entry: &str) -> Option<String> {
+    entry
+        .trim()
+        .parse::<IpNetwork>()
+        .ok()
+        .map(|network| format!("{}/{}", network.network(), network.prefix()))
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Asks the RIPEstat prefix overview whether a route covering `resource` is announced.
+async fn query(client: &reqwest::Client, url: &Url, resource: &str) -> Result<bool, AppError> {
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("resource", resource)
+        .append_pair("sourceapp", "nftblockd");
+    let response = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<PrefixOverview>()
+        .await?;
+    Ok(response.data.announced)
+}
@@ -0,0 +1,99 @@
+use crate::set::feed::Feed;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::OwnedMutexGuard;
+
+/// A parsed feed with the `ETag` of its response, as fetched by one of the sources.
+#[derive(Debug, Clone)]
+struct Response {
+    fetched: Instant,
+    feed: Arc<Feed>,
+    etag: Option<String>,
+}
+
+/// Feeds shared by the sources and profiles of one daemon, so that a URL configured several times, e.g., for both
+/// families, as a consensus feed, or by several profiles, is downloaded and parsed once per update rather than once
+/// per source.
+///
+/// Feeds are keyed by the expanded URL, the request headers, and how the body is parsed, so that sources
+/// authenticating differently to the same URL never see each other's data.
+#[derive(Debug)]
+pub struct SharedFetches {
+    /// How long a feed is reused.
+    max_age: Duration,
+    responses: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<Response>>>>>,
+}
+
+/// Exclusive access to the shared feed of a source while it is fetched; sources fetching the same
+/// URL wait for it and reuse the feed.
+pub struct SharedFetch {
+    max_age: Duration,
+    response: OwnedMutexGuard<Option<Response>>,
+}
+
+impl SharedFetches {
+    #[must_use]
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until no other source fetches the source of `url` with `headers`, parsed as described by `parsing`,
+    /// and locks it.
+    ///
+    /// Feeds older than the maximum age that no source is fetching are dropped first, so that they are not
+    /// kept until the next update.
+    pub async fn lock(
+        &self,
+        url: &str,
+        headers: Option<&HashMap<String, String>>,
+        parsing: &str,
+    ) -> SharedFetch {
+        let headers = headers
+            .map(|headers| headers.iter().collect::<BTreeMap<_, _>>())
+            .unwrap_or_default();
+        let key = format!("{url} {headers:?} {parsing}");
+        let response = {
+            let mut responses = self
+                .responses
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            responses.retain(|_, response| {
+                Arc::strong_count(response) > 1
+                    || response.try_lock().is_ok_and(|response| {
+                        response
+                            .as_ref()
+                            .is_some_and(|response| response.fetched.elapsed() < self.max_age)
+                    })
+            });
+            responses.entry(key).or_default().clone()
+        };
+        SharedFetch {
+            max_age: self.max_age,
+            response: response.lock_owned().await,
+        }
+    }
+}
+
+impl SharedFetch {
+    /// Returns the feed and the `ETag` of its response when another source fetched it recently.
+    #[must_use]
+    pub fn fresh(&self) -> Option<(Arc<Feed>, Option<String>)> {
+        self.response
+            .as_ref()
+            .filter(|response| response.fetched.elapsed() < self.max_age)
+            .map(|response| (response.feed.clone(), response.etag.clone()))
+    }
+
+    /// Stores a freshly fetched and parsed feed for the other sources.
+    pub fn store(&mut self, feed: Arc<Feed>, etag: Option<&str>) {
+        *self.response = Some(Response {
+            fetched: Instant::now(),
+            feed,
+            etag: etag.map(ToString::to_string),
+        });
+    }
+}
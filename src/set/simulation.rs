@@ -0,0 +1,193 @@
+use crate::error::AppError;
+use crate::nftables::builder::SetElements;
+use crate::nftables::element_label;
+use crate::set::attribution::span;
+use crate::set::feed::extract;
+use crate::utils::pcap::{is_pcap, packet_addresses};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// What the rules would do with a simulated address.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    /// Dropped by the element of the set, as `nft` prints it.
+    Dropped { set: String, element: String },
+    /// Accepted before any blocklist is matched, e.g., by the anti-lockout set.
+    Accepted { set: String, element: String },
+    /// Not matched by any set.
+    Passed,
+}
+
+/// A set whose elements are matched as the disjoint spans of addresses they cover.
+#[derive(Debug, Clone)]
+struct SimulatedSet {
+    name: String,
+    drop: bool,
+    spans: Vec<((bool, u128, u128), String)>,
+}
+
+/// Matches addresses against the sets of a ruleset without applying it, e.g., to estimate the collateral
+/// damage of a new feed before enabling it.
+#[derive(Debug, Clone, Default)]
+pub struct Simulation {
+    sets: Vec<SimulatedSet>,
+}
+
+impl Simulation {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a set whose matches are dropped, or accepted when `drop` is `false`.
+    /// The sets are matched in the order they are added, like the rules referencing them.
+    #[must_use]
+    pub fn with_set(
+        mut self,
+        name: String,
+        drop: bool,
+        elements: Option<&SetElements<'_>>,
+    ) -> Self {
+        let mut spans = elements
+            .into_iter()
+            .flatten()
+            .filter_map(element_label)
+            .filter_map(|label| span(&label).map(|span| (span, label)))
+            .collect::<Vec<_>>();
+        spans.sort();
+        self.sets.push(SimulatedSet { name, drop, spans });
+        self
+    }
+
+    /// Returns what the first set matching `address` would do with it.
+    #[must_use]
+    pub fn outcome(&self, address: IpAddr) -> Outcome {
+        let Some((ipv6, address, _)) = span(&address.to_string()) else {
+            return Outcome::Passed;
+        };
+        for set in &self.sets {
+            // The elements are disjoint, so only the last one starting at or before the address can cover it.
+            let index = set
+                .spans
+                .partition_point(|((v6, start, _), _)| (*v6, *start) <= (ipv6, address));
+            if let Some(((v6, _, end), element)) = index.checked_sub(1).map(|i| &set.spans[i])
+                && *v6 == ipv6
+                && *end >= address
+            {
+                let (drop, set, element) = (set.drop, set.name.clone(), element.clone());
+                return if drop {
+                    Outcome::Dropped { set, element }
+                } else {
+                    Outcome::Accepted { set, element }
+                };
+            }
+        }
+        Outcome::Passed
+    }
+
+    /// Simulates every input, counting repeated addresses, e.g., the packets of a capture, as separate hits.
+    #[must_use]
+    pub fn run(&self, inputs: &[IpAddr]) -> SimulationReport {
+        let mut report = SimulationReport::default();
+        for input in inputs {
+            report
+                .hits
+                .entry(*input)
+                .or_insert_with(|| (self.outcome(*input), 0))
+                .1 += 1;
+        }
+        report
+    }
+}
+
+/// Outcome and number of hits of every simulated address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimulationReport {
+    pub hits: BTreeMap<IpAddr, (Outcome, usize)>,
+}
+
+impl SimulationReport {
+    /// Number of inputs, counting repeated addresses.
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.hits.values().map(|(_, hits)| hits).sum()
+    }
+
+    /// Number of inputs that would be dropped, counting repeated addresses.
+    #[must_use]
+    pub fn dropped(&self) -> usize {
+        self.hits
+            .values()
+            .filter(|(outcome, _)| matches!(outcome, Outcome::Dropped { .. }))
+            .map(|(_, hits)| hits)
+            .sum()
+    }
+
+    /// Hits of every set and element that matched an input, most hits first.
+    #[must_use]
+    pub fn hits_by_element(&self) -> Vec<(&Outcome, usize)> {
+        let mut hits = BTreeMap::<&Outcome, usize>::new();
+        for (outcome, count) in self.hits.values() {
+            if *outcome != Outcome::Passed {
+                *hits.entry(outcome).or_default() += count;
+            }
+        }
+        let mut hits = hits.into_iter().collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        hits
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Dropped { set, element } => write!(f, "dropped by {set} via {element}"),
+            Outcome::Accepted { set, element } => write!(f, "accepted by {set} via {element}"),
+            Outcome::Passed => write!(f, "passed"),
+        }
+    }
+}
+
+impl Display for SimulationReport {
+    /// Lists the matched addresses, then the hits of every matching element and a summary.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (address, (outcome, hits)) in &self.hits {
+            if *outcome != Outcome::Passed {
+                writeln!(f, "{address} {outcome} hits={hits}")?;
+            }
+        }
+        for (outcome, hits) in self.hits_by_element() {
+            writeln!(f, "{outcome}: hits={hits}")?;
+        }
+        writeln!(
+            f,
+            "{} of {} inputs ({} distinct addresses) would be dropped",
+            self.dropped(),
+            self.total(),
+            self.hits
+                .values()
+                .filter(|(outcome, _)| matches!(outcome, Outcome::Dropped { .. }))
+                .count()
+        )
+    }
+}
+
+/// Reads the addresses to simulate from a pcap capture, taking the source and destination of every packet,
+/// or from a text file, e.g., a list of addresses or a log, taking every address it mentions.
+///
+/// # Errors
+/// Will return `AppError` when the file cannot be read or is an unsupported capture.
+pub fn read_inputs(path: &Path) -> Result<Vec<IpAddr>, AppError> {
+    let data = std::fs::read(path)?;
+    if is_pcap(&data) {
+        return Ok(packet_addresses(&data)?
+            .into_iter()
+            .flat_map(|(source, destination)| [source, destination])
+            .collect());
+    }
+    Ok(extract(&String::from_utf8_lossy(&data))
+        .filter_map(|candidate| candidate.parse::<IpAddr>().ok())
+        .collect())
+}
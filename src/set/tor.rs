@@ -0,0 +1,188 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use log::warn;
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::SystemTime;
+use time::PrimitiveDateTime;
+use time::macros::format_description;
+
+/// Tor exit relays listed by the exit list of `check.torproject.org` or by a network status consensus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TorExits {
+    /// Addresses of the exit relays, in the order they are listed.
+    pub addresses: Vec<String>,
+    /// The `valid-until` of the consensus, after which its relays are no longer known to be exits.
+    pub valid_until: Option<SystemTime>,
+    /// The `fresh-until` of the consensus, before which no newer consensus is published.
+    pub fresh_until: Option<SystemTime>,
+}
+
+impl TorExits {
+    /// Adds the addresses of `relay` when it is an exit for `ports`.
+    fn add(&mut self, relay: Option<Relay>, ports: Option<&[u16]>, seen: &mut HashSet<String>) {
+        let Some(relay) = relay else {
+            return;
+        };
+        if relay
+            .policy
+            .as_deref()
+            .is_some_and(|policy| allows(policy, ports))
+        {
+            self.addresses.extend(
+                relay
+                    .addresses
+                    .into_iter()
+                    .filter(|address| seen.insert(address.clone())),
+            );
+        }
+    }
+}
+
+/// A relay of a consensus, from its `r` line up to the next one.
+#[derive(Debug, Default)]
+struct Relay {
+    addresses: Vec<String>,
+    /// The exit policy summary of the `p` line, e.g., `accept 80,443` or `reject 1-65535`.
+    policy: Option<String>,
+}
+
+/// Reads the ports the exits must allow from `NFTBLOCKD_TOR_PORTS`, e.g., `22,443`.
+///
+/// # Returns
+///
+/// `None` when the variable is unset, i.e., every exit is listed.
+///
+/// # Errors
+/// Will return `AppError` when a port is invalid.
+pub fn ports_from_env(environment: &Environment) -> Result<Option<Vec<u16>>, AppError> {
+    environment
+        .var("NFTBLOCKD_TOR_PORTS")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(|ports| {
+            ports
+                .split(',')
+                .map(|port| {
+                    port.trim().parse::<u16>().ok().filter(|port| *port > 0).ok_or_else(|| {
+                        AppError::ParseError(format!(
+                            "invalid NFTBLOCKD_TOR_PORTS: {ports}; expected comma-separated ports"
+                        ))
+                    })
+                })
+                .collect()
+        })
+        .transpose()
+}
+
+/// Parses the exit list (`ExitAddress` lines) or a consensus (`r`, `a`, and `p` lines) of the Tor network.
+///
+/// A consensus lists the relays by their OR addresses with a summary of their IPv4 exit policies;
+/// every relay whose policy accepts a port is an exit, or, with `ports`, every relay accepting one of them,
+/// e.g., to block only the exits that can reach the local SSH server. The exit list names the addresses
+/// the exits actually connect from, but has no policies, so `ports` cannot be applied to it.
+#[must_use]
+pub fn parse_exits(body: &str, ports: Option<&[u16]>) -> TorExits {
+    let mut exits = TorExits::default();
+    let mut seen = HashSet::new();
+    let mut relay: Option<Relay> = None;
+    let mut consensus = false;
+    for line in body.lines() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("ExitAddress") => {
+                if let Some(address) = tokens.next().filter(|a| a.parse::<Ipv4Addr>().is_ok())
+                    && seen.insert(address.to_string())
+                {
+                    exits.addresses.push(address.to_string());
+                }
+            }
+            Some("valid-until") => exits.valid_until = consensus_time(tokens),
+            Some("fresh-until") => exits.fresh_until = consensus_time(tokens),
+            Some("r") => {
+                consensus = true;
+                exits.add(relay.take(), ports, &mut seen);
+                relay = tokens
+                    .skip(3)
+                    .find(|t| t.parse::<Ipv4Addr>().is_ok())
+                    .map(|address| Relay {
+                        addresses: vec![address.to_string()],
+                        policy: None,
+                    });
+            }
+            Some("a") => {
+                if let Some(relay) = &mut relay
+                    && let Some(address) = tokens
+                        .next()
+                        .and_then(|a| a.strip_prefix('['))
+                        .and_then(|a| a.split_once(']'))
+                        .and_then(|(address, _)| address.parse::<Ipv6Addr>().ok())
+                {
+                    relay.addresses.push(address.to_string());
+                }
+            }
+            Some("p") => {
+                if let Some(relay) = &mut relay {
+                    relay.policy = Some(tokens.collect::<Vec<_>>().join(" "));
+                }
+            }
+            _ => {}
+        }
+    }
+    exits.add(relay, ports, &mut seen);
+    if ports.is_some() && !consensus && !exits.addresses.is_empty() {
+        warn!(
+            "the Tor exit list has no exit policies; NFTBLOCKD_TOR_PORTS only applies to a consensus"
+        );
+    }
+    exits
+}
+
+/// Returns whether the exit policy summary `policy` accepts one of `ports`, or any port when `None`.
+fn allows(policy: &str, ports: Option<&[u16]>) -> bool {
+    let Some((action, ranges)) = policy.split_once(' ') else {
+        return false;
+    };
+    let ranges = ranges
+        .split(',')
+        .filter_map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            Some((start.parse::<u16>().ok()?, end.parse::<u16>().ok()?))
+        })
+        .collect::<Vec<_>>();
+    let listed = |port: u16| {
+        ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&port))
+    };
+    match (action, ports) {
+        ("accept", Some(ports)) => ports.iter().any(|port| listed(*port)),
+        ("reject", Some(ports)) => ports.iter().any(|port| !listed(*port)),
+        ("accept", None) => !ranges.is_empty(),
+        // A relay rejecting every port, e.g., `reject 1-65535`, is not an exit.
+        ("reject", None) => !covers_all_ports(ranges),
+        _ => false,
+    }
+}
+
+/// Returns whether `ranges` cover every port from 1 to 65535.
+fn covers_all_ports(mut ranges: Vec<(u16, u16)>) -> bool {
+    ranges.sort_unstable();
+    let mut next = 1u32;
+    for (start, end) in ranges {
+        if u32::from(start) > next {
+            return false;
+        }
+        next = next.max(u32::from(end) + 1);
+    }
+    next > u32::from(u16::MAX)
+}
+
+/// Parses the `YYYY-MM-DD HH:MM:SS` time of a consensus header, which is in UTC.
+fn consensus_time<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Option<SystemTime> {
+    let value = format!("{} {}", tokens.next()?, tokens.next()?);
+    let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    PrimitiveDateTime::parse(&value, format)
+        .ok()
+        .map(|time| SystemTime::from(time.assume_utc()))
+}
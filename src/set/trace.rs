@@ -0,0 +1,133 @@
+use crate::error::AppError;
+use crate::nftables::builder::SetElements;
+use crate::nftables::element_label;
+use crate::set::attribution::span;
+use crate::settings::Environment;
+use crate::utils::subnet::normalize_notation;
+use log::info;
+
+/// Entries logged per traced network and stage at most, so that a traced `/8` does not flood the log.
+const MAX_MATCHES: usize = 10;
+
+/// Logs the lifecycle of specific networks through an update: the sources listing them, the entries left out
+/// by the consensus, the elements they were deduplicated into, and the sets they were applied into.
+///
+/// Meant for debugging why a given address is or is not blocked; every entry or element overlapping a traced
+/// network is logged, e.g., both `203.0.113.0/24` and `203.0.113.7` for a traced `203.0.113.0/25`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryTrace {
+    /// The traced networks with their family and first and last address.
+    traced: Vec<(String, (bool, u128, u128))>,
+}
+
+impl EntryTrace {
+    /// Parses comma-separated addresses, networks, or ranges.
+    ///
+    /// # Errors
+    /// Will return `AppError` when an entry is none of them.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let traced = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let entry = normalize_notation(entry).into_owned();
+                span(&entry).map(|span| (entry.clone(), span)).ok_or_else(|| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_TRACE_ENTRIES: {entry}; expected addresses, networks, or ranges"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { traced })
+    }
+
+    /// Reads the traced networks from `NFTBLOCKD_TRACE_ENTRIES`, or `None` when it is not set.
+    ///
+    /// # Errors
+    /// Will return `AppError` when an entry is not an address, a network, or a range.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        environment
+            .var("NFTBLOCKD_TRACE_ENTRIES")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|value| Self::parse(&value))
+            .transpose()
+    }
+
+    /// Returns the entries overlapping every traced network, by the traced network.
+    #[must_use]
+    pub fn matches<'a>(
+        &'a self,
+        entries: impl IntoIterator<Item = &'a str> + Clone,
+    ) -> Vec<(&'a str, Vec<&'a str>)> {
+        self.traced
+            .iter()
+            .map(|(traced, (ipv6, start, end))| {
+                let matching = entries
+                    .clone()
+                    .into_iter()
+                    .filter(|entry| {
+                        span(&normalize_notation(entry.trim()))
+                            .is_some_and(|(v6, s, e)| v6 == *ipv6 && s <= *end && *start <= e)
+                    })
+                    .collect();
+                (traced.as_str(), matching)
+            })
+            .collect()
+    }
+
+    /// Logs the entries of `source` overlapping the traced networks, or that it does not list them.
+    pub fn listed(&self, source: &str, entries: &[impl AsRef<str>]) {
+        for (traced, matching) in self.matches(entries.iter().map(AsRef::as_ref)) {
+            if matching.is_empty() {
+                info!("trace {traced}: not listed by {source}");
+            }
+            for entry in limited(&matching) {
+                info!("trace {traced}: listed by {source} as {entry}");
+            }
+        }
+    }
+
+    /// Logs the entries of `source` overlapping the traced networks that were left out for `reason`.
+    pub fn left_out(&self, source: &str, entries: &[String], reason: &str) {
+        for (traced, matching) in self.matches(entries.iter().map(String::as_str)) {
+            for entry in limited(&matching) {
+                info!("trace {traced}: {entry} of {source} was left out {reason}");
+            }
+        }
+    }
+
+    /// Logs the elements of `stage` overlapping the traced networks, e.g., the network a traced address was
+    /// deduplicated into, or that none does.
+    pub fn elements(&self, stage: &str, elements: &Option<SetElements<'_>>) {
+        let labels = elements
+            .iter()
+            .flatten()
+            .filter_map(element_label)
+            .collect::<Vec<_>>();
+        for (traced, matching) in self.matches(labels.iter().map(String::as_str)) {
+            if matching.is_empty() {
+                info!("trace {traced}: not in {stage}");
+            }
+            for label in limited(&matching) {
+                if *label == traced {
+                    info!("trace {traced}: in {stage}");
+                } else {
+                    info!("trace {traced}: in {stage} as {label}");
+                }
+            }
+        }
+    }
+}
+
+/// The first `MAX_MATCHES` of `matching`, noting in the log how many more were left out.
+fn limited<'a>(matching: &'a [&'a str]) -> &'a [&'a str] {
+    if matching.len() > MAX_MATCHES {
+        info!(
+            "trace: logging {MAX_MATCHES} of {} overlapping entries",
+            matching.len()
+        );
+    }
+    &matching[..matching.len().min(MAX_MATCHES)]
+}
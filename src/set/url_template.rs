@@ -0,0 +1,83 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use crate::utils::hostname;
+use time::OffsetDateTime;
+
+/// Expands the placeholders of a source URL at fetch time.
+///
+/// Supported placeholders are `{date:<format>}` with the `strftime` specifiers `%Y`, `%y`, `%m`, `%d`,
+/// `%j`, `%H`, `%M`, `%S`, and `%%` (in UTC), `{hostname}`, and `{env:<NAME>}`.
+/// Text outside braces is kept as is.
+///
+/// # Arguments
+///
+/// * `environment` - The variables the `{env:<NAME>}` placeholders are expanded from.
+/// * `template` - The configured source URL.
+/// * `now` - The time the date placeholders are expanded with.
+///
+/// # Errors
+/// Will return `AppError` for unknown or unterminated placeholders, unsupported date specifiers,
+/// and unset variables.
+pub fn expand_url(
+    environment: &Environment,
+    template: &str,
+    now: OffsetDateTime,
+) -> Result<String, AppError> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            AppError::ParseError(format!("unterminated placeholder in {template}"))
+        })? + start;
+        let placeholder = &rest[start + 1..end];
+        match placeholder.split_once(':') {
+            Some(("date", format)) => expanded.push_str(&format_date(format, now)?),
+            Some(("env", name)) => expanded.push_str(&environment.var(name).map_err(|_| {
+                AppError::ParseError(format!(
+                    "environment variable {name} used in a source URL is not set"
+                ))
+            })?),
+            None if placeholder == "hostname" => expanded.push_str(&hostname()),
+            _ => {
+                return Err(AppError::ParseError(format!(
+                    "unknown placeholder {{{placeholder}}} in {template}"
+                )));
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Formats `now` with a subset of the `strftime` specifiers.
+fn format_date(format: &str, now: OffsetDateTime) -> Result<String, AppError> {
+    let mut formatted = String::with_capacity(format.len() * 2);
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        let part = match chars.next() {
+            Some('Y') => format!("{:04}", now.year()),
+            Some('y') => format!("{:02}", now.year().rem_euclid(100)),
+            Some('m') => format!("{:02}", u8::from(now.month())),
+            Some('d') => format!("{:02}", now.day()),
+            Some('j') => format!("{:03}", now.ordinal()),
+            Some('H') => format!("{:02}", now.hour()),
+            Some('M') => format!("{:02}", now.minute()),
+            Some('S') => format!("{:02}", now.second()),
+            Some('%') => "%".to_string(),
+            other => {
+                return Err(AppError::ParseError(format!(
+                    "unsupported date specifier %{} in {format}",
+                    other.map(String::from).unwrap_or_default()
+                )));
+            }
+        };
+        formatted.push_str(&part);
+    }
+    Ok(formatted)
+}
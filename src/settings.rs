@@ -0,0 +1,349 @@
+use crate::error::{AppError, ErrorSource};
+use crate::set::fetch_policy::FetchPolicy;
+use crate::utils::duration::env_duration;
+use crate::utils::hostname;
+use crate::utils::instance::state_dir;
+use crate::utils::log_file::LogRotation;
+use crate::utils::scheduling::Scheduling;
+use std::collections::BTreeMap;
+use std::env::{self, VarError};
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Display};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Variables the configuration is read from: the `.env` files, overridden by the environment of the process.
+///
+/// The environment of the process is only read, never modified, so that the files can be reloaded while
+/// other threads are running, and every profile can be configured from its own files.
+#[derive(Clone, Default)]
+pub struct Environment {
+    variables: Arc<BTreeMap<OsString, OsString>>,
+}
+
+impl Environment {
+    /// Reads the environment of the process.
+    #[must_use]
+    pub fn process() -> Self {
+        env::vars_os().collect()
+    }
+
+    /// Loads the `.env` files in the given order over the environment of the process.
+    ///
+    /// Later files override earlier ones, and variables set in the environment of the process override all
+    /// files; command-line arguments, parsed from the result, override both.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a file cannot be read or parsed.
+    pub fn load(files: &[String]) -> Result<Self, AppError> {
+        let file_error = |file: &str, e: dotenvy::Error| {
+            AppError::FileError(
+                format!("failed to load .env file: {file}: {e}"),
+                Some(ErrorSource::new(e)),
+            )
+        };
+        let mut variables = BTreeMap::new();
+        for file in files {
+            for item in dotenvy::from_filename_iter(file).map_err(|e| file_error(file, e))? {
+                let (key, value) = item.map_err(|e| file_error(file, e))?;
+                variables.insert(OsString::from(key), OsString::from(value));
+            }
+        }
+        variables.extend(env::vars_os());
+        Ok(Self {
+            variables: Arc::new(variables),
+        })
+    }
+
+    /// Reads a variable like `std::env::var`.
+    ///
+    /// # Errors
+    /// Will return `VarError` when the variable is not set or not valid Unicode.
+    pub fn var(&self, name: impl AsRef<OsStr>) -> Result<String, VarError> {
+        match self.var_os(name) {
+            Some(value) => value
+                .to_os_string()
+                .into_string()
+                .map_err(VarError::NotUnicode),
+            None => Err(VarError::NotPresent),
+        }
+    }
+
+    /// Reads a variable like `std::env::var_os`.
+    #[must_use]
+    pub fn var_os(&self, name: impl AsRef<OsStr>) -> Option<&OsStr> {
+        self.variables.get(name.as_ref()).map(OsString::as_os_str)
+    }
+}
+
+impl<K: Into<OsString>, V: Into<OsString>> FromIterator<(K, V)> for Environment {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            variables: Arc::new(
+                iter.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Lists only the names of the variables, since the values may be secrets.
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.variables.keys()).finish()
+    }
+}
+
+/// Settings of the daemon itself, parsed once from the environment after the `.env` files are loaded.
+///
+/// The settings of the individual features (e.g., the fetch policies, alerts, or metrics) are read by
+/// the types implementing them.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Delimiter of the entries in blocklists and custom blocklist files; whitespace when `None`.
+    pub split_string: Option<String>,
+    /// Log file replacing the standard output.
+    pub log_file: Option<PathBuf>,
+    pub log_rotation: LogRotation,
+    /// Number of rotated log files to keep.
+    pub log_max_files: usize,
+    /// Drops every capability except `CAP_NET_ADMIN` after startup.
+    pub drop_privileges: bool,
+    /// User to switch to when dropping privileges.
+    pub user: Option<String>,
+    /// Restricts the daemon with Landlock and seccomp.
+    pub sandbox: bool,
+    /// Priorities and CPU quota of the daemon.
+    pub scheduling: Scheduling,
+    /// Removes the table when updates are paused by `SIGUSR1`.
+    pub pause_disables_rules: bool,
+    /// Reloads the configuration when the `.env` files or the local lists change.
+    pub watch_config: bool,
+    /// Applies the last applied ruleset on startup, before the first fetch.
+    pub restore_on_start: bool,
+    pub retry_interval: Duration,
+    pub retry_count: u64,
+    /// Maximum random delay added to every update interval.
+    pub interval_jitter: Duration,
+    /// Maximum random delay before the first update.
+    pub initial_jitter: Duration,
+    /// Interval of refreshing the drop counters.
+    pub stats_interval: Duration,
+    /// History database recording when addresses were blocked.
+    pub history_db: Option<String>,
+    /// Appearances after which a prefix is escalated to the manual set; never when `None`.
+    pub escalate_after: Option<u64>,
+    /// Address serving the Prometheus metrics.
+    pub metrics_addr: Option<SocketAddr>,
+    /// File storing the entries added with `nftblockd add`.
+    pub manual_path: PathBuf,
+    /// File persisting the resolutions of the hostnames listed by the sources.
+    pub dns_cache_path: PathBuf,
+    /// File persisting the verdicts of the route verification.
+    pub route_cache_path: PathBuf,
+    /// File persisting when every source was first configured, for the burn-in of new sources.
+    pub burn_in_path: PathBuf,
+    /// File persisting the responses of the sources that are still fresh.
+    pub http_cache_path: PathBuf,
+    /// File the last applied ruleset is written to, which `reconcile` restores the table from.
+    pub ruleset_path: PathBuf,
+    /// Directory the git sources are checked out to.
+    pub git_dir: PathBuf,
+    /// Name reported to the primary when running as a replica.
+    pub replica_name: String,
+    /// Token authenticating a replica to the primary.
+    pub primary_token: Option<String>,
+    /// Variables the settings were read from, which the features read their settings from as well.
+    pub environment: Environment,
+}
+
+impl Settings {
+    /// Parses and validates the settings of the daemon from the environment.
+    ///
+    /// Every variable is checked, along with the constraints between the settings, before anything fails,
+    /// so that all problems of a configuration are reported at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `environment` - The variables to read the settings from.
+    /// * `instance` - The name of the instance, which determines the default state directory.
+    /// * `interval` - The update interval, which the fetch timeouts must be shorter than.
+    ///
+    /// # Errors
+    /// Will return `AppError::ConfigError` listing every variable that cannot be parsed
+    /// and every constraint that is violated.
+    pub fn from_env(
+        environment: &Environment,
+        instance: Option<&str>,
+        interval: Duration,
+    ) -> Result<Self, AppError> {
+        let var = |name: &str| environment.var(name).ok().filter(|s| !s.is_empty());
+        let mut problems = Problems::new(environment);
+        let settings = Self {
+            split_string: var("NFTBLOCKD_BLOCKLIST_SPLIT_STRING"),
+            log_file: var("NFTBLOCKD_LOG_FILE").map(PathBuf::from),
+            log_rotation: problems.take(
+                LogRotation::parse(&var("NFTBLOCKD_LOG_ROTATION").unwrap_or("daily".to_string())),
+                LogRotation::Daily,
+            ),
+            log_max_files: problems.parse_var("NFTBLOCKD_LOG_MAX_FILES", 7),
+            drop_privileges: problems.parse_var("NFTBLOCKD_DROP_PRIVILEGES", true),
+            user: var("NFTBLOCKD_USER"),
+            sandbox: problems.parse_var("NFTBLOCKD_SANDBOX", false),
+            scheduling: problems.take(Scheduling::from_env(environment), Scheduling::default()),
+            pause_disables_rules: problems.parse_var("NFTBLOCKD_PAUSE_DISABLE_RULES", false),
+            watch_config: problems.parse_var("NFTBLOCKD_WATCH_CONFIG", false),
+            restore_on_start: problems.parse_var("NFTBLOCKD_RESTORE_ON_START", true),
+            retry_interval: problems.duration("NFTBLOCKD_RETRY_INTERVAL", "2s"),
+            retry_count: problems.parse_var("NFTBLOCKD_RETRY_COUNT", 10),
+            interval_jitter: problems.duration("NFTBLOCKD_INTERVAL_JITTER", "0"),
+            initial_jitter: problems.duration("NFTBLOCKD_INITIAL_JITTER", "0"),
+            stats_interval: problems.duration("NFTBLOCKD_STATS_INTERVAL", "10s"),
+            history_db: var("NFTBLOCKD_HISTORY_DB"),
+            escalate_after: var("NFTBLOCKD_ESCALATE_AFTER")
+                .map(|_| problems.parse_var("NFTBLOCKD_ESCALATE_AFTER", 1)),
+            metrics_addr: var("NFTBLOCKD_METRICS_ADDR").map(|addr| {
+                problems.take(
+                    addr.parse::<SocketAddr>().map_err(|e| {
+                        AppError::ParseError(format!("invalid NFTBLOCKD_METRICS_ADDR: {addr}: {e}"))
+                    }),
+                    SocketAddr::from(([0, 0, 0, 0], 0)),
+                )
+            }),
+            manual_path: var("NFTBLOCKD_MANUAL_PATH")
+                .map_or_else(|| state_dir(instance).join("manual.json"), PathBuf::from),
+            dns_cache_path: var("NFTBLOCKD_DNS_CACHE_PATH")
+                .map_or_else(|| state_dir(instance).join("dns-cache.json"), PathBuf::from),
+            route_cache_path: var("NFTBLOCKD_ROUTE_CACHE_PATH").map_or_else(
+                || state_dir(instance).join("route-cache.json"),
+                PathBuf::from,
+            ),
+            burn_in_path: var("NFTBLOCKD_BURN_IN_PATH")
+                .map_or_else(|| state_dir(instance).join("burn-in.json"), PathBuf::from),
+            http_cache_path: var("NFTBLOCKD_HTTP_CACHE_PATH").map_or_else(
+                || state_dir(instance).join("http-cache.json"),
+                PathBuf::from,
+            ),
+            ruleset_path: var("NFTBLOCKD_RULESET_PATH")
+                .map_or_else(|| state_dir(instance).join("ruleset.json"), PathBuf::from),
+            git_dir: var("NFTBLOCKD_GIT_DIR")
+                .map_or_else(|| state_dir(instance).join("git"), PathBuf::from),
+            replica_name: var("NFTBLOCKD_REPLICA_NAME").unwrap_or_else(hostname),
+            primary_token: var("NFTBLOCKD_PRIMARY_TOKEN"),
+            environment: environment.clone(),
+        };
+
+        if environment
+            .var_os("NFTBLOCKD_BLOCKLIST_SPLIT_STRING")
+            .is_some_and(|s| s.is_empty())
+        {
+            problems.push(
+                "NFTBLOCKD_BLOCKLIST_SPLIT_STRING is empty; unset it to split on whitespace"
+                    .to_string(),
+            );
+        }
+        if settings.retry_count == 0 {
+            problems.push("NFTBLOCKD_RETRY_COUNT must be at least 1".to_string());
+        }
+        if let Some(after) = settings.escalate_after {
+            if after == 0 {
+                problems.push("NFTBLOCKD_ESCALATE_AFTER must be at least 1".to_string());
+            }
+            if settings.history_db.is_none() {
+                problems.push("NFTBLOCKD_ESCALATE_AFTER requires NFTBLOCKD_HISTORY_DB".to_string());
+            }
+        }
+        if settings.stats_interval.is_zero() {
+            problems.push("NFTBLOCKD_STATS_INTERVAL must be longer than 0".to_string());
+        }
+        for source in ["IPV4", "IPV6"] {
+            let Some(policy) =
+                problems.take(FetchPolicy::from_env(environment, source).map(Some), None)
+            else {
+                continue;
+            };
+            for (name, timeout) in [
+                ("CONNECT_TIMEOUT", policy.connect_timeout),
+                ("READ_TIMEOUT", policy.read_timeout),
+            ] {
+                if timeout >= interval {
+                    problems.push(format!(
+                        "the {name} of the {source} source ({} s) must be shorter than the update interval ({} s)",
+                        timeout.as_secs_f64(),
+                        interval.as_secs_f64()
+                    ));
+                }
+            }
+        }
+        problems.into_result(settings)
+    }
+}
+
+/// Problems found while parsing the settings, reported together.
+#[derive(Debug)]
+struct Problems<'a> {
+    environment: &'a Environment,
+    problems: Vec<String>,
+}
+
+impl<'a> Problems<'a> {
+    fn new(environment: &'a Environment) -> Self {
+        Self {
+            environment,
+            problems: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, problem: String) {
+        self.problems.push(problem);
+    }
+
+    /// Records the error of `result`, returning `fallback` in its place.
+    /// The fallback is never used, since the settings are discarded once there is a problem.
+    fn take<T>(&mut self, result: Result<T, AppError>, fallback: T) -> T {
+        result.unwrap_or_else(|e| {
+            self.push(match e {
+                AppError::ParseError(message) => message,
+                e => e.to_string(),
+            });
+            fallback
+        })
+    }
+
+    /// Parses a variable, or returns `default` when it is unset or empty.
+    fn parse_var<T>(&mut self, name: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.environment.var(name).ok().filter(|s| !s.is_empty()) {
+            Some(value) => match value.parse::<T>() {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    self.push(format!("invalid {name}: {value}: {e}"));
+                    default
+                }
+            },
+            None => default,
+        }
+    }
+
+    fn duration(&mut self, name: &str, default: &str) -> Duration {
+        self.take(
+            env_duration(self.environment, name, default),
+            Duration::ZERO,
+        )
+    }
+
+    fn into_result<T>(self, value: T) -> Result<T, AppError> {
+        if self.problems.is_empty() {
+            Ok(value)
+        } else {
+            Err(AppError::ConfigError(self.problems))
+        }
+    }
+}
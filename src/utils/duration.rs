@@ -0,0 +1,69 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use std::time::Duration;
+
+/// Parses a duration such as `30s`, `15m`, `6h`, `1d`, or `1h30m`.
+///
+/// Units are `ms`, `s`, `m`, `h`, `d`, and `w`; a bare number is read as seconds,
+/// so existing configurations keep working.
+///
+/// # Errors
+/// Will return `AppError` when the value is empty, has an unknown unit, or overflows.
+pub fn parse_duration(value: &str) -> Result<Duration, AppError> {
+    let invalid = || {
+        AppError::ParseError(format!(
+            "invalid duration: {value}; expected seconds or a duration such as `30s`, `15m`, `6h`, or `1d`"
+        ))
+    };
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(seconds) = trimmed.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = trimmed;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let amount = rest[..digits].parse::<u64>().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let millis = match &rest[..unit] {
+            "ms" => 1,
+            "s" => 1_000,
+            "m" => 60_000,
+            "h" => 3_600_000,
+            "d" => 86_400_000,
+            "w" => 604_800_000,
+            _ => return Err(invalid()),
+        };
+        let part = amount.checked_mul(millis).ok_or_else(invalid)?;
+        total = total
+            .checked_add(Duration::from_millis(part))
+            .ok_or_else(invalid)?;
+        rest = &rest[unit..];
+    }
+    Ok(total)
+}
+
+/// Reads the duration in the variable `name` of `environment`, or parses `default` when it is unset or empty.
+///
+/// # Errors
+/// Will return `AppError` when the value is not a valid duration.
+pub fn env_duration(
+    environment: &Environment,
+    name: &str,
+    default: &str,
+) -> Result<Duration, AppError> {
+    let value = environment
+        .var(name)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or(default.to_string());
+    parse_duration(&value).map_err(|e| AppError::ParseError(format!("{name}: {e}")))
+}
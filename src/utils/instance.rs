@@ -0,0 +1,97 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use std::path::PathBuf;
+
+/// Longest accepted instance name, so that the namespaced table names stay readable.
+const MAX_INSTANCE_LEN: usize = 32;
+
+/// Checks that `instance` can be embedded in table names, socket paths, and metric labels.
+///
+/// # Errors
+/// Will return `AppError` when the name is empty, too long, or contains characters other than
+/// ASCII letters, digits, `-`, and `_`.
+pub fn validate_instance(instance: &str) -> Result<(), AppError> {
+    let valid = !instance.is_empty()
+        && instance.len() <= MAX_INSTANCE_LEN
+        && instance
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::ParseError(format!(
+            "invalid instance name: {instance}; expected at most {MAX_INSTANCE_LEN} letters, digits, `-`, or `_`"
+        )))
+    }
+}
+
+/// Returns the path of the control socket of `instance`.
+#[must_use]
+pub fn socket_path(instance: Option<&str>) -> String {
+    match instance {
+        Some(instance) => format!("/run/nftblockd-{instance}.sock"),
+        None => "/run/nftblockd.sock".to_string(),
+    }
+}
+
+/// Returns the directory holding the persistent state (e.g., the history database) of `instance`.
+#[must_use]
+pub fn state_dir(instance: Option<&str>) -> PathBuf {
+    let base = PathBuf::from("/var/lib/nftblockd");
+    match instance {
+        Some(instance) => base.join(instance),
+        None => base,
+    }
+}
+
+/// A profile run by one daemon next to its main configuration: a name, which namespaces the table, the state,
+/// and the metrics like an instance name, and the `.env` files layered over the main ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub env_files: Vec<String>,
+}
+
+impl Profile {
+    /// Parses `NAME=ENV_FILE[,ENV_FILE...]`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when no `.env` file is given or the name is not a valid instance name.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let Some((name, files)) = value.split_once('=') else {
+            return Err(AppError::ParseError(format!(
+                "invalid profile: {value}; expected NAME=ENV_FILE[,ENV_FILE...]"
+            )));
+        };
+        validate_instance(name)?;
+        let env_files = files
+            .split(',')
+            .map(str::trim)
+            .filter(|file| !file.is_empty())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if env_files.is_empty() {
+            return Err(AppError::ParseError(format!(
+                "invalid profile: {value}; the profile {name} names no .env file"
+            )));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            env_files,
+        })
+    }
+
+    /// Loads the variables the profile is configured from: its `.env` files layered over `main_files`,
+    /// the files of the main configuration, and overridden by the environment of the process.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a file cannot be read or parsed.
+    pub fn environment(&self, main_files: &[String]) -> Result<Environment, AppError> {
+        let files = main_files
+            .iter()
+            .chain(&self.env_files)
+            .cloned()
+            .collect::<Vec<_>>();
+        Environment::load(&files)
+    }
+}
@@ -1,69 +1,83 @@
 use crate::utils::network::{ListNetwork, NetworkType};
 
 /// Represents a generic IP address in either IPv4 or IPv6 format using numeric representations.
-#[allow(dead_code)]
 pub enum BitIp {
     Ipv4(u32),
     Ipv6(u128),
 }
 
-#[allow(dead_code)]
 impl BitIp {
-    /// Performs a right-shift operation on an IP address by `n` bits
-    /// and returns the result in the corresponding `BitIp` format.
-    ///
-    /// # Parameters
-    /// - `n`: The number of bits to shift.
+    /// Returns the address as a 128-bit key aligned to the most significant bit,
+    /// so IPv4 and IPv6 prefixes can be handled by the same bit operations.
     ///
     /// # Returns
-    /// The shifted `BitIp` instance.
-    fn r_shift(&self, n: u8) -> Self {
+    /// The IPv4 address shifted left by 96 bits, or the IPv6 address unchanged.
+    fn left_aligned(&self) -> u128 {
         match self {
-            BitIp::Ipv4(ip) => BitIp::Ipv4(*ip >> n),
-            BitIp::Ipv6(ip) => BitIp::Ipv6(*ip >> n),
+            BitIp::Ipv4(ip) => u128::from(*ip) << 96,
+            BitIp::Ipv6(ip) => *ip,
         }
     }
+}
 
-    /// Performs a bitwise AND operation between the IP address and the given `rhs` value.
-    ///
-    /// # Parameters
-    /// - `rhs`: The value to AND with (8 bits for this implementation).
-    ///
-    /// # Returns
-    /// The result of the operation as an 8-bit value.
-    fn b_and(self, rhs: u8) -> u8 {
-        match self {
-            BitIp::Ipv4(ip) => (ip & rhs as u32) as u8,
-            BitIp::Ipv6(ip) => (ip & rhs as u128) as u8,
-        }
+/// Keeps only the first `len` bits of a left-aligned key.
+fn mask(key: u128, len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        key & (u128::MAX << (128 - u32::from(len)))
     }
 }
 
-/// Represents a node in a prefix trie structure.
-/// Each node tracks whether it's part of a subnet (`is_subnet`)
-/// and has two children corresponding to binary bits (0 or 1).
-#[derive(Default)]
-#[allow(dead_code)]
+/// Returns the bit of a left-aligned key at position `pos`, counting from the most significant bit.
+fn bit_at(key: u128, pos: u8) -> usize {
+    ((key >> (127 - u32::from(pos))) & 1) as usize
+}
+
+/// Represents a node in a path-compressed (Patricia) prefix trie.
+///
+/// Each node stores the whole prefix it represents (`key`/`len`), so a chain of single-child
+/// nodes collapses into one edge. A node either marks a subnet (`is_subnet`) or is a branching
+/// point with two children.
+///
+/// Children are indices into `PrefixTrie::nodes`; `0` means no child,
+/// because the root (index `0`) is never a child of another node.
+#[derive(Default, Clone, Copy)]
 struct TrieNode {
-    children: [Option<Box<TrieNode>>; 2],
+    key: u128,
+    len: u8,
+    children: [usize; 2],
     is_subnet: bool,
 }
 
-#[allow(dead_code)]
-impl TrieNode {
-    /// Creates a new and defaulted `TrieNode` instance.
+/// Path-compressed prefix trie storing all of its nodes in a single vector.
+///
+/// Inserting a prefix creates at most two nodes (a leaf and a branching node) regardless of
+/// the prefix length, instead of one node per bit.
+struct PrefixTrie {
+    nodes: Vec<TrieNode>,
+}
+
+impl PrefixTrie {
+    /// Creates a trie containing only the root node.
     ///
-    /// # Returns
-    /// A fresh `TrieNode` with no children and `is_subnet` set to `false`.
-    fn new() -> Self {
-        Self {
-            children: Default::default(),
-            is_subnet: false,
-        }
+    /// # Parameters
+    /// - `capacity`: The number of nodes to preallocate.
+    fn with_capacity(capacity: usize) -> Self {
+        let mut nodes = Vec::with_capacity(capacity.max(1));
+        nodes.push(TrieNode::default());
+        Self { nodes }
+    }
+
+    /// Appends a node and returns its index.
+    fn push(&mut self, node: TrieNode) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(node);
+        index
     }
 
-    /// Inserts an IP prefix into the trie. The path through the trie is determined
-    /// by iterating over the bits of the IP address.
+    /// Inserts an IP prefix into the trie, descending along the compressed edges
+    /// that share the prefix bits.
     ///
     /// # Parameters
     /// - `ip`: An instance of the `BlockListNetwork` trait, representing the IP prefix to insert.
@@ -75,28 +89,69 @@ impl TrieNode {
     where
         T: ListNetwork,
     {
-        let mut node = self;
+        let len = ip.network_prefix();
+        let key = mask(ip.network_addr().left_aligned(), len);
+        let leaf = TrieNode {
+            key,
+            len,
+            children: [0; 2],
+            is_subnet: true,
+        };
+        let mut index = 0;
 
-        for i in 0..ip.network_prefix() {
+        loop {
+            let node = self.nodes[index];
             if node.is_subnet {
-                // This subnet is already covered by a broader one
+                // This subnet is already covered by a broader one (or is a duplicate).
                 return false;
             }
 
-            let n = ip.max_prefix() - 1 - i;
-            let bit = ip.network_addr().r_shift(n).b_and(1);
-            node = node.children[bit as usize].get_or_insert_with(|| Box::new(TrieNode::new()));
-        }
+            if node.len == len {
+                // The prefix matches a branching node; mark it and prune deeper subnets.
+                self.nodes[index] = leaf;
+                return true;
+            }
 
-        if node.is_subnet {
-            // Exact subnet already exists — this is a duplicate.
-            return false;
-        }
+            let bit = bit_at(key, node.len);
+            let child_index = node.children[bit];
+            if child_index == 0 {
+                let leaf_index = self.push(leaf);
+                self.nodes[index].children[bit] = leaf_index;
+                return true;
+            }
+
+            let child = self.nodes[child_index];
+            let common = u8::try_from((key ^ child.key).leading_zeros())
+                .unwrap_or(128)
+                .min(len)
+                .min(child.len);
+
+            if common == child.len {
+                // The child's prefix covers the inserted prefix; descend.
+                index = child_index;
+                continue;
+            }
 
-        // Mark this node as a subnet and prune deeper subnets
-        node.is_subnet = true;
-        node.children = Default::default(); // Drop more specific subnets
-        true
+            if common == len {
+                // The inserted prefix covers the child; replace it and drop more specific subnets.
+                let leaf_index = self.push(leaf);
+                self.nodes[index].children[bit] = leaf_index;
+                return true;
+            }
+
+            // The prefixes diverge; split the edge with a branching node.
+            let mut branch = TrieNode {
+                key: mask(key, common),
+                len: common,
+                children: [0; 2],
+                is_subnet: false,
+            };
+            branch.children[bit_at(child.key, common)] = child_index;
+            branch.children[bit_at(key, common)] = self.push(leaf);
+            let branch_index = self.push(branch);
+            self.nodes[index].children[bit] = branch_index;
+            return true;
+        }
     }
 }
 
@@ -140,7 +195,9 @@ impl TrieNode {
 ///
 /// # Time Complexity
 /// -   `O(h * n * logn)`: Sorting the IPs contributes `n * logn`, and inserting into the trie has
-///     a height-dependent complexity of `h`, which is 32 for IPv4 and 128 for IPv6.
+///     a height-dependent complexity of `h`, which is at most 32 for IPv4 and 128 for IPv6.
+///     With path compression, `h` is bounded by the number of branching points on the path
+///     rather than by the prefix length.
 pub fn deduplicate<T>(ips: Option<Vec<NetworkType<T>>>) -> Option<Vec<NetworkType<T>>>
 where
     T: ListNetwork,
@@ -154,10 +211,10 @@ where
         }
     }
     networks.sort_by_key(ListNetwork::network_prefix);
-    let mut root = TrieNode::new();
+    let mut trie = PrefixTrie::with_capacity(networks.len());
     let mut result = Vec::new();
     for ip in networks {
-        if root.insert(&ip) {
+        if trie.insert(&ip) {
             result.push(ip);
         }
     }
@@ -0,0 +1,36 @@
+use log::warn;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Why the kernel cannot filter IPv6 traffic, detected once.
+static IPV6_UNSUPPORTED: OnceLock<Option<String>> = OnceLock::new();
+
+/// Explains why the kernel of the host cannot filter IPv6 traffic, or returns `None` when it can.
+/// The result is detected once and logged as a warning.
+#[must_use]
+pub fn ipv6_unsupported() -> Option<&'static str> {
+    IPV6_UNSUPPORTED
+        .get_or_init(|| {
+            let reason = detect_ipv6_unsupported(Path::new("/proc"));
+            if let Some(reason) = &reason {
+                warn!("IPv6 is not supported: {reason}");
+            }
+            reason
+        })
+        .as_deref()
+}
+
+/// Detects a kernel without IPv6 support from the `proc` file system mounted at `proc`.
+///
+/// IPv6 is unsupported when the kernel was built or booted without it (e.g., with `ipv6.disable=1`),
+/// which removes `net/if_inet6`, or when it is disabled on all interfaces with `net.ipv6.conf.all.disable_ipv6`.
+#[must_use]
+pub fn detect_ipv6_unsupported(proc: &Path) -> Option<String> {
+    if !proc.join("net/if_inet6").exists() {
+        return Some("the kernel was built or booted without IPv6".to_string());
+    }
+    fs::read_to_string(proc.join("sys/net/ipv6/conf/all/disable_ipv6"))
+        .is_ok_and(|disabled| disabled.trim() == "1")
+        .then(|| "IPv6 is disabled by net.ipv6.conf.all.disable_ipv6".to_string())
+}
@@ -0,0 +1,145 @@
+use crate::error::AppError;
+use crate::utils::parse_size;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+/// When the log file is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    /// Rotate once the file reaches the given size in bytes.
+    Size(u64),
+    Never,
+}
+
+impl LogRotation {
+    /// Parses the value of `NFTBLOCKD_LOG_ROTATION`: `hourly`, `daily`, `never`,
+    /// or a size with an optional `K`, `M`, or `G` suffix (e.g., `50M`).
+    ///
+    /// # Errors
+    /// Will return `AppError` when the value is neither a known policy nor a size.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            "never" => Ok(LogRotation::Never),
+            size => parse_size(size).map(LogRotation::Size).ok_or_else(|| {
+                AppError::ParseError(format!(
+                    "invalid log rotation: {value}; expected `hourly`, `daily`, `never`, or a size such as `50M`"
+                ))
+            }),
+        }
+    }
+}
+
+/// Opens `path` for logging with the given rotation, keeping at most `max_files` rotated files.
+///
+/// Lines are written by a background thread; the returned guard flushes them when dropped,
+/// so it must be kept alive for as long as the program logs.
+///
+/// # Errors
+/// Will return `AppError` when the log file or its directory cannot be created.
+pub fn open_log_file(
+    path: &Path,
+    rotation: LogRotation,
+    max_files: usize,
+) -> Result<(NonBlocking, WorkerGuard), AppError> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(directory)?;
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| AppError::ParseError(format!("invalid log file: {}", path.display())))?;
+    let time_based = |rotation| {
+        RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(file_name)
+            .max_log_files(max_files.max(1))
+            .build(directory)
+            .map_err(|e| AppError::FileError(format!("failed to open log file: {e}"), None))
+    };
+    Ok(match rotation {
+        LogRotation::Hourly => tracing_appender::non_blocking(time_based(Rotation::HOURLY)?),
+        LogRotation::Daily => tracing_appender::non_blocking(time_based(Rotation::DAILY)?),
+        LogRotation::Never => tracing_appender::non_blocking(time_based(Rotation::NEVER)?),
+        LogRotation::Size(max_bytes) => tracing_appender::non_blocking(SizeRotatingFile::open(
+            path.to_path_buf(),
+            max_bytes,
+            max_files,
+        )?),
+    })
+}
+
+/// A log file rotated to `<path>.1`, `<path>.2`, ... once it exceeds `max_bytes`.
+#[derive(Debug)]
+pub struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingFile {
+    /// Opens `path` for appending.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be opened.
+    pub fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> Result<Self, AppError> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::AppError;
+use crate::settings::Environment;
+use crate::utils::parse_size;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Resets the peak resident memory of the process, so that the next `peak_resident` covers only what follows.
+///
+/// # Returns
+///
+/// `false` when the peak cannot be reset, e.g., on kernels older than 4.0 or in a sandbox; the peak then
+/// covers the lifetime of the process.
+pub fn reset_peak() -> bool {
+    // `5` resets the peak resident set size, see `proc_pid_clear_refs(5)`.
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+/// Tracks the peak resident memory of an update by sampling it, for when `reset_peak` fails,
+/// e.g., after the privileges are dropped, since `/proc/self/clear_refs` then belongs to root.
+#[derive(Debug, Default)]
+pub struct PeakSampler(AtomicU64);
+
+impl PeakSampler {
+    /// Starts a new update with the current resident memory as its peak.
+    pub fn reset(&self) {
+        self.0.store(resident().unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Reads the current resident memory and raises the peak to it.
+    ///
+    /// # Returns
+    ///
+    /// The current resident memory in bytes, or `None` when it cannot be read.
+    pub fn sample(&self) -> Option<u64> {
+        let resident = resident()?;
+        self.0.fetch_max(resident, Ordering::Relaxed);
+        Some(resident)
+    }
+
+    /// Returns the highest sample since the last `reset`, or `None` when none could be read.
+    #[must_use]
+    pub fn peak(&self) -> Option<u64> {
+        Some(self.0.load(Ordering::Relaxed)).filter(|peak| *peak > 0)
+    }
+}
+
+/// Returns the current resident memory of the process in bytes, or `None` when it cannot be read.
+#[must_use]
+pub fn resident() -> Option<u64> {
+    status_field("VmRSS:")
+}
+
+/// Returns the peak resident memory of the process in bytes since the last `reset_peak`,
+/// or `None` when it cannot be read.
+#[must_use]
+pub fn peak_resident() -> Option<u64> {
+    status_field("VmHWM:")
+}
+
+/// Reads a field of `/proc/self/status` given in kB, e.g., `VmRSS:    10240 kB`.
+fn status_field(name: &str) -> Option<u64> {
+    fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .and_then(|value| {
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+        .map(|kib| kib * 1024)
+}
+
+/// Formats `bytes` in MiB for the logs, e.g., `12.5 MiB`.
+#[must_use]
+pub fn format_mib(bytes: u64) -> String {
+    #[allow(clippy::cast_precision_loss)]
+    let mib = bytes as f64 / f64::from(1 << 20);
+    format!("{mib:.1} MiB")
+}
+
+/// Soft cap on the resident memory of the process while the blocklists are parsed.
+///
+/// A feed that suddenly grows by orders of magnitude would otherwise make the daemon grow until the OOM killer
+/// steps in, which may take down other services of the firewall host. The memory is checked while the body of
+/// every source is downloaded and between the stages of parsing it, and the update is aborted with
+/// `AppError::MemoryError` once it is exceeded; the last applied ruleset stays in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimit {
+    /// Resident memory in bytes above which the update is aborted.
+    pub max_bytes: u64,
+}
+
+impl MemoryLimit {
+    /// Reads the limit from `NFTBLOCKD_MEMORY_LIMIT`, e.g., `512M`.
+    ///
+    /// # Returns
+    ///
+    /// `None` when the variable is unset, i.e., the memory is not limited.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the limit is not a size.
+    pub fn from_env(environment: &Environment) -> Result<Option<Self>, AppError> {
+        environment
+            .var("NFTBLOCKD_MEMORY_LIMIT")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|limit| {
+                parse_size(limit.trim())
+                    .map(|max_bytes| Self { max_bytes })
+                    .ok_or_else(|| {
+                        AppError::ParseError(format!(
+                            "invalid NFTBLOCKD_MEMORY_LIMIT: {limit}; expected a size, e.g., 512M"
+                        ))
+                    })
+            })
+            .transpose()
+    }
+
+    /// Checks `resident` bytes against the limit while `stage` runs.
+    ///
+    /// # Errors
+    /// Will return `AppError::MemoryError` when the limit is exceeded.
+    pub fn check_usage(&self, resident: u64, stage: &str) -> Result<(), AppError> {
+        if resident <= self.max_bytes {
+            return Ok(());
+        }
+        Err(AppError::MemoryError(format!(
+            "{} used while {stage} exceeds NFTBLOCKD_MEMORY_LIMIT of {}; aborting the update",
+            format_mib(resident),
+            format_mib(self.max_bytes)
+        )))
+    }
+
+    /// Checks the current resident memory of the process against the limit while `stage` runs;
+    /// passes when the memory cannot be read.
+    ///
+    /// # Errors
+    /// Will return `AppError::MemoryError` when the limit is exceeded.
+    pub fn check(&self, stage: &str) -> Result<(), AppError> {
+        resident().map_or(Ok(()), |resident| self.check_usage(resident, stage))
+    }
+
+    /// Checks the current resident memory plus the `reserved` bytes a stage is about to allocate, e.g., the entries
+    /// parsed from a body of that size, against the limit; passes when the memory cannot be read.
+    ///
+    /// # Errors
+    /// Will return `AppError::MemoryError` when the limit would be exceeded.
+    pub fn check_reserve(&self, reserved: u64, stage: &str) -> Result<(), AppError> {
+        resident().map_or(Ok(()), |resident| {
+            self.check_usage(resident.saturating_add(reserved), stage)
+        })
+    }
+}
@@ -1,20 +1,124 @@
-use crate::error::AppError;
+use crate::error::{AppError, ErrorSource};
 use std::fs;
+use std::path::{Path, PathBuf};
 
+pub mod duration;
+pub mod instance;
 pub mod iptrie;
+pub mod kernel;
+pub mod log_file;
+pub mod memory;
 pub mod network;
+pub mod pcap;
+pub mod prefix_set;
+pub mod priority;
+pub mod privileges;
+pub mod sandbox;
+pub mod scheduling;
 pub mod stats;
 pub mod status;
+pub mod status_file;
 pub mod subnet;
+pub mod watch;
 
 pub fn read_ip_set_file<S: AsRef<str>>(path: Option<S>) -> Result<Option<String>, AppError> {
     let data = path.map_or_else(
         || Ok::<Option<String>, AppError>(None),
         |p| {
-            let data = fs::read_to_string(p.as_ref())
-                .map_err(|e| AppError::FileError(format!("{e}: {}", p.as_ref())))?;
+            let data = fs::read_to_string(p.as_ref()).map_err(|e| {
+                AppError::FileError(format!("{e}: {}", p.as_ref()), Some(ErrorSource::new(e)))
+            })?;
             Ok(Some(data))
         },
     )?;
     Ok(data)
 }
+
+/// Parses a positive size in bytes with an optional `K`, `M`, or `G` suffix (e.g., `50M`).
+#[must_use]
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.to_ascii_lowercase();
+    let (digits, unit) = match value.char_indices().last() {
+        Some((i, 'k')) => (&value[..i], 1 << 10),
+        Some((i, 'm')) => (&value[..i], 1 << 20),
+        Some((i, 'g')) => (&value[..i], 1 << 30),
+        _ => (value.as_str(), 1),
+    };
+    match digits.parse::<u64>() {
+        Ok(n) if n > 0 => Some(n.saturating_mul(unit)),
+        _ => None,
+    }
+}
+
+/// Returns the hostname of the machine, or `localhost` if it cannot be read.
+#[must_use]
+pub fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or("localhost".to_string())
+}
+
+/// Splits a path into the directory it lists and the pattern of the file names it matches,
+/// e.g., `/etc/nftblockd/blocklists/*.txt` or a directory, which matches all of its files.
+///
+/// # Returns
+/// `None` for a path naming a single file.
+#[must_use]
+pub fn path_pattern(path: &Path) -> Option<(PathBuf, String)> {
+    if path.is_dir() {
+        return Some((path.to_path_buf(), "*".to_string()));
+    }
+    let name = path.file_name()?.to_str()?;
+    if !name.contains(['*', '?']) {
+        return None;
+    }
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Some((directory.to_path_buf(), name.to_string()))
+}
+
+/// Expands a file, a directory, or a glob in its last component into the files it names, sorted by name.
+///
+/// # Errors
+/// Will return `AppError` when the directory of a glob cannot be listed.
+pub fn expand_paths(path: &str) -> Result<Vec<PathBuf>, AppError> {
+    let Some((directory, pattern)) = path_pattern(Path::new(path)) else {
+        return Ok(vec![PathBuf::from(path)]);
+    };
+    let mut files = fs::read_dir(&directory)
+        .map_err(|e| AppError::FileError(format!("{e}: {path}"), Some(ErrorSource::new(e))))?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| glob_match(&pattern, name))
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+/// Matches a file name against a pattern with `*` and `?` wildcards; like a shell,
+/// wildcards do not match a leading `.`, so that hidden files, e.g., editor swap files, are left out.
+#[must_use]
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    matches(&pattern, &name)
+}
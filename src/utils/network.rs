@@ -2,7 +2,7 @@ use crate::utils::iptrie::BitIp;
 use ipnetwork::{Ipv4Network, Ipv6Network};
 use std::fmt::Debug;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum NetworkType<T>
 where
     T: ListNetwork + Clone + Debug,
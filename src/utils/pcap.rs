@@ -0,0 +1,133 @@
+use crate::error::AppError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 2] = [0x8100, 0x88a8];
+
+/// Whether `data` starts with the header of a pcap capture.
+#[must_use]
+pub fn is_pcap(data: &[u8]) -> bool {
+    big_endian(data).is_some()
+}
+
+/// Byte order of a pcap capture by its magic number, with microsecond or nanosecond timestamps.
+fn big_endian(data: &[u8]) -> Option<bool> {
+    match data.get(..4)? {
+        [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => Some(false),
+        [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => Some(true),
+        _ => None,
+    }
+}
+
+/// Reads the source and destination addresses of the IPv4 and IPv6 packets of a pcap capture.
+///
+/// Captures of Ethernet (including VLAN tags), Linux cooked, and raw IP links are supported; other packets,
+/// e.g., ARP, are skipped, and a truncated last packet ends the capture.
+///
+/// # Errors
+/// Will return `AppError` when `data` is not a pcap capture, e.g., a pcapng one, or its link type is not supported.
+pub fn packet_addresses(data: &[u8]) -> Result<Vec<(IpAddr, IpAddr)>, AppError> {
+    let big_endian = big_endian(data).ok_or_else(|| {
+        AppError::ParseError(
+            "not a pcap capture; convert pcapng with `editcap -F pcap`".to_string(),
+        )
+    })?;
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+            .map(|bytes| {
+                if big_endian {
+                    u32::from_be_bytes(bytes)
+                } else {
+                    u32::from_le_bytes(bytes)
+                }
+            })
+    };
+    // The upper bits of the link type may carry the FCS length.
+    let link_type = u32_at(20)
+        .ok_or_else(|| AppError::ParseError("truncated pcap header".to_string()))?
+        & 0x0fff_ffff;
+    if ![
+        LINKTYPE_ETHERNET,
+        LINKTYPE_RAW,
+        LINKTYPE_LINUX_SLL,
+        LINKTYPE_IPV4,
+        LINKTYPE_IPV6,
+    ]
+    .contains(&link_type)
+    {
+        return Err(AppError::ParseError(format!(
+            "unsupported pcap link type {link_type}; expected Ethernet, Linux cooked, or raw IP"
+        )));
+    }
+    let mut addresses = Vec::new();
+    let mut offset = 24;
+    while let Some(captured) = u32_at(offset + 8) {
+        let start = offset + 16;
+        let Some(packet) = usize::try_from(captured)
+            .ok()
+            .and_then(|captured| data.get(start..start + captured))
+        else {
+            break;
+        };
+        offset = start + packet.len();
+        if let Some(pair) = link_payload(link_type, packet).and_then(ip_addresses) {
+            addresses.push(pair);
+        }
+    }
+    Ok(addresses)
+}
+
+/// Strips the link-layer header of a packet carrying IPv4 or IPv6.
+fn link_payload(link_type: u32, packet: &[u8]) -> Option<&[u8]> {
+    let ethertype = |offset: usize| {
+        packet
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            while ethertype(offset).is_some_and(|t| ETHERTYPE_VLAN.contains(&t)) {
+                offset += 4;
+            }
+            matches!(ethertype(offset)?, ETHERTYPE_IPV4 | ETHERTYPE_IPV6)
+                .then(|| packet.get(offset + 2..))
+                .flatten()
+        }
+        LINKTYPE_LINUX_SLL => matches!(ethertype(14)?, ETHERTYPE_IPV4 | ETHERTYPE_IPV6)
+            .then(|| packet.get(16..))
+            .flatten(),
+        _ => Some(packet),
+    }
+}
+
+/// Reads the source and destination addresses of an IPv4 or IPv6 header.
+fn ip_addresses(packet: &[u8]) -> Option<(IpAddr, IpAddr)> {
+    match packet.first()? >> 4 {
+        4 => {
+            let source = <[u8; 4]>::try_from(packet.get(12..16)?).ok()?;
+            let destination = <[u8; 4]>::try_from(packet.get(16..20)?).ok()?;
+            Some((
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+            ))
+        }
+        6 => {
+            let source = <[u8; 16]>::try_from(packet.get(8..24)?).ok()?;
+            let destination = <[u8; 16]>::try_from(packet.get(24..40)?).ok()?;
+            Some((
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+            ))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,306 @@
+use crate::error::AppError;
+use crate::nftables::builder::SetElements;
+use crate::utils::iptrie::BitIp;
+use crate::utils::network::{ListNetwork, NetworkType};
+use ipnetwork::IpNetwork;
+use nftables::expr::{Elem, Expression, NamedExpression, Prefix as NftPrefix, Range};
+use std::borrow::Cow;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A network or an address range held by a set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Prefix {
+    /// A network; an address is a network of the maximum prefix length.
+    Network(IpNetwork),
+    /// The addresses from the first to the second one, both included.
+    Range(IpAddr, IpAddr),
+}
+
+impl Prefix {
+    /// Whether the prefix holds IPv6 addresses.
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        match self {
+            Self::Network(network) => network.is_ipv6(),
+            Self::Range(start, _) => start.is_ipv6(),
+        }
+    }
+
+    /// Converts a validated network into a prefix without formatting and parsing its address again.
+    fn from_network<T: ListNetwork>(network: NetworkType<T>) -> Option<Self> {
+        match network {
+            NetworkType::Ip(network) => IpNetwork::new(address(&network), network.network_prefix())
+                .ok()
+                .map(Self::Network),
+            NetworkType::Range(start, end) => Some(Self::Range(address(&start), address(&end))),
+        }
+    }
+
+    /// Converts the prefix into the `nftables` expression matching it.
+    #[must_use]
+    pub fn to_expression<'a>(&self) -> Expression<'a> {
+        match self {
+            Self::Network(network) => Expression::Named(NamedExpression::Prefix(NftPrefix {
+                addr: Box::new(Expression::String(Cow::Owned(
+                    network.network().to_string(),
+                ))),
+                len: u32::from(network.prefix()),
+            })),
+            Self::Range(start, end) => Expression::Range(Box::new(Range {
+                range: [
+                    Expression::String(Cow::Owned(start.to_string())),
+                    Expression::String(Cow::Owned(end.to_string())),
+                ],
+            })),
+        }
+    }
+
+    /// Converts an address, prefix, or range expression back into a prefix.
+    ///
+    /// # Returns
+    /// `None` if the expression is none of them.
+    #[must_use]
+    pub fn from_expression(expression: &Expression<'_>) -> Option<Self> {
+        match expression {
+            Expression::String(addr) => addr
+                .parse::<IpAddr>()
+                .ok()
+                .map(|addr| Self::Network(IpNetwork::from(addr))),
+            Expression::Named(NamedExpression::Prefix(prefix)) => {
+                let Expression::String(addr) = prefix.addr.as_ref() else {
+                    return None;
+                };
+                let addr = addr.parse::<IpAddr>().ok()?;
+                let len = u8::try_from(prefix.len).ok()?;
+                IpNetwork::new(addr, len).ok().map(Self::Network)
+            }
+            Expression::Range(range) => {
+                let [Expression::String(start), Expression::String(end)] = &range.range else {
+                    return None;
+                };
+                Some(Self::Range(start.parse().ok()?, end.parse().ok()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Prefix {
+    /// Formats the prefix the way `nft` lists it, e.g., `192.0.2.1`, `192.0.2.0/24`, or `192.0.2.1-192.0.2.9`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(network) if network.prefix() == max_prefix(network) => {
+                write!(f, "{}", network.ip())
+            }
+            Self::Network(network) => write!(f, "{}/{}", network.network(), network.prefix()),
+            Self::Range(start, end) => write!(f, "{start}-{end}"),
+        }
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = AppError;
+
+    /// Parses an address, a network, or a range of two addresses separated by `-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || AppError::ParseError(format!("invalid prefix: {s}"));
+        match s.split_once('-') {
+            Some((start, end)) => {
+                let start = start.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+                let end = end.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+                if start.is_ipv6() != end.is_ipv6() || start > end {
+                    return Err(invalid());
+                }
+                Ok(Self::Range(start, end))
+            }
+            None => s
+                .parse::<IpNetwork>()
+                .map(Self::Network)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+/// The network address of `network`.
+fn address<T: ListNetwork>(network: &T) -> IpAddr {
+    match network.network_addr() {
+        BitIp::Ipv4(bits) => IpAddr::V4(Ipv4Addr::from_bits(bits)),
+        BitIp::Ipv6(bits) => IpAddr::V6(Ipv6Addr::from_bits(bits)),
+    }
+}
+
+/// Length of the prefix of a single address of the family of `network`.
+fn max_prefix(network: &IpNetwork) -> u8 {
+    if network.is_ipv6() { 128 } else { 32 }
+}
+
+/// An element of a set: a prefix and the metadata `nft` keeps next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrefixElement {
+    pub prefix: Prefix,
+    /// Time after which the kernel removes the element; it never expires when `None`.
+    pub timeout: Option<Duration>,
+    /// Comment shown by `nft list set`, e.g., the source of the element.
+    pub comment: Option<String>,
+}
+
+impl PrefixElement {
+    /// Converts the element into its `nftables` expression, wrapped in an `elem` only when it carries metadata.
+    #[must_use]
+    pub fn to_expression<'a>(&self) -> Expression<'a> {
+        self.clone().into_expression()
+    }
+
+    /// Converts the element into its `nftables` expression like `to_expression`, moving its comment.
+    #[must_use]
+    pub fn into_expression<'a>(self) -> Expression<'a> {
+        let expression = self.prefix.to_expression();
+        if self.timeout.is_none() && self.comment.is_none() {
+            return expression;
+        }
+        Expression::Named(NamedExpression::Elem(Elem {
+            val: Box::new(expression),
+            timeout: self
+                .timeout
+                .map(|ttl| u32::try_from(ttl.as_secs().max(1)).unwrap_or(u32::MAX)),
+            comment: self.comment.map(Cow::Owned),
+            ..Elem::default()
+        }))
+    }
+
+    /// Converts an expression built by `to_expression` back into an element.
+    ///
+    /// # Returns
+    /// `None` if the expression holds no address, prefix, or range.
+    #[must_use]
+    pub fn from_expression(expression: &Expression<'_>) -> Option<Self> {
+        match expression {
+            Expression::Named(NamedExpression::Elem(elem)) => Some(Self {
+                prefix: Prefix::from_expression(&elem.val)?,
+                timeout: elem.timeout.map(|ttl| Duration::from_secs(u64::from(ttl))),
+                comment: elem.comment.as_ref().map(ToString::to_string),
+            }),
+            expression => Prefix::from_expression(expression).map(Self::from),
+        }
+    }
+}
+
+impl From<Prefix> for PrefixElement {
+    fn from(prefix: Prefix) -> Self {
+        Self {
+            prefix,
+            timeout: None,
+            comment: None,
+        }
+    }
+}
+
+/// The elements of a set, independent of the `nftables` expressions they are applied as.
+///
+/// The pipeline validates, deduplicates, and annotates the entries of a source as a `PrefixSet` and converts it
+/// into expressions only when building the ruleset, so that the elements can be inspected and compared as plain
+/// networks, without the lifetimes of the `nftables` types.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefixSet {
+    elements: Vec<PrefixElement>,
+}
+
+impl PrefixSet {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts validated and deduplicated networks into a set, keeping their order and freeing them as it goes.
+    #[must_use]
+    pub fn from_networks<T: ListNetwork>(networks: Vec<NetworkType<T>>) -> Self {
+        networks
+            .into_iter()
+            .filter_map(Prefix::from_network)
+            .collect()
+    }
+
+    /// Converts `nftables` expressions into a set, leaving out those that are not addresses, prefixes, or ranges.
+    #[must_use]
+    pub fn from_elements(elements: &[Expression<'_>]) -> Self {
+        Self {
+            elements: elements
+                .iter()
+                .filter_map(PrefixElement::from_expression)
+                .collect(),
+        }
+    }
+
+    /// Annotates every element with `comment`, so `nft list set` shows it next to the element.
+    #[must_use]
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        for element in &mut self.elements {
+            element.comment = Some(comment.to_string());
+        }
+        self
+    }
+
+    pub fn push(&mut self, element: impl Into<PrefixElement>) {
+        self.elements.push(element.into());
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PrefixElement> {
+        self.elements.iter()
+    }
+
+    /// Whether an element of the set holds `prefix`.
+    #[must_use]
+    pub fn contains(&self, prefix: &Prefix) -> bool {
+        self.elements
+            .iter()
+            .any(|element| element.prefix == *prefix)
+    }
+
+    /// Converts the set into the `nftables` expressions of its elements.
+    #[must_use]
+    pub fn into_elements<'a>(self) -> SetElements<'a> {
+        self.elements
+            .into_iter()
+            .map(PrefixElement::into_expression)
+            .collect()
+    }
+}
+
+impl FromIterator<Prefix> for PrefixSet {
+    fn from_iter<I: IntoIterator<Item = Prefix>>(iter: I) -> Self {
+        Self {
+            elements: iter.into_iter().map(PrefixElement::from).collect(),
+        }
+    }
+}
+
+impl FromIterator<PrefixElement> for PrefixSet {
+    fn from_iter<I: IntoIterator<Item = PrefixElement>>(iter: I) -> Self {
+        Self {
+            elements: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for PrefixSet {
+    type Item = PrefixElement;
+    type IntoIter = std::vec::IntoIter<PrefixElement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
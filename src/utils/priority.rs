@@ -0,0 +1,61 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// An element competing for a place in a set truncated to its maximum size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// The element as `address/prefix`, or `start-end` for a range.
+    pub entry: String,
+    /// Number of host bits of the element, i.e., how broad it is.
+    pub host_bits: u32,
+    /// Position of the element in its list.
+    pub position: usize,
+}
+
+/// Chooses which elements survive when a list is truncated to the maximum set size.
+///
+/// The candidates are sorted by `compare` and the first ones are kept, so other strategies, e.g., ranking
+/// by threat intelligence scores, can be plugged into `ValidatedSubnetList::deduplicate_prioritized`.
+pub trait Prioritizer: Send + Sync {
+    /// Orders two candidates; the one ordered first is kept rather than the other.
+    fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering;
+}
+
+/// Keeps the elements listed first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListOrder;
+
+impl Prioritizer for ListOrder {
+    fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering {
+        a.position.cmp(&b.position)
+    }
+}
+
+/// Keeps the broadest elements, covering as many addresses as possible, then those listed first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BroaderFirst;
+
+impl Prioritizer for BroaderFirst {
+    fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering {
+        b.host_bits
+            .cmp(&a.host_bits)
+            .then_with(|| ListOrder.compare(a, b))
+    }
+}
+
+/// Keeps the elements with the highest scores, e.g., the combined weight of the consensus feeds listing them,
+/// then the broadest ones. Elements without a score rank last.
+#[derive(Debug, Clone, Default)]
+pub struct HighestScore {
+    /// Scores by element as `address/prefix`.
+    pub scores: BTreeMap<String, u32>,
+}
+
+impl Prioritizer for HighestScore {
+    fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering {
+        let score = |candidate: &Candidate| self.scores.get(&candidate.entry).copied();
+        score(b)
+            .cmp(&score(a))
+            .then_with(|| BroaderFirst.compare(a, b))
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::AppError;
+use crate::nftables::applier::Applier;
+use caps::{CapSet, Capability, CapsHashSet};
+use log::{info, warn};
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+/// The only capability the daemon needs: loading rulesets and reading nflog both go through netfilter netlink.
+pub const REQUIRED_CAPABILITY: Capability = Capability::CAP_NET_ADMIN;
+
+/// Outcome of a single probe of `check_privileges`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivilegeCheck {
+    pub name: String,
+    pub ok: bool,
+    /// What is missing and how to grant it; empty when the check passed.
+    pub detail: String,
+}
+
+impl Display for PrivilegeCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ok {
+            write!(f, "[ok]      {}", self.name)
+        } else {
+            write!(f, "[missing] {}: {}", self.name, self.detail)
+        }
+    }
+}
+
+/// Returns whether the process can currently use `CAP_NET_ADMIN`.
+#[must_use]
+pub fn has_required_capability() -> bool {
+    caps::has_cap(None, CapSet::Effective, REQUIRED_CAPABILITY).unwrap_or(false)
+}
+
+/// Probes everything the daemon needs at runtime and explains what is missing.
+#[must_use]
+pub fn check_privileges(socket_path: &str, applier: &dyn Applier) -> Vec<PrivilegeCheck> {
+    let mut checks = vec![PrivilegeCheck {
+        name: "CAP_NET_ADMIN".to_string(),
+        ok: has_required_capability(),
+        detail: "required to load the nftables ruleset and to read nflog; run as root or grant it \
+                 with `AmbientCapabilities=CAP_NET_ADMIN`"
+            .to_string(),
+    }];
+
+    let directory = Path::new(socket_path).parent().unwrap_or(Path::new("/"));
+    let probe = directory.join(format!(".nftblockd-probe-{}", std::process::id()));
+    let writable = fs::write(&probe, b"").and_then(|()| fs::remove_file(&probe));
+    checks.push(PrivilegeCheck {
+        name: format!("write access to {}", directory.display()),
+        ok: writable.is_ok(),
+        detail: writable
+            .err()
+            .map(|e| format!("required to create the control socket {socket_path}: {e}"))
+            .unwrap_or_default(),
+    });
+
+    let listed = applier.current_ruleset();
+    checks.push(PrivilegeCheck {
+        name: "listing the nftables ruleset".to_string(),
+        ok: listed.is_ok(),
+        detail: listed
+            .err()
+            .map(|e| format!("`nft` failed: {e}"))
+            .unwrap_or_default(),
+    });
+    checks
+}
+
+/// Drops every capability except `CAP_NET_ADMIN`, optionally switching to `user` first.
+///
+/// `CAP_NET_ADMIN` is also raised in the ambient set, so the `nft` executable spawned to apply
+/// rulesets keeps it even after switching to a non-root user.
+///
+/// # Errors
+/// Will return `AppError` when the user does not exist or the capabilities cannot be changed.
+pub fn drop_privileges(user: Option<&str>) -> Result<(), AppError> {
+    let caps_error = |e: caps::errors::CapsError| {
+        AppError::PrivilegeError(format!("failed to drop capabilities: {e}"))
+    };
+    let mut keep = CapsHashSet::new();
+    if caps::has_cap(None, CapSet::Permitted, REQUIRED_CAPABILITY).map_err(caps_error)? {
+        keep.insert(REQUIRED_CAPABILITY);
+    } else {
+        warn!("{REQUIRED_CAPABILITY} is not permitted; applying rulesets will fail");
+    }
+
+    // The bounding set can only be reduced while CAP_SETPCAP is still effective.
+    if caps::has_cap(None, CapSet::Effective, Capability::CAP_SETPCAP).map_err(caps_error)? {
+        for capability in caps::all().difference(&keep) {
+            caps::drop(None, CapSet::Bounding, *capability).map_err(caps_error)?;
+        }
+    }
+
+    if let Some(user) = user {
+        let (uid, gid) = lookup_user(user)?;
+        switch_user(uid, gid)?;
+        info!("switched to user {user} (uid {uid}, gid {gid})");
+    }
+
+    caps::set(None, CapSet::Effective, &keep).map_err(caps_error)?;
+    caps::set(None, CapSet::Permitted, &keep).map_err(caps_error)?;
+    caps::set(None, CapSet::Inheritable, &keep).map_err(caps_error)?;
+    for capability in &keep {
+        caps::raise(None, CapSet::Ambient, *capability).map_err(caps_error)?;
+    }
+    info!("dropped all capabilities except {REQUIRED_CAPABILITY}");
+    Ok(())
+}
+
+/// Resolves a user name, a numeric `uid`, or a `uid:gid` pair via `/etc/passwd`.
+fn lookup_user(user: &str) -> Result<(u32, u32), AppError> {
+    if let Some((uid, gid)) = user.split_once(':') {
+        return Ok((uid.parse()?, gid.parse()?));
+    }
+    let passwd = fs::read_to_string("/etc/passwd")?;
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 3 && (fields[0] == user || fields[2] == user))
+        .map(|fields| Ok::<_, AppError>((fields[2].parse()?, fields[3].parse()?)))
+        .transpose()?
+        .ok_or_else(|| AppError::PrivilegeError(format!("unknown user: {user}")))
+}
+
+/// Switches to `uid` and `gid` while keeping the permitted capabilities.
+fn switch_user(uid: u32, gid: u32) -> Result<(), AppError> {
+    let failed = |what: &str| {
+        let e = std::io::Error::last_os_error();
+        AppError::PrivilegeError(format!("failed to {what}: {e}"))
+    };
+    // SAFETY: the calls only take integers and a pointer to a single, live `gid`.
+    unsafe {
+        if libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) != 0 {
+            return Err(failed("keep capabilities"));
+        }
+        if libc::setgroups(1, &raw const gid) != 0 {
+            return Err(failed("set supplementary groups"));
+        }
+        if libc::setresgid(gid, gid, gid) != 0 {
+            return Err(failed("switch group"));
+        }
+        if libc::setresuid(uid, uid, uid) != 0 {
+            return Err(failed("switch user"));
+        }
+    }
+    Ok(())
+}
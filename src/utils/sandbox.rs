@@ -0,0 +1,194 @@
+use crate::error::AppError;
+use landlock::{
+    ABI, Access, AccessFs, AccessNet, NetPort, PathBeneath, PathFd, Ruleset, RulesetAttr,
+    RulesetCreatedAttr, RulesetStatus,
+};
+use log::{info, warn};
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule, TargetArch,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Syscalls a blocklist daemon never needs; they are denied with `EPERM`.
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_kexec_file_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_keyctl,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_adjtimex,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_userfaultfd,
+];
+
+/// Socket families the daemon uses: the control socket, HTTP(S), and netfilter netlink.
+const ALLOWED_SOCKET_FAMILIES: &[libc::c_int] = &[
+    libc::AF_UNIX,
+    libc::AF_INET,
+    libc::AF_INET6,
+    libc::AF_NETLINK,
+];
+
+/// What the sandboxed daemon may still access.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxConfig {
+    /// Paths the daemon may write to (state, logs, control socket); everything else is read-only.
+    pub writable_paths: Vec<PathBuf>,
+    /// TCP ports the daemon may connect to (blocklist servers, SMTP relay).
+    pub connect_ports: Vec<u16>,
+    /// TCP ports the daemon may listen on (metrics, aggregator).
+    pub bind_ports: Vec<u16>,
+}
+
+impl SandboxConfig {
+    #[must_use]
+    pub fn with_writable_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.writable_paths.push(path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_connect_port(mut self, port: u16) -> Self {
+        self.connect_ports.push(port);
+        self
+    }
+
+    #[must_use]
+    pub fn with_bind_port(mut self, port: u16) -> Self {
+        self.bind_ports.push(port);
+        self
+    }
+}
+
+/// Restricts the process with Landlock and a seccomp filter.
+///
+/// Must be called before any threads are spawned, since Landlock only applies to the calling
+/// thread and the threads it creates afterwards. Both restrictions are inherited by `nft`.
+///
+/// # Errors
+/// Will return `AppError` when a restriction cannot be built or applied.
+pub fn apply_sandbox(config: &SandboxConfig) -> Result<(), AppError> {
+    restrict_landlock(config)?;
+    restrict_syscalls()?;
+    info!("sandbox applied");
+    Ok(())
+}
+
+fn restrict_landlock(config: &SandboxConfig) -> Result<(), AppError> {
+    let landlock_error = |e: landlock::RulesetError| {
+        AppError::PrivilegeError(format!("failed to apply Landlock: {e}"))
+    };
+    let abi = ABI::V4;
+    let mut ruleset = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))
+        .map_err(landlock_error)?
+        .handle_access(AccessNet::from_all(abi))
+        .map_err(landlock_error)?
+        .create()
+        .map_err(landlock_error)?;
+
+    // Reading and executing stay allowed everywhere, e.g., for `nft`, `/etc`, and `/proc`.
+    let rules = std::iter::once((Path::new("/"), AccessFs::from_read(abi)))
+        .chain(
+            config
+                .writable_paths
+                .iter()
+                .map(|p| (p.as_path(), AccessFs::from_all(abi))),
+        )
+        // `std::process::Command` opens `/dev/null` for writing.
+        .chain(std::iter::once((
+            Path::new("/dev/null"),
+            AccessFs::from_all(abi),
+        )));
+    for (path, access) in rules {
+        let Ok(fd) = PathFd::new(path) else {
+            warn!("not sandboxing {}: it does not exist", path.display());
+            continue;
+        };
+        ruleset = ruleset
+            .add_rule(PathBeneath::new(fd, access))
+            .map_err(landlock_error)?;
+    }
+    for port in &config.connect_ports {
+        ruleset = ruleset
+            .add_rule(NetPort::new(*port, AccessNet::ConnectTcp))
+            .map_err(landlock_error)?;
+    }
+    for port in &config.bind_ports {
+        ruleset = ruleset
+            .add_rule(NetPort::new(*port, AccessNet::BindTcp))
+            .map_err(landlock_error)?;
+    }
+
+    let status = ruleset.restrict_self().map_err(landlock_error)?;
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => info!("Landlock fully enforced"),
+        RulesetStatus::PartiallyEnforced => {
+            warn!("Landlock partially enforced; the kernel does not support every restriction");
+        }
+        RulesetStatus::NotEnforced => warn!("Landlock is not supported by the kernel"),
+    }
+    Ok(())
+}
+
+fn restrict_syscalls() -> Result<(), AppError> {
+    let seccomp_error = |e: &dyn std::fmt::Display| {
+        AppError::PrivilegeError(format!("failed to apply the seccomp filter: {e}"))
+    };
+    let mut rules = DENIED_SYSCALLS
+        .iter()
+        .map(|syscall| (*syscall, Vec::new()))
+        .collect::<BTreeMap<i64, Vec<SeccompRule>>>();
+
+    // A single rule matches when all its conditions do, i.e., the family is none of the allowed ones.
+    let conditions = ALLOWED_SOCKET_FAMILIES
+        .iter()
+        .map(|family| {
+            SeccompCondition::new(
+                0,
+                SeccompCmpArgLen::Dword,
+                SeccompCmpOp::Ne,
+                u64::try_from(*family).unwrap_or_default(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| seccomp_error(&e))?;
+    rules.insert(
+        libc::SYS_socket,
+        vec![SeccompRule::new(conditions).map_err(|e| seccomp_error(&e))?],
+    );
+
+    let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(|e| seccomp_error(&e))?;
+    let filter = SeccompFilter::new(
+        rules,
+        SeccompAction::Allow,
+        SeccompAction::Errno(u32::try_from(libc::EPERM).unwrap_or_default()),
+        arch,
+    )
+    .map_err(|e| seccomp_error(&e))?;
+    let program = BpfProgram::try_from(filter).map_err(|e| seccomp_error(&e))?;
+    seccompiler::apply_filter_all_threads(&program).map_err(|e| seccomp_error(&e))?;
+    Ok(())
+}
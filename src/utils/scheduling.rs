@@ -0,0 +1,224 @@
+use crate::error::{AppError, ErrorSource};
+use crate::settings::Environment;
+use log::warn;
+use std::fs;
+use std::path::PathBuf;
+
+/// Period of the CPU quota written to `cpu.max`, in microseconds.
+const CPU_PERIOD: u32 = 100_000;
+/// Mount point of the unified cgroup hierarchy.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// `ioprio_set` targets and classes, see `ioprio_set(2)`.
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_BE: libc::c_int = 2;
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+/// I/O scheduling class of the daemon, as set by `ionice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    /// Best-effort with a level from 0 (highest) to 7 (lowest).
+    BestEffort(u8),
+    /// Only gets disk time when no other process needs it.
+    Idle,
+}
+
+impl IoClass {
+    /// Parses `idle`, `best-effort`, or `best-effort:<level>` with a level from 0 to 7;
+    /// `best-effort` alone is the lowest level, 7.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "idle" => Ok(Self::Idle),
+            "best-effort" => Ok(Self::BestEffort(7)),
+            class => class
+                .strip_prefix("best-effort:")
+                .and_then(|level| level.parse::<u8>().ok())
+                .filter(|level| *level <= 7)
+                .map(Self::BestEffort)
+                .ok_or_else(|| {
+                    AppError::ParseError(format!(
+                        "invalid NFTBLOCKD_IO_CLASS: {value}; expected idle, best-effort, or best-effort:<0-7>"
+                    ))
+                }),
+        }
+    }
+
+    /// The value `ioprio_set` takes for the class.
+    fn ioprio(self) -> libc::c_int {
+        match self {
+            Self::BestEffort(level) => {
+                (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | libc::c_int::from(level)
+            }
+            Self::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        }
+    }
+}
+
+/// How much of the host the update cycles may take from packet forwarding.
+///
+/// `nice` and `ionice` apply per thread on Linux, so the priorities are only lowered on the threads running
+/// the parsing and deduplication (see `Scheduling::run`); the rest of the daemon, e.g., applying the ruleset
+/// or answering the control socket, keeps its own. The CPU quota applies to the whole cgroup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Scheduling {
+    /// Niceness of the parsing threads from -20 to 19; unchanged when `None`.
+    pub nice: Option<i32>,
+    /// I/O scheduling class of the parsing threads; unchanged when `None`.
+    pub io_class: Option<IoClass>,
+    /// CPU time the daemon may use, in percent of one CPU; unlimited when `None`.
+    pub cpu_quota: Option<u32>,
+}
+
+impl Scheduling {
+    /// Reads `NFTBLOCKD_NICE`, `NFTBLOCKD_IO_CLASS`, and `NFTBLOCKD_CPU_QUOTA`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when a variable cannot be parsed or is out of range.
+    pub fn from_env(environment: &Environment) -> Result<Self, AppError> {
+        let var = |name: &str| environment.var(name).ok().filter(|s| !s.is_empty());
+        let nice = var("NFTBLOCKD_NICE")
+            .map(|nice| {
+                nice.trim()
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|nice| (-20..=19).contains(nice))
+                    .ok_or_else(|| {
+                        AppError::ParseError(format!(
+                            "invalid NFTBLOCKD_NICE: {nice}; expected -20 to 19"
+                        ))
+                    })
+            })
+            .transpose()?;
+        let io_class = var("NFTBLOCKD_IO_CLASS")
+            .map(|class| IoClass::parse(&class))
+            .transpose()?;
+        let cpu_quota = var("NFTBLOCKD_CPU_QUOTA")
+            .map(|quota| parse_cpu_quota(&quota))
+            .transpose()?;
+        Ok(Self {
+            nice,
+            io_class,
+            cpu_quota,
+        })
+    }
+
+    /// Applies the CPU quota to the cgroup of the process.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the quota cannot be written, e.g., when the cgroup is not delegated.
+    pub fn apply(&self) -> Result<(), AppError> {
+        if let Some(quota) = self.cpu_quota {
+            let path = own_cgroup()?.join("cpu.max");
+            fs::write(&path, cpu_max(quota)).map_err(|e| {
+                AppError::FileError(
+                    format!("failed to set the CPU quota: {}: {e}", path.display()),
+                    Some(ErrorSource::new(e)),
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Runs `work`, e.g., parsing and deduplicating a blocklist, on a separate thread with the lowered priorities
+    /// and waits for it. Without a niceness or an I/O class, `work` runs on the calling thread.
+    ///
+    /// A priority that cannot be set, e.g., a negative niceness without `CAP_SYS_NICE`, is logged,
+    /// and `work` runs with the other one.
+    pub fn run<T: Send>(&self, work: impl FnOnce() -> T + Send) -> T {
+        if self.nice.is_none() && self.io_class.is_none() {
+            return work();
+        }
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    for e in self.lower_priorities() {
+                        warn!("{e}");
+                    }
+                    work()
+                })
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    /// Sets the niceness and the I/O class of the calling thread, each independently of the other.
+    ///
+    /// # Returns
+    /// The errors of the settings that could not be applied.
+    fn lower_priorities(&self) -> Vec<AppError> {
+        let failed = |what: &str| {
+            let e = std::io::Error::last_os_error();
+            AppError::IoError(format!("failed to {what}: {e}"), Some(ErrorSource::new(e)))
+        };
+        let mut errors = Vec::new();
+        // SAFETY: the call only takes integers; `0` is the calling thread.
+        if let Some(nice) = self.nice
+            && unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0
+        {
+            errors.push(failed("set the niceness"));
+        }
+        // SAFETY: the call only takes integers; `0` is the calling thread.
+        if let Some(io_class) = self.io_class
+            && unsafe {
+                libc::syscall(
+                    libc::SYS_ioprio_set,
+                    IOPRIO_WHO_PROCESS,
+                    0,
+                    io_class.ioprio(),
+                )
+            } != 0
+        {
+            errors.push(failed("set the I/O class"));
+        }
+        errors
+    }
+}
+
+/// Parses a CPU quota in percent of one CPU, e.g., `50%` or `200%` for two CPUs.
+///
+/// # Errors
+/// Will return `AppError` when the quota is not a positive number.
+pub fn parse_cpu_quota(value: &str) -> Result<u32, AppError> {
+    value
+        .trim()
+        .trim_end_matches('%')
+        .parse::<u32>()
+        .ok()
+        .filter(|quota| *quota > 0)
+        .ok_or_else(|| {
+            AppError::ParseError(format!(
+                "invalid NFTBLOCKD_CPU_QUOTA: {value}; expected a positive percentage, e.g., 50%"
+            ))
+        })
+}
+
+/// The `cpu.max` line granting `quota` percent of one CPU, e.g., `50000 100000` for `50`.
+#[must_use]
+pub fn cpu_max(quota: u32) -> String {
+    format!(
+        "{} {CPU_PERIOD}",
+        u64::from(quota) * u64::from(CPU_PERIOD) / 100
+    )
+}
+
+/// The directory of the cgroup v2 the process belongs to, e.g., `/sys/fs/cgroup/system.slice/nftblockd.service`.
+fn own_cgroup() -> Result<PathBuf, AppError> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup").map_err(|e| {
+        AppError::FileError(
+            format!("failed to read /proc/self/cgroup: {e}"),
+            Some(ErrorSource::new(e)),
+        )
+    })?;
+    cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| PathBuf::from(CGROUP_ROOT).join(path.trim_start_matches('/')))
+        .ok_or_else(|| {
+            AppError::NftblockdError(
+                "NFTBLOCKD_CPU_QUOTA requires the unified cgroup hierarchy (cgroup v2)".to_string(),
+            )
+        })
+}
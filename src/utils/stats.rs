@@ -21,6 +21,16 @@ pub struct Stats {
     pub custom_blocklist_drop_stats: ChainDropStats,
 }
 
+/// Elements contributed by a source to the last applied blocklists and its failed fetches so far.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceStats {
+    pub ipv4_elements: usize,
+    pub ipv6_elements: usize,
+    pub failures: u64,
+    /// Entries of the last fetch no announced route covers, when the routes are verified.
+    pub unrouted: usize,
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DropStats {
     pub packets: u64,
@@ -8,6 +8,8 @@ pub enum NftblockdStatus {
     Failed(AppError),
     Pending,
     PreFail(AppError),
+    /// Updates are paused for maintenance.
+    Paused,
 }
 
 impl NftblockdStatus {
@@ -17,6 +19,7 @@ impl NftblockdStatus {
             NftblockdStatus::Pending => 1,
             NftblockdStatus::PreFail(_) => 2,
             NftblockdStatus::Failed(_) => 3,
+            NftblockdStatus::Paused => 4,
         }
     }
 
@@ -26,12 +29,15 @@ impl NftblockdStatus {
             NftblockdStatus::Failed(_) => "failed".to_string(),
             NftblockdStatus::Pending => "pending".to_string(),
             NftblockdStatus::PreFail(_) => "pre-fail".to_string(),
+            NftblockdStatus::Paused => "paused".to_string(),
         }
     }
 
     pub fn get_message(&self) -> String {
         match self {
-            NftblockdStatus::Ok | NftblockdStatus::Pending => String::default(),
+            NftblockdStatus::Ok | NftblockdStatus::Pending | NftblockdStatus::Paused => {
+                String::default()
+            }
             NftblockdStatus::Failed(e) | NftblockdStatus::PreFail(e) => e.to_string(),
         }
     }
@@ -44,6 +50,7 @@ impl Display for NftblockdStatus {
             NftblockdStatus::Failed(e) => write!(f, "failed: {e}"),
             NftblockdStatus::Pending => write!(f, "pending"),
             NftblockdStatus::PreFail(e) => write!(f, "pre-fail: {e}"),
+            NftblockdStatus::Paused => write!(f, "paused"),
         }
     }
 }
@@ -54,6 +61,12 @@ impl From<NftblockdStatus> for StatusSummary {
             status_code: status.get_status_code(),
             status: status.get_status(),
             message: status.get_message(),
+            replicas: Vec::new(),
+            stats: None,
+            last_applied: 0,
+            deferred: Vec::new(),
+            confirm_deadline: 0,
+            notes: Vec::new(),
         }
     }
 }
@@ -64,6 +77,12 @@ impl StatusSummary {
             status_code: 0,
             status: "ok".to_string(),
             message: message.to_string(),
+            replicas: Vec::new(),
+            stats: None,
+            last_applied: 0,
+            deferred: Vec::new(),
+            confirm_deadline: 0,
+            notes: Vec::new(),
         }
     }
 
@@ -72,6 +91,39 @@ impl StatusSummary {
             status_code: 3,
             status: "failed".to_string(),
             message: message.to_string(),
+            replicas: Vec::new(),
+            stats: None,
+            last_applied: 0,
+            deferred: Vec::new(),
+            confirm_deadline: 0,
+            notes: Vec::new(),
         }
     }
 }
+
+impl StatusSummary {
+    /// Checks that the daemon has not failed and applied a ruleset at most `max_age` seconds before `now`.
+    ///
+    /// # Errors
+    /// Will return the reason why the daemon is unhealthy.
+    pub fn check_health(&self, now: i64, max_age: i64) -> Result<(), String> {
+        // Status code of `NftblockdStatus::Failed`.
+        if self.status_code == 3 {
+            return Err(format!("nftblockd has failed: {}", self.message));
+        }
+        // Status code of `NftblockdStatus::Paused`; the blocklist is not expected to be fresh during maintenance.
+        if self.status_code == 4 {
+            return Ok(());
+        }
+        if self.last_applied == 0 {
+            return Err("no blocklist has been applied yet".to_string());
+        }
+        let age = now - self.last_applied;
+        if age > max_age {
+            return Err(format!(
+                "the blocklist was last applied {age}s ago, more than the allowed {max_age}s"
+            ));
+        }
+        Ok(())
+    }
+}
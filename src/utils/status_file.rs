@@ -0,0 +1,220 @@
+use crate::error::{AppError, FailureKind};
+use crate::nftables::builder::{RuleProto, SetElements};
+use crate::set::observer::{UpdateObserver, UpdateReport};
+use crate::settings::Environment;
+use crate::utils::stats::SourceStats;
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Outcome of the last update cycle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CycleState {
+    /// No cycle finished yet.
+    #[default]
+    Starting,
+    /// The last cycle applied the blocklists.
+    Ok,
+    /// The last cycle failed and is retried.
+    Failing,
+    /// The retry budget was exhausted and the table flushed.
+    Failed,
+}
+
+/// The last error of an update cycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LastError {
+    /// Unix timestamp of the failure.
+    pub time: u64,
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+/// Contents of the status file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StatusSnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub state: CycleState,
+    /// Unix timestamp the file was written at.
+    pub updated: u64,
+    /// Unix timestamp of the last successful update.
+    pub last_success: Option<u64>,
+    /// Failed update attempts since the last successful one.
+    pub consecutive_failures: u64,
+    pub last_error: Option<LastError>,
+    pub ipv4_elements: usize,
+    pub ipv6_elements: usize,
+    pub invalid_entries: usize,
+    /// Duration of the last successful update in milliseconds.
+    pub duration_ms: u128,
+    /// Peak resident memory of the last successful update in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_memory: Option<u64>,
+    /// Elements and failed fetches of every source.
+    pub sources: BTreeMap<String, SourceStats>,
+}
+
+/// Writes a small JSON summary of the daemon to a file after every update cycle, so that monitoring
+/// agents and shell scripts can check its health without the control socket or an HTTP endpoint, e.g.:
+///
+/// ```text
+/// jq -e '.state == "ok" and .last_success > (now - 3600)' /run/nftblockd/status.json
+/// ```
+///
+/// The file is replaced atomically, so a reader never sees a partially written file.
+#[derive(Debug)]
+pub struct StatusFile {
+    path: PathBuf,
+    snapshot: Mutex<StatusSnapshot>,
+}
+
+impl StatusFile {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, instance: Option<&str>) -> Self {
+        Self {
+            path: path.into(),
+            snapshot: Mutex::new(StatusSnapshot {
+                instance: instance.map(ToString::to_string),
+                ..StatusSnapshot::default()
+            }),
+        }
+    }
+
+    /// Creates a `StatusFile` writing to `NFTBLOCKD_STATUS_FILE`, or `None` when it is not set.
+    #[must_use]
+    pub fn from_env(environment: &Environment, instance: Option<&str>) -> Option<Self> {
+        environment
+            .var("NFTBLOCKD_STATUS_FILE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|path| Self::new(path, instance))
+    }
+
+    /// Names the `profile`, if any, in the file and writes it next to the main one,
+    /// e.g., `status-tenant-a.json` next to `status.json`.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<&str>) -> Self {
+        if let Some(profile) = profile {
+            let stem = self
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.path.set_file_name(format!("{stem}-{profile}.json"));
+            self.snapshot
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .profile = Some(profile.to_string());
+        }
+        self
+    }
+
+    /// Returns the current contents of the file.
+    #[must_use]
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Writes the file next to its final path and renames it into place.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the file cannot be written or renamed.
+    pub fn write(&self) -> Result<(), AppError> {
+        let data = serde_json::to_string_pretty(&self.snapshot())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, data)?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    /// Updates the contents and writes the file.
+    fn update(&self, update: impl FnOnce(&mut StatusSnapshot)) {
+        {
+            let mut snapshot = self.snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+            update(&mut snapshot);
+            snapshot.updated = unix_now();
+        }
+        if let Err(e) = self.write() {
+            warn!("failed to write {}: {e}", self.path.display());
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl UpdateObserver for StatusFile {
+    fn on_applied(&self, report: &UpdateReport) {
+        self.update(|snapshot| {
+            snapshot.state = CycleState::Ok;
+            snapshot.last_success = Some(unix_now());
+            snapshot.consecutive_failures = 0;
+            snapshot.ipv4_elements = report.ipv4_elements;
+            snapshot.ipv6_elements = report.ipv6_elements;
+            snapshot.invalid_entries = report.ipv4_invalid_entries + report.ipv6_invalid_entries;
+            snapshot.duration_ms = report.duration.as_millis();
+            snapshot.peak_memory = report.peak_memory;
+        });
+    }
+
+    fn on_blocked(&self, source: &str, proto: &RuleProto, elements: &SetElements<'_>) {
+        self.update(|snapshot| {
+            let stats = snapshot.sources.entry(source.to_string()).or_default();
+            match proto {
+                RuleProto::Ip6 => stats.ipv6_elements = elements.len(),
+                _ => stats.ipv4_elements = elements.len(),
+            }
+        });
+    }
+
+    fn on_error(&self, error: &AppError) {
+        self.update(|snapshot| {
+            if snapshot.state != CycleState::Failed {
+                snapshot.state = CycleState::Failing;
+            }
+            snapshot.consecutive_failures += 1;
+            snapshot.last_error = Some(LastError {
+                time: unix_now(),
+                kind: error.kind(),
+                message: error.to_string(),
+            });
+        });
+    }
+
+    fn on_source_failed(&self, source: &str, _error: &AppError) {
+        self.update(|snapshot| {
+            snapshot
+                .sources
+                .entry(source.to_string())
+                .or_default()
+                .failures += 1;
+        });
+    }
+
+    fn on_retries_exhausted(&self, _error: &AppError) {
+        self.update(|snapshot| snapshot.state = CycleState::Failed);
+    }
+
+    fn on_updates_stopped(&self, _error: &AppError) {
+        self.update(|snapshot| snapshot.state = CycleState::Failed);
+    }
+}
@@ -1,12 +1,19 @@
 use crate::error::AppError;
 use crate::nftables::builder::SetElements;
-use crate::utils::iptrie::deduplicate;
+use crate::set::fetch_policy::source_var;
+use crate::settings::Environment;
+use crate::utils::iptrie::{BitIp, deduplicate};
 use crate::utils::network::{ListNetwork, NetworkType};
+use crate::utils::prefix_set::PrefixSet;
+use crate::utils::priority::{BroaderFirst, Candidate, HighestScore, ListOrder, Prioritizer};
 use ipnetwork::{Ipv4Network, Ipv6Network};
 use log::{debug, warn};
-use nftables::expr::{Expression, NamedExpression, Prefix, Range};
+use nftables::expr::Expression;
 use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
+use std::hash::Hash;
+use std::net::Ipv4Addr;
 use std::str::FromStr;
 
 /// Represents a collection of subnets, either IPv4 or IPv6.
@@ -29,14 +36,39 @@ impl SubnetList {
     /// # Errors
     /// Returns an `AppError` if no valid addresses are available after validation.
     pub fn validate_blocklist(self, strict: bool) -> Result<ValidatedSubnetList, AppError> {
+        self.validate_with(if strict {
+            Strictness::Strict
+        } else {
+            Strictness::Lenient
+        })
+    }
+
+    /// Validates the subnets like `validate_blocklist`, handling invalid entries according to `strictness`.
+    ///
+    /// # Errors
+    /// Returns an `AppError` when `strictness` does not tolerate the invalid entries.
+    pub fn validate_with(self, strictness: Strictness) -> Result<ValidatedSubnetList, AppError> {
+        self.validate_collecting(strictness, &mut Rejected::default())
+    }
+
+    /// Validates the subnets like `validate_with`, counting the skipped entries in `rejected`.
+    ///
+    /// # Errors
+    /// Returns an `AppError` when `strictness` does not tolerate the invalid entries.
+    pub fn validate_collecting(
+        self,
+        strictness: Strictness,
+        rejected: &mut Rejected,
+    ) -> Result<ValidatedSubnetList, AppError> {
         let blocklist = match self {
             // Parse and validate the IPv4 blocklist.
+            // The entries are consumed, so that every string is freed as soon as it is parsed.
             Self::IPv4(parsed_ips) => {
-                ValidatedSubnetList::IPv4(validate_subnets::<Ipv4Network>(&parsed_ips, strict)?)
+                ValidatedSubnetList::IPv4(validate_subnets_with(parsed_ips, strictness, rejected)?)
             }
             // Parse and validate the IPv6 blocklist.
             Self::IPv6(parsed_ips) => {
-                ValidatedSubnetList::IPv6(validate_subnets::<Ipv6Network>(&parsed_ips, strict)?)
+                ValidatedSubnetList::IPv6(validate_subnets_with(parsed_ips, strictness, rejected)?)
             }
         };
 
@@ -51,6 +83,131 @@ impl SubnetList {
     }
 }
 
+/// How invalid entries of a list are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strictness {
+    /// The first invalid entry fails the whole list.
+    Strict,
+    /// Invalid entries are skipped with a warning.
+    Lenient,
+    /// Invalid entries are skipped with a warning, but more than the given number fail the whole list,
+    /// e.g., when a feed serves an error page instead of its entries.
+    WarnThreshold(usize),
+}
+
+impl Strictness {
+    /// Parses `strict`, `lenient`, or `warn-threshold:N`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let error = || {
+            AppError::ParseError(format!(
+                "invalid NFTBLOCKD_STRICTNESS: {value}; expected strict, lenient, or warn-threshold:N"
+            ))
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            other => other
+                .strip_prefix("warn-threshold:")
+                .and_then(|threshold| threshold.trim().parse().ok())
+                .map(Self::WarnThreshold)
+                .ok_or_else(error),
+        }
+    }
+
+    /// Reads the strictness of `source` from `NFTBLOCKD_{source}_STRICTNESS` or `NFTBLOCKD_STRICTNESS`,
+    /// falling back to `default`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when the strictness is invalid.
+    pub fn from_env(
+        environment: &Environment,
+        source: &str,
+        default: Self,
+    ) -> Result<Self, AppError> {
+        source_var(environment, source, "STRICTNESS")
+            .map(|s| Self::parse(&s))
+            .transpose()
+            .map(|strictness| strictness.unwrap_or(default))
+    }
+}
+
+/// What happens when a list has more elements than its set may hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The elements past the cap are left out in the order they are listed, with a warning.
+    #[default]
+    Truncate,
+    /// The broadest elements are kept, so that the capped set covers as many addresses as possible.
+    PreferBroader,
+    /// The elements with the highest scores are kept, e.g., those listed by the most consensus feeds,
+    /// then the broadest ones.
+    PreferScored,
+    /// The whole list fails.
+    Fail,
+}
+
+impl OverflowPolicy {
+    /// Parses `truncate`, `prefer-broader`, `prefer-scored`, or `fail`.
+    ///
+    /// # Errors
+    /// Will return `AppError` for any other value.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.to_ascii_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "prefer-broader" => Ok(Self::PreferBroader),
+            "prefer-scored" => Ok(Self::PreferScored),
+            "fail" => Ok(Self::Fail),
+            _ => Err(AppError::ParseError(format!(
+                "invalid NFTBLOCKD_OVERFLOW_POLICY: {value}; expected truncate, prefer-broader, prefer-scored, or fail"
+            ))),
+        }
+    }
+
+    /// Returns the strategy choosing the elements that survive a truncation, ranking by `scores`
+    /// for `PreferScored`; `None` for `Fail`.
+    #[must_use]
+    pub fn prioritizer(self, scores: BTreeMap<String, u32>) -> Option<Box<dyn Prioritizer>> {
+        match self {
+            Self::Truncate => Some(Box::new(ListOrder)),
+            Self::PreferBroader => Some(Box::new(BroaderFirst)),
+            Self::PreferScored => Some(Box::new(HighestScore { scores })),
+            Self::Fail => None,
+        }
+    }
+}
+
+/// Invalid entries skipped while validating a list, keeping up to `max_samples` of them along with
+/// the reason they were rejected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rejected {
+    /// Number of skipped entries.
+    pub count: usize,
+    /// The first skipped entries, e.g., `invalid IP address syntax: <html>`.
+    pub samples: Vec<String>,
+    max_samples: usize,
+}
+
+impl Rejected {
+    /// Creates an empty `Rejected` keeping up to `max_samples` samples.
+    #[must_use]
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            max_samples,
+            ..Self::default()
+        }
+    }
+
+    fn push(&mut self, error: &str) {
+        self.count += 1;
+        if self.samples.len() < self.max_samples {
+            self.samples.push(error.to_string());
+        }
+    }
+}
+
 /// Represents a validated list of IPv4 or IPv6 subnets that can be deduplicated.
 pub enum ValidatedSubnetList {
     IPv4(Option<Vec<NetworkType<Ipv4Network>>>), // IPv4 list after validation.
@@ -58,6 +215,24 @@ pub enum ValidatedSubnetList {
 }
 
 impl ValidatedSubnetList {
+    /// Validates entries borrowed from a fetched list like `SubnetList::validate_collecting`, without
+    /// copying them into a `SubnetList` first.
+    ///
+    /// # Errors
+    /// Returns an `AppError` when `strictness` does not tolerate the invalid entries.
+    pub fn validate_entries<'a>(
+        entries: impl IntoIterator<Item = &'a str>,
+        ipv6: bool,
+        strictness: Strictness,
+        rejected: &mut Rejected,
+    ) -> Result<Self, AppError> {
+        Ok(if ipv6 {
+            Self::IPv6(validate_subnets_with(entries, strictness, rejected)?)
+        } else {
+            Self::IPv4(validate_subnets_with(entries, strictness, rejected)?)
+        })
+    }
+
     /// Deduplicates the validated subnets using a prefix trie, removing redundant subnets.
     ///
     /// # Returns
@@ -73,6 +248,124 @@ impl ValidatedSubnetList {
             ValidatedSubnetList::IPv6(ips) => Ok(DeduplicatedSubnetList::IPv6(deduplicate(ips))),
         }
     }
+
+    /// Deduplicates the validated subnets like `deduplicate`, keeping at most `max_elements` of them
+    /// according to `policy`.
+    ///
+    /// # Returns
+    /// The deduplicated subnets and the number of subnets left out.
+    ///
+    /// # Errors
+    /// Returns an `AppError` when there are more subnets than `max_elements` and `policy` is `OverflowPolicy::Fail`.
+    pub fn deduplicate_capped(
+        self,
+        max_elements: usize,
+        policy: OverflowPolicy,
+    ) -> Result<(DeduplicatedSubnetList, usize), AppError> {
+        self.deduplicate_prioritized(max_elements, policy.prioritizer(BTreeMap::new()).as_deref())
+    }
+
+    /// Deduplicates the validated subnets like `deduplicate`, keeping at most `max_elements` of them
+    /// in the order of `prioritizer`.
+    ///
+    /// # Returns
+    /// The deduplicated subnets and the number of subnets left out.
+    ///
+    /// # Errors
+    /// Returns an `AppError` when there are more subnets than `max_elements` and no `prioritizer` is given.
+    pub fn deduplicate_prioritized(
+        self,
+        max_elements: usize,
+        prioritizer: Option<&dyn Prioritizer>,
+    ) -> Result<(DeduplicatedSubnetList, usize), AppError> {
+        match self {
+            ValidatedSubnetList::IPv4(ips) => cap(ips, max_elements, prioritizer)
+                .map(|(ips, dropped)| (DeduplicatedSubnetList::IPv4(ips), dropped)),
+            ValidatedSubnetList::IPv6(ips) => cap(ips, max_elements, prioritizer)
+                .map(|(ips, dropped)| (DeduplicatedSubnetList::IPv6(ips), dropped)),
+        }
+    }
+}
+
+/// The networks kept by `cap` and the number of networks it dropped.
+type Capped<T> = (Option<Vec<NetworkType<T>>>, usize);
+
+/// Deduplicates `ips` and keeps at most `max_elements` of them in the order of `prioritizer`.
+fn cap<T>(
+    ips: Option<Vec<NetworkType<T>>>,
+    max_elements: usize,
+    prioritizer: Option<&dyn Prioritizer>,
+) -> Result<Capped<T>, AppError>
+where
+    T: ListNetwork + Hash + Eq,
+{
+    // Deduplication cannot grow the list, so the order is only kept when it may be needed.
+    let listed = if ips.as_ref().is_some_and(|ips| ips.len() > max_elements) {
+        ips.clone()
+    } else {
+        None
+    };
+    let Some(deduplicated) = deduplicate(ips) else {
+        return Ok((None, 0));
+    };
+    let total = deduplicated.len();
+    if total <= max_elements {
+        return Ok((Some(deduplicated), 0));
+    }
+    let Some(prioritizer) = prioritizer else {
+        return Err(AppError::NftblockdError(format!(
+            "{total} elements exceed the maximum set size of {max_elements}"
+        )));
+    };
+    // Deduplication orders the subnets by prefix, so their positions are taken from the list.
+    let mut position = HashMap::new();
+    for (index, ip) in listed.iter().flatten().enumerate() {
+        position.entry(ip).or_insert(index);
+    }
+    let mut candidates = deduplicated
+        .into_iter()
+        .map(|ip| {
+            let candidate = Candidate {
+                entry: candidate_entry(&ip),
+                host_bits: host_bits(&ip),
+                position: position.get(&ip).copied().unwrap_or(usize::MAX),
+            };
+            (candidate, ip)
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|(a, _), (b, _)| prioritizer.compare(a, b));
+    candidates.truncate(max_elements);
+    Ok((
+        Some(candidates.into_iter().map(|(_, ip)| ip).collect()),
+        total - max_elements,
+    ))
+}
+
+/// Formats a subnet as `address/prefix`, or a range as `start-end`, the way scores are keyed.
+fn candidate_entry<T: ListNetwork>(ip: &NetworkType<T>) -> String {
+    match ip {
+        NetworkType::Ip(network) => {
+            format!("{}/{}", network.network_string(), network.network_prefix())
+        }
+        NetworkType::Range(start, end) => {
+            format!("{}-{}", start.network_string(), end.network_string())
+        }
+    }
+}
+
+/// Number of host bits of a subnet, or of the smallest prefix spanning a range, i.e., how broad it is.
+fn host_bits<T: ListNetwork>(ip: &NetworkType<T>) -> u32 {
+    match ip {
+        NetworkType::Ip(network) => u32::from(network.max_prefix() - network.network_prefix()),
+        NetworkType::Range(start, end) => {
+            let span = match (start.network_addr(), end.network_addr()) {
+                (BitIp::Ipv4(start), BitIp::Ipv4(end)) => u128::from(end.saturating_sub(start)),
+                (BitIp::Ipv6(start), BitIp::Ipv6(end)) => end.saturating_sub(start),
+                _ => 0,
+            };
+            128 - span.leading_zeros()
+        }
+    }
 }
 
 /// Represents a deduplicated list of IPv4 or IPv6 subnets.
@@ -84,6 +377,18 @@ pub enum DeduplicatedSubnetList {
 }
 
 impl DeduplicatedSubnetList {
+    /// Converts the deduplicated subnets into a `PrefixSet`, keeping their order.
+    ///
+    /// # Returns
+    /// `None` if there are no subnets.
+    #[must_use]
+    pub fn into_prefix_set(self) -> Option<PrefixSet> {
+        match self {
+            DeduplicatedSubnetList::IPv4(ips) => ips.map(PrefixSet::from_networks),
+            DeduplicatedSubnetList::IPv6(ips) => ips.map(PrefixSet::from_networks),
+        }
+    }
+
     /// Transforms the deduplicated subnets into a list of `nftables` expressions.
     /// These expressions can be used directly in the `nftables` ruleset.
     ///
@@ -126,6 +431,20 @@ impl<'a> NftExpressionSubnetList<'a> {
     }
 }
 
+/// Splits a list into its entries without copying them, on `split_string` or on whitespace when `None`.
+///
+/// # Returns
+/// The trimmed entries borrowed from `data`.
+pub fn split_entries<'a>(
+    data: &'a str,
+    split_string: Option<&'a str>,
+) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+    match split_string {
+        None => Box::new(data.split_whitespace()),
+        Some(split_str) => Box::new(data.split(split_str).map(str::trim)),
+    }
+}
+
 /// Parses a single space-separated string into a vector of subnet strings.
 ///
 /// # Parameters
@@ -139,20 +458,11 @@ pub fn parse_from_string<S: AsRef<str>>(
     split_string: Option<&str>,
 ) -> Option<Vec<String>> {
     match data {
-        Some(s) if !s.as_ref().is_empty() => match split_string {
-            None => Some(
-                s.as_ref()
-                    .split_whitespace()
-                    .map(|s| s.trim().to_string())
-                    .collect(),
-            ),
-            Some(split_str) => Some(
-                s.as_ref()
-                    .split(split_str)
-                    .map(|s| s.trim().to_string())
-                    .collect(),
-            ),
-        },
+        Some(s) if !s.as_ref().is_empty() => Some(
+            split_entries(s.as_ref(), split_string)
+                .map(ToString::to_string)
+                .collect(),
+        ),
         _ => None,
     }
 }
@@ -163,7 +473,8 @@ pub fn parse_from_string<S: AsRef<str>>(
 /// - `T`: A type that implements the `BlockListNetwork` trait (e.g., `Ipv4Network` or `Ipv6Network`).
 ///
 /// # Parameters
-/// - `ips`: A `Vec` of raw subnet strings to validate.
+/// - `ips`: The raw subnet strings to validate, e.g., a `&[String]`, or the entries of `split_entries`
+///   parsed in place.
 /// - `strict`: Whether to return an error if an invalid subnet is encountered.
 ///
 /// # Returns
@@ -171,7 +482,7 @@ pub fn parse_from_string<S: AsRef<str>>(
 /// # Errors
 /// Will return `AppError` when subnets are invalid
 pub fn validate_subnets<T>(
-    ips: &[String],
+    ips: impl IntoIterator<Item = impl AsRef<str>>,
     strict: bool,
 ) -> Result<Option<Vec<NetworkType<T>>>, AppError>
 where
@@ -179,20 +490,44 @@ where
     <T as FromStr>::Err: Display,
     AppError: From<<T as FromStr>::Err>,
 {
-    let mut parsed = Vec::new();
+    validate_subnets_with(
+        ips,
+        if strict {
+            Strictness::Strict
+        } else {
+            Strictness::Lenient
+        },
+        &mut Rejected::default(),
+    )
+}
+
+/// Validates a list of subnets like `validate_subnets`, handling invalid entries according to `strictness`.
+/// The skipped entries are counted in `rejected` and summarized in a single warning.
+///
+/// # Errors
+/// Will return `AppError` when `strictness` does not tolerate the invalid subnets
+pub fn validate_subnets_with<T>(
+    ips: impl IntoIterator<Item = impl AsRef<str>>,
+    strictness: Strictness,
+    rejected: &mut Rejected,
+) -> Result<Option<Vec<NetworkType<T>>>, AppError>
+where
+    T: ListNetwork + FromStr + Display + std::fmt::Debug,
+    <T as FromStr>::Err: Display,
+    AppError: From<<T as FromStr>::Err>,
+{
+    let ips = ips.into_iter();
+    let mut parsed = Vec::with_capacity(ips.size_hint().0);
+    let mut invalid = 0;
+    let mut first_invalid = None;
     for ip in ips {
-        match ip.parse::<T>() {
-            Ok(parsed_ip) => {
-                if parsed_ip.is_network() {
-                    parsed.push(NetworkType::Ip(parsed_ip));
-                } else if strict {
-                    return Err(AppError::ParseError(format!(
-                        "invalid ip: {parsed_ip}; not a network"
-                    )));
-                } else {
-                    warn!("invalid ip: {ip}; not a network");
-                }
+        let ip = normalize_notation(ip.as_ref());
+        let error = match ip.parse::<T>() {
+            Ok(parsed_ip) if parsed_ip.is_network() => {
+                parsed.push(NetworkType::Ip(parsed_ip));
+                continue;
             }
+            Ok(parsed_ip) => format!("invalid ip: {parsed_ip}; not a network"),
             Err(e) => {
                 let mut ip_split = ip.split("-");
                 let start = ip_split.next();
@@ -204,21 +539,66 @@ where
                     parsed.push(NetworkType::Range(start, end));
                     continue;
                 }
-                if strict {
-                    return Err(AppError::ParseError(format!("{e}: {ip}")));
-                }
-                warn!("ip could not be parsed: {ip}; {e}");
+                format!("{e}: {ip}")
             }
+        };
+        if strictness == Strictness::Strict {
+            return Err(AppError::ParseError(error));
         }
+        debug!("skipping an invalid entry: {error}");
+        invalid += 1;
+        rejected.push(&error);
+        first_invalid.get_or_insert(error);
+    }
+    if let Strictness::WarnThreshold(threshold) = strictness
+        && invalid > threshold
+    {
+        return Err(AppError::ParseError(format!(
+            "{invalid} invalid entries exceed the threshold of {threshold}; the first is {}",
+            first_invalid.unwrap_or_default()
+        )));
+    }
+
+    if let Some(first_invalid) = first_invalid {
+        warn!("skipped {invalid} invalid entries, the first is {first_invalid}");
     }
 
     Ok(if parsed.is_empty() {
         None
     } else {
+        parsed.shrink_to_fit();
         Some(parsed)
     })
 }
 
+/// Converts the legacy IPv4 notations found in older blocklists into CIDR, leaving any other entry as is.
+///
+/// Trailing wildcard octets become a prefix, e.g., `1.2.3.*` is `1.2.3.0/24` and `10.*` is `10.0.0.0/8`,
+/// and a contiguous netmask becomes its prefix length, e.g., `1.2.0.0/255.255.0.0` is `1.2.0.0/16`.
+#[must_use]
+pub fn normalize_notation(ip: &str) -> Cow<'_, str> {
+    if ip.contains('*') {
+        let octets = ip.split('.').collect::<Vec<_>>();
+        let fixed = octets.iter().take_while(|octet| **octet != "*").count();
+        if (1..octets.len()).contains(&fixed)
+            && octets.len() <= 4
+            && octets[fixed..].iter().all(|o| *o == "*")
+        {
+            let mut network = octets[..fixed].to_vec();
+            network.resize(4, "0");
+            return Cow::Owned(format!("{}/{}", network.join("."), fixed * 8));
+        }
+    } else if let Some((address, mask)) = ip.split_once('/')
+        && let Ok(mask) = mask.parse::<Ipv4Addr>()
+    {
+        let bits = mask.to_bits();
+        if bits.leading_ones() + bits.trailing_zeros() == 32 {
+            return Cow::Owned(format!("{address}/{}", bits.leading_ones()));
+        }
+    }
+    Cow::Borrowed(ip)
+}
+
 /// Converts a vector of subnets into `nftables` expressions.
 ///
 /// # Type Parameters
@@ -234,22 +614,7 @@ pub fn get_nft_expressions<'a, T>(ips: Option<Vec<NetworkType<T>>>) -> Option<Se
 where
     T: ListNetwork,
 {
-    Some(
-        ips?.iter()
-            .map(|ip| match ip {
-                NetworkType::Ip(ip) => Expression::Named(NamedExpression::Prefix(Prefix {
-                    addr: Box::new(Expression::String(Cow::from(ip.network_string()))),
-                    len: u32::from(ip.network_prefix()),
-                })),
-                NetworkType::Range(start, end) => Expression::Range(Box::new(Range {
-                    range: [
-                        Expression::String(Cow::from(start.network_string())),
-                        Expression::String(Cow::from(end.network_string())),
-                    ],
-                })),
-            })
-            .collect::<Vec<Expression>>(),
-    )
+    Some(PrefixSet::from_networks(ips?).into_elements())
 }
 
 // pub fn validate_subnets<T>(ips: Vec<String>) -> Vec<T>
@@ -0,0 +1,127 @@
+use crate::error::AppError;
+use crate::utils::{glob_match, path_pattern};
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Size of `struct inotify_event` without the name.
+const EVENT_HEADER_LEN: usize = 16;
+
+/// Events signalling that a file in a watched directory was written or replaced.
+const WATCH_MASK: u32 =
+    libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE;
+
+/// Watches files for changes with inotify.
+///
+/// The parent directories are watched instead of the files themselves, because editors and
+/// configuration management usually replace a file by renaming a new one over it.
+/// A directory or a glob, e.g., `/etc/nftblockd/blocklists/*.txt`, reports every matching file,
+/// including files added later.
+pub struct FileWatcher {
+    inotify: File,
+    /// Watched directories by watch descriptor.
+    directories: HashMap<i32, PathBuf>,
+    files: HashSet<PathBuf>,
+    /// Watched directories and the pattern of the file names reported in them.
+    patterns: HashSet<(PathBuf, String)>,
+}
+
+impl FileWatcher {
+    /// Starts watching `files`.
+    ///
+    /// # Errors
+    /// Will return `AppError` when inotify is unavailable or a directory cannot be watched.
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Result<Self, AppError> {
+        // SAFETY: plain syscall without pointers; the descriptor is owned right away.
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: `fd` is a freshly created descriptor owned by nobody else.
+        let inotify = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        let mut patterns = HashSet::new();
+        let mut watched = HashSet::new();
+        for file in files {
+            let file = std::path::absolute(&file).unwrap_or(file);
+            match path_pattern(&file) {
+                Some(pattern) => patterns.insert(pattern),
+                None => watched.insert(file),
+            };
+        }
+        let files = watched;
+        let mut directories = HashMap::new();
+        for directory in files
+            .iter()
+            .map(|file| file.parent().unwrap_or(Path::new("/")).to_path_buf())
+            .chain(patterns.iter().map(|(directory, _)| directory.clone()))
+            .collect::<HashSet<_>>()
+        {
+            let path = CString::new(directory.as_os_str().as_bytes())?;
+            // SAFETY: `path` is a valid NUL-terminated string that outlives the call.
+            let wd =
+                unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), WATCH_MASK) };
+            if wd < 0 {
+                return Err(AppError::IoError(
+                    format!("failed to watch {}", directory.display()),
+                    Some(crate::error::ErrorSource::new(io::Error::last_os_error())),
+                ));
+            }
+            directories.insert(wd, directory);
+        }
+        Ok(Self {
+            inotify,
+            directories,
+            files,
+            patterns,
+        })
+    }
+
+    /// Blocks until one of the watched files changes and returns its path.
+    ///
+    /// # Errors
+    /// Will return `AppError` when reading the inotify events fails.
+    pub fn wait(&mut self) -> Result<PathBuf, AppError> {
+        let mut buffer = [0u8; 4096];
+        loop {
+            let len = self.inotify.read(&mut buffer)?;
+            let mut offset = 0;
+            while offset + EVENT_HEADER_LEN <= len {
+                let field = |at: usize| {
+                    u32::from_ne_bytes(
+                        buffer[offset + at..offset + at + 4]
+                            .try_into()
+                            .unwrap_or_default(),
+                    )
+                };
+                let wd = i32::from_ne_bytes(field(0).to_ne_bytes());
+                let name_len = usize::try_from(field(12)).unwrap_or(usize::MAX);
+                let name_start = offset + EVENT_HEADER_LEN;
+                let Some(name) = buffer.get(name_start..name_start.saturating_add(name_len)) else {
+                    break;
+                };
+                offset = name_start + name_len;
+                let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                let Some(directory) = self.directories.get(&wd) else {
+                    continue;
+                };
+                let path = directory.join(std::ffi::OsStr::from_bytes(name));
+                let matches_pattern = || {
+                    std::str::from_utf8(name).is_ok_and(|name| {
+                        self.patterns.iter().any(|(watched, pattern)| {
+                            watched == directory && glob_match(pattern, name)
+                        })
+                    })
+                };
+                if self.files.contains(&path) || matches_pattern() {
+                    debug!("{} changed", path.display());
+                    return Ok(path);
+                }
+            }
+        }
+    }
+}
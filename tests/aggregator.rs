@@ -0,0 +1,198 @@
+#![cfg(feature = "aggregator")]
+
+mod common;
+
+use common::status;
+use nftblockd::aggregator::Aggregator;
+use nftblockd::nftables::applier::MockApplier;
+use nftblockd::nftables::builder::{RuleProto, SetElements};
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::set::blocklist::BlockList;
+use nftblockd::set::observer::UpdateObserver;
+use nftblockd::settings::Environment;
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+async fn start(aggregator: &Aggregator) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let router = aggregator.router();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    url
+}
+
+#[tokio::test]
+async fn test_requests_without_token_are_rejected() {
+    let aggregator = Aggregator::new("secret");
+    let url = start(&aggregator).await;
+
+    let response = reqwest::get(format!("{url}/ipv4")).await.unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_nothing_is_served_before_the_first_update() {
+    let aggregator = Aggregator::new("secret");
+    let url = start(&aggregator).await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{url}/ipv4"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_replica_applies_served_blocklist() {
+    let aggregator = Aggregator::new("secret");
+    let url = start(&aggregator).await;
+    let elements = SubnetList::IPv4(
+        parse_from_string(Some("10.0.0.0/8 10.1.0.0/16 192.0.2.1-192.0.2.3"), None).unwrap(),
+    )
+    .validate_blocklist(true)
+    .unwrap()
+    .deduplicate()
+    .unwrap()
+    .transform_to_nft_expressions()
+    .get_elements()
+    .unwrap();
+    aggregator.on_blocked("https://feed/a", &RuleProto::Ip, &elements);
+
+    let mut replica = BlockList::new(
+        &Environment::default(),
+        Some(format!("{url}/ipv4")),
+        None,
+        None,
+    )
+    .unwrap();
+    replica.headers = Some(HashMap::from([(
+        "Authorization".to_string(),
+        "Bearer secret".to_string(),
+    )]));
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+    let report = replica.update(&config, status()).await.unwrap();
+
+    assert_eq!(
+        report.ipv4_elements, 3,
+        "The range should be served as CIDRs: 10.0.0.0/8, 192.0.2.1/32, 192.0.2.2/31."
+    );
+    assert!(applier.applied()[0].contains("\"192.0.2.2\""));
+}
+
+fn elements<'a>(data: &str) -> SetElements<'a> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_unchanged_list_is_not_modified() {
+    let aggregator = Aggregator::new("secret");
+    let url = start(&aggregator).await;
+    aggregator.on_blocked("https://feed/a", &RuleProto::Ip, &elements("10.0.0.0/8"));
+    let client = reqwest::Client::new();
+
+    let first = client
+        .get(format!("{url}/ipv4"))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    let etag = first.headers()["etag"].to_str().unwrap().to_string();
+    let second = client
+        .get(format!("{url}/ipv4"))
+        .bearer_auth("secret")
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(second.status(), reqwest::StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn test_replica_convergence_is_reported() {
+    let aggregator = Aggregator::new("secret");
+    let url = start(&aggregator).await;
+    aggregator.on_blocked("https://feed/a", &RuleProto::Ip, &elements("10.0.0.0/8"));
+    let replica = BlockList::new(
+        &Environment::default(),
+        Some(format!("{url}/ipv4")),
+        None,
+        None,
+    )
+    .unwrap()
+    .with_replica("edge-1".to_string(), Some("secret"));
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+
+    replica.update(&config, status()).await.unwrap();
+    replica.update(&config, status()).await.unwrap();
+    let replicas = aggregator.replicas();
+    assert_eq!(replicas.len(), 1);
+    assert_eq!(replicas[0].name, "edge-1");
+    assert!(
+        replicas[0].converged,
+        "The replica applied the served list."
+    );
+    assert_eq!(applier.applied().len(), 2);
+    assert_eq!(
+        applier.applied()[0],
+        applier.applied()[1],
+        "A 304 response should reuse the cached elements."
+    );
+
+    aggregator.on_blocked("https://feed/a", &RuleProto::Ip, &elements("192.0.2.0/24"));
+    assert!(!aggregator.replicas()[0].converged);
+
+    replica.update(&config, status()).await.unwrap();
+    replica.update(&config, status()).await.unwrap();
+    assert!(aggregator.replicas()[0].converged);
+    assert!(applier.applied()[3].contains("\"192.0.2.0\""));
+}
+
+#[tokio::test]
+async fn test_tracked_replicas_are_capped() {
+    let aggregator = Aggregator::new("secret").with_max_replicas(2);
+    let url = start(&aggregator).await;
+    aggregator.on_blocked("https://feed/a", &RuleProto::Ip, &elements("10.0.0.0/8"));
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+
+    for name in ["edge-1", "edge-2", "edge-3"] {
+        BlockList::new(
+            &Environment::default(),
+            Some(format!("{url}/ipv4")),
+            None,
+            None,
+        )
+        .unwrap()
+        .with_replica(name.to_string(), Some("secret"))
+        .update(&config, status())
+        .await
+        .unwrap();
+    }
+
+    let names = aggregator
+        .replicas()
+        .into_iter()
+        .map(|replica| replica.name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["edge-2", "edge-3"],
+        "The least recently seen replica should be forgotten."
+    );
+}
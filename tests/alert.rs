@@ -0,0 +1,14 @@
+#![cfg(feature = "alerts")]
+
+use nftblockd::alert::smtp::SmtpSecurity;
+
+#[test]
+fn test_smtp_security_parse() {
+    assert_eq!(
+        SmtpSecurity::parse("STARTTLS").unwrap(),
+        SmtpSecurity::StartTls
+    );
+    assert_eq!(SmtpSecurity::parse("tls").unwrap(), SmtpSecurity::Tls);
+    assert_eq!(SmtpSecurity::parse("none").unwrap(), SmtpSecurity::None);
+    assert!(SmtpSecurity::parse("ssl").is_err());
+}
@@ -0,0 +1,347 @@
+mod common;
+
+use common::status;
+use nftables::schema::Nftables;
+use nftblockd::error::{AppError, NftablesFailure};
+use nftblockd::grpc::server::{Command, ServiceStatusStruct};
+use nftblockd::nftables::applier::{Applier, MockApplier, NftApplier};
+use nftblockd::nftables::builder::{ApplyStrategy, TableFamily};
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::nftables::confirm::Confirmation;
+use nftblockd::nftables::flush_table;
+use nftblockd::set::blocklist::{BlockList, Schedule, blocklist_loop};
+use nftblockd::settings::Environment;
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn test_apply_nft_records_ruleset() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+    let ipv4 = SubnetList::IPv4(parse_from_string(Some("10.0.0.0/8 10.1.0.0/16"), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements();
+
+    config.apply_nft(&ipv4, &None).unwrap();
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 1, "Exactly one ruleset should be applied.");
+    assert!(applied[0].contains("\"blocklist_set_ipv4\""));
+    assert!(applied[0].contains("\"10.0.0.0\""));
+    assert!(
+        !applied[0].contains("\"10.1.0.0\""),
+        "Nested subnets should be deduplicated before applying."
+    );
+}
+
+#[test]
+fn test_flush_table_deletes_through_applier() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+
+    flush_table(&config);
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 1);
+    assert!(applied[0].contains("\"delete\""));
+}
+
+#[test]
+fn test_flush_sets_keeps_table_and_anti_lockout() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+
+    config.flush_sets_and_apply().unwrap();
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 1);
+    assert!(!applied[0].contains("\"delete\""));
+    assert!(applied[0].contains("\"blocklist_set_ipv4\""));
+    assert!(applied[0].contains("\"custom_blocklist_set_ipv6\""));
+    assert!(
+        !applied[0].contains("anti_lockout"),
+        "The anti-lockout sets should not be flushed."
+    );
+}
+
+#[test]
+fn test_trace_rules_live_in_their_own_table() {
+    let applier = Arc::new(MockApplier::new());
+    let mut config = NftConfig::default().with_applier(applier.clone());
+
+    config
+        .apply_trace(&"192.0.2.0/24".parse().unwrap())
+        .unwrap();
+    config.remove_trace().unwrap();
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 2);
+    assert!(applied[0].contains("\"nftblockd_trace\""));
+    assert!(applied[0].contains("\"nftrace\""));
+    assert!(applied[0].contains("\"192.0.2.0\""));
+    assert!(
+        !applied[0].contains("blocklist_set"),
+        "The blocklist table should not be touched."
+    );
+    assert!(applied[1].contains("\"delete\""));
+    assert!(applied[1].contains("\"nftblockd_trace\""));
+
+    config.family = TableFamily::Ip;
+    assert!(
+        config
+            .apply_trace(&"2001:db8::1/128".parse().unwrap())
+            .is_err()
+    );
+}
+
+#[test]
+fn test_refill_keeps_the_live_table() {
+    let applier = Arc::new(MockApplier::new());
+    let mut config = NftConfig::default().with_applier(applier.clone());
+    config.apply_strategy = ApplyStrategy::Refill;
+    let ipv4 = SubnetList::IPv4(parse_from_string(Some("10.0.0.0/8"), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements();
+
+    // The mock lists no table, so the complete ruleset is applied.
+    config.apply_nft(&ipv4, &None).unwrap();
+    assert!(applier.applied()[0].contains("\"delete\""));
+
+    let live = serde_json::to_string(&config.generate_ruleset(&ipv4, &None)).unwrap();
+    let live = serde_json::from_str::<Nftables>(&live).unwrap();
+    let refill = config
+        .generate_refill_ruleset(&live, &ipv4, &None, &None, &None)
+        .expect("the live table has every set");
+    let refill = serde_json::to_string(&refill).unwrap();
+    assert!(!refill.contains("\"delete\""));
+    assert!(!refill.contains("\"rule\""));
+    assert!(!refill.contains("\"chain\""));
+    assert!(refill.contains("\"flush\""));
+    assert!(refill.contains("\"10.0.0.0\""));
+    assert!(
+        config
+            .generate_refill_ruleset(&live, &ipv4, &None, &Some(Vec::new()), &None)
+            .is_none(),
+        "New monitor sets require the table to be replaced."
+    );
+}
+
+#[test]
+fn test_monitor_quota_drops_greylisted_entries_over_budget() {
+    let mut config = NftConfig {
+        monitor_quota: Some(50 << 20),
+        ..NftConfig::default()
+    };
+    config.apply_strategy = ApplyStrategy::Refill;
+    let monitor = SubnetList::IPv4(parse_from_string(Some("192.0.2.0/24"), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements();
+
+    let ruleset = config.generate_monitored_ruleset(&None, &None, &monitor, &None);
+    let serialized = serde_json::to_string(&ruleset).unwrap();
+    assert!(serialized.contains("\"name\":\"monitor_quota\",\"bytes\":52428800,\"inv\":true"));
+    assert!(serialized.contains("prerouting ipv4 monitor quota rule"));
+    assert!(serialized.contains("\"quota\":\"monitor_quota\""));
+
+    let live = serde_json::from_str::<Nftables>(&serialized).unwrap();
+    assert!(
+        config
+            .generate_refill_ruleset(&live, &None, &None, &monitor, &None)
+            .is_some()
+    );
+    let without_quota = serde_json::to_string(
+        &NftConfig::default().generate_monitored_ruleset(&None, &None, &monitor, &None),
+    )
+    .unwrap();
+    let without_quota = serde_json::from_str::<Nftables>(&without_quota).unwrap();
+    assert!(
+        config
+            .generate_refill_ruleset(&without_quota, &None, &None, &monitor, &None)
+            .is_none(),
+        "A new quota requires the table to be replaced."
+    );
+}
+
+#[tokio::test]
+async fn test_update_without_endpoints_applies_empty_blocklist() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+    let blocklist = BlockList::new(&Environment::default(), None, None, None).unwrap();
+
+    let report = blocklist.update(&config, status()).await.unwrap();
+
+    assert_eq!(report.ipv4_elements, 0);
+    assert_eq!(report.ipv6_elements, 0);
+    assert_eq!(applier.applied().len(), 1);
+}
+
+#[tokio::test]
+async fn test_update_propagates_applier_failure() {
+    let applier = Arc::new(MockApplier::new());
+    applier.fail_with(Some(AppError::NftablesError(
+        NftablesFailure::ApplyFailed,
+        "mock failure".to_string(),
+        None,
+    )));
+    let config = NftConfig::default().with_applier(applier.clone());
+    let blocklist = BlockList::new(&Environment::default(), None, None, None).unwrap();
+
+    let actual = blocklist.update(&config, status()).await.unwrap_err();
+
+    assert_eq!(
+        actual,
+        AppError::NftablesError(
+            NftablesFailure::ApplyFailed,
+            "mock failure".to_string(),
+            None
+        )
+    );
+    assert!(actual.is_retryable());
+    assert!(applier.applied().is_empty());
+}
+
+#[test]
+fn test_confirmation_only_for_configuration_changes() {
+    let mut confirmation = Confirmation::default();
+    let timeout = Duration::from_secs(60);
+
+    assert!(
+        confirmation
+            .applied("a".into(), "a1".into(), timeout)
+            .is_none()
+    );
+    assert!(
+        confirmation
+            .applied("a".into(), "a2".into(), timeout)
+            .is_none(),
+        "New elements alone should not need a confirmation."
+    );
+    let deadline = confirmation.applied("b".into(), "b1".into(), timeout);
+    assert!(deadline.is_some());
+    assert_eq!(
+        confirmation.applied("b".into(), "b2".into(), timeout),
+        deadline,
+        "Updates of a pending configuration should keep its deadline."
+    );
+
+    assert_eq!(confirmation.rollback().as_deref(), Some("a2"));
+    assert!(confirmation.deadline().is_none());
+    assert!(confirmation.confirm().is_err());
+
+    confirmation.applied("b".into(), "b3".into(), timeout);
+    confirmation.confirm().unwrap();
+    assert!(
+        confirmation
+            .applied("b".into(), "b4".into(), timeout)
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_unconfirmed_ruleset_is_rolled_back_and_paused() {
+    let applier = Arc::new(MockApplier::new());
+    let (command_channel, mut commands) = tokio::sync::mpsc::channel(1);
+    let status = Arc::new(ServiceStatusStruct::new(command_channel));
+    let mut blocklist = BlockList::new(&Environment::default(), None, None, None).unwrap();
+    blocklist.confirm_timeout = Some(Duration::from_millis(100));
+
+    let confirmed = NftConfig::default().with_applier(applier.clone());
+    blocklist.update(&confirmed, status.clone()).await.unwrap();
+    let changed = NftConfig {
+        log_group: Some(5),
+        ..NftConfig::default().with_applier(applier.clone())
+    };
+    let token = CancellationToken::new();
+    let handle = tokio::spawn(blocklist_loop(
+        status.clone(),
+        blocklist,
+        changed,
+        Schedule::new(Duration::from_secs(60), 2, Duration::ZERO),
+        token.clone(),
+    ));
+
+    let Some(Command::Pause {
+        disable_rules,
+        respond_to,
+    }) = commands.recv().await
+    else {
+        panic!("The loop should pause updates after a rollback.");
+    };
+    assert!(!disable_rules);
+    token.cancel();
+    respond_to.send(Ok(())).unwrap();
+    handle.await.unwrap();
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 3);
+    assert!(applied[1].contains("\"group\""));
+    assert!(
+        !applied[2].contains("\"group\""),
+        "The last confirmed ruleset should be restored."
+    );
+}
+
+/// Writes a shell script standing in for `nft`.
+fn fake_nft(name: &str, script: &str) -> PathBuf {
+    let path = common::temp_path(name);
+    fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn test_hung_nft_is_killed_after_the_timeout() {
+    let config = NftConfig::default();
+    let ruleset = config.generate_ruleset(&None, &None);
+    let hung = fake_nft("hung-nft", "exec sleep 30");
+    let applier = NftApplier::default()
+        .with_program(&hung)
+        .with_timeout(Some(Duration::from_millis(200)));
+
+    let started = Instant::now();
+    let error = applier.apply(&ruleset).unwrap_err();
+    assert_eq!(
+        error.nftables_failure(),
+        Some(NftablesFailure::Timeout),
+        "{error}"
+    );
+    assert!(error.is_retryable());
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    let failing = fake_nft(
+        "failing-nft",
+        "cat >/dev/null; echo 'syntax error' >&2; exit 1",
+    );
+    let error = NftApplier::default()
+        .with_program(&failing)
+        .apply(&ruleset)
+        .unwrap_err();
+    assert!(matches!(error, AppError::NftablesError(..)), "{error}");
+    assert!(error.to_string().contains("syntax error"), "{error}");
+
+    let working = fake_nft("working-nft", "cat >/dev/null; echo '{\"nftables\": []}'");
+    let applier = NftApplier::default().with_program(&working);
+    applier.apply(&ruleset).unwrap();
+    assert!(applier.current_ruleset().unwrap().objects.is_empty());
+
+    for path in [hung, failing, working] {
+        let _ = fs::remove_file(path);
+    }
+}
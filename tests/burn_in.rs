@@ -0,0 +1,39 @@
+mod common;
+
+use nftblockd::set::burn_in::BurnIn;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_new_sources_are_observed_for_the_burn_in_period() {
+    let path = common::temp_path("burn-in.json");
+    let _ = std::fs::remove_file(&path);
+    let day = Duration::from_secs(86_400);
+    let start = SystemTime::now();
+
+    let burn_in = BurnIn::new(day, Some(path.clone()));
+    assert_eq!(
+        burn_in.remaining("https://example.com/ipv4", start),
+        Some(day)
+    );
+    assert_eq!(
+        burn_in.remaining("https://example.com/ipv4", start + day / 4),
+        Some(day * 3 / 4)
+    );
+
+    // A restart keeps the first appearance instead of starting the period over.
+    let burn_in = BurnIn::new(day, Some(path.clone()));
+    assert_eq!(
+        burn_in.remaining("https://example.com/ipv4", start + day / 2),
+        Some(day / 2)
+    );
+    assert_eq!(
+        burn_in.remaining("https://example.com/ipv4", start + day),
+        None
+    );
+    // A source added later gets its own period.
+    assert_eq!(
+        burn_in.remaining("https://example.com/ipv6", start + day),
+        Some(day)
+    );
+    let _ = std::fs::remove_file(&path);
+}
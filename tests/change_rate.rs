@@ -0,0 +1,45 @@
+use nftblockd::set::change_rate::ChangeRate;
+
+fn entries(range: std::ops::Range<u32>) -> Vec<String> {
+    range
+        .map(|i| format!("10.{}.{}.0/24", i / 256, i % 256))
+        .collect()
+}
+
+#[test]
+fn test_burst_of_changes_is_flagged_against_the_rolling_average() {
+    let mut rate = ChangeRate::new(5.0, 10, 100);
+    let source = "https://example.com/ipv4.txt";
+
+    // The baseline and a steady churn of 50 entries per fetch.
+    assert!(rate.record(source, &entries(0..1000)).is_none());
+    for i in 1..=4 {
+        assert!(
+            rate.record(source, &entries(i * 50..1000 + i * 50))
+                .is_none()
+        );
+    }
+    // A truncated feed drops most of its entries.
+    let anomaly = rate.record(source, &entries(200..300)).unwrap();
+
+    assert_eq!(anomaly.source, source);
+    assert_eq!(anomaly.added, 0);
+    assert_eq!(anomaly.removed, 900);
+    assert!((anomaly.average - 100.0).abs() < f64::EPSILON);
+    assert!(
+        rate.record("https://example.com/other.txt", &entries(0..10))
+            .is_none()
+    );
+}
+
+#[test]
+fn test_small_changes_of_a_quiet_source_are_not_flagged() {
+    let mut rate = ChangeRate::new(5.0, 10, 100);
+    let source = "https://example.com/ipv4.txt";
+
+    for _ in 0..4 {
+        assert!(rate.record(source, &entries(0..100)).is_none());
+    }
+
+    assert!(rate.record(source, &entries(0..150)).is_none());
+}
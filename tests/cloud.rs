@@ -0,0 +1,107 @@
+use nftblockd::set::cloud::{CloudFilter, CloudProvider, parse_ranges};
+use nftblockd::set::feed::{Feed, FeedFormat};
+use std::time::SystemTime;
+
+const AWS: &str = r#"{
+  "syncToken": "1767225600",
+  "prefixes": [
+    {"ip_prefix": "3.5.140.0/22", "region": "ap-northeast-2", "service": "AMAZON", "network_border_group": "ap-northeast-2"},
+    {"ip_prefix": "3.5.140.0/22", "region": "ap-northeast-2", "service": "EC2", "network_border_group": "ap-northeast-2"},
+    {"ip_prefix": "52.94.76.0/22", "region": "eu-west-1", "service": "EC2", "network_border_group": "eu-west-1"}
+  ],
+  "ipv6_prefixes": [
+    {"ipv6_prefix": "2a05:d050:8000::/40", "region": "eu-west-1", "service": "EC2", "network_border_group": "eu-west-1"}
+  ]
+}"#;
+
+const GCP: &str = r#"{
+  "prefixes": [
+    {"ipv4Prefix": "34.1.208.0/20", "service": "Google Cloud", "scope": "africa-south1"},
+    {"ipv6Prefix": "2600:1900:8000::/44", "service": "Google Cloud", "scope": "europe-west1"}
+  ]
+}"#;
+
+const AZURE: &str = r#"{
+  "changeNumber": 1,
+  "cloud": "Public",
+  "values": [
+    {"name": "AzureCloud.westeurope", "id": "AzureCloud.westeurope",
+     "properties": {"region": "westeurope", "platform": "Azure", "systemService": "", "addressPrefixes": ["13.69.0.0/17", "2603:1020:200::/46"]}},
+    {"name": "Storage.eastus", "id": "Storage.eastus",
+     "properties": {"region": "eastus", "platform": "Azure", "systemService": "AzureStorage", "addressPrefixes": ["20.38.98.0/24"]}}
+  ]
+}"#;
+
+const CLOUDFLARE: &str = r#"{"result": {"ipv4_cidrs": ["173.245.48.0/20"], "ipv6_cidrs": ["2400:cb00::/32"], "etag": "x"}, "success": true}"#;
+
+fn filter(services: &[&str], regions: &[&str]) -> CloudFilter {
+    CloudFilter {
+        services: services.iter().map(ToString::to_string).collect(),
+        regions: regions.iter().map(ToString::to_string).collect(),
+    }
+}
+
+#[test]
+fn test_every_provider_is_parsed() {
+    let all = CloudFilter::default();
+    assert_eq!(
+        parse_ranges(AWS, CloudProvider::Aws, &all).unwrap(),
+        ["3.5.140.0/22", "52.94.76.0/22", "2a05:d050:8000::/40"]
+    );
+    assert_eq!(
+        parse_ranges(GCP, CloudProvider::Gcp, &all).unwrap(),
+        ["34.1.208.0/20", "2600:1900:8000::/44"]
+    );
+    assert_eq!(
+        parse_ranges(AZURE, CloudProvider::Azure, &all).unwrap(),
+        ["13.69.0.0/17", "2603:1020:200::/46", "20.38.98.0/24"]
+    );
+    assert_eq!(
+        parse_ranges(CLOUDFLARE, CloudProvider::Cloudflare, &all).unwrap(),
+        ["173.245.48.0/20", "2400:cb00::/32"]
+    );
+    assert!(
+        parse_ranges(GCP, CloudProvider::Aws, &all)
+            .unwrap()
+            .is_empty()
+    );
+    assert!(parse_ranges("<html>", CloudProvider::Aws, &all).is_err());
+}
+
+#[test]
+fn test_ranges_are_filtered_by_service_and_region() {
+    assert_eq!(
+        parse_ranges(AWS, CloudProvider::Aws, &filter(&["ec2"], &["eu-*"])).unwrap(),
+        ["52.94.76.0/22", "2a05:d050:8000::/40"]
+    );
+    assert_eq!(
+        parse_ranges(GCP, CloudProvider::Gcp, &filter(&[], &["europe-west1"])).unwrap(),
+        ["2600:1900:8000::/44"]
+    );
+    // Azure service tags match with or without their region suffix.
+    assert_eq!(
+        parse_ranges(AZURE, CloudProvider::Azure, &filter(&["azurecloud"], &[])).unwrap(),
+        ["13.69.0.0/17", "2603:1020:200::/46"]
+    );
+    assert_eq!(
+        parse_ranges(AZURE, CloudProvider::Azure, &filter(&["AzureStorage"], &[])).unwrap(),
+        ["20.38.98.0/24"]
+    );
+}
+
+#[test]
+fn test_cloud_formats_list_both_families() {
+    let format = FeedFormat::parse("aws").unwrap();
+    assert_eq!(format, FeedFormat::Cloud(CloudProvider::Aws));
+    assert!(format.mixes_families());
+
+    let mut feed = Feed::parse(
+        CLOUDFLARE,
+        FeedFormat::Cloud(CloudProvider::Cloudflare),
+        None,
+        SystemTime::now(),
+    )
+    .unwrap();
+    feed.retain_family(true);
+    assert_eq!(feed.entries().collect::<Vec<_>>(), ["2400:cb00::/32"]);
+}
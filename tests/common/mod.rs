@@ -0,0 +1,125 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use nftblockd::grpc::server::ServiceStatusStruct;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Returns a path in the temporary directory unique to the running test binary, e.g., `nftblockd-{pid}-{name}`.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("nftblockd-{}-{name}", std::process::id()))
+}
+
+/// Returns the status of a daemon whose command channel is already closed.
+pub fn status() -> Arc<ServiceStatusStruct> {
+    let (command_channel, _) = tokio::sync::mpsc::channel(1);
+    Arc::new(ServiceStatusStruct::new(command_channel))
+}
+
+/// Response served by the `FixtureServer`.
+#[derive(Clone)]
+pub enum Fixture {
+    /// `200 OK` with the given body.
+    Body(&'static str),
+    /// Empty response with the given status code.
+    Status(u16),
+    /// Announces a longer body than it sends, then closes the connection.
+    Truncated(&'static str),
+    /// `200 OK` with the given body after a delay.
+    Slow(Duration, &'static str),
+    /// `302 Found` redirecting to the given location.
+    Redirect(&'static str),
+    /// `429 Too Many Requests` with the given `Retry-After`.
+    Throttled(&'static str),
+    /// The response the given function returns for the raw request.
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+impl Fixture {
+    pub fn custom(respond: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(respond))
+    }
+}
+
+/// Minimal HTTP server serving a switchable `Fixture` on every path.
+pub struct FixtureServer {
+    /// Address of the server, e.g., `http://127.0.0.1:8080`.
+    pub address: String,
+    /// URL of the `/blocklist` path.
+    pub url: String,
+    fixture: Arc<Mutex<Fixture>>,
+    /// Number of requests served.
+    pub requests: Arc<AtomicUsize>,
+}
+
+impl FixtureServer {
+    pub async fn start(fixture: Fixture) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let fixture = Arc::new(Mutex::new(fixture));
+        let served = fixture.clone();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counted.fetch_add(1, Ordering::SeqCst);
+                let fixture = served.lock().unwrap().clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 4096];
+                    let read = socket.read(&mut request).await.unwrap_or_default();
+                    let response = match fixture {
+                        Fixture::Body(body) => ok_response(body, body.len()),
+                        Fixture::Status(code) => format!(
+                            "HTTP/1.1 {code} Fixture\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        ),
+                        Fixture::Truncated(body) => ok_response(body, body.len() + 64),
+                        Fixture::Slow(delay, body) => {
+                            tokio::time::sleep(delay).await;
+                            ok_response(body, body.len())
+                        }
+                        Fixture::Throttled(retry_after) => format!(
+                            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: {retry_after}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        ),
+                        Fixture::Redirect(location) => format!(
+                            "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        ),
+                        Fixture::Custom(respond) => {
+                            respond(&String::from_utf8_lossy(&request[..read]))
+                        }
+                    };
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        Self {
+            url: format!("{address}/blocklist"),
+            address,
+            fixture,
+            requests,
+        }
+    }
+
+    pub fn set(&self, fixture: Fixture) {
+        *self.fixture.lock().unwrap() = fixture;
+    }
+}
+
+/// Returns a `200 OK` response announcing `content_length` bytes of `body`.
+pub fn ok_response(body: &str, content_length: usize) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {content_length}\r\nConnection: close\r\n\r\n{body}"
+    )
+}
+
+/// Returns a `200 OK` response with the given JSON body.
+pub fn json_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
@@ -0,0 +1,54 @@
+use nftblockd::set::consensus::Consensus;
+use std::collections::BTreeMap;
+
+fn consensus(threshold: u32) -> Consensus {
+    Consensus {
+        feeds: BTreeMap::from([
+            ("https://a.example/list".to_string(), 1),
+            ("https://b.example/list".to_string(), 2),
+        ]),
+        primary_weight: 1,
+        threshold,
+        monitor: true,
+    }
+}
+
+fn entries(list: &[&str]) -> Vec<String> {
+    list.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn test_entries_need_enough_feeds() {
+    let feeds = BTreeMap::from([
+        (
+            "https://a.example/list".to_string(),
+            entries(&["192.0.2.1/32", "198.51.100.0/24", "2001:db8::/32"]),
+        ),
+        (
+            "https://b.example/list".to_string(),
+            entries(&["203.0.113.0/24"]),
+        ),
+    ]);
+
+    let (blocked, below) = consensus(2).split(
+        &entries(&["192.0.2.1", "192.0.2.1", "10.0.0.0/8"]),
+        &feeds,
+        false,
+    );
+
+    assert_eq!(blocked, entries(&["192.0.2.1/32", "203.0.113.0/24"]));
+    assert_eq!(below, entries(&["10.0.0.0/8", "198.51.100.0/24"]));
+}
+
+#[test]
+fn test_entries_of_the_other_family_are_ignored() {
+    let feeds = BTreeMap::from([(
+        "https://a.example/list".to_string(),
+        entries(&["2001:db8::/32", "192.0.2.0/24"]),
+    )]);
+
+    let (blocked, below) = consensus(2).split(&entries(&["2001:db8::/32"]), &feeds, true);
+
+    assert_eq!(blocked, entries(&["2001:db8::/32"]));
+    assert!(below.is_empty());
+}
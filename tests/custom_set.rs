@@ -1,8 +1,11 @@
-use nftables::expr::{Expression, NamedExpression, Prefix};
+mod common;
+
+use nftables::expr::{Elem, Expression, NamedExpression, Prefix};
 use nftblockd::error::AppError;
-use nftblockd::set::custom_set::CustomSet;
-use nftblockd::utils::subnet::parse_from_string;
+use nftblockd::set::custom_set::{CustomSet, read_custom_feed, read_custom_file};
+use nftblockd::utils::subnet::{Strictness, parse_from_string};
 use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
 
 #[test]
 fn test_valid_custom_set() {
@@ -411,3 +414,128 @@ fn test_ipv6_loopback_and_full_block() {
         "Loopback should be excluded if ::/0 is present."
     );
 }
+
+#[test]
+fn test_custom_file_reports_the_position_of_invalid_entries() {
+    let path = common::temp_path("custom.txt");
+    std::fs::write(
+        &path,
+        "# local additions\n192.0.2.0/24\n198.51.100.7  192.0.2.1/16  # typo\n",
+    )
+    .unwrap();
+    let path_str = path.display().to_string();
+
+    let error = read_custom_file(Some(&path_str), None, false, Strictness::Strict).unwrap_err();
+    let skipped = read_custom_file(Some(&path_str), None, false, Strictness::Lenient).unwrap();
+
+    assert_eq!(
+        error,
+        AppError::ParseError(format!(
+            "{path_str}:3:15: invalid ip: 192.0.2.1/16; not a network"
+        ))
+    );
+    assert_eq!(
+        skipped,
+        Some(vec!["192.0.2.0/24".to_string(), "198.51.100.7".to_string()])
+    );
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_custom_file_reads_directories_and_globs() {
+    let dir = common::temp_path("custom-dir");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("20-noc.txt"), "198.51.100.0/24\n").unwrap();
+    std::fs::write(dir.join("10-base.txt"), "192.0.2.0/24\n").unwrap();
+    std::fs::write(dir.join("notes.md"), "203.0.113.0/24\n").unwrap();
+    std::fs::write(dir.join(".10-base.txt.swp"), "garbage").unwrap();
+
+    let all = read_custom_file(
+        Some(&dir.display().to_string()),
+        None,
+        false,
+        Strictness::Strict,
+    )
+    .unwrap();
+    let glob = read_custom_file(
+        Some(&dir.join("*.txt").display().to_string()),
+        None,
+        false,
+        Strictness::Strict,
+    )
+    .unwrap();
+    let none = read_custom_file(
+        Some(&dir.join("*.list").display().to_string()),
+        None,
+        false,
+        Strictness::Strict,
+    )
+    .unwrap();
+
+    assert_eq!(
+        all,
+        Some(
+            ["192.0.2.0/24", "198.51.100.0/24", "203.0.113.0/24"]
+                .map(String::from)
+                .to_vec()
+        )
+    );
+    assert_eq!(
+        glob,
+        Some(
+            ["192.0.2.0/24", "198.51.100.0/24"]
+                .map(String::from)
+                .to_vec()
+        )
+    );
+    assert_eq!(none, None);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_custom_file_entries_carry_their_ttl_and_comment() {
+    let path = common::temp_path("custom-ttl.txt");
+    std::fs::write(
+        &path,
+        "192.0.2.0/24 7d # ticket 1234\n198.51.100.7 1h # expired\n203.0.113.0/24\n",
+    )
+    .unwrap();
+    let modified = SystemTime::now() - Duration::from_secs(2 * 3600);
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+    let feed = read_custom_feed(
+        Some(&path.display().to_string()),
+        None,
+        false,
+        Strictness::Strict,
+    )
+    .unwrap();
+    let set = CustomSet::new("set".to_string(), feed.to_entries(), None)
+        .unwrap()
+        .with_annotations(feed.expiry, &feed.comments);
+
+    let permanent = Expression::Named(NamedExpression::Prefix(Prefix {
+        addr: Box::new(Expression::String(Cow::from("203.0.113.0"))),
+        len: 24,
+    }));
+    let commented = Expression::Named(NamedExpression::Elem(Elem {
+        val: Box::new(Expression::Named(NamedExpression::Prefix(Prefix {
+            addr: Box::new(Expression::String(Cow::from("192.0.2.0"))),
+            len: 24,
+        }))),
+        comment: Some(Cow::from("ticket 1234")),
+        ..Elem::default()
+    }));
+    assert_eq!(set.ipv4_elements, Some(vec![commented, permanent.clone()]));
+    assert_eq!(
+        set.unexpired(modified + Duration::from_secs(8 * 86400))
+            .ipv4_elements,
+        Some(vec![permanent])
+    );
+    std::fs::remove_file(&path).unwrap();
+}
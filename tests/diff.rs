@@ -0,0 +1,62 @@
+use nftblockd::nftables::applier::MockApplier;
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::nftables::diff::diff_rulesets;
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::sync::Arc;
+
+fn ipv4_elements<'a>(data: &str) -> Option<SetElements<'a>> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+#[test]
+fn test_diff_reports_added_and_removed_elements() {
+    let config = NftConfig::default();
+    let live = ipv4_elements("10.0.0.0/8 192.0.2.1 198.51.100.1-198.51.100.9");
+    let desired = ipv4_elements("10.0.0.0/8 203.0.113.0/24");
+
+    let diffs = diff_rulesets(
+        &config.table_name,
+        &config.generate_ruleset(&live, &None),
+        &config.generate_ruleset(&desired, &None),
+    );
+
+    let blocklist = diffs
+        .iter()
+        .find(|d| d.set_name == "blocklist_set_ipv4")
+        .unwrap();
+    assert_eq!(blocklist.added, vec!["203.0.113.0/24"]);
+    assert_eq!(
+        blocklist.removed,
+        vec!["192.0.2.1", "198.51.100.1-198.51.100.9"]
+    );
+    assert!(
+        diffs
+            .iter()
+            .filter(|d| d.set_name != "blocklist_set_ipv4")
+            .all(|d| d.is_empty())
+    );
+}
+
+#[test]
+fn test_diff_nft_does_not_apply() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+
+    let diffs = config
+        .diff_nft(&ipv4_elements("192.0.2.0/24"), &None)
+        .unwrap();
+
+    assert!(applier.applied().is_empty());
+    assert!(
+        diffs
+            .iter()
+            .any(|d| d.set_name == "blocklist_set_ipv4" && d.added == vec!["192.0.2.0/24"])
+    );
+}
@@ -0,0 +1,70 @@
+use nftblockd::set::dual_stack::{DualStackAction, DualStackCheck, FamilyState, Inconsistency};
+use nftblockd::set::fetch_policy::parse_http_date;
+use std::time::{Duration, SystemTime};
+
+fn check() -> DualStackCheck {
+    DualStackCheck {
+        action: DualStackAction::Warn,
+        max_skew: Duration::from_secs(86_400),
+        min_elements: 1000,
+    }
+}
+
+#[test]
+fn test_families_modified_far_apart_are_inconsistent() {
+    let ipv4 = FamilyState {
+        elements: 5000,
+        last_modified: parse_http_date("Thu, 01 Jan 2026 00:00:00 GMT"),
+    };
+    let ipv6 = FamilyState {
+        elements: 800,
+        last_modified: parse_http_date("Sat, 03 Jan 2026 00:00:00 GMT"),
+    };
+
+    let inconsistencies = check().inconsistencies(ipv4, ipv6);
+    assert_eq!(
+        inconsistencies,
+        [Inconsistency::Skew {
+            skew: Duration::from_secs(2 * 86_400),
+            ipv6_newer: true
+        }]
+    );
+    assert_eq!(
+        inconsistencies[0].to_string(),
+        "the IPv6 list is 172800 s newer than the IPv4 list"
+    );
+
+    let ipv6 = FamilyState {
+        last_modified: ipv4.last_modified,
+        ..ipv6
+    };
+    assert!(check().inconsistencies(ipv4, ipv6).is_empty());
+}
+
+#[test]
+fn test_empty_family_next_to_a_large_one_is_inconsistent() {
+    let now = Some(SystemTime::now());
+    let large = FamilyState {
+        elements: 20_000,
+        last_modified: now,
+    };
+    let empty = FamilyState {
+        elements: 0,
+        last_modified: now,
+    };
+
+    assert_eq!(
+        check().inconsistencies(large, empty),
+        [Inconsistency::Empty {
+            elements: 20_000,
+            ipv6_empty: true
+        }]
+    );
+    // A small feed without IPv6 entries is not suspicious.
+    let small = FamilyState {
+        elements: 10,
+        last_modified: None,
+    };
+    assert!(check().inconsistencies(small, empty).is_empty());
+    assert!(DualStackAction::parse("block").is_err());
+}
@@ -0,0 +1,29 @@
+use nftblockd::utils::duration::parse_duration;
+use std::time::Duration;
+
+#[test]
+fn test_durations_with_units_are_parsed() {
+    for (value, expected) in [
+        ("30", 30),
+        ("30s", 30),
+        ("15m", 900),
+        ("6h", 21_600),
+        ("1d", 86_400),
+        ("1h30m", 5_400),
+        (" 2w ", 1_209_600),
+    ] {
+        assert_eq!(
+            parse_duration(value).unwrap(),
+            Duration::from_secs(expected),
+            "{value}"
+        );
+    }
+    assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+}
+
+#[test]
+fn test_invalid_durations_are_rejected() {
+    for value in ["", "m", "10x", "1h30", "-5s", "99999999999999999999d"] {
+        assert!(parse_duration(value).is_err(), "{value} should be rejected");
+    }
+}
@@ -0,0 +1,84 @@
+use nftblockd::error::AppError;
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::set::element_cache::ElementCache;
+use nftblockd::utils::subnet::SubnetList;
+use std::sync::Arc;
+
+fn list(entries: &[&str]) -> Vec<String> {
+    entries.iter().map(ToString::to_string).collect()
+}
+
+fn generate(list: &[String]) -> Result<Option<SetElements<'static>>, AppError> {
+    Ok(SubnetList::IPv4(list.to_vec())
+        .validate_blocklist(true)?
+        .deduplicate()?
+        .transform_to_nft_expressions()
+        .get_elements())
+}
+
+#[test]
+fn test_unchanged_list_reuses_elements() {
+    let cache = ElementCache::default();
+
+    let first = cache
+        .get_or_generate(&list(&["10.0.0.0/8", "192.0.2.0/24"]), &(), generate)
+        .unwrap();
+    let second = cache
+        .get_or_generate(&list(&["10.0.0.0/8", "192.0.2.0/24"]), &(), |_| {
+            panic!("an unchanged list must not be regenerated")
+        })
+        .unwrap();
+
+    assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[test]
+fn test_changed_list_regenerates_elements() {
+    let cache = ElementCache::default();
+
+    let first = cache
+        .get_or_generate(&list(&["10.0.0.0/8"]), &(), generate)
+        .unwrap();
+    let second = cache
+        .get_or_generate(&list(&["192.0.2.0/24"]), &(), generate)
+        .unwrap();
+
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_eq!(Option::as_ref(&second).map(Vec::len), Some(1));
+}
+
+#[test]
+fn test_failed_generation_keeps_cache() {
+    let cache = ElementCache::default();
+
+    let first = cache
+        .get_or_generate(&list(&["10.0.0.0/8"]), &(), generate)
+        .unwrap();
+    cache
+        .get_or_generate(&list(&["not_an_ip"]), &(), |_| {
+            Err(AppError::ParseError("not_an_ip".to_string()))
+        })
+        .unwrap_err();
+    let second = cache
+        .get_or_generate(&list(&["10.0.0.0/8"]), &(), generate)
+        .unwrap();
+
+    assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[test]
+fn test_changed_context_regenerates_elements() {
+    let cache = ElementCache::default();
+
+    let first = cache
+        .get_or_generate(&list(&["10.0.0.0/8"]), &Some("fetched at 10:00"), generate)
+        .unwrap();
+    let second = cache
+        .get_or_generate(&list(&["10.0.0.0/8"]), &Some("fetched at 11:00"), generate)
+        .unwrap();
+
+    assert!(
+        !Arc::ptr_eq(&first, &second),
+        "Elements generated with another comment should not be reused."
+    );
+}
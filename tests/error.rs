@@ -0,0 +1,156 @@
+mod common;
+
+use nftblockd::error::{AppError, ErrorClass, FailureKind, FailureReport, NftablesFailure};
+use nftblockd::set::blocklist::{FailureAction, Schedule};
+use nftblockd::utils::read_ip_set_file;
+use std::error::Error;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_file_error_keeps_source() {
+    let actual = read_ip_set_file(Some("/nonexistent/nftblockd/ipv4.txt")).unwrap_err();
+
+    assert!(matches!(actual, AppError::FileError(_, Some(_))));
+    assert!(
+        actual.source().is_some(),
+        "The file error should expose the underlying io error."
+    );
+    assert_eq!(actual.class(), ErrorClass::Fatal);
+}
+
+#[test]
+fn test_io_error_is_retryable() {
+    let actual = AppError::from(std::io::Error::other("broken pipe"));
+
+    assert!(actual.is_retryable());
+    assert_eq!(actual.source().unwrap().to_string(), "broken pipe");
+}
+
+#[test]
+fn test_parse_error_is_fatal() {
+    let actual = AppError::ParseError("invalid prefix: 10.0.0.0/33".to_string());
+
+    assert_eq!(actual.class(), ErrorClass::Fatal);
+    assert!(actual.source().is_none());
+}
+
+#[test]
+fn test_exit_codes_tell_failures_apart() {
+    assert_eq!(
+        AppError::ParseError("invalid NFTBLOCKD_INTERVAL".to_string())
+            .kind()
+            .exit_code(),
+        2
+    );
+    assert_eq!(
+        AppError::RequestError("connection refused".to_string(), None).kind(),
+        FailureKind::Fetch
+    );
+    assert_eq!(
+        AppError::NftablesError(
+            NftablesFailure::ApplyFailed,
+            "syntax error".to_string(),
+            None
+        )
+        .kind()
+        .exit_code(),
+        4
+    );
+    assert_eq!(
+        AppError::PrivilegeError("unknown user: nftblockd".to_string())
+            .kind()
+            .exit_code(),
+        5
+    );
+}
+
+#[test]
+fn test_failure_report_is_written_as_json() {
+    let path = common::temp_path("failure.json");
+    let error = AppError::RequestError("connection refused".to_string(), None);
+
+    FailureReport::new(&error, UNIX_EPOCH + Duration::from_secs(60))
+        .write(&path)
+        .unwrap();
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(report["kind"], "fetch");
+    assert_eq!(report["exit_code"], 3);
+    assert_eq!(report["retryable"], true);
+    assert_eq!(report["time"], 60);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_nftables_failures_are_told_apart_by_cause() {
+    use nftables::helper::NftablesError;
+
+    let not_found = NftablesError::NftExecution {
+        program: "nft".into(),
+        inner: std::io::Error::from(std::io::ErrorKind::NotFound),
+    };
+    let actual = AppError::from(not_found);
+    assert_eq!(
+        actual.nftables_failure(),
+        Some(NftablesFailure::NftNotFound)
+    );
+    assert_eq!(actual.class(), ErrorClass::Fatal);
+    assert!(actual.to_string().contains("PATH"), "{actual}");
+
+    assert_eq!(
+        NftablesFailure::classify_message("Error: Could not process rule: Operation not permitted"),
+        NftablesFailure::PermissionDenied
+    );
+    assert_eq!(
+        NftablesFailure::classify_message("Error: Could not process rule: Not supported"),
+        NftablesFailure::KernelFeatureMissing
+    );
+    assert_eq!(
+        NftablesFailure::classify_message("Error: syntax error, unexpected newline"),
+        NftablesFailure::ApplyFailed
+    );
+
+    let denied = AppError::NftablesError(NftablesFailure::PermissionDenied, String::new(), None);
+    assert_eq!(denied.kind(), FailureKind::Privilege);
+    assert_eq!(denied.class(), ErrorClass::Fatal);
+    let timeout = AppError::NftablesError(NftablesFailure::Timeout, String::new(), None);
+    assert_eq!(timeout.kind(), FailureKind::Apply);
+    assert!(timeout.is_retryable());
+
+    let report = serde_json::to_value(FailureReport::new(
+        &denied,
+        UNIX_EPOCH + Duration::from_secs(60),
+    ))
+    .unwrap();
+    assert_eq!(report["nftables_failure"], "permission-denied");
+    assert_eq!(report["exit_code"], 5);
+}
+
+#[test]
+fn test_fatal_error_stops_updates_without_flushing() {
+    let schedule = Schedule::new(Duration::from_secs(30), 3, Duration::from_secs(1));
+    let fatal = AppError::NftablesError(
+        NftablesFailure::NftNotFound,
+        "No such file or directory".to_string(),
+        None,
+    );
+    let retryable = AppError::RequestError("connection refused".to_string(), None);
+
+    assert_eq!(schedule.on_failure(&fatal, 1), FailureAction::Stop);
+    assert_eq!(schedule.on_failure(&fatal, 3), FailureAction::Stop);
+    assert_eq!(schedule.on_failure(&retryable, 1), FailureAction::Retry);
+    assert_eq!(schedule.on_failure(&retryable, 3), FailureAction::Flush);
+}
+
+#[test]
+fn test_malformed_feed_holds_the_ruleset_and_keeps_updating() {
+    let schedule = Schedule::new(Duration::from_secs(30), 3, Duration::from_secs(1));
+    let malformed = AppError::DeserializeError("expected value at line 1 column 1".to_string());
+    let rejected = AppError::ParseError("too many invalid entries".to_string());
+
+    assert!(!malformed.is_local());
+    assert_eq!(schedule.on_failure(&malformed, 1), FailureAction::Hold);
+    assert_eq!(schedule.on_failure(&malformed, 3), FailureAction::Hold);
+    assert_eq!(schedule.on_failure(&rejected, 3), FailureAction::Hold);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use nftblockd::error::AppError;
+use nftblockd::events::EventStream;
+use nftblockd::set::observer::{UpdateObserver, UpdateReport};
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixListener;
+
+#[test]
+fn test_event_stream_writes_json_lines_to_a_unix_socket() {
+    let path = common::temp_path("events.sock");
+    let _ = std::fs::remove_file(&path);
+    let events = EventStream::new(path.clone(), Some("edge".to_string()));
+
+    // Without a reader, events are dropped.
+    events.on_cycle_start();
+
+    let listener = UnixListener::bind(&path).unwrap();
+    events.on_cycle_start();
+    events.on_fetched("https://example.com/ipv4", Some(3));
+    events.on_fetched("https://example.com/ipv6", None);
+    events.on_applied(&UpdateReport {
+        table_name: "blocklist".to_string(),
+        ipv4_elements: 3,
+        ..UpdateReport::default()
+    });
+    events.on_error(&AppError::RequestError("timed out".to_string(), None));
+
+    let (stream, _) = listener.accept().unwrap();
+    let lines = BufReader::new(stream)
+        .lines()
+        .take(5)
+        .map(|line| serde_json::from_str::<serde_json::Value>(&line.unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    let _ = std::fs::remove_file(&path);
+
+    let kinds = lines
+        .iter()
+        .map(|l| l["event"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        ["cycle_start", "fetched", "fetched", "applied", "error"]
+    );
+    assert!(
+        lines
+            .iter()
+            .all(|l| l["instance"] == "edge" && l["time"].is_u64())
+    );
+    assert_eq!(lines[1]["entries"], 3);
+    assert_eq!(lines[2]["modified"], false);
+    assert!(lines[2].get("entries").is_none());
+    assert_eq!(lines[3]["ipv4_elements"], 3);
+    assert_eq!(lines[4]["kind"], "fetch");
+    assert_eq!(lines[4]["retryable"], true);
+}
@@ -0,0 +1,95 @@
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::set::attribution::attribute;
+use nftblockd::set::export::{ExportFormat, export_attributed, export_elements, range_to_networks};
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+
+fn ipv4_elements<'a>(data: &str) -> Option<SetElements<'a>> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+#[test]
+fn test_range_to_networks() {
+    let actual = range_to_networks("192.0.2.1".parse().unwrap(), "192.0.2.9".parse().unwrap())
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        actual,
+        vec![
+            "192.0.2.1/32",
+            "192.0.2.2/31",
+            "192.0.2.4/30",
+            "192.0.2.8/31"
+        ]
+    );
+    assert_eq!(
+        range_to_networks(
+            "::".parse().unwrap(),
+            "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff".parse().unwrap()
+        )
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>(),
+        vec!["::/0"]
+    );
+}
+
+#[test]
+fn test_export_formats() {
+    let ipv4 = ipv4_elements("10.0.0.0/8 10.1.0.0/16 192.0.2.1");
+
+    let plain = export_elements(ExportFormat::Plain, "blocklist_set", &ipv4, &None).unwrap();
+    assert_eq!(plain, "10.0.0.0/8\n192.0.2.1/32\n");
+
+    let csv = export_elements(ExportFormat::Csv, "blocklist_set", &ipv4, &None).unwrap();
+    assert!(csv.starts_with("network,family,first_address,last_address\n"));
+    assert!(csv.contains("10.0.0.0/8,ipv4,10.0.0.0,10.255.255.255\n"));
+
+    let json = export_elements(ExportFormat::Json, "blocklist_set", &ipv4, &None).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["ipv4"].as_array().unwrap().len(), 2);
+    assert!(json["ipv6"].as_array().unwrap().is_empty());
+
+    let ipset = export_elements(ExportFormat::Ipset, "blocklist_set", &ipv4, &None).unwrap();
+    assert!(ipset.contains("create blocklist_set_ipv4 hash:net family inet -exist\n"));
+    assert!(ipset.contains("add blocklist_set_ipv4 10.0.0.0/8 -exist\n"));
+    assert!(ipset.contains("create blocklist_set_ipv6 hash:net family inet6 -exist\n"));
+}
+
+#[test]
+fn test_export_attributes_merged_entries_to_their_sources() {
+    let primary = ["192.0.2.0/24", "198.51.100.7"];
+    let feed = ["192.0.2.9", "198.51.100.7/32", "203.0.113.1"];
+    let ipv4 = ipv4_elements("192.0.2.0/24 192.0.2.9 198.51.100.7");
+    let attribution = attribute(
+        ipv4.as_ref().unwrap(),
+        &[
+            ("https://a.example", primary.as_slice()),
+            ("https://b.example", feed.as_slice()),
+        ],
+    );
+
+    let csv = export_attributed(
+        ExportFormat::Csv,
+        "blocklist_set",
+        &ipv4,
+        &None,
+        Some(&attribution),
+    )
+    .unwrap();
+
+    assert_eq!(
+        csv,
+        "network,family,first_address,last_address,sources\n\
+         192.0.2.0/24,ipv4,192.0.2.0,192.0.2.255,https://a.example https://b.example\n\
+         198.51.100.7/32,ipv4,198.51.100.7,198.51.100.7,https://a.example https://b.example\n"
+    );
+    assert!(!attribution.contains_key("203.0.113.1"));
+}
@@ -0,0 +1,166 @@
+use nftblockd::nftables::expire_elements;
+use nftblockd::set::feed::{Feed, FeedFormat, Splitter};
+use nftblockd::utils::subnet::SubnetList;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_crowdsec_decisions_expire_after_their_duration() {
+    let now = SystemTime::now();
+    let body = r#"[
+        {"scope": "Ip", "type": "ban", "value": "192.0.2.1", "duration": "3h59m58.630254393s"},
+        {"scope": "Range", "type": "ban", "value": "198.51.100.0/24", "duration": "-1s"},
+        {"scope": "Ip", "type": "captcha", "value": "192.0.2.2", "duration": "1h"},
+        {"scope": "Country", "type": "ban", "value": "XX", "duration": "1h"}
+    ]"#;
+
+    let feed = Feed::parse(body, FeedFormat::Crowdsec, None, now).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["192.0.2.1", "198.51.100.0/24"].map(String::from)
+    );
+    assert_eq!(
+        feed.expiry["192.0.2.1"],
+        now + Duration::from_secs(4 * 3600 - 2)
+    );
+    assert_eq!(feed.expiry["198.51.100.0/24"], now);
+    assert_eq!(
+        Feed::parse("null", FeedFormat::Crowdsec, None, now).unwrap(),
+        Feed::default()
+    );
+}
+
+#[test]
+fn test_misp_attributes_expire_at_their_last_seen() {
+    let body = r#"{"response": {"Attribute": [
+        {"type": "ip-dst|port", "value": "192.0.2.1|443", "last_seen": "2030-01-01T00:00:00.000000+00:00"},
+        {"type": "ip-src", "value": "192.0.2.2"},
+        {"type": "domain", "value": "example.com"}
+    ]}}"#;
+
+    let feed = Feed::parse(body, FeedFormat::Misp, None, SystemTime::now()).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["192.0.2.1", "192.0.2.2"].map(String::from)
+    );
+    assert_eq!(
+        feed.expiry["192.0.2.1"],
+        UNIX_EPOCH + Duration::from_secs(1_893_456_000)
+    );
+    assert!(!feed.expiry.contains_key("192.0.2.2"));
+}
+
+#[test]
+fn test_expired_elements_are_left_out_and_the_others_time_out() {
+    let now = SystemTime::now();
+    let feed = Feed::parse(
+        r#"[{"scope": "Ip", "value": "192.0.2.1", "duration": "1h"},
+            {"scope": "Ip", "value": "192.0.2.2", "duration": "0s"}]"#,
+        FeedFormat::Crowdsec,
+        None,
+        now,
+    )
+    .unwrap();
+    let mut entries = feed.entries().map(String::from).collect::<Vec<_>>();
+    entries.push("203.0.113.0/24".to_string());
+    let elements = SubnetList::IPv4(entries)
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+        .unwrap();
+
+    let expired = serde_json::to_string(&expire_elements(elements, &feed.expiry, now)).unwrap();
+
+    assert!(expired.contains(r#""timeout":3600"#), "{expired}");
+    assert!(!expired.contains("192.0.2.2"));
+    assert!(expired.contains("203.0.113.0"));
+}
+
+#[test]
+fn test_extract_scans_unstructured_text() {
+    let body = r#"<html><p>Block 192.0.2.1 and 198.51.100.0/24 (seen 2025-01-01 12:30:00),
+        also 2001:db8::/32; not 999.1.1.1, std::vector, or 192.0.2.1 again.</p></html>"#;
+
+    let mut feed = Feed::parse(body, FeedFormat::Extract, None, SystemTime::now()).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["192.0.2.1", "198.51.100.0/24", "2001:db8::/32"].map(String::from)
+    );
+    assert!(!FeedFormat::Extract.expires());
+    feed.retain_family(true);
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["2001:db8::/32"].map(String::from)
+    );
+}
+
+#[test]
+fn test_annotated_entries_carry_their_ttl_and_comment() {
+    let now = SystemTime::now();
+    let body =
+        "192.0.2.1 12h # brute force on ssh\n198.51.100.0/24 # scanner\n\n203.0.113.7/32 3600\n";
+
+    let feed = Feed::parse(body, FeedFormat::Annotated, None, now).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["192.0.2.1", "198.51.100.0/24", "203.0.113.7/32"].map(String::from)
+    );
+    assert_eq!(feed.expiry.len(), 2);
+    assert_eq!(
+        feed.expiry["192.0.2.1"],
+        now + Duration::from_secs(12 * 3600)
+    );
+    assert_eq!(feed.expiry["203.0.113.7"], now + Duration::from_secs(3600));
+    assert_eq!(feed.comments["192.0.2.1"], "brute force on ssh");
+    assert_eq!(feed.comments["198.51.100.0/24"], "scanner");
+    assert!(FeedFormat::Annotated.expires());
+}
+
+#[test]
+fn test_mixed_separators_are_split_by_a_delimiter_set_or_regex() {
+    let body = "192.0.2.1,198.51.100.0/24;\n203.0.113.7 , 192.0.2.9\r\n";
+    let entries = ["192.0.2.1", "198.51.100.0/24", "203.0.113.7", "192.0.2.9"];
+
+    let splitter = Splitter::delimiters("newline, comma, semicolon").unwrap();
+    let feed = Feed::parse_split(body, FeedFormat::Text, &splitter, SystemTime::now()).unwrap();
+    assert_eq!(feed.entries().collect::<Vec<_>>(), entries);
+
+    let splitter = Splitter::regex(r"[,;\s]+").unwrap();
+    let feed = Feed::parse_split(body, FeedFormat::Text, &splitter, SystemTime::now()).unwrap();
+    assert_eq!(feed.entries().collect::<Vec<_>>(), entries);
+
+    // A single literal delimiter leaves the other separators in the entries.
+    let feed = Feed::parse(body, FeedFormat::Text, Some(","), SystemTime::now()).unwrap();
+    assert_eq!(feed.len(), 3);
+
+    assert!(Splitter::delimiters("colon").is_err());
+    assert!(Splitter::delimiters(" , ").is_err());
+    assert!(Splitter::regex("[").is_err());
+}
+
+#[test]
+fn test_columns_take_the_first_address_of_every_line() {
+    let body = "# ip hits last_seen\n192.0.2.1 42 2025-01-01\n17 198.51.100.0/24 2025-01-02 # scanner\n\
+                2001:db8::1\t3\nno address here 12\n192.0.2.10-192.0.2.20 1.5 n/a\n";
+
+    let feed = Feed::parse(body, FeedFormat::Columns, None, SystemTime::now()).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        [
+            "192.0.2.1",
+            "198.51.100.0/24",
+            "2001:db8::1",
+            "192.0.2.10-192.0.2.20"
+        ]
+        .map(String::from)
+    );
+    assert_eq!(FeedFormat::parse("columns").unwrap(), FeedFormat::Columns);
+    assert!(!FeedFormat::Columns.expires());
+}
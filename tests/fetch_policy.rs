@@ -0,0 +1,126 @@
+mod common;
+
+use common::{Fixture, FixtureServer, ok_response};
+use nftblockd::set::fetch_policy::{AddressFamily, FetchPolicy, is_public_ip, parse_retry_after};
+use nftblockd::settings::Environment;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_plain_http_requires_opt_in() {
+    let strict = FetchPolicy::default();
+    let lenient = FetchPolicy {
+        allow_http: true,
+        ..FetchPolicy::default()
+    };
+
+    assert!(strict.check_source("https://example.com/ipv4").is_ok());
+    assert!(strict.check_source("http://example.com/ipv4").is_err());
+    assert!(lenient.check_source("http://example.com/ipv4").is_ok());
+    assert!(lenient.check_source("ftp://example.com/ipv4").is_err());
+}
+
+#[test]
+fn test_internal_addresses_are_not_public() {
+    for ip in [
+        "10.1.2.3",
+        "127.0.0.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:192.168.1.1",
+    ] {
+        assert!(
+            !is_public_ip(ip.parse().unwrap()),
+            "{ip} should not be public"
+        );
+    }
+    assert!(is_public_ip("198.51.100.7".parse().unwrap()));
+    assert!(is_public_ip("2001:db8::1".parse().unwrap()));
+}
+
+#[test]
+fn test_redirect_hosts_allowlist() {
+    let open = FetchPolicy::default();
+    let restricted = FetchPolicy {
+        redirect_hosts: Some(vec![
+            "cdn.example.com".to_string(),
+            "*.example.net".to_string(),
+        ]),
+        ..FetchPolicy::default()
+    };
+
+    assert!(open.allows_redirect_host("anything.example.org"));
+    assert!(restricted.allows_redirect_host("CDN.example.com"));
+    assert!(restricted.allows_redirect_host("eu.edge.example.net"));
+    assert!(!restricted.allows_redirect_host("example.net"));
+    assert!(!restricted.allows_redirect_host("evil-example.net"));
+    assert!(!restricted.allows_redirect_host("other.example.com"));
+}
+
+#[test]
+fn test_address_family_pins_transport() {
+    let ipv4 = AddressFamily::parse("IPv4").unwrap();
+
+    assert_eq!(ipv4, AddressFamily::IPv4);
+    assert!(ipv4.allows("198.51.100.7".parse().unwrap()));
+    assert!(!ipv4.allows("2001:db8::1".parse().unwrap()));
+    assert!(AddressFamily::Any.allows("2001:db8::1".parse().unwrap()));
+    assert!(AddressFamily::parse("ipv5").is_err());
+}
+
+#[test]
+fn test_retry_after_accepts_seconds_and_dates() {
+    let now = SystemTime::now();
+
+    assert_eq!(
+        parse_retry_after("120", now),
+        Some(now + Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now),
+        Some(UNIX_EPOCH + Duration::from_secs(784_111_777))
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+}
+
+#[tokio::test]
+async fn test_sources_are_fetched_through_the_configured_proxy() {
+    let request = Arc::new(Mutex::new(String::new()));
+    let recorded = request.clone();
+    let server = FixtureServer::start(Fixture::custom(move |request| {
+        *recorded.lock().unwrap() = request.to_string();
+        ok_response("192.0.2.1\n", 10)
+    }))
+    .await;
+    let proxy = server.address;
+    let environment = Environment::from_iter([
+        ("http_proxy", proxy.as_str()),
+        ("NFTBLOCKD_ALLOW_HTTP", "true"),
+    ]);
+    let policy = FetchPolicy::from_env(&environment, "IPV4").unwrap();
+    let source = "http://blocklist.invalid/ipv4";
+
+    let body = policy
+        .apply(reqwest::Client::builder(), [source])
+        .build()
+        .unwrap()
+        .get(source)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert_eq!(body, "192.0.2.1\n");
+    assert!(
+        request
+            .lock()
+            .unwrap()
+            .starts_with("GET http://blocklist.invalid/ipv4 ")
+    );
+    assert_eq!(policy.proxies.urls, [proxy]);
+}
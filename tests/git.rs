@@ -0,0 +1,93 @@
+mod common;
+
+use nftblockd::set::fetch_policy::FetchPolicy;
+use nftblockd::set::git::GitSource;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// Runs `git` with `args` in `dir` and returns its trimmed output.
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=nftblockd",
+            "-c",
+            "user.email=nftblockd@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Commits `list` as `lists/ipv4.txt` of the repository in `dir` and returns the commit hash.
+fn commit(dir: &Path, list: &str) -> String {
+    std::fs::create_dir_all(dir.join("lists")).unwrap();
+    std::fs::write(dir.join("lists/ipv4.txt"), list).unwrap();
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "-m", "update the blocklist"]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+#[test]
+fn test_git_source_is_parsed() {
+    let source =
+        GitSource::parse("git+ssh://git@git.example.com/lists.git#lists/ipv4.txt").unwrap();
+    assert_eq!(source.remote, "ssh://git@git.example.com/lists.git");
+    assert_eq!(source.path, Path::new("lists/ipv4.txt"));
+    assert_eq!(source.scheme(), "ssh");
+
+    assert!(GitSource::parse("git+https://git.example.com/lists.git").is_err());
+    assert!(GitSource::parse("git+https://git.example.com/lists.git#../etc/passwd").is_err());
+    assert!(GitSource::parse("git+https://git.example.com/lists.git#/etc/passwd").is_err());
+
+    let policy = FetchPolicy::default();
+    assert!(
+        policy
+            .check_source("git+https://git.example.com/lists.git#ipv4.txt")
+            .is_ok()
+    );
+    assert!(
+        policy
+            .check_source("git+http://git.example.com/lists.git#ipv4.txt")
+            .is_err()
+    );
+    assert!(
+        policy
+            .check_source("git+ftp://git.example.com/lists.git#ipv4.txt")
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_git_source_is_cloned_and_pulled() {
+    let root = common::temp_path("git");
+    let _ = std::fs::remove_dir_all(&root);
+    let repository = root.join("repository");
+    std::fs::create_dir_all(&repository).unwrap();
+    git(&repository, &["init", "-q"]);
+    let first = commit(&repository, "192.0.2.1\n");
+
+    let source = GitSource::parse(&format!(
+        "git+file://{}#lists/ipv4.txt",
+        repository.display()
+    ))
+    .unwrap();
+    let checkouts = root.join("checkouts");
+    let timeout = Duration::from_secs(30);
+
+    let (body, revision) = source.fetch(&checkouts, timeout).await.unwrap();
+    assert_eq!((body.as_str(), revision), ("192.0.2.1\n", first));
+
+    let second = commit(&repository, "192.0.2.1\n198.51.100.0/24\n");
+    let (body, revision) = source.fetch(&checkouts, timeout).await.unwrap();
+    assert_eq!(
+        (body.as_str(), revision),
+        ("192.0.2.1\n198.51.100.0/24\n", second)
+    );
+
+    let _ = std::fs::remove_dir_all(root);
+}
@@ -0,0 +1,38 @@
+use nftblockd::grpc::ctl::nftblockd::StatusSummary;
+use nftblockd::utils::status::NftblockdStatus;
+
+#[test]
+fn test_recent_apply_is_healthy() {
+    let mut summary = StatusSummary::new_ok("");
+    summary.last_applied = 1_000;
+
+    assert!(summary.check_health(1_060, 90).is_ok());
+}
+
+#[test]
+fn test_stale_or_missing_apply_is_unhealthy() {
+    let mut summary = StatusSummary::new_ok("");
+
+    assert!(summary.check_health(1_060, 90).is_err());
+    summary.last_applied = 900;
+    assert!(summary.check_health(1_060, 90).is_err());
+}
+
+#[test]
+fn test_failed_daemon_is_unhealthy() {
+    let mut summary = StatusSummary::new_failed("retries exhausted");
+    summary.last_applied = 1_000;
+
+    let actual = summary.check_health(1_000, 90).unwrap_err();
+
+    assert!(actual.contains("retries exhausted"));
+}
+
+#[test]
+fn test_paused_daemon_is_healthy() {
+    let mut summary = StatusSummary::from(NftblockdStatus::Paused);
+    summary.last_applied = 100;
+
+    assert_eq!(summary.status, "paused");
+    assert!(summary.check_health(1_060, 90).is_ok());
+}
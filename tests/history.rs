@@ -0,0 +1,124 @@
+#![cfg(feature = "history")]
+
+mod common;
+
+use nftblockd::history::History;
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::set::manual::ManualSet;
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+fn database(name: &str) -> PathBuf {
+    let path = common::temp_path(&format!("{name}.db"));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn elements<'a>(data: &str) -> SetElements<'a> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+        .unwrap()
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_lookup_finds_containing_elements() {
+    let path = database("lookup");
+    let history = History::open(&path).unwrap();
+    history
+        .record(
+            "https://feed/a",
+            &elements("10.0.0.0/8 192.0.2.1-192.0.2.9"),
+            100,
+        )
+        .unwrap();
+
+    let actual = history.lookup(ip("10.20.30.40")).unwrap();
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].prefix, "10.0.0.0/8");
+    assert_eq!(actual[0].source, "https://feed/a");
+
+    assert_eq!(
+        history.lookup(ip("192.0.2.5")).unwrap()[0].prefix,
+        "192.0.2.1-192.0.2.9"
+    );
+    assert!(history.lookup(ip("192.0.2.10")).unwrap().is_empty());
+    assert!(history.lookup(ip("::ffff:10.0.0.1")).unwrap().is_empty());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_record_keeps_first_seen() {
+    let path = database("first-seen");
+    let history = History::open(&path).unwrap();
+    history
+        .record("https://feed/a", &elements("10.0.0.0/8"), 100)
+        .unwrap();
+    history
+        .record("https://feed/a", &elements("10.0.0.0/8"), 200)
+        .unwrap();
+    history
+        .record("https://feed/b", &elements("10.0.0.0/8"), 300)
+        .unwrap();
+
+    let actual = History::open_read_only(&path)
+        .unwrap()
+        .lookup(ip("10.0.0.1"))
+        .unwrap();
+
+    assert_eq!(actual.len(), 2, "Each source should be tracked separately.");
+    assert_eq!((actual[0].first_seen, actual[0].last_seen), (100, 200));
+    assert_eq!((actual[1].first_seen, actual[1].last_seen), (300, 300));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_repeat_offenders_are_escalated_to_the_manual_set() {
+    let path = database("escalation");
+    let manual = common::temp_path("escalation.json");
+    let _ = std::fs::remove_file(&manual);
+    let history = History::open(&path)
+        .unwrap()
+        .with_escalation(Some(3), manual.clone());
+    // 192.0.2.1 disappears from feed a and comes back, and feed b lists it too.
+    history
+        .record(
+            "https://feed/a",
+            &elements("192.0.2.1 198.51.100.0/24"),
+            100,
+        )
+        .unwrap();
+    history
+        .record("https://feed/a", &elements("198.51.100.0/24"), 200)
+        .unwrap();
+    history
+        .record(
+            "https://feed/a",
+            &elements("192.0.2.1 198.51.100.0/24"),
+            300,
+        )
+        .unwrap();
+    assert!(history.escalate().unwrap().is_empty());
+
+    history
+        .record("https://feed/b", &elements("192.0.2.1"), 400)
+        .unwrap();
+
+    assert_eq!(history.escalate().unwrap(), ["192.0.2.1"]);
+    assert!(history.escalate().unwrap().is_empty());
+    let entries = ManualSet::load(&manual).unwrap().entries().to_vec();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].network, "192.0.2.1/32");
+    assert_eq!(entries[0].expires, None);
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(manual);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use nftblockd::set::http_cache::{HttpCache, freshness};
+use reqwest::header::{AGE, CACHE_CONTROL, DATE, EXPIRES, HeaderMap, HeaderValue};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn headers(pairs: &[(reqwest::header::HeaderName, &'static str)]) -> HeaderMap {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+        .collect()
+}
+
+#[test]
+fn test_freshness_follows_the_caching_headers() {
+    let now = SystemTime::now();
+    assert_eq!(
+        freshness(&headers(&[(CACHE_CONTROL, "public, max-age=3600")]), now),
+        Some(Duration::from_secs(3600))
+    );
+    assert_eq!(
+        freshness(
+            &headers(&[(CACHE_CONTROL, "max-age=3600"), (AGE, "600")]),
+            now
+        ),
+        Some(Duration::from_secs(3000))
+    );
+    // `Expires` is relative to the `Date` of the response, whatever the local clock.
+    assert_eq!(
+        freshness(
+            &headers(&[
+                (DATE, "Thu, 01 Jan 2026 00:00:00 GMT"),
+                (EXPIRES, "Thu, 01 Jan 2026 02:00:00 GMT")
+            ]),
+            now
+        ),
+        Some(Duration::from_secs(7200))
+    );
+    assert_eq!(
+        freshness(
+            &headers(&[
+                (CACHE_CONTROL, "max-age=60"),
+                (EXPIRES, "Thu, 01 Jan 2026 02:00:00 GMT")
+            ]),
+            now
+        ),
+        Some(Duration::from_secs(60))
+    );
+    assert_eq!(
+        freshness(&headers(&[(CACHE_CONTROL, "no-cache, max-age=3600")]), now),
+        None
+    );
+    assert_eq!(
+        freshness(
+            &headers(&[(CACHE_CONTROL, "max-age=60"), (AGE, "120")]),
+            now
+        ),
+        None
+    );
+    assert_eq!(freshness(&headers(&[(EXPIRES, "0")]), now), None);
+    assert_eq!(freshness(&HeaderMap::new(), now), None);
+}
+
+#[test]
+fn test_fresh_responses_are_reused_across_restarts() {
+    let path = common::temp_path("http-cache.json");
+    let _ = std::fs::remove_file(&path);
+    let url = "https://example.com/ipv4";
+    let token = HashMap::from([("Authorization".to_string(), "Bearer secret".to_string())]);
+    let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+
+    let cache = HttpCache::new(Some(path.clone()));
+    assert_eq!(
+        cache.store(
+            url,
+            Some(&token),
+            &headers(&[(CACHE_CONTROL, "max-age=3600")]),
+            "192.0.2.0/24",
+            Some("\"v1\""),
+            now,
+        ),
+        Some(Duration::from_secs(3600))
+    );
+    assert_eq!(
+        cache.store(
+            "https://example.com/ipv6",
+            None,
+            &headers(&[(CACHE_CONTROL, "no-store")]),
+            "2001:db8::/32",
+            None,
+            now,
+        ),
+        None
+    );
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("secret"));
+
+    let cache = HttpCache::new(Some(path.clone()));
+    let later = now + Duration::from_secs(1800);
+    assert_eq!(
+        cache.fresh(url, Some(&token), later),
+        Some(("192.0.2.0/24".to_string(), Some("\"v1\"".to_string())))
+    );
+    assert_eq!(cache.fresh(url, None, later), None);
+    assert_eq!(cache.fresh("https://example.com/ipv6", None, later), None);
+    assert_eq!(
+        cache.fresh(url, Some(&token), now + Duration::from_secs(3600)),
+        None
+    );
+    let _ = std::fs::remove_file(&path);
+}
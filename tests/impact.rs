@@ -0,0 +1,44 @@
+use nftblockd::set::impact::{affected_flows, parse_conntrack, parse_flow_csv};
+use nftblockd::set::simulation::{Outcome, Simulation};
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+
+#[test]
+fn test_active_flows_to_newly_blocked_peers_are_listed() {
+    let conntrack = "\
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.0.2.10 dst=203.0.113.7 sport=51234 dport=443 src=203.0.113.7 dst=192.0.2.10 sport=443 dport=51234 [ASSURED] mark=0 use=1
+ipv4     2 udp      17 29 src=192.0.2.10 dst=198.51.100.53 sport=40000 dport=53 [UNREPLIED] src=198.51.100.53 dst=192.0.2.10 sport=53 dport=40000 mark=0 use=1
+";
+    let flows = parse_conntrack(conntrack);
+    assert_eq!(flows.len(), 2);
+    assert_eq!(
+        flows[0].to_string(),
+        "tcp 192.0.2.10 -> 203.0.113.7 ESTABLISHED"
+    );
+    let csv =
+        parse_flow_csv("ts,sa,da,pr\n2026-10-16,203.0.113.9,192.0.2.10,TCP\nSummary\n").unwrap();
+    assert_eq!(csv.len(), 1);
+    assert!(parse_flow_csv("ts,bytes\n").is_err());
+
+    let elements = SubnetList::IPv4(parse_from_string(Some("203.0.113.0/24"), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements();
+    let simulation =
+        Simulation::new().with_set("blocklist_set_ipv4".to_string(), true, elements.as_ref());
+    let flows = flows.into_iter().chain(csv).collect::<Vec<_>>();
+
+    let affected = affected_flows(&flows, &simulation);
+
+    assert_eq!(affected.len(), 2);
+    assert_eq!(affected[0].0, &flows[0]);
+    assert_eq!(
+        affected[1].1,
+        Outcome::Dropped {
+            set: "blocklist_set_ipv4".to_string(),
+            element: "203.0.113.0/24".to_string(),
+        }
+    );
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::settings::{Environment, Settings};
+use nftblockd::utils::instance::{Profile, socket_path, state_dir, validate_instance};
+use std::time::Duration;
+
+#[test]
+fn test_instance_names_are_validated() {
+    assert!(validate_instance("tenant-a_1").is_ok());
+    assert!(validate_instance("").is_err());
+    assert!(validate_instance("../etc").is_err());
+    assert!(validate_instance(&"a".repeat(33)).is_err());
+}
+
+#[test]
+fn test_instance_namespaces_table_and_paths() {
+    let config = NftConfig::default();
+    let table_name = config.table_name.clone();
+
+    let actual = config.with_instance(Some("tenant"));
+
+    assert_eq!(actual.table_name, format!("{table_name}_tenant"));
+    assert_eq!(socket_path(Some("tenant")), "/run/nftblockd-tenant.sock");
+    assert_eq!(socket_path(None), "/run/nftblockd.sock");
+    assert!(state_dir(Some("tenant")).ends_with("nftblockd/tenant"));
+}
+
+#[test]
+fn test_profiles_are_parsed_with_their_env_files() {
+    let profile =
+        Profile::parse("tenant-a=/etc/nftblockd/a.env, /etc/nftblockd/strict.env").unwrap();
+
+    assert_eq!(profile.name, "tenant-a");
+    assert_eq!(
+        profile.env_files,
+        ["/etc/nftblockd/a.env", "/etc/nftblockd/strict.env"]
+    );
+    assert!(Profile::parse("tenant-a").is_err());
+    assert!(Profile::parse("tenant-a=").is_err());
+    assert!(Profile::parse("../a=/etc/nftblockd/a.env").is_err());
+}
+
+#[test]
+fn test_profiles_are_configured_from_their_own_variables() {
+    let write = |name: &str, contents: &str| {
+        let path = common::temp_path(&format!("instance-{name}.env"));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+    let main = vec![write(
+        "main",
+        "NFTBLOCKD_RETRY_COUNT=3\nNFTBLOCKD_TABLE_NAME=main\n",
+    )];
+    let a = Profile::parse(&format!("a={}", write("a", "NFTBLOCKD_RETRY_COUNT=5\n"))).unwrap();
+    let b = Profile::parse(&format!("b={}", write("b", "NFTBLOCKD_TABLE_NAME=b\n"))).unwrap();
+
+    let main_environment = Environment::load(&main).unwrap();
+    let a_environment = a.environment(&main).unwrap();
+    let b_environment = b.environment(&main).unwrap();
+    let retry_count = |environment: &Environment| {
+        Settings::from_env(environment, None, Duration::from_secs(30))
+            .unwrap()
+            .retry_count
+    };
+
+    assert_eq!(retry_count(&main_environment), 3);
+    assert_eq!(retry_count(&a_environment), 5);
+    assert_eq!(retry_count(&b_environment), 3);
+    assert_eq!(
+        main_environment.var("NFTBLOCKD_TABLE_NAME").unwrap(),
+        "main"
+    );
+    assert_eq!(a_environment.var("NFTBLOCKD_TABLE_NAME").unwrap(), "main");
+    assert_eq!(b_environment.var("NFTBLOCKD_TABLE_NAME").unwrap(), "b");
+    assert!(std::env::var("NFTBLOCKD_RETRY_COUNT").is_err());
+    for file in main.iter().chain(&a.env_files).chain(&b.env_files) {
+        let _ = std::fs::remove_file(file);
+    }
+}
@@ -457,3 +457,31 @@ fn test_deduplicate_ipv4_mixed_overlap_and_disjoint() {
         "Only the broadest covering subnets should remain after deduplication."
     );
 }
+
+#[test]
+fn test_deduplicate_ipv6_diverging_long_prefixes() {
+    let subnets = vec![
+        "2001:db8:aaaa:bbbb::1/128",
+        "2001:db8:aaaa:bbbb::2/128",
+        "2001:db8:aaaa:cccc::/64",
+        "2001:db8:aaaa:bbbb::/96",
+        "2001:db8:aaaa:cccc::5/128",
+        "2001:db8:aaaa:bbbb::3/128",
+    ];
+
+    let deduped: Vec<Ipv6Network> = deduplicate(parse_subnets(subnets))
+        .unwrap()
+        .iter()
+        .map(|n| n.inner())
+        .collect();
+
+    let expected = vec![
+        Ipv6Network::from_str("2001:db8:aaaa:cccc::/64").unwrap(),
+        Ipv6Network::from_str("2001:db8:aaaa:bbbb::/96").unwrap(),
+    ];
+
+    assert_eq!(
+        deduped, expected,
+        "Long prefixes sharing compressed edges should be absorbed by their supernets."
+    );
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use nftblockd::utils::kernel::detect_ipv6_unsupported;
+use std::fs;
+
+#[test]
+fn test_missing_or_disabled_ipv6_is_detected() {
+    let proc = common::temp_path("proc");
+    let sysctl = proc.join("sys/net/ipv6/conf/all");
+    fs::create_dir_all(proc.join("net")).unwrap();
+    fs::create_dir_all(&sysctl).unwrap();
+
+    assert!(detect_ipv6_unsupported(&proc).is_some_and(|reason| reason.contains("without IPv6")));
+
+    fs::write(proc.join("net/if_inet6"), "").unwrap();
+    fs::write(sysctl.join("disable_ipv6"), "0\n").unwrap();
+    assert_eq!(detect_ipv6_unsupported(&proc), None);
+
+    fs::write(sysctl.join("disable_ipv6"), "1\n").unwrap();
+    assert!(detect_ipv6_unsupported(&proc).is_some_and(|reason| reason.contains("disable_ipv6")));
+
+    fs::remove_dir_all(&proc).unwrap();
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use nftblockd::utils::log_file::{LogRotation, SizeRotatingFile};
+use std::fs;
+use std::io::Write;
+
+#[test]
+fn test_parse_log_rotation() {
+    assert_eq!(LogRotation::parse("daily").unwrap(), LogRotation::Daily);
+    assert_eq!(LogRotation::parse("Hourly").unwrap(), LogRotation::Hourly);
+    assert_eq!(
+        LogRotation::parse("50M").unwrap(),
+        LogRotation::Size(50 << 20)
+    );
+    assert_eq!(LogRotation::parse("4096").unwrap(), LogRotation::Size(4096));
+    assert!(LogRotation::parse("weekly").is_err());
+    assert!(LogRotation::parse("0K").is_err());
+}
+
+#[test]
+fn test_size_rotation_keeps_max_files() {
+    let dir = common::temp_path("log");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("nftblockd.log");
+    let mut file = SizeRotatingFile::open(path.clone(), 16, 2).unwrap();
+
+    for line in [
+        "first line\n",
+        "second line\n",
+        "third line\n",
+        "fourth line\n",
+    ] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+
+    let current = fs::read_to_string(&path).unwrap();
+    let previous = fs::read_to_string(dir.join("nftblockd.log.1")).unwrap();
+    let oldest = fs::read_to_string(dir.join("nftblockd.log.2")).unwrap();
+    let dropped = dir.join("nftblockd.log.3").exists();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(current, "fourth line\n");
+    assert_eq!(previous, "third line\n");
+    assert_eq!(oldest, "second line\n");
+    assert!(!dropped, "Only `max_files` rotated files should be kept.");
+}
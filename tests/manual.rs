@@ -0,0 +1,91 @@
+mod common;
+
+use nftblockd::nftables::applier::MockApplier;
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::set::manual::ManualSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    common::temp_path(&format!("manual-{name}.json"))
+}
+
+#[test]
+fn test_manual_set_survives_reload() {
+    let path = temp_path("reload");
+    let _ = std::fs::remove_file(&path);
+    let mut manual = ManualSet::load(&path).unwrap();
+    assert!(manual.entries().is_empty());
+
+    let entry = manual
+        .add(
+            "203.0.113.7",
+            Some(Duration::from_secs(7200)),
+            Some("ticket-1234".to_string()),
+            SystemTime::now(),
+        )
+        .unwrap();
+    manual
+        .add("2001:db8::/32", None, None, SystemTime::now())
+        .unwrap();
+    manual.save().unwrap();
+
+    assert_eq!(entry.network, "203.0.113.7/32");
+    let mut reloaded = ManualSet::load(&path).unwrap();
+    assert_eq!(reloaded.entries(), manual.entries());
+
+    assert!(reloaded.remove("203.0.113.7/32").unwrap());
+    assert!(!reloaded.remove("203.0.113.7").unwrap());
+    assert_eq!(reloaded.entries().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_manual_set_leaves_out_expired_entries() {
+    let mut manual = ManualSet::load(&temp_path("expired")).unwrap();
+    let added = SystemTime::now() - Duration::from_secs(3600);
+    manual
+        .add(
+            "198.51.100.0/24",
+            Some(Duration::from_secs(60)),
+            None,
+            added,
+        )
+        .unwrap();
+    manual.add("192.0.2.1", None, None, added).unwrap();
+
+    let active = manual.active(SystemTime::now());
+
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].network, "192.0.2.1/32");
+    assert!(manual.add("not an address", None, None, added).is_err());
+}
+
+#[test]
+fn test_apply_manual_replaces_live_manual_sets() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default()
+        .with_manual_set(Some("manual_set".to_string()))
+        .with_applier(applier.clone());
+    let mut manual = ManualSet::load(&temp_path("apply")).unwrap();
+    manual
+        .add(
+            "203.0.113.7",
+            Some(Duration::from_secs(7200)),
+            Some("ticket-1234".to_string()),
+            SystemTime::now(),
+        )
+        .unwrap();
+
+    config
+        .apply_manual(&manual.active(SystemTime::now()))
+        .unwrap();
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 1);
+    assert!(applied[0].contains("\"flush\""));
+    assert!(applied[0].contains("\"manual_set_ipv6\""));
+    assert!(applied[0].contains("\"203.0.113.7\""));
+    assert!(applied[0].contains("ticket-1234"));
+    assert!(applied[0].contains("\"timeout\""));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{Fixture, FixtureServer, ok_response, status};
+use nftblockd::error::{AppError, FailureKind};
+use nftblockd::nftables::applier::MockApplier;
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::set::blocklist::BlockList;
+use nftblockd::settings::Environment;
+use nftblockd::utils::memory::{MemoryLimit, PeakSampler, peak_resident, resident};
+use std::sync::Arc;
+
+#[test]
+fn test_exceeding_the_memory_limit_aborts_the_update() {
+    let limit = MemoryLimit {
+        max_bytes: 64 << 20,
+    };
+
+    limit.check_usage(32 << 20, "parsing a feed").unwrap();
+    let error = limit
+        .check_usage(96 << 20, "parsing https://example.com/ipv4")
+        .unwrap_err();
+    assert!(matches!(error, AppError::MemoryError(_)));
+    assert_eq!(
+        error.to_string(),
+        "memory limit exceeded: 96.0 MiB used while parsing https://example.com/ipv4 exceeds NFTBLOCKD_MEMORY_LIMIT of 64.0 MiB; aborting the update"
+    );
+    assert!(error.is_retryable());
+    assert_eq!(error.kind(), FailureKind::Other);
+
+    let resident = resident().unwrap();
+    assert!(peak_resident().unwrap() >= resident);
+    MemoryLimit {
+        max_bytes: resident * 4,
+    }
+    .check("a test")
+    .unwrap();
+
+    let sampler = PeakSampler::default();
+    assert_eq!(sampler.peak(), None);
+    sampler.reset();
+    let sampled = sampler.sample().unwrap();
+    assert!(sampler.peak().unwrap() >= sampled);
+}
+
+#[tokio::test]
+async fn test_oversized_body_aborts_the_update_while_downloading() {
+    let body = "192.0.2.0/24\n".repeat(320_000);
+    let server =
+        FixtureServer::start(Fixture::custom(move |_| ok_response(&body, body.len()))).await;
+    let mut blocklist =
+        BlockList::new(&Environment::default(), Some(server.url), None, None).unwrap();
+    blocklist.memory_limit = Some(MemoryLimit { max_bytes: 1 << 20 });
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default().with_applier(applier.clone());
+
+    let error = blocklist.update(&config, status()).await.unwrap_err();
+
+    assert!(matches!(error, AppError::MemoryError(_)));
+    assert!(
+        error.to_string().contains("while downloading"),
+        "The download should be aborted before the body is parsed: {error}"
+    );
+    assert!(applier.applied().is_empty());
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use nftblockd::error::AppError;
+use nftblockd::metrics::statsd::StatsdSink;
+use nftblockd::metrics::textfile::TextfileExporter;
+use nftblockd::metrics::{
+    render_metrics, render_source_metrics, render_source_stats, with_instance_label, with_labels,
+};
+use nftblockd::set::observer::{UpdateObserver, UpdateReport};
+use nftblockd::utils::stats::{SourceStats, Stats};
+use nftblockd::utils::status::NftblockdStatus;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_render_metrics_per_set_and_direction() {
+    let mut stats = Stats::default();
+    stats.main_blocklist_drop_stats.prerouting.ipv4.packets = 7;
+    stats.main_blocklist_drop_stats.prerouting.ipv4.bytes = 420;
+    stats.custom_blocklist_drop_stats.postrouting.ipv6.packets = 3;
+
+    let actual = render_metrics(&NftblockdStatus::Pending, &stats);
+
+    assert!(actual.contains("nftblockd_status 1\n"));
+    assert!(actual.contains("# TYPE nftblockd_dropped_packets_total counter\n"));
+    assert!(actual.contains(
+        "nftblockd_dropped_packets_total{set=\"blocklist\",chain=\"prerouting\",family=\"ipv4\"} 7\n"
+    ));
+    assert!(actual.contains(
+        "nftblockd_dropped_bytes_total{set=\"blocklist\",chain=\"prerouting\",family=\"ipv4\"} 420\n"
+    ));
+    assert!(actual.contains(
+        "nftblockd_dropped_packets_total{set=\"custom_blocklist\",chain=\"postrouting\",family=\"ipv6\"} 3\n"
+    ));
+}
+
+#[test]
+fn test_textfile_is_written_after_each_cycle() {
+    let path = common::temp_path("metrics.prom");
+    let exporter = TextfileExporter::new(&path);
+
+    exporter.on_applied(&UpdateReport {
+        ipv4_elements: 12,
+        ipv6_elements: 3,
+        ..UpdateReport::default()
+    });
+    exporter.on_error(&AppError::RequestError("timeout".to_string(), None));
+
+    let actual = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(actual.contains("nftblockd_last_success_timestamp_seconds "));
+    assert!(actual.contains("nftblockd_elements{family=\"ipv4\"} 12\n"));
+    assert!(actual.contains("nftblockd_elements{family=\"ipv6\"} 3\n"));
+    assert!(actual.contains("nftblockd_update_failures_total 1\n"));
+}
+
+#[test]
+fn test_stopped_updates_are_counted_apart_from_exhausted_retries() {
+    let path = common::temp_path("stopped.prom");
+    let exporter = TextfileExporter::new(&path);
+
+    exporter.on_updates_stopped(&AppError::TableNotFound("blocklist".to_string()));
+
+    let actual = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(actual.contains("nftblockd_updates_stopped_total 1\n"));
+    assert!(actual.contains("nftblockd_retries_exhausted_total 0\n"));
+}
+
+#[test]
+fn test_statsd_sink_sends_timings_and_counts() {
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    agent
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+    let sink = StatsdSink::new(
+        &agent.local_addr().unwrap().to_string(),
+        "nftblockd",
+        Some("env:test".to_string()),
+    )
+    .unwrap();
+
+    sink.on_applied(&UpdateReport {
+        ipv4_elements: 12,
+        apply_duration: std::time::Duration::from_millis(30),
+        ..UpdateReport::default()
+    });
+
+    let mut buffer = [0u8; 1024];
+    let len = agent.recv(&mut buffer).unwrap();
+    let actual = String::from_utf8_lossy(&buffer[..len]);
+    assert!(actual.contains("nftblockd.apply_time:30|ms|#env:test\n"));
+    assert!(actual.contains("nftblockd.elements.ipv4:12|g|#env:test\n"));
+}
+
+#[test]
+fn test_instance_label_is_added_to_every_sample() {
+    let metrics = "# TYPE nftblockd_status gauge\nnftblockd_status 0\nnftblockd_elements{family=\"ipv4\"} 3\n";
+
+    let actual = with_instance_label(metrics, Some("tenant"));
+
+    assert_eq!(
+        actual,
+        "# TYPE nftblockd_status gauge\n\
+         nftblockd_status{nftblockd_instance=\"tenant\"} 0\n\
+         nftblockd_elements{nftblockd_instance=\"tenant\",family=\"ipv4\"} 3\n"
+    );
+    assert_eq!(with_instance_label(metrics, None), metrics);
+}
+
+#[test]
+fn test_source_data_age_is_rendered() {
+    let now = SystemTime::now();
+    let refreshed = BTreeMap::from([(
+        "https://example.com/ipv4".to_string(),
+        now - Duration::from_secs(90),
+    )]);
+
+    let actual = render_source_metrics(&refreshed, now);
+
+    assert!(
+        actual.contains(
+            "nftblockd_source_data_age_seconds{source=\"https://example.com/ipv4\"} 90\n"
+        )
+    );
+}
+
+#[test]
+fn test_profile_and_source_labels_are_rendered() {
+    let sources = BTreeMap::from([(
+        "https://example.com/ipv4".to_string(),
+        SourceStats {
+            ipv4_elements: 12,
+            ipv6_elements: 0,
+            failures: 2,
+            unrouted: 3,
+        },
+    )]);
+
+    let actual = with_labels(
+        &render_source_stats(&sources),
+        Some("shared"),
+        Some("tenant"),
+    );
+
+    assert!(actual.contains(
+        "nftblockd_source_elements{nftblockd_instance=\"shared\",nftblockd_profile=\"tenant\",source=\"https://example.com/ipv4\",family=\"ipv4\"} 12\n"
+    ));
+    assert!(actual.contains(
+        "nftblockd_source_failures_total{nftblockd_instance=\"shared\",nftblockd_profile=\"tenant\",source=\"https://example.com/ipv4\"} 2\n"
+    ));
+    assert!(actual.contains(
+        "nftblockd_source_unrouted{nftblockd_instance=\"shared\",nftblockd_profile=\"tenant\",source=\"https://example.com/ipv4\"} 3\n"
+    ));
+    assert!(
+        with_labels("nftblockd_status 0\n", None, Some("tenant"))
+            .contains("nftblockd_status{nftblockd_profile=\"tenant\"} 0")
+    );
+}
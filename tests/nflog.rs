@@ -0,0 +1,61 @@
+use nftblockd::nflog::{NflogPacket, OffenderStats, parse_packet_attributes};
+
+fn attribute(kind: u16, data: &[u8]) -> Vec<u8> {
+    let len = u16::try_from(4 + data.len()).unwrap();
+    let mut out = [len.to_ne_bytes(), kind.to_ne_bytes()].concat();
+    out.extend(data);
+    out.resize(out.len().div_ceil(4) * 4, 0);
+    out
+}
+
+fn packet(src: &str, set_name: &str) -> NflogPacket {
+    NflogPacket {
+        chain: "prerouting".to_string(),
+        set_name: set_name.to_string(),
+        src: src.parse().unwrap(),
+        dst: "192.0.2.1".parse().unwrap(),
+        dst_port: Some(22),
+    }
+}
+
+#[test]
+fn test_parse_tcp_packet() {
+    // IPv4 header from 198.51.100.7 to 192.0.2.1, followed by a TCP header with destination port 22.
+    let mut payload = vec![0x45, 0, 0, 40, 0, 0, 0, 0, 64, 6, 0, 0];
+    payload.extend([198, 51, 100, 7, 192, 0, 2, 1]);
+    payload.extend([0xc3, 0x50, 0, 22]);
+    let attributes = [
+        attribute(
+            10,
+            b"blocklist_table;prerouting;blocklist_set_ipv4;dropped: \0",
+        ),
+        attribute(9, &payload),
+    ]
+    .concat();
+
+    let actual = parse_packet_attributes(&attributes).unwrap();
+
+    assert_eq!(actual, packet("198.51.100.7", "blocklist_set_ipv4"));
+}
+
+#[test]
+fn test_packet_without_prefix_is_ignored() {
+    let payload = [0x45u8; 20];
+
+    assert!(parse_packet_attributes(&attribute(9, &payload)).is_none());
+}
+
+#[test]
+fn test_top_offenders_are_sorted_by_packets() {
+    let mut stats = OffenderStats::default();
+    stats.record(packet("198.51.100.7", "blocklist_set_ipv4"));
+    stats.record(packet("203.0.113.9", "custom_blocklist_set_ipv4"));
+    stats.record(packet("203.0.113.9", "custom_blocklist_set_ipv4"));
+
+    let actual = stats.top(1);
+
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].source, "203.0.113.9");
+    assert_eq!(actual[0].packets, 2);
+    assert_eq!(stats.per_set["blocklist_set_ipv4"], 1);
+}
@@ -0,0 +1,39 @@
+use nftblockd::set::overrides::{Overrides, merge_entries};
+
+#[test]
+fn test_overrides_parse_sections() {
+    let overrides = Overrides::parse(
+        "# pinned by the NOC\n\
+         block:\n\
+         \x20 - 203.0.113.7\n\
+         \x20 - 2001:db8::/32\n\
+         \n\
+         never-block:\n\
+         \x20 192.0.2.0/24  # monitoring\n",
+    )
+    .unwrap();
+
+    assert_eq!(overrides.block(false), vec!["203.0.113.7"]);
+    assert_eq!(overrides.block(true), vec!["2001:db8::/32"]);
+    assert_eq!(overrides.never_block(false), vec!["192.0.2.0/24"]);
+    assert!(overrides.never_block(true).is_empty());
+}
+
+#[test]
+fn test_overrides_reject_entries_outside_sections() {
+    let error = Overrides::parse("203.0.113.7\nblock:\n").unwrap_err();
+
+    assert!(error.to_string().contains("line 1"));
+}
+
+#[test]
+fn test_merge_entries_keeps_configured_entries() {
+    assert_eq!(merge_entries(None, Vec::new()), None);
+    assert_eq!(
+        merge_entries(
+            Some(vec!["10.0.0.0/8".to_string()]),
+            vec!["192.0.2.1".to_string()]
+        ),
+        Some(vec!["10.0.0.0/8".to_string(), "192.0.2.1".to_string()])
+    );
+}
@@ -0,0 +1,78 @@
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::set::policy::{ElementPolicy, Violation};
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+
+fn elements<'a>(data: &str, ipv6: bool) -> Option<SetElements<'a>> {
+    let list = parse_from_string(Some(data), None).unwrap();
+    let list = if ipv6 {
+        SubnetList::IPv6(list)
+    } else {
+        SubnetList::IPv4(list)
+    };
+    list.validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+const POLICY: &str = "
+# never block the monitoring network
+never-block: 192.0.2.0/24
+never-block: 2001:db8::/32
+max-elements: 3
+min-prefix-ipv4: 10
+min-prefix-ipv6: 32
+";
+
+#[test]
+fn test_policy_is_parsed() {
+    let policy = ElementPolicy::parse(POLICY).unwrap();
+    assert_eq!(policy.never_block.len(), 2);
+    assert_eq!(policy.max_elements, Some(3));
+    assert_eq!(policy.min_prefix_ipv4, Some(10));
+    assert_eq!(policy.min_prefix_ipv6, Some(32));
+
+    let error = ElementPolicy::parse("max-size: 3").unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("line 1: max-size: 3: unknown rule")
+    );
+    assert!(ElementPolicy::parse("min-prefix-ipv4: 33").is_err());
+}
+
+#[test]
+fn test_every_violation_is_reported() {
+    let policy = ElementPolicy::parse(POLICY).unwrap();
+    let ipv4 = elements("10.0.0.0/8 192.0.2.128/25 198.51.100.0/24", false);
+    let ipv6 = elements("2001:db8:1::/48", true);
+
+    assert_eq!(
+        policy.check(&ipv4, &ipv6),
+        [
+            Violation::MaxElements {
+                elements: 4,
+                max: 3
+            },
+            Violation::MinPrefix {
+                element: "10.0.0.0/8".to_string(),
+                min: 10
+            },
+            Violation::NeverBlock {
+                element: "192.0.2.128/25".to_string(),
+                network: "192.0.2.0/24".parse().unwrap()
+            },
+            Violation::NeverBlock {
+                element: "2001:db8:1::/48".to_string(),
+                network: "2001:db8::/32".parse().unwrap()
+            },
+        ]
+    );
+    let error = policy.enforce(&ipv4, &ipv6).unwrap_err().to_string();
+    assert!(error.contains("violates the policy 4 times"));
+
+    let compliant = elements("198.51.100.0/24 203.0.113.7", false);
+    assert!(policy.check(&compliant, &None).is_empty());
+}
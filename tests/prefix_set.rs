@@ -0,0 +1,58 @@
+use nftblockd::nftables::element_label;
+use nftblockd::utils::prefix_set::{Prefix, PrefixElement, PrefixSet};
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::time::Duration;
+
+#[test]
+fn test_pipeline_produces_typed_prefixes() {
+    let set = SubnetList::IPv4(
+        parse_from_string(
+            Some("10.0.0.0/8 10.1.0.0/16 192.0.2.7 192.0.2.10-192.0.2.20"),
+            None,
+        )
+        .unwrap(),
+    )
+    .validate_blocklist(true)
+    .unwrap()
+    .deduplicate()
+    .unwrap()
+    .into_prefix_set()
+    .unwrap();
+
+    assert_eq!(set.len(), 3);
+    assert!(set.contains(&"10.0.0.0/8".parse().unwrap()));
+    assert!(set.contains(&"192.0.2.7/32".parse().unwrap()));
+    assert!(set.contains(&"192.0.2.10-192.0.2.20".parse().unwrap()));
+    assert!(!set.contains(&"10.1.0.0/16".parse().unwrap()));
+    let labels = set
+        .iter()
+        .map(|element| element.prefix.to_string())
+        .collect::<Vec<_>>();
+    assert!(labels.contains(&"192.0.2.7".to_string()));
+}
+
+#[test]
+fn test_elements_round_trip_through_expressions() {
+    let mut set = ["2001:db8::/32", "192.0.2.1-192.0.2.9"]
+        .into_iter()
+        .map(|prefix| prefix.parse::<Prefix>().unwrap())
+        .collect::<PrefixSet>()
+        .with_comment("source=example.com");
+    set.push(PrefixElement {
+        prefix: "198.51.100.0/24".parse().unwrap(),
+        timeout: Some(Duration::from_secs(3600)),
+        comment: None,
+    });
+
+    let elements = set.clone().into_elements();
+    assert_eq!(
+        elements
+            .iter()
+            .filter_map(element_label)
+            .collect::<Vec<_>>(),
+        ["2001:db8::/32", "192.0.2.1-192.0.2.9", "198.51.100.0/24"]
+    );
+    assert_eq!(PrefixSet::from_elements(&elements), set);
+    assert!("192.0.2.9-192.0.2.1".parse::<Prefix>().is_err());
+    assert!("192.0.2.1-2001:db8::1".parse::<Prefix>().is_err());
+}
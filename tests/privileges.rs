@@ -0,0 +1,15 @@
+use nftblockd::nftables::applier::MockApplier;
+use nftblockd::utils::privileges::check_privileges;
+
+#[test]
+fn test_check_privileges_reports_every_probe() {
+    let socket = std::env::temp_dir().join("nftblockd-check.sock");
+
+    let actual = check_privileges(&socket.to_string_lossy(), &MockApplier::new());
+
+    assert_eq!(actual.len(), 3);
+    assert_eq!(actual[0].name, "CAP_NET_ADMIN");
+    assert!(actual[1].ok, "The temporary directory should be writable.");
+    assert!(actual[2].ok, "The mock backend always lists a ruleset.");
+    assert!(actual[2].to_string().starts_with("[ok]"));
+}
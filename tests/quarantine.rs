@@ -0,0 +1,94 @@
+use nftblockd::nflog::NflogPacket;
+use nftblockd::nftables::applier::MockApplier;
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::set::quarantine::Quarantine;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+fn packet(chain: &str, set_name: &str) -> NflogPacket {
+    NflogPacket {
+        chain: chain.to_string(),
+        set_name: set_name.to_string(),
+        src: "198.51.100.7".parse().unwrap(),
+        dst: "192.0.2.1".parse().unwrap(),
+        dst_port: Some(22),
+    }
+}
+
+#[test]
+fn test_address_is_promoted_after_threshold_hits() {
+    let mut quarantine = Quarantine::new(3, Duration::from_secs(60), Duration::from_secs(600));
+    let addr: IpAddr = "198.51.100.7".parse().unwrap();
+    let now = Instant::now();
+
+    assert!(!quarantine.record(addr, now));
+    assert!(!quarantine.record(addr, now));
+    assert!(quarantine.record(addr, now));
+    assert!(
+        !quarantine.record(addr, now),
+        "A quarantined address should not be promoted again."
+    );
+
+    let active = quarantine.active(SystemTime::now());
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].0, addr);
+    assert!(active[0].1 <= Duration::from_secs(600));
+    assert!(
+        quarantine
+            .active(SystemTime::now() + Duration::from_secs(601))
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_hits_outside_the_window_start_over() {
+    let mut quarantine = Quarantine::new(2, Duration::from_secs(10), Duration::from_secs(600));
+    let addr: IpAddr = "2001:db8::1".parse().unwrap();
+    let now = Instant::now();
+
+    assert!(!quarantine.record(addr, now));
+    assert!(!quarantine.record(addr, now + Duration::from_secs(11)));
+    assert!(quarantine.record(addr, now + Duration::from_secs(12)));
+}
+
+#[test]
+fn test_monitored_address_follows_the_chain() {
+    let config = NftConfig::default();
+
+    assert_eq!(
+        config.monitored_address(&packet("prerouting", "monitor_set_ipv4")),
+        Some("198.51.100.7".parse().unwrap())
+    );
+    assert_eq!(
+        config.monitored_address(&packet("postrouting", "monitor_set_ipv4")),
+        Some("192.0.2.1".parse().unwrap())
+    );
+    assert_eq!(
+        config.monitored_address(&packet("prerouting", "blocklist_set_ipv4")),
+        None
+    );
+}
+
+#[test]
+fn test_quarantined_addresses_expire_in_the_kernel() {
+    let applier = Arc::new(MockApplier::new());
+    let config = NftConfig::default()
+        .with_applier(applier.clone())
+        .with_quarantine_set(Some("quarantine_set".to_string()));
+
+    let ruleset = serde_json::to_string(&config.generate_ruleset(&None, &None)).unwrap();
+    assert!(ruleset.contains("\"quarantine_set_ipv4\""));
+    assert!(ruleset.contains("\"timeout\""));
+
+    config
+        .apply_quarantine(&[("198.51.100.7".parse().unwrap(), Duration::from_secs(600))])
+        .unwrap();
+
+    let applied = applier.applied();
+    assert_eq!(applied.len(), 1);
+    assert!(applied[0].contains("\"quarantine_set_ipv4\""));
+    assert!(applied[0].contains("\"198.51.100.7\""));
+    assert!(applied[0].contains("\"timeout\": 600"));
+    assert!(!applied[0].contains("\"quarantine_set_ipv6\""));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use nftables::schema::Nftables;
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::nftables::reconcile::{AppliedRuleset, detect_drift};
+use nftblockd::nftables::serialize_ruleset;
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
+
+fn ipv4_elements<'a>(data: &str) -> Option<SetElements<'a>> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+#[test]
+fn test_drift_is_reported_in_both_directions() {
+    let config = NftConfig::default();
+    let live_elements = ipv4_elements("10.0.0.0/8 192.0.2.1");
+    let desired_elements = ipv4_elements("10.0.0.0/8 203.0.113.0/24");
+    let live = config.generate_ruleset(&live_elements, &None);
+    let desired = config.generate_ruleset(&desired_elements, &None);
+
+    let drift = detect_drift(&config.table_name, &live, &desired, &[]);
+
+    assert!(!drift.table_missing);
+    assert!(drift.missing_chains.is_empty() && drift.rule_counts.is_empty());
+    assert_eq!(drift.sets.len(), 1);
+    assert_eq!(drift.sets[0].set_name, "blocklist_set_ipv4");
+    assert_eq!(drift.sets[0].added, ["203.0.113.0/24"]);
+    assert_eq!(drift.sets[0].removed, ["192.0.2.1"]);
+    assert!(detect_drift(&config.table_name, &desired, &desired, &[]).is_empty());
+}
+
+#[test]
+fn test_missing_table_is_drift() {
+    let config = NftConfig::default();
+    let live = Nftables {
+        objects: Cow::Owned(Vec::new()),
+    };
+    let desired = config.generate_ruleset(&None, &None);
+
+    let drift = detect_drift(&config.table_name, &live, &desired, &[]);
+
+    assert!(drift.table_missing);
+    assert_eq!(drift.to_string(), "table nftblockd: missing");
+}
+
+#[test]
+fn test_applied_ruleset_is_imported_with_its_elements() {
+    let config = NftConfig::default();
+    let elements = ipv4_elements("10.0.0.0/8 192.0.2.1");
+    let path = common::temp_path("ruleset.json");
+
+    AppliedRuleset::write(
+        &path,
+        &serialize_ruleset(&config.generate_ruleset(&elements, &None)).unwrap(),
+    )
+    .unwrap();
+    let applied = AppliedRuleset::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let now = SystemTime::now() + Duration::from_secs(60);
+    let imported = applied.elements(&config.table_name, "blocklist_set_ipv4", now);
+    assert_eq!(imported, elements);
+    assert_eq!(
+        applied.elements(&config.table_name, "blocklist_set_ipv6", now),
+        None
+    );
+}
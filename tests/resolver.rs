@@ -0,0 +1,66 @@
+mod common;
+
+use common::{Fixture, FixtureServer, json_response};
+use nftblockd::set::resolver::DomainResolver;
+use nftblockd::settings::Environment;
+
+/// Returns the variables configuring `url` as the DNS-over-HTTPS resolver.
+fn doh_url(url: &str) -> Environment {
+    Environment::from_iter([("NFTBLOCKD_DOH_URL", url)])
+}
+
+/// Starts a DNS-over-HTTPS resolver answering every query with `answer` and returns its URL.
+async fn doh_server(answer: &'static str) -> String {
+    let body = format!(r#"{{"Status":0,"Answer":[{answer}]}}"#);
+    let server = FixtureServer::start(Fixture::custom(move |_| json_response(&body))).await;
+    format!("{}/dns-query", server.address)
+}
+
+#[tokio::test]
+async fn test_hostnames_are_resolved_over_doh_and_cached_on_disk() {
+    let url =
+        doh_server(r#"{"name":"bad.example.","type":1,"TTL":300,"data":"198.51.100.7"}"#).await;
+    let cache = common::temp_path("dns.json");
+    let _ = std::fs::remove_file(&cache);
+    let entries = vec![
+        "192.0.2.1".to_string(),
+        "10.0.0.0/8".to_string(),
+        "bad.example".to_string(),
+    ];
+
+    let resolved = DomainResolver::from_env(&doh_url(&url), Some(cache.clone()))
+        .unwrap()
+        .resolve(entries.clone(), false)
+        .await;
+
+    assert_eq!(resolved, ["192.0.2.1", "10.0.0.0/8", "198.51.100.7"]);
+    assert!(
+        std::fs::read_to_string(&cache)
+            .unwrap()
+            .contains("198.51.100.7")
+    );
+
+    // The persisted resolution is used without asking the resolver, which is no longer reachable.
+    let restarted = DomainResolver::from_env(
+        &doh_url("http://127.0.0.1:9/dns-query"),
+        Some(cache.clone()),
+    )
+    .unwrap();
+    assert_eq!(
+        restarted.resolve(entries, false).await,
+        ["192.0.2.1", "10.0.0.0/8", "198.51.100.7"]
+    );
+    let _ = std::fs::remove_file(cache);
+}
+
+#[tokio::test]
+async fn test_doh_resolver_must_use_https() {
+    let resolver = DomainResolver::from_env(&doh_url("http://dns.example/dns-query"), None);
+
+    assert!(
+        resolver
+            .unwrap_err()
+            .to_string()
+            .contains("expected an https URL")
+    );
+}
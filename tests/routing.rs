@@ -0,0 +1,90 @@
+mod common;
+
+use common::{Fixture, FixtureServer, json_response};
+use nftblockd::set::routing::{RouteAction, RouteVerifier, Verified};
+use reqwest::Url;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Starts a RIPEstat prefix overview announcing everything but `192.0.2.0/24`, and returns its URL
+/// and the number of queries it answered.
+async fn ripestat_server() -> (Url, Arc<AtomicUsize>) {
+    let server = FixtureServer::start(Fixture::custom(|request| {
+        let announced = !request.contains("resource=192.0.2.");
+        json_response(&format!(
+            r#"{{"status":"ok","data":{{"announced":{announced}}}}}"#
+        ))
+    }))
+    .await;
+    let url = format!("{}/data/prefix-overview/data.json", server.address);
+    (Url::parse(&url).unwrap(), server.requests)
+}
+
+fn entries() -> Vec<String> {
+    [
+        "198.51.100.7",
+        "192.0.2.0/24",
+        "203.0.113.0/24",
+        "192.0.2.1-192.0.2.9",
+    ]
+    .map(ToString::to_string)
+    .to_vec()
+}
+
+#[tokio::test]
+async fn test_unrouted_entries_are_dropped_and_verdicts_cached_on_disk() {
+    let (url, queries) = ripestat_server().await;
+    let cache = common::temp_path("routes.json");
+    let _ = std::fs::remove_file(&cache);
+    let verifier = RouteVerifier::new(RouteAction::Drop, url.clone(), Some(cache.clone())).unwrap();
+
+    assert_eq!(
+        verifier.verify(entries()).await,
+        Verified {
+            entries: ["198.51.100.7", "203.0.113.0/24", "192.0.2.1-192.0.2.9"]
+                .map(ToString::to_string)
+                .to_vec(),
+            unrouted: vec!["192.0.2.0/24".to_string()],
+            // The range is not a network RIPEstat can verify.
+            unverified: 1,
+        }
+    );
+    assert_eq!(queries.load(Ordering::SeqCst), 3);
+
+    // A restart verifies nothing again.
+    let verifier = RouteVerifier::new(RouteAction::Report, url, Some(cache.clone())).unwrap();
+    let verified = verifier.verify(entries()).await;
+    assert_eq!(verified.entries, entries());
+    assert_eq!(verified.unrouted, ["192.0.2.0/24"]);
+    assert_eq!(queries.load(Ordering::SeqCst), 3);
+    let _ = std::fs::remove_file(&cache);
+}
+
+#[tokio::test]
+async fn test_entries_over_the_query_budget_are_kept_unverified() {
+    let (url, queries) = ripestat_server().await;
+    let verifier = RouteVerifier::new(RouteAction::Drop, url, None)
+        .unwrap()
+        .with_max_queries(1);
+
+    let verified = verifier
+        .verify(vec![
+            "192.0.2.0/24".to_string(),
+            "192.0.2.128/25".to_string(),
+        ])
+        .await;
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+    assert_eq!(verified.unrouted.len() + verified.unverified, 2);
+    assert_eq!(verified.unverified, 1);
+
+    // The next fetch verifies the rest.
+    let verified = verifier
+        .verify(vec![
+            "192.0.2.0/24".to_string(),
+            "192.0.2.128/25".to_string(),
+        ])
+        .await;
+    assert!(verified.entries.is_empty());
+    assert_eq!(verified.unrouted.len(), 2);
+    assert!(RouteAction::parse("block").is_err());
+}
@@ -0,0 +1,242 @@
+use nftblockd::nftables::builder::{
+    AddressFamilies, Direction, RateLimit, SetElements, TableFamily, TimeWindow,
+};
+use nftblockd::nftables::config::NftConfig;
+use nftblockd::nftables::extra_rules::parse_extra_rules;
+use nftblockd::nftables::serialize_ruleset;
+use nftblockd::set::custom_set::CustomSet;
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use serde_json::Value;
+use std::path::Path;
+use std::{env, fs};
+
+/// Set `NFTBLOCKD_UPDATE_GOLDEN=1` to regenerate the golden files after an intended change.
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    if env::var_os("NFTBLOCKD_UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(actual).unwrap(),
+        serde_json::from_str::<Value>(&expected).unwrap(),
+        "The generated ruleset does not match {name}."
+    );
+}
+
+fn ipv4_elements<'a>(data: &str) -> Option<SetElements<'a>> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+fn ipv6_elements<'a>(data: &str) -> Option<SetElements<'a>> {
+    SubnetList::IPv6(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+fn config<'a>() -> NftConfig<'a> {
+    NftConfig {
+        anti_lockout_set: CustomSet::new(
+            "anti_lockout_set".to_string(),
+            parse_from_string(Some("192.168.0.0/16 10.0.0.0/8"), None),
+            parse_from_string(Some("fe80::/48"), None),
+        )
+        .unwrap(),
+        custom_blocklist_set: CustomSet::new(
+            "custom_blocklist_set".to_string(),
+            parse_from_string(Some("203.0.113.0/24"), None),
+            None,
+        )
+        .unwrap(),
+        ..NftConfig::default()
+    }
+}
+
+#[test]
+fn test_ruleset_matches_golden_file() {
+    let config = config();
+    let ipv4 = ipv4_elements("192.168.100.0/24 1.2.3.4 10.10.0.0/16 5.5.5.1-5.5.5.9");
+    let ipv6 = ipv6_elements("2001:db8::/32 2001:db8:1::/48 2001:db9::1");
+
+    let actual = serialize_ruleset(&config.generate_ruleset(&ipv4, &ipv6)).unwrap();
+
+    assert_golden("ruleset.json", &actual);
+}
+
+#[test]
+fn test_ruleset_does_not_depend_on_feed_order() {
+    let config = config();
+    let ipv4_first = ipv4_elements("192.168.100.0/24 1.2.3.4 10.10.0.0/16 5.5.5.1-5.5.5.9");
+    let ipv4_second = ipv4_elements("5.5.5.1-5.5.5.9 10.10.0.0/16 1.2.3.4 192.168.100.0/24");
+
+    assert_eq!(
+        config.generate_ruleset(&ipv4_first, &None),
+        config.generate_ruleset(&ipv4_second, &None),
+        "The generated ruleset should not depend on the feed order."
+    );
+}
+
+#[test]
+fn test_ruleset_generates_only_the_rules_of_the_configured_direction() {
+    let config = NftConfig {
+        ipv4_direction: Direction::Egress,
+        ipv6_direction: Direction::Ingress,
+        ..config()
+    };
+    let ipv4 = ipv4_elements("1.2.3.4");
+    let ipv6 = ipv6_elements("2001:db8::/32");
+
+    let actual = serialize_ruleset(&config.generate_ruleset(&ipv4, &ipv6)).unwrap();
+
+    assert!(actual.contains("postrouting ipv4 blocklist rule"));
+    assert!(!actual.contains("prerouting ipv4 blocklist rule"));
+    assert!(actual.contains("prerouting ipv6 blocklist rule"));
+    assert!(!actual.contains("postrouting ipv6 blocklist rule"));
+}
+
+#[test]
+fn test_ruleset_of_an_ip_table_leaves_out_ipv6() {
+    let config = NftConfig {
+        family: TableFamily::Ip,
+        ..config()
+    };
+    let ipv4 = ipv4_elements("1.2.3.4");
+    let ipv6 = ipv6_elements("2001:db8::/32");
+
+    let actual = serialize_ruleset(&config.generate_ruleset(&ipv4, &ipv6)).unwrap();
+
+    assert!(actual.contains("\"family\": \"ip\""));
+    assert!(!actual.contains("\"inet\""));
+    assert!(actual.contains("blocklist_set_ipv4"));
+    assert!(!actual.contains("_ipv6"));
+    assert!(!actual.contains("2001:db8::"));
+    assert_eq!(TableFamily::parse("bridge").unwrap(), TableFamily::Bridge);
+    assert!(TableFamily::parse("netdev").is_err());
+}
+
+#[test]
+fn test_ipv6_only_mode_leaves_out_ipv4() {
+    let bridge = NftConfig {
+        family: TableFamily::Bridge,
+        ..config()
+    }
+    .with_address_families(AddressFamilies::Ipv6);
+    let ipv4 = ipv4_elements("1.2.3.4");
+    let ipv6 = ipv6_elements("2001:db8::/32");
+
+    let actual = serialize_ruleset(&bridge.generate_ruleset(&ipv4, &ipv6)).unwrap();
+
+    assert!(actual.contains("\"family\": \"bridge\""));
+    assert!(actual.contains("blocklist_set_ipv6"));
+    assert!(!actual.contains("_ipv4"));
+    assert!(!actual.contains("1.2.3.4"));
+    assert_eq!(
+        config().with_address_families(AddressFamilies::Ipv6).family,
+        TableFamily::Ip6
+    );
+}
+
+#[test]
+fn test_extra_rules_are_rendered_after_the_anti_lockout_rules() {
+    let config = NftConfig {
+        extra_rules: parse_extra_rules(
+            "# ssh from the management networks only\n\
+             prerouting tcp dport 22,8000-8080 saddr @anti_lockout_set_ipv4 accept\n\
+             postrouting udp dport 53 drop\n",
+        )
+        .unwrap(),
+        ..config()
+    };
+    config.validate_extra_rules().unwrap();
+
+    let actual = serialize_ruleset(&config.generate_ruleset(&None, &None)).unwrap();
+
+    let extra = actual
+        .find("extra rule: prerouting tcp dport 22,8000-8080 saddr @anti_lockout_set_ipv4 accept")
+        .unwrap();
+    assert!(actual.find("postrouting ipv6 anti-lockout rule").unwrap() < extra);
+    assert!(
+        extra
+            < actual
+                .find("prerouting ipv4 custom blocklist rule")
+                .unwrap()
+    );
+    assert!(actual.contains("extra rule: postrouting udp dport 53 drop"));
+    assert!(actual.contains("\"dport\""));
+    assert!(actual.contains("8080"));
+}
+
+#[test]
+fn test_invalid_extra_rules_are_rejected() {
+    assert!(parse_extra_rules("forward tcp dport 22 drop").is_err());
+    assert!(parse_extra_rules("prerouting tcp dport 0 drop").is_err());
+    assert!(parse_extra_rules("prerouting tcp dport 22 log").is_err());
+    assert!(parse_extra_rules("prerouting drop").is_err());
+
+    let config = NftConfig {
+        extra_rules: parse_extra_rules("prerouting saddr @unknown_set_ipv4 drop").unwrap(),
+        ..config()
+    };
+
+    assert!(config.validate_extra_rules().is_err());
+}
+
+#[test]
+fn test_rate_limited_source_drops_only_the_excess_per_address() {
+    let config = NftConfig {
+        ipv4_rate_limit: Some(RateLimit::parse("20/minute", 10).unwrap()),
+        ..config()
+    };
+    let ipv4 = ipv4_elements("198.51.100.0/24");
+
+    let actual = serialize_ruleset(&config.generate_ruleset(&ipv4, &None)).unwrap();
+
+    assert!(actual.contains("prerouting ipv4 rate limit rule"));
+    assert!(!actual.contains("prerouting ipv4 blocklist rule"));
+    assert!(actual.contains("prerouting ipv6 blocklist rule"));
+    assert!(actual.contains("\"blocklist_set_ipv4_saddr_meter\""));
+    assert!(actual.contains("\"per\": \"minute\""));
+    assert!(RateLimit::parse("10", 5).is_err());
+    assert!(RateLimit::parse("0/second", 5).is_err());
+    assert!(RateLimit::parse("10/fortnight", 5).is_err());
+}
+
+#[test]
+fn test_time_window_restricts_only_the_blocklist_rules_of_its_source() {
+    let window = TimeWindow::parse(Some("18:00-8:00"), Some("fri-mon,wed")).unwrap();
+    let config = NftConfig {
+        ipv4_time_window: window.clone(),
+        ..config()
+    };
+    let ipv4 = ipv4_elements("1.2.3.4");
+
+    let actual = serialize_ruleset(&config.generate_ruleset(&ipv4, &None)).unwrap();
+
+    let window = window.unwrap();
+    assert_eq!(
+        window.hours,
+        Some(("18:00".to_string(), "08:00".to_string()))
+    );
+    assert_eq!(
+        window.days,
+        ["Sunday", "Monday", "Wednesday", "Friday", "Saturday"]
+    );
+    assert_eq!(actual.matches("\"hour\"").count(), 2);
+    assert!(actual.contains("\"!=\""));
+    assert!(actual.contains("\"Wednesday\""));
+    assert_eq!(TimeWindow::parse(None, None).unwrap(), None);
+    assert!(TimeWindow::parse(Some("25:00-08:00"), None).is_err());
+    assert!(TimeWindow::parse(None, Some("someday")).is_err());
+}
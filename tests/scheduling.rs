@@ -0,0 +1,53 @@
+use nftblockd::utils::scheduling::{IoClass, Scheduling, cpu_max, parse_cpu_quota};
+
+#[test]
+fn test_io_class_and_cpu_quota_are_parsed() {
+    assert_eq!(IoClass::parse("idle").unwrap(), IoClass::Idle);
+    assert_eq!(
+        IoClass::parse("Best-Effort").unwrap(),
+        IoClass::BestEffort(7)
+    );
+    assert_eq!(
+        IoClass::parse("best-effort:2").unwrap(),
+        IoClass::BestEffort(2)
+    );
+    assert!(IoClass::parse("best-effort:8").is_err());
+    assert!(IoClass::parse("realtime").is_err());
+
+    assert_eq!(parse_cpu_quota("50%").unwrap(), 50);
+    assert_eq!(parse_cpu_quota("200").unwrap(), 200);
+    assert!(parse_cpu_quota("0%").is_err());
+    assert!(parse_cpu_quota("half").is_err());
+
+    assert_eq!(cpu_max(50), "50000 100000");
+    assert_eq!(cpu_max(250), "250000 100000");
+}
+
+#[test]
+fn test_priorities_are_lowered_only_for_the_work() {
+    let niceness = || {
+        let stat = std::fs::read_to_string("/proc/thread-self/stat").unwrap();
+        // The niceness is the 19th field; the command name in parentheses may contain spaces.
+        let (_, rest) = stat.rsplit_once(')').unwrap();
+        rest.split_whitespace()
+            .nth(16)
+            .unwrap()
+            .parse::<i32>()
+            .unwrap()
+    };
+    let before = niceness();
+    let scheduling = Scheduling {
+        nice: Some(19),
+        ..Scheduling::default()
+    };
+
+    let (thread, during) = scheduling.run(|| (std::thread::current().id(), niceness()));
+
+    assert_ne!(thread, std::thread::current().id());
+    assert_eq!(during, 19);
+    assert_eq!(
+        niceness(),
+        before,
+        "The calling thread should keep its priority."
+    );
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use nftblockd::error::{AppError, FailureKind};
+use nftblockd::settings::{Environment, Settings};
+use std::env;
+use std::time::Duration;
+
+fn env_file(name: &str, contents: &str) -> String {
+    let path = common::temp_path(&format!("settings-{name}.env"));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_env_files_are_layered_below_the_process_environment() {
+    let process = env::var("PATH").unwrap();
+    let base = env_file(
+        "base",
+        "NFTBLOCKD_TEST_BASE=base\nNFTBLOCKD_TEST_LAYERED=base\nPATH=base\n",
+    );
+    let local = env_file("local", "NFTBLOCKD_TEST_LAYERED=local\n");
+
+    let environment = Environment::load(&[base.clone(), local.clone()]).unwrap();
+
+    assert_eq!(environment.var("NFTBLOCKD_TEST_BASE").unwrap(), "base");
+    assert_eq!(environment.var("NFTBLOCKD_TEST_LAYERED").unwrap(), "local");
+    assert_eq!(environment.var("PATH").unwrap(), process);
+    assert!(env::var("NFTBLOCKD_TEST_BASE").is_err());
+
+    std::fs::write(&base, "NFTBLOCKD_TEST_LAYERED=base\n").unwrap();
+    let environment = Environment::load(&[base.clone(), local.clone()]).unwrap();
+
+    assert!(environment.var("NFTBLOCKD_TEST_BASE").is_err());
+    assert_eq!(environment.var("NFTBLOCKD_TEST_LAYERED").unwrap(), "local");
+    assert_eq!(environment.var("PATH").unwrap(), process);
+
+    assert!(Environment::load(&[base.clone(), "/nonexistent/nftblockd.env".to_string()]).is_err());
+    let _ = std::fs::remove_file(base);
+    let _ = std::fs::remove_file(local);
+}
+
+#[test]
+fn test_settings_report_every_problem_at_once() {
+    let environment = Environment::from_iter([
+        ("NFTBLOCKD_RETRY_COUNT", "0"),
+        ("NFTBLOCKD_LOG_MAX_FILES", "many"),
+        ("NFTBLOCKD_IPV4_READ_TIMEOUT", "1m"),
+    ]);
+
+    let result = Settings::from_env(&environment, None, Duration::from_secs(30));
+
+    let Err(AppError::ConfigError(problems)) = &result else {
+        panic!("expected a configuration error, got {result:?}");
+    };
+    assert_eq!(problems.len(), 3, "{problems:?}");
+    assert!(problems[0].starts_with("invalid NFTBLOCKD_LOG_MAX_FILES: many"));
+    assert_eq!(problems[1], "NFTBLOCKD_RETRY_COUNT must be at least 1");
+    assert!(problems[2].contains("READ_TIMEOUT of the IPV4 source"));
+    assert_eq!(result.unwrap_err().kind(), FailureKind::Config);
+    assert!(Settings::from_env(&Environment::default(), None, Duration::from_secs(30)).is_ok());
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use nftblockd::nftables::builder::SetElements;
+use nftblockd::set::simulation::{Outcome, Simulation, read_inputs};
+use nftblockd::utils::subnet::{SubnetList, parse_from_string};
+use std::fs;
+use std::net::IpAddr;
+
+fn ipv4_elements<'a>(data: &str) -> Option<SetElements<'a>> {
+    SubnetList::IPv4(parse_from_string(Some(data), None).unwrap())
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+        .transform_to_nft_expressions()
+        .get_elements()
+}
+
+/// A little-endian pcap capture of raw IP packets.
+fn capture(packets: &[([u8; 4], [u8; 4])]) -> Vec<u8> {
+    let mut data = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(&65535u32.to_le_bytes());
+    data.extend_from_slice(&101u32.to_le_bytes());
+    for (source, destination) in packets {
+        let mut packet = vec![0x45, 0, 0, 20, 0, 0, 0, 0, 64, 6, 0, 0];
+        packet.extend_from_slice(source);
+        packet.extend_from_slice(destination);
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&u32::try_from(packet.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(&u32::try_from(packet.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(&packet);
+    }
+    data
+}
+
+#[test]
+fn test_simulation_reports_the_dropping_set_and_element() {
+    let anti_lockout = ipv4_elements("198.51.100.10");
+    let blocklist = ipv4_elements("198.51.100.0/24 203.0.113.0/24");
+    let simulation = Simulation::new()
+        .with_set(
+            "anti_lockout_set_ipv4".to_string(),
+            false,
+            anti_lockout.as_ref(),
+        )
+        .with_set("blocklist_set_ipv4".to_string(), true, blocklist.as_ref());
+    let path = common::temp_path("simulate.pcap");
+    fs::write(
+        &path,
+        capture(&[
+            ([203, 0, 113, 7], [192, 0, 2, 1]),
+            ([203, 0, 113, 7], [192, 0, 2, 1]),
+            ([198, 51, 100, 10], [192, 0, 2, 1]),
+        ]),
+    )
+    .unwrap();
+
+    let report = simulation.run(&read_inputs(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+
+    let address = |a: &str| a.parse::<IpAddr>().unwrap();
+    assert_eq!(
+        report.hits[&address("203.0.113.7")],
+        (
+            Outcome::Dropped {
+                set: "blocklist_set_ipv4".to_string(),
+                element: "203.0.113.0/24".to_string(),
+            },
+            2
+        )
+    );
+    assert!(matches!(
+        report.hits[&address("198.51.100.10")].0,
+        Outcome::Accepted { .. }
+    ));
+    assert_eq!(report.hits[&address("192.0.2.1")], (Outcome::Passed, 3));
+    assert_eq!((report.dropped(), report.total()), (2, 6));
+    assert!(
+        report
+            .to_string()
+            .ends_with("2 of 6 inputs (1 distinct addresses) would be dropped\n")
+    );
+}
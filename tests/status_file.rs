@@ -0,0 +1,41 @@
+mod common;
+
+use nftblockd::error::AppError;
+use nftblockd::set::observer::{UpdateObserver, UpdateReport};
+use nftblockd::utils::status_file::{CycleState, StatusFile};
+
+#[test]
+fn test_status_file_is_written_after_each_cycle() {
+    let dir = common::temp_path("status");
+    let file = StatusFile::new(dir.join("status.json"), Some("edge")).with_profile(Some("tenant"));
+    let path = dir.join("status-tenant.json");
+
+    file.on_error(&AppError::RequestError("timeout".to_string(), None));
+    let status: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(status["state"], "failing");
+    assert_eq!(status["instance"], "edge");
+    assert_eq!(status["profile"], "tenant");
+    assert_eq!(status["consecutive_failures"], 1);
+    assert_eq!(status["last_error"]["kind"], "fetch");
+    assert!(status["last_success"].is_null());
+
+    file.on_applied(&UpdateReport {
+        ipv4_elements: 12,
+        ipv6_elements: 3,
+        ..UpdateReport::default()
+    });
+    let status: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(status["state"], "ok");
+    assert_eq!(status["ipv4_elements"], 12);
+    assert_eq!(status["consecutive_failures"], 0);
+    assert!(status["last_success"].as_u64().is_some());
+    // The last error stays for the scripts investigating a flapping source.
+    assert_eq!(
+        status["last_error"]["message"],
+        file.snapshot().last_error.unwrap().message
+    );
+    assert_eq!(file.snapshot().state, CycleState::Ok);
+}
@@ -0,0 +1,144 @@
+use ipnetwork::Ipv4Network;
+use nftblockd::error::AppError;
+use nftblockd::utils::subnet::{
+    DeduplicatedSubnetList, OverflowPolicy, SubnetList, normalize_notation, parse_from_string,
+    split_entries, validate_subnets,
+};
+use std::collections::BTreeMap;
+
+#[test]
+fn test_wildcard_and_netmask_notations_become_cidrs() {
+    assert_eq!(normalize_notation("192.0.2.*"), "192.0.2.0/24");
+    assert_eq!(normalize_notation("10.*.*"), "10.0.0.0/8");
+    assert_eq!(
+        normalize_notation("198.51.0.0/255.255.0.0"),
+        "198.51.0.0/16"
+    );
+    assert_eq!(normalize_notation("*"), "*");
+    assert_eq!(normalize_notation("192.*.2.1"), "192.*.2.1");
+    assert_eq!(
+        normalize_notation("192.0.2.0/255.0.255.0"),
+        "192.0.2.0/255.0.255.0"
+    );
+
+    let list = SubnetList::IPv4(
+        parse_from_string(
+            Some("192.0.2.* 198.51.100.0/255.255.255.128 203.0.*.7"),
+            None,
+        )
+        .unwrap(),
+    );
+    let DeduplicatedSubnetList::IPv4(Some(ips)) = list
+        .validate_blocklist(false)
+        .unwrap()
+        .deduplicate()
+        .unwrap()
+    else {
+        panic!("expected IPv4 subnets");
+    };
+
+    let mut actual = ips
+        .iter()
+        .map(|ip| ip.inner().to_string())
+        .collect::<Vec<_>>();
+    actual.sort();
+    assert_eq!(actual, ["192.0.2.0/24", "198.51.100.0/25"]);
+}
+
+#[test]
+fn test_overflowing_lists_are_capped_by_their_policy() {
+    let capped = |policy| {
+        let list = SubnetList::IPv4(
+            parse_from_string(
+                Some("203.0.113.7 198.51.100.0/24 192.0.2.1 10.0.0.0/8 10.1.0.0/16"),
+                None,
+            )
+            .unwrap(),
+        );
+        list.validate_blocklist(true)
+            .unwrap()
+            .deduplicate_capped(2, policy)
+            .map(|(deduplicated, dropped)| {
+                let DeduplicatedSubnetList::IPv4(Some(ips)) = deduplicated else {
+                    panic!("expected IPv4 subnets");
+                };
+                let ips = ips
+                    .iter()
+                    .map(|ip| ip.inner().to_string())
+                    .collect::<Vec<_>>();
+                (ips, dropped)
+            })
+    };
+
+    assert_eq!(
+        capped(OverflowPolicy::Truncate).unwrap(),
+        (
+            vec!["203.0.113.7/32".to_string(), "198.51.100.0/24".to_string()],
+            2
+        )
+    );
+    assert_eq!(
+        capped(OverflowPolicy::PreferBroader).unwrap(),
+        (
+            vec!["10.0.0.0/8".to_string(), "198.51.100.0/24".to_string()],
+            2
+        )
+    );
+    assert_eq!(
+        capped(OverflowPolicy::Fail).unwrap_err(),
+        AppError::NftblockdError("4 elements exceed the maximum set size of 2".to_string())
+    );
+}
+
+#[test]
+fn test_scored_entries_survive_the_cap_before_broader_ones() {
+    let list = SubnetList::IPv4(
+        parse_from_string(
+            Some("203.0.113.7 198.51.100.0/24 192.0.2.1 10.0.0.0/8"),
+            None,
+        )
+        .unwrap(),
+    );
+    let scores = BTreeMap::from([
+        ("192.0.2.1/32".to_string(), 3),
+        ("203.0.113.7/32".to_string(), 2),
+    ]);
+    let prioritizer = OverflowPolicy::PreferScored.prioritizer(scores);
+    let (deduplicated, dropped) = list
+        .validate_blocklist(true)
+        .unwrap()
+        .deduplicate_prioritized(3, prioritizer.as_deref())
+        .unwrap();
+    let DeduplicatedSubnetList::IPv4(Some(ips)) = deduplicated else {
+        panic!("expected IPv4 subnets");
+    };
+    let ips = ips
+        .iter()
+        .map(|ip| ip.inner().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(ips, ["192.0.2.1/32", "203.0.113.7/32", "10.0.0.0/8"]);
+    assert_eq!(dropped, 1);
+}
+
+#[test]
+fn test_entries_are_validated_in_place() {
+    let body = "192.0.2.0/24 ;  198.51.100.7;;10.*";
+    assert_eq!(
+        split_entries(body, Some(";")).collect::<Vec<_>>(),
+        ["192.0.2.0/24", "198.51.100.7", "", "10.*"]
+    );
+    assert_eq!(
+        parse_from_string(Some(body), Some(";")).unwrap(),
+        split_entries(body, Some(";")).collect::<Vec<_>>()
+    );
+
+    let borrowed = validate_subnets::<Ipv4Network>(split_entries(body, Some(";")), false)
+        .unwrap()
+        .unwrap();
+    let owned =
+        validate_subnets::<Ipv4Network>(&parse_from_string(Some(body), Some(";")).unwrap(), false)
+            .unwrap()
+            .unwrap();
+    assert_eq!(borrowed, owned);
+    assert_eq!(borrowed.len(), 3);
+}
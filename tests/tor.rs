@@ -0,0 +1,68 @@
+use nftblockd::set::feed::{Feed, FeedFormat};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const CONSENSUS: &str = "network-status-version 3 microdesc
+vote-status consensus
+valid-after 2026-01-01 00:00:00
+fresh-until 2026-01-01 01:00:00
+valid-until 2026-01-01 03:00:00
+r exit1 AAAA 2026-01-01 00:00:00 192.0.2.1 9001 0
+a [2001:db8::1]:9001
+s Exit Fast Running Valid
+p accept 20-23,43,53,79-81,443
+r exit2 BBBB 2026-01-01 00:00:00 192.0.2.2 443 0
+s Exit Fast Running Valid
+p reject 22,25
+r guard CCCC 2026-01-01 00:00:00 198.51.100.1 9001 9030
+s Fast Guard Running Valid
+p reject 1-65535
+";
+
+const EXIT_LIST: &str = "ExitNode 0011BD2485AD45D984EC4159C88FC066E5E3300E
+Published 2026-01-01 00:00:00
+LastStatus 2026-01-01 01:00:00
+ExitAddress 203.0.113.5 2026-01-01 01:03:04
+ExitNode 0111BA9B604669E636FFD5B503F382A4B7AD6E80
+ExitAddress 203.0.113.6 2026-01-01 00:40:00
+";
+
+fn at(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+#[test]
+fn test_consensus_exits_expire_with_the_consensus() {
+    let feed = Feed::parse(CONSENSUS, FeedFormat::Tor, None, SystemTime::now()).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["192.0.2.1", "2001:db8::1", "192.0.2.2"]
+    );
+    // 2026-01-01 03:00:00 UTC
+    assert_eq!(feed.expiry.get("192.0.2.1"), Some(&at(1_767_236_400)));
+    assert_eq!(feed.fresh_until, Some(at(1_767_229_200)));
+}
+
+#[test]
+fn test_only_exits_allowing_the_ports_are_listed() {
+    let ssh = Feed::parse_tor(CONSENSUS, Some(&[22]));
+    assert_eq!(
+        ssh.entries().collect::<Vec<_>>(),
+        ["192.0.2.1", "2001:db8::1"]
+    );
+
+    let smtp = Feed::parse_tor(CONSENSUS, Some(&[25, 8080]));
+    assert_eq!(smtp.entries().collect::<Vec<_>>(), ["192.0.2.2"]);
+}
+
+#[test]
+fn test_exit_list_is_parsed() {
+    let feed = Feed::parse(EXIT_LIST, FeedFormat::Tor, None, SystemTime::now()).unwrap();
+
+    assert_eq!(
+        feed.entries().collect::<Vec<_>>(),
+        ["203.0.113.5", "203.0.113.6"]
+    );
+    assert!(feed.expiry.is_empty());
+    assert_eq!(feed.fresh_until, None);
+}
@@ -0,0 +1,31 @@
+use nftblockd::set::trace::EntryTrace;
+
+#[test]
+fn test_traced_networks_match_overlapping_entries() {
+    let trace = EntryTrace::parse("203.0.113.0/25, 2001:db8::1").unwrap();
+    let entries = [
+        "203.0.113.0/24",
+        "203.0.113.7",
+        "203.0.113.128/25",
+        "203.0.113.100-203.0.113.200",
+        "2001:db8::/32",
+        "198.51.100.1",
+        "not an address",
+    ];
+
+    assert_eq!(
+        trace.matches(entries),
+        [
+            (
+                "203.0.113.0/25",
+                vec![
+                    "203.0.113.0/24",
+                    "203.0.113.7",
+                    "203.0.113.100-203.0.113.200"
+                ]
+            ),
+            ("2001:db8::1", vec!["2001:db8::/32"]),
+        ]
+    );
+    assert!(EntryTrace::parse("203.0.113.0/24,example.com").is_err());
+}
@@ -0,0 +1,61 @@
+use nftblockd::set::url_template::expand_url;
+use nftblockd::settings::Environment;
+use nftblockd::utils::hostname;
+use time::OffsetDateTime;
+
+fn now() -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp(784_111_777).unwrap()
+}
+
+#[test]
+fn test_date_and_hostname_placeholders_are_expanded() {
+    let actual = expand_url(
+        &Environment::default(),
+        "https://example.com/{date:%Y%m%d}/{date:%H%%}/{hostname}.txt",
+        now(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        actual,
+        format!("https://example.com/19941106/08%/{}.txt", hostname())
+    );
+}
+
+#[test]
+fn test_env_placeholder_is_expanded() {
+    let environment = Environment::from_iter([("NFTBLOCKD_TEST_KEY", "secret")]);
+
+    let actual = expand_url(
+        &environment,
+        "https://example.com/list?key={env:NFTBLOCKD_TEST_KEY}",
+        now(),
+    )
+    .unwrap();
+
+    assert_eq!(actual, "https://example.com/list?key=secret");
+}
+
+#[test]
+fn test_invalid_placeholders_are_rejected() {
+    for template in [
+        "https://example.com/{date:%Q}",
+        "https://example.com/{user}",
+        "https://example.com/{date:%Y",
+        "https://example.com/{env:NFTBLOCKD_TEST_UNSET_VARIABLE}",
+    ] {
+        assert!(
+            expand_url(&Environment::default(), template, now()).is_err(),
+            "{template} should be rejected"
+        );
+    }
+    assert_eq!(
+        expand_url(
+            &Environment::default(),
+            "https://example.com/ipv4.txt",
+            now()
+        )
+        .unwrap(),
+        "https://example.com/ipv4.txt"
+    );
+}
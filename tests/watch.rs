@@ -0,0 +1,46 @@
+mod common;
+
+use nftblockd::utils::watch::FileWatcher;
+use std::fs;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn test_replaced_file_is_reported() {
+    let dir = common::temp_path("watch");
+    fs::create_dir_all(&dir).unwrap();
+    let watched = dir.join("nftblockd.env");
+    fs::write(&watched, "NFTBLOCKD_INTERVAL=30s\n").unwrap();
+    let mut watcher = FileWatcher::new([watched.clone()]).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(watcher.wait().unwrap());
+    });
+
+    fs::write(dir.join("unrelated.txt"), "ignored").unwrap();
+    let replacement = dir.join("nftblockd.env.tmp");
+    fs::write(&replacement, "NFTBLOCKD_INTERVAL=1m\n").unwrap();
+    fs::rename(&replacement, &watched).unwrap();
+
+    let actual = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(actual, watched);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_new_file_matching_a_glob_is_reported() {
+    let dir = common::temp_path("watch-glob");
+    fs::create_dir_all(&dir).unwrap();
+    let mut watcher = FileWatcher::new([dir.join("*.txt")]).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(watcher.wait().unwrap());
+    });
+
+    fs::write(dir.join("notes.md"), "ignored").unwrap();
+    fs::write(dir.join("30-ticket.txt"), "203.0.113.7\n").unwrap();
+
+    let actual = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(actual, dir.join("30-ticket.txt"));
+    fs::remove_dir_all(&dir).unwrap();
+}